@@ -17,6 +17,24 @@ extern crate failure;
 
 pub type Result<T> = std::result::Result<T, failure::Error>;
 
+/// Log what nbted is doing for the whole file, such as decompressing it, if
+/// built with the trace feature. Compiles to nothing otherwise.
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "trace")]
+        tracing::debug!(target: "nbted", $($arg)*);
+    };
+}
+
+/// Like debug!, for the details inside of a file, such as every compound
+/// that is read
+macro_rules! trace {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "trace")]
+        tracing::trace!(target: "nbted", $($arg)*);
+    };
+}
+
 pub mod unstable;
 
 use unstable::*;
@@ -1,55 +1,551 @@
 #[macro_use]
 extern crate failure;
 
-use nbted::unstable::{data, read, string_read, string_write, write};
+use nbted::unstable::data::{Compression, Endianness, NBTFile, NBT};
+use nbted::unstable::deflate::Strategy;
+use nbted::unstable::io as io_util;
+use nbted::unstable::path::{self, PathFilter};
+use nbted::unstable::read::ReadOptions;
+use nbted::unstable::string_read::TextReadOptions;
+use nbted::unstable::string_write::{ArrayFormat, ByteArrayEncoding, TextFormat};
+use nbted::unstable::write::WriteOptions;
+use nbted::unstable::{
+    components, diff, humanize, lint, patch, read, region, repro, snbt_read, snbt_write,
+    string_read, string_write, write,
+};
 use nbted::Result;
 
 use std::env;
-use std::fs::File;
+use std::fs::{self, File};
 use std::io;
-use std::io::{BufReader, BufWriter};
-use std::path::Path;
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
 use std::process::exit;
 use std::process::Command;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-use getopts::Options;
+use getopts::{Matches, Options};
 
 use tempdir::TempDir;
 
 use failure::ResultExt;
 
 fn main() {
-    match run_cmdline() {
-        Ok(ret) => {
-            exit(ret);
+    #[cfg(feature = "trace")]
+    init_logger();
+
+    exit(run_cmdline());
+}
+
+/// Main entrypoint for program.
+///
+/// Returns an integer representing the program's exit status.
+fn run_cmdline() -> i32 {
+    let (opts, matches, operands) = match parse_args() {
+        Ok(x) => x,
+        Err(e) => return report_error(&e, 1),
+    };
+    /* Like diff(1), --diff exits with 2 on any trouble, including bad
+     * arguments, since 1 means that the files differ */
+    let error_status = if matches.opt_present("diff") { 2 } else { 1 };
+
+    match run(&opts, matches, operands) {
+        Ok(x) => x,
+        Err(e) => report_error(&e, error_status),
+    }
+}
+
+/// Print an error that ended the program, and return the exit status to end
+/// it with
+fn report_error(e: &failure::Error, status: i32) -> i32 {
+    eprintln!("{}", e.backtrace());
+
+    print_error(e);
+
+    eprintln!("For help, run with --help or read the manpage.");
+    status
+}
+
+/// Print the error and its causes to stderr, for actions that give an exit
+/// status of their own when they fail
+fn print_error(e: &failure::Error) {
+    eprintln!("Error: {}", e);
+
+    for e in e.iter_chain().skip(1) {
+        eprintln!("	caused by: {}", e);
+    }
+}
+
+/// Print what nbted is doing to stderr if built with the trace feature and
+/// RUST_LOG asks for it, either for everything like RUST_LOG=debug, or only
+/// for nbted like RUST_LOG=nbted=trace. Invalid directives are ignored.
+#[cfg(feature = "trace")]
+fn init_logger() {
+    if env::var_os("RUST_LOG").is_none() {
+        return;
+    }
+
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_writer(io::stderr)
+        .init();
+}
+
+/// The options that select a transformation, see transform()
+const TRANSFORMS: &[&str] = &[
+    "apply-binary-patch",
+    "clamp",
+    "coerce-lists",
+    "convert-endian",
+    "copy",
+    "merge",
+    "recompress",
+    "remove",
+    "rename-key",
+    "rename-key-prefix",
+    "set",
+];
+
+/// The transformations that take more than one value, with their values.
+/// getopts only gives an option a single value, so the others are taken out
+/// of the arguments that follow it before parsing, see take_operands.
+const MULTI_VALUE_TRANSFORMS: &[(&str, &str)] = &[("clamp", "PATH MIN MAX"), ("copy", "SRC DST")];
+
+/// The compound keys that --list-ids looks for identifiers under, which
+/// --id-keys adds to
+const ID_KEYS: &[&str] = &["id", "Name", "Id"];
+
+/// The text formats that --reverse can read
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum ReverseFormat {
+    /// nbted's own text format, as written by --print
+    Native,
+    /// The stringified NBT used in Minecraft commands
+    Snbt,
+    /// nbted's text format with braces around the contents of containers
+    Braces,
+}
+
+/// The formats that --print can write the file in
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum OutputFormat {
+    /// The pretty text format
+    Text,
+    /// The pretty text format, with arrays and short lists on a single line
+    PrettyCompact,
+    /// SNBT, as used in Minecraft commands
+    Snbt,
+    /// JSON tagged with the type of every tag, on a single line
+    Json,
+    /// The same JSON, indented
+    JsonPretty,
+}
+impl OutputFormat {
+    /// Whether the format is one of the text formats, which the options for
+    /// the text format apply to
+    fn is_text(self) -> bool {
+        match self {
+            OutputFormat::Text | OutputFormat::PrettyCompact => true,
+            OutputFormat::Snbt | OutputFormat::Json | OutputFormat::JsonPretty => false,
         }
-        Err(e) => {
-            eprintln!("{}", e.backtrace());
+    }
+}
 
-            eprintln!("Error: {}", e);
+/// The names of the OutputFormats for --output-format. Each format other
+/// than the default text also has a flag of the same name, like
+/// --pretty-compact, except for snbt since --snbt is the input format of
+/// --reverse.
+const OUTPUT_FORMATS: &[(&str, OutputFormat)] = &[
+    ("text", OutputFormat::Text),
+    ("pretty-compact", OutputFormat::PrettyCompact),
+    ("snbt", OutputFormat::Snbt),
+    ("json", OutputFormat::Json),
+    ("json-pretty", OutputFormat::JsonPretty),
+];
 
-            for e in e.iter_chain().skip(1) {
-                eprintln!("	caused by: {}", e);
-            }
+/// How an existing output file is kept before it's overwritten, as given by
+/// --backup
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Backup {
+    /// Copy it to the file name with .bak appended, replacing the last backup
+    Simple,
+    /// Copy it to the file name with the time and .bak appended, keeping
+    /// every backup
+    Numbered,
+}
+
+/// How an existing output file is treated when it's written over
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+struct Overwrite {
+    /// Copy it somewhere first, as given by --backup
+    backup: Option<Backup>,
+    /// Give the new file the modification time of the old one, as asked for
+    /// with --keep-mtime
+    keep_mtime: bool,
+}
+
+/// How sizes and counts are written in reports
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct Units {
+    /// Write plain numbers for other programs to parse, as asked for with
+    /// --bytes, instead of formatting them for people
+    raw: bool,
+}
+impl Units {
+    fn bytes(self, n: u64) -> String {
+        if self.raw {
+            format!("{} bytes", n)
+        } else {
+            humanize::bytes(n)
+        }
+    }
 
-            eprintln!("For help, run with --help or read the manpage.");
-            exit(1);
+    fn count(self, n: usize) -> String {
+        if self.raw {
+            n.to_string()
+        } else {
+            humanize::count(n as u64)
         }
     }
 }
 
-/// Main entrypoint for program.
-///
-/// Returns an integer representing the program's exit status.
-fn run_cmdline() -> Result<i32> {
-    let args: Vec<String> = env::args().collect();
+/// Parse the command line arguments, returning the options for the help
+/// menu, what was given, and the values taken out by take_operands.
+fn parse_args() -> Result<(Options, Matches, Vec<String>)> {
+    let mut args: Vec<String> = env::args().collect();
+    let operands = take_operands(&mut args)?;
 
     let mut opts = Options::new();
     let _: &Options = opts.optflagopt("e", "edit", "edit a NBT file with your $EDITOR.
     If [FILE] is specified, then that file is edited in place, but specifying --input and/or --output will override the input/output.
     If no file is specified, default to read from --input and writing to --output.", "FILE");
-    let _: &Options = opts.optflagopt("p", "print", "print NBT file to text format. Adding an argument to this is the same as specifying --input", "FILE");
-    let _: &Options = opts.optflagopt("r", "reverse", "reverse a file in text format to NBT format. Adding an argument to this is the same as specifying --input", "FILE");
+    let _: &Options = opts.optflagopt("p", "print", "print NBT file to text format. Adding an argument to this is the same as specifying --input. Several files may be given, each printed to a .txt file next to it. The input may be an http:// or https:// URL if nbted was built with the http feature", "FILE");
+    let _: &Options = opts.optflagopt("r", "reverse", "reverse a file in text format to NBT format. Adding an argument to this is the same as specifying --input. Several .txt files may be given, each reversed to a file next to it without the .txt", "FILE");
+    let _: &Options = opts.optflag(
+        "",
+        "estimate-size",
+        "with --reverse, print how large the NBT file would be, uncompressed and with its compression, instead of writing it",
+    );
+    let _: &Options = opts.optopt(
+        "",
+        "exec",
+        "with --edit, run the shell command CMD with the file in the text format as its last argument instead of opening $EDITOR, failing if the result doesn't parse",
+        "CMD",
+    );
+    let _: &Options = opts.optflag(
+        "",
+        "diff",
+        "print the differences between the two NBT files given as arguments",
+    );
+    let _: &Options = opts.optflag(
+        "",
+        "unified",
+        "with --diff, print the differences in a format resembling a unified diff",
+    );
+    let _: &Options = opts.optopt(
+        "",
+        "list-key",
+        "with --diff, compare lists of compounds by the value of KEY in each element instead of by index",
+        "KEY",
+    );
+    let _: &Options = opts.optflag(
+        "",
+        "make-binary-patch",
+        "write a binary patch that turns the first of the two NBT files given as arguments into the second",
+    );
+    let _: &Options = opts.optopt(
+        "",
+        "apply-binary-patch",
+        "apply the binary patch PATCH, as written by --make-binary-patch, writing the file back in place like --edit",
+        "PATCH",
+    );
+    let _: &Options = opts.optopt(
+        "",
+        "grep",
+        "print the path and value of every string matching the regular expression PATTERN, if nbted was built with the grep feature",
+        "PATTERN",
+    );
+    let _: &Options = opts.optopt(
+        "",
+        "find-int",
+        "print the path of every Int and Long equal to N",
+        "N",
+    );
+    let _: &Options = opts.optopt(
+        "",
+        "get",
+        "print the tag at PATH in the pretty text format, exiting with status 2 if there is none",
+        "PATH",
+    );
+    let _: &Options = opts.optflag(
+        "",
+        "dot",
+        "print the structure of the NBT file as a Graphviz DOT graph, such as for drawing with dot -Tpng",
+    );
+    let _: &Options = opts.optflag(
+        "",
+        "list-ids",
+        "print every distinct String stored under the keys id, Name and Id, such as the IDs of items and blocks, with how many times it occurs",
+    );
+    let _: &Options = opts.optopt(
+        "",
+        "id-keys",
+        "with --list-ids, also look for identifiers under the comma separated compound keys KEYS",
+        "KEYS",
+    );
+    let _: &Options = opts.optflag(
+        "",
+        "size-breakdown",
+        "print how many bytes every compound, list, array and string takes up in the decompressed file, largest first",
+    );
+    let _: &Options = opts.optflag(
+        "",
+        "lint",
+        "print the path of every problem in the NBT file that a buggy tool may have caused, such as lists of mixed types, duplicate keys and strings that aren't UTF-8, exiting with a nonzero status if there are any",
+    );
+    let _: &Options = opts.optflag(
+        "",
+        "extract-error",
+        "if the NBT file fails to parse, print the error along with a hex dump of the bytes around the point where it failed, for attaching to a bug report, exiting with a nonzero status",
+    );
+    let _: &Options = opts.optflagopt(
+        "",
+        "check",
+        "check that the NBT file parses, printing nothing and exiting with a nonzero status if it doesn't. Adding an argument to this is the same as specifying --input",
+        "FILE",
+    );
+    let _: &Options = opts.optflag(
+        "",
+        "recursive",
+        "with --check or --print, read the input as a directory and check or print every file in it and its subdirectories",
+    );
+    let _: &Options = opts.optflag(
+        "",
+        "bytes",
+        "write sizes and counts in reports, such as those of --size-breakdown, as plain numbers instead of in units like MiB",
+    );
+    let _: &Options = opts.optopt(
+        "",
+        "split-region",
+        "write every chunk in the region file FILE to a file of its own in the directory given by --output",
+        "FILE",
+    );
+    let _: &Options = opts.optopt(
+        "",
+        "join-region",
+        "write the chunk files in DIR, as written by --split-region, to the region file given by --output",
+        "DIR",
+    );
+    let _: &Options = opts.optopt(
+        "",
+        "rename-key",
+        "rename every compound key that is exactly OLD to NEW, writing the file back in place like --edit",
+        "OLD=NEW",
+    );
+    let _: &Options = opts.optopt(
+        "",
+        "rename-key-prefix",
+        "like --rename-key, but replace the prefix OLD of every key starting with it",
+        "OLD=NEW",
+    );
+    let _: &Options = opts.optflag(
+        "",
+        "strict",
+        "treat problems that are normally warnings or allowed, such as duplicate keys in a compound or a rename creating one, as errors",
+    );
+    let _: &Options = opts.optopt(
+        "",
+        "clamp",
+        "clamp every integer at the paths matching PATH into the range MIN to MAX, writing the file back in place like --edit. A * in PATH matches any key or index",
+        "PATH MIN MAX",
+    );
+    let _: &Options = opts.optflag(
+        "",
+        "coerce-lists",
+        "convert the elements of every list to the type of its first element, writing the file back in place like --edit",
+    );
+    let _: &Options = opts.optopt(
+        "",
+        "convert-endian",
+        "convert the file to ORDER, either little for Bedrock Edition or big for Java Edition, writing the file back in place like --edit. The file is read in the other byte order",
+        "ORDER",
+    );
+    let _: &Options = opts.optopt(
+        "",
+        "copy",
+        "copy the tag at SRC to DST, writing the file back in place like --edit. Missing compounds on the way to DST are created",
+        "SRC DST",
+    );
+    let _: &Options = opts.optopt(
+        "",
+        "copy-from",
+        "with --copy, copy the tag at SRC from the NBT file FILE instead",
+        "FILE",
+    );
+    let _: &Options = opts.optopt(
+        "",
+        "merge",
+        "merge the compounds of the NBT file OVERLAY into the file, writing the file back in place like --edit. Tags in OVERLAY replace those in the file, except that compounds are merged",
+        "OVERLAY",
+    );
+    let _: &Options = opts.optopt(
+        "",
+        "recompress",
+        "change the compression of the file to SCHEME, one of none, gzip, zlib, zstd and lz4, writing the file back in place like --edit",
+        "SCHEME",
+    );
+    let _: &Options = opts.optflag(
+        "",
+        "force",
+        "with --coerce-lists, also convert values that don't fit in the new type, limiting them to the nearest value that does",
+    );
+    let _: &Options = opts.optopt(
+        "",
+        "remove",
+        "remove the tag at PATH, writing the file back in place like --edit. Later elements of a list are shifted down",
+        "PATH",
+    );
+    let _: &Options = opts.optopt(
+        "",
+        "set",
+        "set the number or String at PATH to VALUE, parsed as the tag's type, writing the file back in place like --edit",
+        "PATH=VALUE",
+    );
+    let _: &Options = opts.optflag(
+        "",
+        "uuid-strings",
+        "with --print, add a comment with the UUID string to every IntArray of length 4",
+    );
+    let _: &Options = opts.optopt(
+        "",
+        "byte-arrays",
+        "with --print, write the bytes of ByteArrays in ENCODING, one of decimal, hex and base64, where hex and base64 put them all in a single token",
+        "ENCODING",
+    );
+    let _: &Options = opts.optflag(
+        "",
+        "escape-whitespace",
+        "with --print, write newlines, tabs and carriage returns in strings as \\n, \\t and \\r",
+    );
+    let _: &Options = opts.optflag(
+        "",
+        "snbt",
+        "with --reverse, read the input as SNBT, instead of detecting the format",
+    );
+    let _: &Options = opts.optflag(
+        "",
+        "native",
+        "with --reverse, read the input as nbted's text format, instead of detecting the format",
+    );
+    let _: &Options = opts.optflag(
+        "",
+        "braces",
+        "with --print or --reverse, use the variant of the text format with the contents of compounds, lists and arrays between { and }",
+    );
+    let _: &Options = opts.optflag(
+        "",
+        "sort-keys",
+        "with --print, write the tags of every compound sorted by their names, so that files that only differ in the order of their keys print the same",
+    );
+    let _: &Options = opts.optflag(
+        "",
+        "canonical",
+        "with --print, write the text in a fixed form for keeping in version control, with the keys of compounds sorted",
+    );
+    let _: &Options = opts.optflag(
+        "",
+        "recursive-decompress",
+        "decompress files that were compressed several times over, such as a gzipped zlib stream",
+    );
+    let _: &Options = opts.optflagopt(
+        "",
+        "backup",
+        "when writing over an existing NBT file, first copy it to its name with .bak appended, or with the time and .bak appended with --backup=numbered",
+        "numbered",
+    );
+    let _: &Options = opts.optflag(
+        "",
+        "keep-mtime",
+        "when writing over an existing NBT file, keep its modification time",
+    );
+    let _: &Options = opts.optflag(
+        "",
+        "text-sidecar",
+        "with --edit or --reverse, also write the text format of the result to the output file with .txt appended",
+    );
+    let _: &Options = opts.optopt(
+        "",
+        "output-format",
+        "with --print, the format to print the file in, one of text (the default), pretty-compact, snbt, json or json-pretty",
+        "FORMAT",
+    );
+    let _: &Options = opts.optflag(
+        "",
+        "pretty-compact",
+        "same as --output-format pretty-compact, write arrays and short lists on a single line instead of one element per line",
+    );
+    let _: &Options = opts.optflag(
+        "",
+        "json",
+        "same as --output-format json, write JSON tagged with the type of every tag, such as {\"Int\": 5}",
+    );
+    let _: &Options = opts.optflag(
+        "",
+        "json-pretty",
+        "same as --output-format json-pretty, write the JSON of --json indented",
+    );
+    let _: &Options = opts.optopt(
+        "",
+        "preview",
+        "with --print, only print the first N tags inside of the root compound, skipping over the rest without reading them",
+        "N",
+    );
+    let _: &Options = opts.optopt(
+        "",
+        "since",
+        "with --print, read the input as a region file and only print the chunks saved at or after the Unix timestamp TIMESTAMP",
+        "TIMESTAMP",
+    );
+    let _: &Options = opts.optopt(
+        "",
+        "skip-leading",
+        "skip N bytes of junk before the NBT data when reading, and write them back in front of the file with --edit and the other actions that write the file in place",
+        "N",
+    );
+    let _: &Options = opts.optflag(
+        "",
+        "components",
+        "with --print or --reverse, read or write the item components of a 1.20.5+ network packet instead of an NBT file",
+    );
+    let _: &Options = opts.optflag(
+        "",
+        "root-end",
+        "end the root compound with an End tag when writing an NBT file, for programs that expect one",
+    );
+    let _: &Options = opts.optopt(
+        "",
+        "pad",
+        "pad the NBT file with zero bytes up to a multiple of N bytes when writing it, for containers made of fixed size sectors",
+        "N",
+    );
+    let _: &Options = opts.optopt(
+        "",
+        "compression-strategy",
+        "compress gzip and zlib files with STRATEGY when writing them, one of default, filtered, huffman and rle, which can compress some data better or faster",
+        "STRATEGY",
+    );
+    let _: &Options = opts.optopt(
+        "",
+        "compression-level",
+        "compress gzip and zlib files at LEVEL from 0 to 9 when writing them, where 9 is the smallest and 1 the fastest",
+        "LEVEL",
+    );
+    let _: &Options = opts.optopt(
+        "",
+        "max-tags",
+        "fail instead of reading an NBT file containing more than N tags, for reading untrusted files",
+        "N",
+    );
     let _: &Options = opts.optopt(
         "i",
         "input",
@@ -68,9 +564,16 @@ fn run_cmdline() -> Result<i32> {
 
     let matches = opts.parse(&args[1..]).context("error parsing options")?;
 
+    Ok((opts, matches, operands))
+}
+
+/// Take the action the user asked for.
+///
+/// Returns an integer representing the program's exit status.
+fn run(opts: &Options, matches: Matches, operands: Vec<String>) -> Result<i32> {
     if matches.opt_present("h") {
         let brief = "Usage: nbted [options] FILE";
-        print!("{}", opts.usage(&brief));
+        print!("{}", opts.usage(brief));
         println!("\nThe default action, taken if no action is explicitly selected, is to --edit.");
         println!(
             "\nFor detailed usage information, read the nbted man page. If the nbted man page\
@@ -93,18 +596,50 @@ fn run_cmdline() -> Result<i32> {
     }
 
     if matches.opt_present("man") {
-        print!(include_str!("../nbted.1"));
+        print!("{}", include_str!("../nbted.1"));
         return Ok(0);
     }
 
     let is_print: bool = matches.opt_present("print");
     let is_reverse: bool = matches.opt_present("reverse");
+    let is_grep: bool = matches.opt_present("grep");
+    let is_diff: bool = matches.opt_present("diff");
+    let is_make_patch: bool = matches.opt_present("make-binary-patch");
+    let is_find_int: bool = matches.opt_present("find-int");
+    let is_get: bool = matches.opt_present("get");
+    let is_list_ids: bool = matches.opt_present("list-ids");
+    let is_dot: bool = matches.opt_present("dot");
+    let is_size_breakdown: bool = matches.opt_present("size-breakdown");
+    let is_lint: bool = matches.opt_present("lint");
+    let is_extract_error: bool = matches.opt_present("extract-error");
+    let is_check: bool = matches.opt_present("check");
+    let is_split_region: bool = matches.opt_present("split-region");
+    let is_join_region: bool = matches.opt_present("join-region");
+    /* Transformations modify the file without opening an editor, and like
+     * --edit they write the result back to the file */
+    let transform_count = TRANSFORMS.iter().filter(|x| matches.opt_present(x)).count();
+    let is_transform: bool = transform_count > 0;
     let is_edit: bool = if matches.opt_present("edit") {
         true
     } else {
         /* If edit is not explicitly defined, it is the default action and is
          * selected if no other action is specified */
-        !(is_reverse || is_print)
+        !(is_reverse
+            || is_print
+            || is_grep
+            || is_diff
+            || is_make_patch
+            || is_find_int
+            || is_get
+            || is_list_ids
+            || is_dot
+            || is_size_breakdown
+            || is_lint
+            || is_extract_error
+            || is_check
+            || is_split_region
+            || is_join_region
+            || is_transform)
     };
 
     /* Hopefully this is a simpler way of ensuring that only one action can be
@@ -119,117 +654,751 @@ fn run_cmdline() -> Result<i32> {
     if is_edit {
         action_count += 1;
     }
+    if is_grep {
+        action_count += 1;
+    }
+    if is_diff {
+        action_count += 1;
+    }
+    if is_make_patch {
+        action_count += 1;
+    }
+    if is_find_int {
+        action_count += 1;
+    }
+    if is_get {
+        action_count += 1;
+    }
+    if is_list_ids {
+        action_count += 1;
+    }
+    if is_dot {
+        action_count += 1;
+    }
+    if is_size_breakdown {
+        action_count += 1;
+    }
+    if is_lint {
+        action_count += 1;
+    }
+    if is_extract_error {
+        action_count += 1;
+    }
+    if is_check {
+        action_count += 1;
+    }
+    if is_split_region {
+        action_count += 1;
+    }
+    if is_join_region {
+        action_count += 1;
+    }
+    action_count += transform_count;
     if action_count > 1 {
         bail!("You can only specify one action at a time.");
     }
 
-    /* Figure out the input file, by trying to read the arguments for all of
-     * --input, --edit, --print and --reverse, prioritizing --input over the
-     * other arguments, if none of the arguments are specified but there is a
-     * free argument, use that, else we finally default to - (stdin) */
-    let input = if let Some(x) = matches.opt_str("input") {
-        x
-    } else if let Some(x) = matches.opt_str("edit") {
-        x
-    } else if let Some(x) = matches.opt_str("print") {
-        x
-    } else if let Some(x) = matches.opt_str("reverse") {
-        x
-    } else if matches.free.len() == 1 {
-        matches.free[0].clone()
-    } else {
-        /* stdin */
-        "-".to_string()
-    };
-
-    let output = if let Some(x) = matches.opt_str("output") {
-        x
-    } else if let Some(x) = matches.opt_str("edit") {
-        x
-    } else if is_edit && matches.free.len() == 1 {
-        /* Only want to default to the free argument if we're editing
-         * (DO NOT WRITE BACK TO THE READ FILE UNLESS EDITING!) */
-        matches.free[0].clone()
-    } else {
-        /* stdout */
-        "-".to_string()
-    };
+    if matches.opt_present("unified") && !is_diff {
+        bail!("--unified can only be used with --diff");
+    }
+    let estimate_size = matches.opt_present("estimate-size");
+    if estimate_size && !is_reverse {
+        bail!("--estimate-size can only be used with --reverse");
+    }
+    if estimate_size && matches.opt_present("output") {
+        bail!("--estimate-size doesn't write the NBT file, so it can't be used with --output");
+    }
+    if matches.opt_present("exec") && !is_edit {
+        bail!("--exec can only be used with --edit");
+    }
+    if matches.opt_present("list-key") && !is_diff {
+        bail!("--list-key can only be used with --diff");
+    }
 
-    if matches.free.len() > 1 {
-        bail!("nbted was given multiple arguments, but only supports editing one file at a time.");
+    if matches.opt_present("id-keys") && !is_list_ids {
+        bail!("--id-keys can only be used with --list-ids");
     }
 
-    if is_print {
-        print(&input, &output)
-    } else if is_reverse {
-        reverse(&input, &output)
-    } else if is_edit {
-        edit(&input, &output)
-    } else {
-        bail!("Internal error: No action selected. (Please report this.)");
+    if matches.opt_present("copy-from") && !matches.opt_present("copy") {
+        bail!("--copy-from can only be used with --copy");
     }
-}
 
-/// When the user wants to edit a specific file in place
-///
-/// Returns an integer representing the program's exit status.
-fn edit(input: &str, output: &str) -> Result<i32> {
-    /* First we read the NBT data from the input */
-    let nbt = if input == "-" {
-        // let mut f = BufReader::new(io::stdin());
-        let f = io::stdin();
-        let mut f = f.lock();
-        read::read_file(&mut f).context("Unable to parse any NBT files from stdin")?
-    } else {
-        let path: &Path = Path::new(input);
-        let f = File::open(path).context(format!("Unable to open file {}", input))?;
-        let mut f = BufReader::new(f);
+    let recursive = matches.opt_present("recursive");
+    if recursive && !(is_check || is_print) {
+        bail!("--recursive can only be used with --check and --print");
+    }
+    if recursive && is_print && matches.opt_present("output") {
+        bail!("--print --recursive writes every file next to its input, so it can't be used with --output");
+    }
 
-        read::read_file(&mut f).context(format_err!(
-            "Unable to parse {}, are you sure it's an NBT file?",
-            input
-        ))?
+    let units = Units {
+        raw: matches.opt_present("bytes"),
     };
 
-    /* Then we create a temporary file and write the NBT data in text format
-     * to the temporary file */
-    let tmpdir = TempDir::new("nbted").context("Unable to create temporary directory")?;
+    let output_format = output_format(&matches)?;
+    if output_format.is_some() && !is_print {
+        bail!("An output format can only be given with --print");
+    }
+    let output_format = output_format.unwrap_or(OutputFormat::Text);
 
-    let tmp = match Path::new(input).file_name() {
-        Some(x) => {
-            let mut x = x.to_os_string();
-            x.push(".txt");
-            x
-        }
-        None => bail!("Error reading file name"),
-    };
-    let tmp_path = tmpdir.path().join(tmp);
+    let braces = matches.opt_present("braces");
+    if braces && !(is_print || is_reverse) {
+        bail!("--braces can only be used with --print or --reverse");
+    }
 
-    {
-        let mut f = File::create(&tmp_path).context("Unable to create temporary file")?;
+    let uuid_strings = matches.opt_present("uuid-strings");
+    if uuid_strings && !is_print {
+        bail!("--uuid-strings can only be used with --print");
+    }
 
-        string_write::write_file(&mut f, &nbt).context("Unable to write temporary file")?;
+    let byte_array_encoding = match matches.opt_str("byte-arrays") {
+        Some(x) => match ByteArrayEncoding::from_str(&x) {
+            Some(x) => Some(x),
+            None => bail!(
+                "Invalid --byte-arrays {}, expected one of decimal, hex and base64",
+                x
+            ),
+        },
+        None => None,
+    };
+    if byte_array_encoding.is_some() && !is_print {
+        bail!("--byte-arrays can only be used with --print");
+    }
 
-        f.sync_all().context("Unable to synchronize file")?;
+    let escape_whitespace = matches.opt_present("escape-whitespace");
+    if escape_whitespace && !is_print {
+        bail!("--escape-whitespace can only be used with --print");
     }
 
-    let new_nbt = {
-        let mut new_nbt = open_editor(&tmp_path);
+    let sort_keys = matches.opt_present("sort-keys");
+    if sort_keys && !is_print {
+        bail!("--sort-keys can only be used with --print");
+    }
+    if sort_keys && !output_format.is_text() {
+        bail!("--sort-keys can only be used with the text output formats");
+    }
 
-        while let Err(e) = new_nbt {
-            eprintln!("Unable to parse edited file");
-            for e in e.iter_chain() {
-                eprintln!("	caused by: {}", e);
-            }
-            eprintln!("Do you want to open the file for editing again? (y/N)");
+    let canonical = matches.opt_present("canonical");
+    if canonical && !is_print {
+        bail!("--canonical can only be used with --print");
+    }
+    if canonical && output_format != OutputFormat::Text {
+        bail!("--canonical can only be used with the text output format");
+    }
+    if canonical && (braces || uuid_strings || escape_whitespace || byte_array_encoding.is_some()) {
+        bail!("--canonical can't be used with --braces, --uuid-strings, --escape-whitespace or --byte-arrays");
+    }
 
-            let mut line = String::new();
-            let _: usize = io::stdin()
-                .read_line(&mut line)
-                .context("Error reading from stdin. Nothing was changed")?;
+    let text_format = if canonical {
+        TextFormat::canonical()
+    } else {
+        TextFormat {
+            uuid_strings,
+            braces,
+            escape_whitespace,
+            byte_array_encoding: byte_array_encoding.unwrap_or_default(),
+            sort_keys,
+            ..Default::default()
+        }
+    };
 
-            if line.trim() == "y" {
-                new_nbt = open_editor(&tmp_path);
+    let mut reverse_formats = Vec::new();
+    if matches.opt_present("snbt") {
+        reverse_formats.push(ReverseFormat::Snbt);
+    }
+    if matches.opt_present("native") {
+        reverse_formats.push(ReverseFormat::Native);
+    }
+    if !reverse_formats.is_empty() && !is_reverse {
+        bail!("--snbt and --native can only be used with --reverse");
+    }
+    if braces && is_reverse {
+        reverse_formats.push(ReverseFormat::Braces);
+    }
+    if reverse_formats.len() > 1 {
+        bail!("Only one of --snbt, --native and --braces can be given");
+    }
+    let reverse_format = reverse_formats.pop();
+
+    let write_options = WriteOptions {
+        root_end: matches.opt_present("root-end"),
+        pad: match matches.opt_str("pad") {
+            Some(x) => match x.parse::<usize>() {
+                Ok(x) if x > 0 => Some(x),
+                _ => bail!("Invalid --pad {}, expected a number of bytes above 0", x),
+            },
+            None => None,
+        },
+        endianness: Endianness::Big,
+        strategy: match matches.opt_str("compression-strategy") {
+            Some(x) => match Strategy::from_str(&x) {
+                Some(x) => x,
+                None => bail!(
+                    "Invalid --compression-strategy {}, expected one of default, filtered, huffman and rle",
+                    x
+                ),
+            },
+            None => Strategy::Default,
+        },
+        level: match matches.opt_str("compression-level") {
+            Some(x) => match x.parse::<u32>() {
+                Ok(x) if x <= 9 => Some(x),
+                _ => bail!("Invalid --compression-level {}, expected a number from 0 to 9", x),
+            },
+            None => None,
+        },
+    };
+    if write_options.root_end && !(is_edit || is_reverse || is_transform) {
+        bail!("--root-end can only be used with --edit, --reverse and the other actions that write an NBT file");
+    }
+    if write_options.pad.is_some() && !(is_edit || is_reverse || is_transform) {
+        bail!("--pad can only be used with --edit, --reverse and the other actions that write an NBT file");
+    }
+    if matches.opt_present("compression-strategy") && !(is_edit || is_reverse || is_transform) {
+        bail!("--compression-strategy can only be used with --edit, --reverse and the other actions that write an NBT file");
+    }
+    if write_options.level.is_some() && !(is_edit || is_reverse || is_transform) {
+        bail!("--compression-level can only be used with --edit, --reverse and the other actions that write an NBT file");
+    }
+
+    let overwrite = Overwrite {
+        backup: if matches.opt_present("backup") {
+            if !(is_edit || is_reverse || is_transform) {
+                bail!("--backup can only be used with --edit, --reverse and the other actions that write an NBT file");
+            }
+            match matches.opt_str("backup").as_deref() {
+                None => Some(Backup::Simple),
+                Some("numbered") => Some(Backup::Numbered),
+                Some(x) => bail!(
+                    "Invalid --backup={}, the only kind of backup is numbered",
+                    x
+                ),
+            }
+        } else {
+            None
+        },
+        keep_mtime: matches.opt_present("keep-mtime"),
+    };
+    if overwrite.keep_mtime && !(is_edit || is_reverse || is_transform) {
+        bail!("--keep-mtime can only be used with --edit, --reverse and the other actions that write an NBT file");
+    }
+
+    let text_sidecar = matches.opt_present("text-sidecar");
+    if text_sidecar && estimate_size {
+        bail!("--text-sidecar can't be used with --estimate-size, since nothing is written");
+    }
+    if text_sidecar && !(is_edit || is_reverse) {
+        bail!("--text-sidecar can only be used with --edit or --reverse");
+    }
+
+    let preview = match matches.opt_str("preview") {
+        Some(x) => {
+            if !is_print {
+                bail!("--preview can only be used with --print");
+            }
+            Some(
+                x.parse::<usize>()
+                    .context(format_err!("Invalid --preview {}, expected a number", x))?,
+            )
+        }
+        None => None,
+    };
+
+    let since = match matches.opt_str("since") {
+        Some(x) => {
+            if !is_print {
+                bail!("--since can only be used with --print");
+            }
+            if preview.is_some() {
+                bail!("--since can't be used with --preview");
+            }
+            Some(x.parse::<u32>().context(format_err!(
+                "Invalid --since {}, expected a Unix timestamp",
+                x
+            ))?)
+        }
+        None => None,
+    };
+
+    let read_options = ReadOptions {
+        max_tags: match matches.opt_str("max-tags") {
+            Some(x) => {
+                if is_reverse {
+                    bail!("--max-tags can't be used with --reverse, which reads the text format");
+                }
+                Some(
+                    x.parse::<usize>()
+                        .context(format_err!("Invalid --max-tags {}, expected a number", x))?,
+                )
+            }
+            None => None,
+        },
+        max_string_bytes: None,
+        max_depth: Some(read::DEFAULT_MAX_DEPTH),
+        recursive_decompress: matches.opt_present("recursive-decompress"),
+        skip_leading: match matches.opt_str("skip-leading") {
+            Some(x) => {
+                if is_reverse || since.is_some() {
+                    bail!("--skip-leading can't be used with --reverse or --since");
+                }
+                x.parse::<usize>().context(format_err!(
+                    "Invalid --skip-leading {}, expected a number of bytes",
+                    x
+                ))?
+            }
+            None => 0,
+        },
+        endianness: Endianness::Big,
+        array_endianness: None,
+        forbid_duplicate_keys: matches.opt_present("strict"),
+    };
+    if read_options.recursive_decompress && (is_reverse || preview.is_some()) {
+        bail!("--recursive-decompress can't be used with --reverse or --preview");
+    }
+
+    /* Item components are a packet payload rather than an NBT file, so none
+     * of the options for reading and writing files apply to them */
+    let components = matches.opt_present("components");
+    if components {
+        if !(is_print || is_reverse) {
+            bail!("--components can only be used with --print or --reverse");
+        }
+        if preview.is_some()
+            || since.is_some()
+            || read_options.max_tags.is_some()
+            || read_options.skip_leading > 0
+            || read_options.recursive_decompress
+            || write_options.root_end
+            || write_options.pad.is_some()
+            || matches.opt_present("compression-strategy")
+            || write_options.level.is_some()
+            || text_sidecar
+            || estimate_size
+        {
+            bail!("--components can't be used with the options for NBT files, such as --preview, --skip-leading, --root-end, --pad or --estimate-size");
+        }
+    }
+
+    /* Diffing is the only action that takes two input files, so it is
+     * handled separately from the others */
+    if is_diff {
+        if matches.free.len() != 2 {
+            bail!("--diff needs exactly two NBT files to compare");
+        }
+        let output = matches.opt_str("output").unwrap_or_else(|| "-".to_string());
+        /* Like diff(1), trouble is exit status 2, since 1 means that the
+         * files differ */
+        return match diff(
+            &matches.free[0],
+            &matches.free[1],
+            &output,
+            &read_options,
+            matches.opt_present("unified"),
+            matches.opt_str("list-key").as_deref(),
+        ) {
+            Ok(x) => Ok(x),
+            Err(e) => {
+                print_error(&e);
+                Ok(2)
+            }
+        };
+    }
+
+    if is_make_patch {
+        if matches.free.len() != 2 {
+            bail!("--make-binary-patch needs exactly two NBT files, the old and the new");
+        }
+        let output = matches.opt_str("output").unwrap_or_else(|| "-".to_string());
+        return make_binary_patch(
+            &matches.free[0],
+            &matches.free[1],
+            &output,
+            &read_options,
+            units,
+        );
+    }
+
+    /* Splitting and joining regions work on directories of chunks rather
+     * than single NBT files, so they're also handled separately */
+    if let Some(x) = matches.opt_str("split-region") {
+        let output = match matches.opt_str("output") {
+            Some(x) => x,
+            None => {
+                bail!("--split-region needs a directory to write the chunks to given with --output")
+            }
+        };
+        return split_region(&x, &output, units);
+    }
+    if let Some(x) = matches.opt_str("join-region") {
+        let output = match matches.opt_str("output") {
+            Some(x) => x,
+            None => bail!("--join-region needs a region file to write given with --output"),
+        };
+        return join_region(&x, &output, units);
+    }
+
+    /* --print and --reverse can convert several files at once, writing each
+     * one next to its input */
+    if (is_print || is_reverse) && since.is_none() && !recursive {
+        let inputs: Vec<String> = ["input", "print", "reverse"]
+            .iter()
+            .filter_map(|x| matches.opt_str(x))
+            .chain(matches.free.iter().cloned())
+            .collect();
+        if inputs.len() > 1 {
+            if matches.opt_present("output") {
+                bail!("--output can't be used when converting multiple files, each file is written next to its input");
+            }
+            if is_print {
+                return convert_each(&inputs, ".txt", |input, output| {
+                    print(
+                        input,
+                        output,
+                        &read_options,
+                        preview,
+                        components,
+                        output_format,
+                        &text_format,
+                    )
+                });
+            } else {
+                return convert_each(&inputs, "", |input, output| {
+                    reverse(
+                        input,
+                        output,
+                        reverse_format,
+                        read_options.forbid_duplicate_keys,
+                        components,
+                        &write_options,
+                        overwrite,
+                        text_sidecar,
+                        estimate_size.then_some(units),
+                    )
+                });
+            }
+        }
+    }
+
+    /* Figure out the input file, by trying to read the arguments for all of
+     * --input, --edit, --print and --reverse, prioritizing --input over the
+     * other arguments, if none of the arguments are specified but there is a
+     * free argument, use that, else we finally default to - (stdin) */
+    let input = if let Some(x) = matches.opt_str("input") {
+        x
+    } else if let Some(x) = matches.opt_str("edit") {
+        x
+    } else if let Some(x) = matches.opt_str("print") {
+        x
+    } else if let Some(x) = matches.opt_str("reverse") {
+        x
+    } else if let Some(x) = matches.opt_str("check") {
+        x
+    } else if matches.free.len() == 1 {
+        matches.free[0].clone()
+    } else {
+        /* stdin */
+        "-".to_string()
+    };
+
+    let output = if let Some(x) = matches.opt_str("output") {
+        x
+    } else if let Some(x) = matches.opt_str("edit") {
+        x
+    } else if (is_edit || is_transform) && matches.free.len() == 1 {
+        /* Only want to default to the free argument if we're editing
+         * (DO NOT WRITE BACK TO THE READ FILE UNLESS EDITING!) */
+        matches.free[0].clone()
+    } else {
+        /* stdout */
+        "-".to_string()
+    };
+
+    if matches.free.len() > 1 {
+        bail!("nbted was given multiple arguments, but only supports editing one file at a time.");
+    }
+
+    /* Files can be downloaded for inspection, but we never write anything
+     * back to a URL */
+    if is_url(&output) {
+        bail!(
+            "Unable to write to {}, nbted can only write to files",
+            output
+        );
+    }
+    if is_url(&input)
+        && !(is_print
+            || is_grep
+            || is_find_int
+            || is_get
+            || is_list_ids
+            || is_dot
+            || is_size_breakdown
+            || is_lint
+            || is_check)
+    {
+        bail!("Reading from a URL is only supported with --print, --grep, --find-int, --get, --list-ids, --dot, --size-breakdown, --lint and --check");
+    }
+    if recursive && (input == "-" || is_url(&input)) {
+        bail!("--recursive needs a directory to read, not stdin or a URL");
+    }
+    if since.is_some() && recursive {
+        bail!("--since can't be used with --recursive");
+    }
+    if since.is_some() && (input == "-" || is_url(&input)) {
+        bail!("--since can only read region files from disk, not from stdin or a URL");
+    }
+    if text_sidecar && output == "-" {
+        bail!("--text-sidecar needs an output file to name the text file after, not stdout");
+    }
+
+    if let Some(since) = since {
+        print_region_since(
+            &input,
+            &output,
+            &read_options,
+            since,
+            output_format,
+            &text_format,
+            units,
+        )
+    } else if is_print && recursive {
+        print_recursive(
+            &input,
+            &read_options,
+            preview,
+            components,
+            output_format,
+            &text_format,
+            units,
+        )
+    } else if is_print {
+        print(
+            &input,
+            &output,
+            &read_options,
+            preview,
+            components,
+            output_format,
+            &text_format,
+        )
+    } else if is_reverse {
+        reverse(
+            &input,
+            &output,
+            reverse_format,
+            read_options.forbid_duplicate_keys,
+            components,
+            &write_options,
+            overwrite,
+            text_sidecar,
+            estimate_size.then_some(units),
+        )
+    } else if is_edit {
+        edit(
+            &input,
+            &output,
+            &read_options,
+            &write_options,
+            overwrite,
+            text_sidecar,
+            matches.opt_str("exec").as_deref(),
+        )
+    } else if let Some(x) = matches.opt_str("grep") {
+        grep(&input, &output, &read_options, &x, units)
+    } else if let Some(x) = matches.opt_str("find-int") {
+        find_int(&input, &output, &read_options, &x, units)
+    } else if let Some(x) = matches.opt_str("get") {
+        get(&input, &output, &read_options, &x)
+    } else if is_dot {
+        let nbt = read_input(&input, &read_options)?;
+        write_text_output(&output, nbt.root.to_dot().as_bytes())
+    } else if is_list_ids {
+        list_ids(
+            &input,
+            &output,
+            &read_options,
+            matches.opt_str("id-keys").as_deref(),
+            units,
+        )
+    } else if is_size_breakdown {
+        size_breakdown(&input, &output, &read_options, units)
+    } else if is_lint {
+        lint(&input, &output, &read_options, units)
+    } else if is_extract_error {
+        extract_error(&input, &output, &read_options)
+    } else if is_check && recursive {
+        check_recursive(&input, &read_options, units)
+    } else if is_check {
+        let _: NBTFile = read_input(&input, &read_options)?;
+        Ok(0)
+    } else if is_transform {
+        transform(
+            &input,
+            &output,
+            &read_options,
+            &write_options,
+            overwrite,
+            &matches,
+            &operands,
+            units,
+        )
+    } else {
+        bail!("Internal error: No action selected. (Please report this.)");
+    }
+}
+
+/// Take the values after the first of a transformation in
+/// MULTI_VALUE_TRANSFORMS out of the arguments, such as the DST of
+/// --copy SRC DST, so that getopts doesn't see them as files. They are the
+/// arguments right after the first value.
+fn take_operands(args: &mut Vec<String>) -> Result<Vec<String>> {
+    for &(name, values) in MULTI_VALUE_TRANSFORMS {
+        let flag = format!("--{}", name);
+        let pos = match args
+            .iter()
+            .take_while(|x| *x != "--")
+            .position(|x| *x == flag || x.starts_with(&format!("{}=", flag)))
+        {
+            Some(x) => x,
+            None => continue,
+        };
+
+        /* The first value is either in the same argument or the next */
+        let start = if args[pos] == flag { pos + 2 } else { pos + 1 };
+        let count = values.split(' ').count() - 1;
+        if args.len() < start + count {
+            bail!(
+                "--{} expects {} values, as in --{} {}",
+                name,
+                count + 1,
+                name,
+                values
+            );
+        }
+        return Ok(args.drain(start..start + count).collect());
+    }
+
+    Ok(Vec::new())
+}
+
+/// Convert each of the inputs with the given function, writing the output of
+/// each next to it. With a suffix the output is named by appending it to the
+/// input, as for --print, and without one the input must end in .txt, which
+/// is removed, as for --reverse.
+///
+/// Stops at the first file that fails, returning its exit status.
+fn convert_each<F>(inputs: &[String], suffix: &str, convert: F) -> Result<i32>
+where
+    F: Fn(&str, &str) -> Result<i32>,
+{
+    /* Name all of the outputs first, so nothing is written if any of the
+     * inputs can't be converted */
+    let mut outputs = Vec::with_capacity(inputs.len());
+    for input in inputs {
+        if input == "-" || is_url(input) {
+            bail!(
+                "Unable to convert {} along with other files, only files on disk can be converted together",
+                input
+            );
+        }
+        let output = if !suffix.is_empty() {
+            format!("{}{}", input, suffix)
+        } else if let Some(x) = input.strip_suffix(".txt").filter(|x| !x.is_empty()) {
+            x.to_string()
+        } else {
+            bail!(
+                "Unable to name the NBT file for {}, it doesn't end in .txt",
+                input
+            );
+        };
+        outputs.push(output);
+    }
+
+    for (input, output) in inputs.iter().zip(&outputs) {
+        let ret = convert(input, output)?;
+        if ret != 0 {
+            return Ok(ret);
+        }
+    }
+
+    Ok(0)
+}
+
+/// When the user wants to edit a specific file in place
+///
+/// Returns an integer representing the program's exit status.
+#[cfg_attr(
+    feature = "trace",
+    tracing::instrument(skip_all, fields(input = %input, output = %output))
+)]
+fn edit(
+    input: &str,
+    output: &str,
+    options: &ReadOptions,
+    write_options: &WriteOptions,
+    overwrite: Overwrite,
+    text_sidecar: bool,
+    exec: Option<&str>,
+) -> Result<i32> {
+    /* First we read the NBT data from the input */
+    let (prefix, nbt) = read_input_with_prefix(input, options)?;
+
+    /* Then we create a temporary file and write the NBT data in text format
+     * to the temporary file */
+    let tmpdir = TempDir::new("nbted").context("Unable to create temporary directory")?;
+
+    let tmp = match Path::new(input).file_name() {
+        Some(x) => {
+            let mut x = x.to_os_string();
+            x.push(".txt");
+            x
+        }
+        None => bail!("Error reading file name"),
+    };
+    let tmp_path = tmpdir.path().join(tmp);
+
+    write_temp_file(&tmp_path, &nbt)?;
+
+    let text_options = TextReadOptions {
+        forbid_duplicate_keys: options.forbid_duplicate_keys,
+        ..Default::default()
+    };
+    let mut new_nbt = {
+        let mut new_nbt = open_editor(&tmp_path, exec, &text_options);
+
+        while let Err(e) = new_nbt {
+            /* Nobody is there to answer when running a command, so fail
+             * instead of asking */
+            if exec.is_some() {
+                return Err(e);
+            }
+
+            /* Editors that save by writing a new file and renaming it over
+             * the old one may leave the temporary file missing, in which case
+             * there is nothing to parse */
+            let missing = !tmp_path.exists();
+            if missing {
+                eprintln!("Unable to read edited file");
+            } else {
+                eprintln!("Unable to parse edited file");
+            }
+            for e in e.iter_chain() {
+                eprintln!("	caused by: {}", e);
+            }
+            eprintln!("Do you want to open the file for editing again? (y/N)");
+
+            let mut line = String::new();
+            let _: usize = io::stdin()
+                .read_line(&mut line)
+                .context("Error reading from stdin. Nothing was changed")?;
+
+            if line.trim() == "y" {
+                /* Start over from the original file if the edited one is gone,
+                 * instead of opening the editor on an empty file */
+                if missing {
+                    write_temp_file(&tmp_path, &nbt)?;
+                }
+                new_nbt = open_editor(&tmp_path, exec, &text_options);
             } else {
                 eprintln!("Exiting ... File is unchanged.");
                 return Ok(0);
@@ -239,44 +1408,75 @@ fn edit(input: &str, output: &str) -> Result<i32> {
         new_nbt.expect("new_nbt was Error")
     };
 
-    if nbt == new_nbt {
+    /* The text format doesn't record the compression level, so keep the one
+     * the file was read with unless the compression was changed */
+    if new_nbt.compression == nbt.compression {
+        new_nbt.compression_level = nbt.compression_level;
+    }
+
+    if nbt.bitwise_eq(&new_nbt) {
         eprintln!("No changes, will do nothing.");
         return Ok(0);
     }
 
     /* And finally we write the edited nbt (new_nbt) into the output file */
-    if output == "-" {
-        let f = io::stdout();
-        let mut f = f.lock();
-        /* If we get an error writing to stdout, we want to just silently exit
-         * with exit code 1. (It can generally be assumed that nbted will not
-         * error in serializing the data, so any error here would be because of
-         * writing to stdout) */
-        match write::write_file(&mut f, &new_nbt) {
-            Ok(()) => (),
-            Err(_) => return Ok(1),
-        }
-    } else {
-        let path: &Path = Path::new(output);
-        let f = File::create(&path).context(format_err!(
-            "Unable to write to output NBT file {}. Nothing was changed",
-            output
-        ))?;
-        let mut f = BufWriter::new(f);
-
-        write::write_file(&mut f, &new_nbt).context(
-            format_err!("Error writing NBT file {}. State of NBT file is unknown, consider restoring it from a backup.",
-                       output))?;
+    if write_output(output, &prefix, &new_nbt, write_options, overwrite)? != 0 {
+        return Ok(1);
+    }
+    if text_sidecar {
+        write_sidecar(output, &new_nbt)?;
     }
 
     eprintln!("File edited successfully.");
     Ok(0)
 }
 
+/// Write the NBT file in the text format to the temporary file for editing
+#[cfg_attr(feature = "trace", tracing::instrument(skip_all))]
+fn write_temp_file(tmp_path: &Path, nbt: &NBTFile) -> Result<()> {
+    let mut f = File::create(tmp_path).context("Unable to create temporary file")?;
+
+    string_write::write_file(&mut f, nbt).context("Unable to write temporary file")?;
+
+    f.sync_all().context("Unable to synchronize file")?;
+
+    Ok(())
+}
+
 /// Open the user's $EDITOR on the temporary file, wait until the editor is
 /// closed again, read the temporary file and attempt to parse it into NBT,
 /// returning the result.
-fn open_editor(tmp_path: &Path) -> Result<data::NBTFile> {
+///
+/// If exec is given, that shell command is run with the temporary file as
+/// its last argument instead of the editor.
+#[cfg_attr(feature = "trace", tracing::instrument(skip_all, fields(exec = ?exec)))]
+fn open_editor(
+    tmp_path: &Path,
+    exec: Option<&str>,
+    text_options: &TextReadOptions,
+) -> Result<NBTFile> {
+    if let Some(exec) = exec {
+        /* The file is given to the shell as $1, so that it doesn't need to be
+         * quoted inside of the command */
+        let status = Command::new("sh")
+            .arg("-c")
+            .arg(format!("{} \"$1\"", exec))
+            .arg("sh")
+            .arg(tmp_path.as_os_str())
+            .status()
+            .context(format_err!("Error running {}", exec))?;
+        if !status.success() {
+            bail!("{} did not exit correctly, nothing was changed", exec);
+        }
+
+        let mut f = File::open(tmp_path).context(format_err!(
+            "Unable to read temporary file, {} may have moved or deleted it",
+            exec
+        ))?;
+        return Ok(string_read::read_file_with_options(&mut f, text_options)
+            .context(format_err!("Unable to parse the file edited by {}", exec))?);
+    }
+
     let editor = match env::var("VISUAL") {
         Ok(x) => x,
         Err(_) => match env::var("EDITOR") {
@@ -286,7 +1486,7 @@ fn open_editor(tmp_path: &Path) -> Result<data::NBTFile> {
     };
 
     let mut cmd = Command::new(editor);
-    let _: &mut Command = cmd.arg(&tmp_path.as_os_str());
+    let _: &mut Command = cmd.arg(tmp_path.as_os_str());
     let mut cmd = cmd.spawn().context("Error opening editor")?;
 
     match cmd.wait().context("error executing editor")? {
@@ -295,32 +1495,78 @@ fn open_editor(tmp_path: &Path) -> Result<data::NBTFile> {
     }
 
     /* Then we parse the text format in the temporary file into NBT */
-    let mut f = File::open(&tmp_path).context(format_err!(
-        "Unable to read temporary file. Nothing was changed."
-    ))?;
+    let f = File::open(tmp_path);
+    if let Err(ref e) = f {
+        if e.kind() == io::ErrorKind::NotFound {
+            bail!(
+                "The temporary file {} no longer exists, the editor may have moved or deleted the temporary file",
+                tmp_path.display()
+            );
+        }
+    }
+    let mut f = f.context(
+        "Unable to read temporary file, the editor may have moved or deleted the temporary file",
+    )?;
+
+    string_read::read_file_with_options(&mut f, text_options)
+}
+
+/// Find the output format given by --output-format or one of its aliases,
+/// if any
+fn output_format(matches: &Matches) -> Result<Option<OutputFormat>> {
+    let mut ret = Vec::new();
+
+    if let Some(x) = matches.opt_str("output-format") {
+        match OUTPUT_FORMATS.iter().find(|(name, _)| *name == x) {
+            Some((_, format)) => ret.push(*format),
+            None => {
+                let names: Vec<&str> = OUTPUT_FORMATS.iter().map(|(name, _)| *name).collect();
+                bail!(
+                    "Unknown output format {}, expected one of {}",
+                    x,
+                    names.join(", ")
+                );
+            }
+        }
+    }
+    for (name, format) in OUTPUT_FORMATS {
+        if *format != OutputFormat::Text
+            && *format != OutputFormat::Snbt
+            && matches.opt_present(name)
+        {
+            ret.push(*format);
+        }
+    }
+
+    /* Giving the same format twice is harmless */
+    ret.dedup();
+    if ret.len() > 1 {
+        bail!("You can only specify one output format at a time.");
+    }
 
-    string_read::read_file(&mut f)
+    Ok(ret.pop())
 }
 
 /// When the user wants to print an NBT file to text format
-fn print(input: &str, output: &str) -> Result<i32> {
+///
+/// If preview is given, only that many tags inside of the root compound are
+/// read and printed, followed by a note saying how many were left out.
+/// text_format applies to the formats that are based on the pretty text
+/// format.
+fn print(
+    input: &str,
+    output: &str,
+    options: &ReadOptions,
+    preview: Option<usize>,
+    components: bool,
+    format: OutputFormat,
+    text_format: &TextFormat,
+) -> Result<i32> {
     /* First we read a NBTFile from the input */
-    let nbt = if input == "-" {
-        let f = io::stdin();
-        let mut f = f.lock();
-        read::read_file(&mut f).context(format_err!(
-            "Unable to parse {}, are you sure it's an NBT file?",
-            input
-        ))?
-    } else {
-        let path: &Path = Path::new(input);
-        let f = File::open(path).context(format_err!("Unable to open file {}", input))?;
-        let mut f = BufReader::new(f);
-
-        read::read_file(&mut f).context(format_err!(
-            "Unable to parse {}, are you sure it's an NBT file?",
-            input
-        ))?
+    let (nbt, skipped) = match preview {
+        Some(limit) => read_input_with(input, |f| read::read_file_preview(f, limit, options))?,
+        None if components => (read_input_with(input, components::read_components)?, 0),
+        None => (read_input(input, options)?, 0),
     };
 
     /* Then we write the NBTFile to the output in text format */
@@ -331,61 +1577,1540 @@ fn print(input: &str, output: &str) -> Result<i32> {
          * with exit code 1. (It can generally be assumed that nbted will not
          * error in serializing the data, so any error here would be because of
          * writing to stdout) */
-        match string_write::write_file(&mut f, &nbt) {
+        match write_print(&mut f, &nbt, skipped, format, text_format) {
             Ok(()) => (),
             Err(_) => return Ok(1),
         }
     } else {
-        let path: &Path = Path::new(output);
-        let f = File::create(&path).context(format_err!(
-            "Unable to write to output NBT file {}. Nothing was changed.",
-            output
-        ))?;
-        let mut f = BufWriter::new(f);
-
-        string_write::write_file(&mut f, &nbt).context(
-            format_err!("Error writing NBT file {}. State of NBT file is unknown, consider restoring it from a backup.",
-                       output))?;
+        replace_output_file(output, "output file", |f| {
+            write_print(f, &nbt, skipped, format, text_format)
+        })?;
     }
 
     Ok(0)
 }
 
-/// When the user wants to convert a text format file into an NBT file
+/// Write a printed file in the given format, noting any skipped tags
+fn write_print<W: Write>(
+    w: &mut W,
+    nbt: &NBTFile,
+    skipped: usize,
+    format: OutputFormat,
+    text_format: &TextFormat,
+) -> Result<()> {
+    match format {
+        OutputFormat::Text => string_write::write_file_with(w, nbt, text_format)?,
+        OutputFormat::PrettyCompact => {
+            let text_format = TextFormat {
+                arrays: ArrayFormat::SingleLine,
+                ..*text_format
+            };
+            string_write::write_file_with(w, nbt, &text_format)?
+        }
+        OutputFormat::Snbt => {
+            /* SNBT is only the root compound, without its name */
+            match &nbt.root {
+                NBT::Compound(x) if x.len() == 1 => snbt_write::write_snbt(w, &x[0].1)?,
+                NBT::Compound(x) => bail!(
+                    "Unable to print the file as SNBT, which can only hold a single root compound, but the file has {}",
+                    x.len()
+                ),
+                _ => bail!("Unable to print the file as SNBT, its root isn't a compound"),
+            }
+            writeln!(w)?;
+        }
+        OutputFormat::Json | OutputFormat::JsonPretty => {
+            let value = nbt
+                .root
+                .to_tagged_json()
+                .context("Unable to print the file as JSON")?;
+            if format == OutputFormat::Json {
+                serde_json::to_writer(&mut *w, &value)?;
+            } else {
+                serde_json::to_writer_pretty(&mut *w, &value)?;
+            }
+            writeln!(w)?;
+        }
+    }
+    if skipped > 0 {
+        writeln!(w, "… (truncated, {} more top-level tags)", skipped)?;
+    }
+    Ok(())
+}
+
+/// When the user wants to print the chunks in a region file that were saved
+/// at or after the given Unix timestamp. Each chunk is preceded by a comment
+/// giving its coordinates within the region and when it was saved.
 ///
 /// Returns an integer representing the program's exit status.
-fn reverse(input: &str, output: &str) -> Result<i32> {
-    /* First we read the input file in the text format */
-    let path: &Path = Path::new(input);
-    let mut f = File::open(&path).context(format_err!("Unable to read text file {}", input))?;
+fn print_region_since(
+    input: &str,
+    output: &str,
+    options: &ReadOptions,
+    since: u32,
+    format: OutputFormat,
+    text_format: &TextFormat,
+    units: Units,
+) -> Result<i32> {
+    let f = open_input_file(input)?;
+    let mut chunks = region::ChunkIter::with_options(BufReader::new(f), options)
+        .context(format_err!(
+            "Unable to parse {}, are you sure it's a region file?",
+            input
+        ))?
+        .since(since);
+    let total = chunks.chunk_count();
 
-    let nbt = string_read::read_file(&mut f)
-        .context(format_err!("Unable to parse text file {}", input))?;
+    /* Each chunk is printed as soon as it's read, so only one is in memory
+     * at a time */
+    let mut count = 0;
+    let mut write = |mut w: &mut dyn Write| -> Result<()> {
+        while let Some((pos, nbt)) = chunks.next() {
+            let nbt = nbt.context(format_err!(
+                "Unable to parse chunk {}, {} in {}",
+                pos.x,
+                pos.z,
+                input
+            ))?;
+            if count > 0 {
+                writeln!(w)?;
+            }
+            writeln!(
+                w,
+                "# Chunk {}, {} saved at {}",
+                pos.x,
+                pos.z,
+                chunks.timestamp(pos).unwrap_or(0)
+            )?;
+            write_print(&mut w, &nbt, 0, format, text_format)?;
+            count += 1;
+        }
+        Ok(())
+    };
 
-    /* Then we write the parsed NBT to the output file in NBT format */
-    if output == "-" {
+    let status = if output == "-" {
         let f = io::stdout();
         let mut f = f.lock();
-        /* If we get an error writing to stdout, we want to just silently exit
-         * with exit code 1. (It can generally be assumed that nbted will not
-         * error in serializing the data, so any error here would be because of
-         * writing to stdout) */
-        match write::write_file(&mut f, &nbt) {
-            Ok(()) => (),
-            Err(_) => return Ok(1),
+        /* Like print, an error writing to stdout silently exits with 1, while
+         * a chunk that can't be parsed is still reported */
+        match write(&mut f).and_then(|()| Ok(f.flush()?)) {
+            Ok(()) => 0,
+            Err(e) if e.downcast_ref::<io::Error>().is_some() => 1,
+            Err(e) => return Err(e),
         }
     } else {
-        let path: &Path = Path::new(output);
-        let f = File::create(&path).context(format_err!(
-            "Unable to write to output NBT file {}. Nothing was changed",
-            output
-        ))?;
-        let mut f = BufWriter::new(f);
+        replace_output_file(output, "output file", |f| write(f))?;
+        0
+    };
 
-        write::write_file(&mut f, &nbt).context(
-            format_err!("error writing to NBT FILE {}, state of NBT file is unknown, consider restoring it from a backup.",
-                       output))?;
-    }
+    eprintln!(
+        "{} of {} chunks were saved at or after {}.",
+        units.count(count),
+        units.count(total),
+        since
+    );
+
+    Ok(status)
+}
+
+/// When the user wants to convert a text format file into an NBT file
+///
+/// If strict is set, a compound with the same key more than once is an
+/// error. If estimate_size is given, the file isn't written, and instead how large
+/// it would be is printed to stdout in those units.
+///
+/// Returns an integer representing the program's exit status.
+#[allow(clippy::too_many_arguments)]
+fn reverse(
+    input: &str,
+    output: &str,
+    format: Option<ReverseFormat>,
+    strict: bool,
+    components: bool,
+    write_options: &WriteOptions,
+    overwrite: Overwrite,
+    text_sidecar: bool,
+    estimate_size: Option<Units>,
+) -> Result<i32> {
+    /* First we read the input file in the text format */
+    let mut text = Vec::new();
+    let _: usize = open_input_file(input)?
+        .read_to_end(&mut text)
+        .context(format_err!("Unable to read text file {}", input))?;
+
+    let format = format.unwrap_or(if snbt_read::looks_like_snbt(&text) {
+        ReverseFormat::Snbt
+    } else {
+        ReverseFormat::Native
+    });
+    let nbt = match format {
+        ReverseFormat::Native | ReverseFormat::Braces => {
+            let options = TextReadOptions {
+                braces: format == ReverseFormat::Braces,
+                forbid_duplicate_keys: strict,
+                ..Default::default()
+            };
+            string_read::read_file_with_options(&mut io::Cursor::new(text), &options)
+                .context(format_err!("Unable to parse text file {}", input))?
+        }
+        ReverseFormat::Snbt => {
+            let text = String::from_utf8(text).context(format_err!(
+                "Unable to parse SNBT file {}, it isn't valid UTF-8",
+                input
+            ))?;
+            let root = snbt_read::read_snbt(&text)
+                .context(format_err!("Unable to parse SNBT file {}", input))?;
+            if let NBT::Compound(_) = root {
+            } else {
+                bail!(
+                    "Unable to use SNBT file {}, it is a {} but must be a compound",
+                    input,
+                    root.type_string()
+                );
+            }
+            /* SNBT is only the root compound, which is stored with an empty
+             * name, and doesn't say anything about compression */
+            NBTFile {
+                root: NBT::Compound(vec![(Vec::new(), root)]),
+                compression: Compression::None,
+                compression_level: None,
+            }
+        }
+    };
+
+    if components {
+        let mut buf = Vec::new();
+        components::write_components(&mut buf, &nbt)
+            .context(format_err!("Unable to write {} as item components", input))?;
+        return write_text_output(output, &buf);
+    }
+
+    if let Some(units) = estimate_size {
+        return print_size_estimate(input, nbt, write_options, units);
+    }
+
+    /* Then we write the parsed NBT to the output file in NBT format */
+    let ret = write_output(output, &[], &nbt, write_options, overwrite)?;
+    if ret == 0 && text_sidecar {
+        write_sidecar(output, &nbt)?;
+    }
+
+    Ok(ret)
+}
+
+/// Print how many bytes the NBT file would take up when written, both
+/// uncompressed and with the file's compression, as a single line
+fn print_size_estimate(
+    input: &str,
+    mut nbt: NBTFile,
+    write_options: &WriteOptions,
+    units: Units,
+) -> Result<i32> {
+    let compressed = match nbt.compression {
+        Compression::None => None,
+        _ => Some(write::file_len(&nbt, write_options)?),
+    };
+    let compression = std::mem::replace(&mut nbt.compression, Compression::None);
+
+    let mut line = format!(
+        "{}: {} uncompressed",
+        input,
+        units.bytes(write::file_len(&nbt, write_options)?)
+    );
+    if let Some(x) = compressed {
+        line.push_str(&format!(
+            ", {} with {} compression",
+            units.bytes(x),
+            compression.to_str()
+        ));
+    }
+    line.push('\n');
+
+    write_text_output("-", line.as_bytes())
+}
+
+/// Write the text format of the NBT file next to the binary output file, as
+/// the output file name with .txt appended. Only called once the binary
+/// output has been written successfully.
+fn write_sidecar(output: &str, nbt: &NBTFile) -> Result<()> {
+    let path = format!("{}.txt", output);
+    let f = File::create(&path).context(format_err!("Unable to write to text file {}", path))?;
+    let mut f = BufWriter::new(f);
+
+    string_write::write_file(&mut f, nbt)
+        .and_then(|()| f.flush().map_err(|e| e.into()))
+        .context(format_err!("Error writing text file {}", path))?;
+
+    Ok(())
+}
+
+/// When the user wants to see the differences between two NBT files
+///
+/// Returns an integer representing the program's exit status, which is 0 if
+/// the trees are the same and 1 if they differ.
+fn diff(
+    old: &str,
+    new: &str,
+    output: &str,
+    options: &ReadOptions,
+    unified: bool,
+    list_key: Option<&str>,
+) -> Result<i32> {
+    let old_nbt = read_input(old, options)?;
+    let new_nbt = read_input(new, options)?;
+
+    let changes = match list_key {
+        Some(key) => diff::diff_by_key(&old_nbt.root, &new_nbt.root, key.as_bytes()),
+        None => diff::diff(&old_nbt.root, &new_nbt.root),
+    };
+
+    let mut buf = Vec::new();
+    if unified {
+        diff::write_unified(&mut buf, old, new, &changes)?;
+    } else {
+        diff::write_changes(&mut buf, &changes)?;
+    }
+    if old_nbt.compression != new_nbt.compression {
+        eprintln!(
+            "Compression differs: {} -> {}",
+            old_nbt.compression.to_str(),
+            new_nbt.compression.to_str()
+        );
+    }
+
+    let _: i32 = write_text_output(output, &buf)?;
+    Ok(if changes.is_empty() { 0 } else { 1 })
+}
+
+/// When the user wants a binary patch of the changes from one file to another
+///
+/// Returns an integer representing the program's exit status.
+fn make_binary_patch(
+    old: &str,
+    new: &str,
+    output: &str,
+    options: &ReadOptions,
+    units: Units,
+) -> Result<i32> {
+    let old_nbt = read_input(old, options)?;
+    let new_nbt = read_input(new, options)?;
+
+    let ops = patch::make_patch(&old_nbt.root, &new_nbt.root);
+    let mut buf = Vec::new();
+    patch::write_patch(&mut buf, &ops)?;
+    eprintln!(
+        "{} operations, {}.",
+        units.count(ops.len()),
+        units.bytes(buf.len() as u64)
+    );
+
+    write_text_output(output, &buf)
+}
+
+/// When the user wants to find where a number is stored
+///
+/// Returns an integer representing the program's exit status.
+fn find_int(
+    input: &str,
+    output: &str,
+    options: &ReadOptions,
+    number: &str,
+    units: Units,
+) -> Result<i32> {
+    let number = number.parse::<i64>().context(format_err!(
+        "Invalid --find-int {}, expected an integer",
+        number
+    ))?;
+
+    let nbt = read_input(input, options)?;
+    let found = nbt.root.find_value(|x| match *x {
+        NBT::Int(x) => i64::from(x) == number,
+        NBT::Long(x) => x == number,
+        _ => false,
+    });
+
+    let mut buf = Vec::new();
+    for path in &found {
+        writeln!(buf, "{}", path)?;
+    }
+    eprintln!("{} matching tags.", units.count(found.len()));
+
+    write_text_output(output, &buf)
+}
+
+/// When the user wants a single value out of a file, such as for a script
+///
+/// Returns an integer representing the program's exit status, which is 2 if
+/// there is no tag at the path.
+fn get(input: &str, output: &str, options: &ReadOptions, path: &str) -> Result<i32> {
+    let nbt = read_input(input, options)?;
+    let tag = match nbt.root.get_path(path) {
+        Some(x) => x,
+        None => {
+            /* Find the first component that doesn't resolve, to say which
+             * part of the path is wrong */
+            let components: Vec<&str> = path.split('/').collect();
+            let i = (1..components.len())
+                .find(|&i| nbt.root.get_path(components[..i].join("/")).is_none())
+                .unwrap_or(components.len())
+                - 1;
+            let parent = components[..i].join("/");
+            eprintln!(
+                "Unable to get {}, there is no \"{}\" in {}",
+                path,
+                components[i],
+                if i == 0 { "the root" } else { &parent }
+            );
+            return Ok(2);
+        }
+    };
+
+    let mut buf = Vec::new();
+    string_write::write_value(&mut buf, &tag, &TextFormat::default())?;
+
+    write_text_output(output, &buf)
+}
+
+/// When the user wants to know which items, blocks and the like a file
+/// refers to, by the Strings stored under keys such as id
+///
+/// Returns an integer representing the program's exit status.
+fn list_ids(
+    input: &str,
+    output: &str,
+    options: &ReadOptions,
+    extra_keys: Option<&str>,
+    units: Units,
+) -> Result<i32> {
+    let mut keys: Vec<&[u8]> = ID_KEYS.iter().map(|x| x.as_bytes()).collect();
+    if let Some(extra_keys) = extra_keys {
+        for key in extra_keys.split(',') {
+            if key.is_empty() {
+                bail!("Invalid --id-keys {}, a key is empty", extra_keys);
+            }
+            keys.push(key.as_bytes());
+        }
+    }
+
+    let nbt = read_input(input, options)?;
+    let ids = nbt.root.count_ids(&keys);
+
+    let mut buf = Vec::new();
+    for (id, count) in &ids {
+        writeln!(buf, "{}\t{}", id, count)?;
+    }
+    eprintln!(
+        "{} distinct IDs, occurring {} times.",
+        units.count(ids.len()),
+        units.count(ids.values().sum())
+    );
+
+    write_text_output(output, &buf)
+}
+
+/// When the user wants to find the problems in a file that parses, such as
+/// lists of mixed types or duplicate keys
+///
+/// Returns an integer representing the program's exit status, which is 1 if
+/// any problems were found.
+fn lint(input: &str, output: &str, options: &ReadOptions, units: Units) -> Result<i32> {
+    let issues = read_input_with(input, |f| lint::lint_file(f, options))?;
+
+    let mut buf = Vec::new();
+    for issue in &issues {
+        writeln!(buf, "{}: {}", issue.path, issue.kind)?;
+    }
+    eprintln!("{} problems found.", units.count(issues.len()));
+
+    let status = write_text_output(output, &buf)?;
+    if status == 0 && !issues.is_empty() {
+        Ok(1)
+    } else {
+        Ok(status)
+    }
+}
+
+/// When the user wants the bytes around where a file fails to parse, to
+/// attach to a bug report
+///
+/// Exits with a nonzero status if the file failed to parse.
+fn extract_error(input: &str, output: &str, options: &ReadOptions) -> Result<i32> {
+    let failure = match read_input_with(input, |f| repro::extract_error(f, options))? {
+        Some(x) => x,
+        None => {
+            eprintln!("{} parsed without any errors.", input);
+            return Ok(0);
+        }
+    };
+
+    let mut buf = Vec::new();
+    repro::write_report(&mut buf, input, &failure)?;
+
+    let status = write_text_output(output, &buf)?;
+    if status == 0 {
+        Ok(1)
+    } else {
+        Ok(status)
+    }
+}
+
+/// When the user wants to check every file in a directory, such as a world
+/// folder, for whether it parses
+///
+/// Every file that fails is reported, and the exit status is nonzero if any
+/// of them did.
+fn check_recursive(dir: &str, options: &ReadOptions, units: Units) -> Result<i32> {
+    if !Path::new(dir).is_dir() {
+        bail!(
+            "--check --recursive needs a directory, but {} isn't one",
+            dir
+        );
+    }
+    let mut files = Vec::new();
+    let unreadable = list_files(Path::new(dir), &mut files)?;
+
+    let mut failed = 0;
+    for path in &files {
+        let input = path.to_string_lossy();
+        if let Err(e) = read_input(&input, options) {
+            failed += 1;
+            print_error(&e);
+        }
+    }
+
+    let passed = files.len() - failed;
+    failed += unreadable;
+    eprintln!(
+        "{} files passed, {} failed.",
+        units.count(passed),
+        units.count(failed)
+    );
+
+    Ok(if failed == 0 { 0 } else { 1 })
+}
+
+/// When the user wants to print every NBT file in a directory, such as a
+/// world folder, each to a .txt file next to it
+///
+/// Files that don't start like an NBT file are skipped, and files that can't
+/// be read or fail to parse are reported without stopping the others. The exit status is
+/// nonzero if any file failed.
+fn print_recursive(
+    dir: &str,
+    options: &ReadOptions,
+    preview: Option<usize>,
+    components: bool,
+    format: OutputFormat,
+    text_format: &TextFormat,
+    units: Units,
+) -> Result<i32> {
+    if !Path::new(dir).is_dir() {
+        bail!(
+            "--print --recursive needs a directory, but {} isn't one",
+            dir
+        );
+    }
+
+    let mut files = Vec::new();
+    let mut failed = list_files(Path::new(dir), &mut files)?;
+
+    let (mut converted, mut skipped) = (0, 0);
+    for path in &files {
+        let input = path.to_string_lossy();
+        match looks_like_nbt(path) {
+            Ok(true) => (),
+            Ok(false) => {
+                skipped += 1;
+                eprintln!("Skipping {}, it doesn't look like an NBT file", input);
+                continue;
+            }
+            /* Such as a file without read permission, which shouldn't stop
+             * the others from being converted */
+            Err(e) => {
+                failed += 1;
+                print_error(&e);
+                continue;
+            }
+        }
+
+        let output = format!("{}.txt", input);
+        match print(
+            &input,
+            &output,
+            options,
+            preview,
+            components,
+            format,
+            text_format,
+        ) {
+            Ok(0) => converted += 1,
+            Ok(x) => return Ok(x),
+            Err(e) => {
+                failed += 1;
+                print_error(&e);
+            }
+        }
+    }
+
+    eprintln!(
+        "{} files converted, {} skipped, {} failed.",
+        units.count(converted),
+        units.count(skipped),
+        units.count(failed)
+    );
+
+    Ok(if failed == 0 { 0 } else { 1 })
+}
+
+/// Whether the first byte of the file is one that an NBT file, compressed
+/// or not, can start with
+fn looks_like_nbt(path: &Path) -> Result<bool> {
+    let mut byte = [0];
+    let n = File::open(path)
+        .and_then(|mut f| f.read(&mut byte))
+        .context(format!("Unable to read file {}", path.display()))?;
+    Ok(n == 1 && Compression::from_first_byte(byte[0]).is_some())
+}
+
+/// Add every file in the directory and its subdirectories to files, sorted
+/// by path so that the order doesn't depend on the file system
+///
+/// Symbolic links to directories aren't followed, so that a link back up the
+/// tree can't make this go on forever. A subdirectory that can't be read is
+/// reported without stopping the rest, and the number of them is returned.
+fn list_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<usize> {
+    let mut entries = fs::read_dir(dir)
+        .context(format!("Unable to read directory {}", dir.display()))?
+        .map(|x| x.map(|x| x.path()))
+        .collect::<io::Result<Vec<PathBuf>>>()
+        .context(format!("Unable to read directory {}", dir.display()))?;
+    entries.sort();
+
+    let mut failed = 0;
+    for path in entries {
+        let metadata = match fs::symlink_metadata(&path)
+            .context(format!("Unable to read {}", path.display()))
+        {
+            Ok(x) => x,
+            Err(e) => {
+                failed += 1;
+                print_error(&e.into());
+                continue;
+            }
+        };
+
+        if metadata.is_dir() {
+            match list_files(&path, files) {
+                Ok(x) => failed += x,
+                Err(e) => {
+                    failed += 1;
+                    print_error(&e);
+                }
+            }
+        } else if metadata.file_type().is_symlink() && path.is_dir() {
+            eprintln!("Skipping {}, it's a link to a directory", path.display());
+        } else {
+            files.push(path);
+        }
+    }
+
+    Ok(failed)
+}
+
+/// When the user wants to know what takes up the space in a file
+///
+/// Returns an integer representing the program's exit status.
+fn size_breakdown(input: &str, output: &str, options: &ReadOptions, units: Units) -> Result<i32> {
+    let nbt = read_input(input, options)?;
+    let mut sizes = nbt.root.size_breakdown()?;
+
+    /* The implicit compound has no End tag, so the file is exactly the tags
+     * directly inside of it */
+    let total: u64 = sizes
+        .iter()
+        .filter(|(path, _, _)| path.segments().len() == 1)
+        .map(|(_, _, size)| size)
+        .sum();
+
+    /* Single numbers are never the culprit, so leave them out to keep the
+     * list readable */
+    sizes.retain(|(path, tag, _)| match tag {
+        NBT::Byte(_)
+        | NBT::Short(_)
+        | NBT::Int(_)
+        | NBT::Long(_)
+        | NBT::Float(_)
+        | NBT::Double(_) => false,
+        _ => !path.segments().is_empty(),
+    });
+    sizes.sort_by_key(|(_, _, size)| std::cmp::Reverse(*size));
+
+    let mut buf = Vec::new();
+    writeln!(buf, "{} decompressed", units.bytes(total))?;
+    for (path, _, size) in &sizes {
+        let percent = if total == 0 {
+            0.0
+        } else {
+            *size as f64 * 100.0 / total as f64
+        };
+        let size = if units.raw {
+            size.to_string()
+        } else {
+            humanize::bytes(*size)
+        };
+        writeln!(buf, "{:>12} {:>6.2}%  {}", size, percent, path)?;
+    }
+
+    write_text_output(output, &buf)
+}
+
+/// When the user wants to write every chunk in a region file to a file of
+/// its own, named chunk.X.Z.nbt after the coordinates of the chunk within the
+/// region. The chunks are written exactly as stored, so they keep their
+/// compression, and the modification time of each file is set to the
+/// timestamp of the chunk.
+///
+/// Returns an integer representing the program's exit status.
+fn split_region(input: &str, output: &str, units: Units) -> Result<i32> {
+    let f = open_input_file(input)?;
+    let mut chunks = region::ChunkIter::new(BufReader::new(f)).context(format_err!(
+        "Unable to parse {}, are you sure it's a region file?",
+        input
+    ))?;
+
+    fs::create_dir_all(output).context(format_err!("Unable to create directory {}", output))?;
+
+    /* The chunks are copied as they are stored, one at a time */
+    let mut count = 0;
+    while let Some((pos, chunk)) = chunks.next_raw() {
+        let chunk = chunk.context(format_err!(
+            "Unable to read chunk {}, {} in {}",
+            pos.x,
+            pos.z,
+            input
+        ))?;
+        let path = Path::new(output).join(format!("chunk.{}.{}.nbt", chunk.x, chunk.z));
+        let mut f =
+            File::create(&path).context(format_err!("Unable to create file {}", path.display()))?;
+        f.write_all(&chunk.data)
+            .context(format_err!("Unable to write file {}", path.display()))?;
+        f.set_modified(UNIX_EPOCH + Duration::from_secs(u64::from(chunk.timestamp)))
+            .context(format_err!(
+                "Unable to set the timestamp of {}",
+                path.display()
+            ))?;
+        count += 1;
+    }
+
+    eprintln!("Wrote {} chunks to {}.", units.count(count), output);
 
     Ok(0)
 }
+
+/// When the user wants to put the chunk files written by split_region back
+/// together into a region file
+///
+/// Returns an integer representing the program's exit status.
+fn join_region(input: &str, output: &str, units: Units) -> Result<i32> {
+    let mut chunks = Vec::new();
+
+    for entry in fs::read_dir(input).context(format_err!("Unable to read directory {}", input))? {
+        let entry = entry.context(format_err!("Unable to read directory {}", input))?;
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+
+        let (x, z) = match parse_chunk_name(&name)? {
+            Some(x) => x,
+            None => {
+                eprintln!("Ignoring {}, which is not a chunk file", name);
+                continue;
+            }
+        };
+
+        let path = entry.path();
+        let data =
+            fs::read(&path).context(format_err!("Unable to read file {}", path.display()))?;
+        let modified = entry
+            .metadata()
+            .and_then(|x| x.modified())
+            .context(format_err!(
+                "Unable to read the timestamp of {}",
+                path.display()
+            ))?;
+        let timestamp = match modified.duration_since(UNIX_EPOCH) {
+            Ok(x) => x.as_secs().min(u64::from(u32::MAX)) as u32,
+            Err(_) => 0,
+        };
+
+        chunks.push(region::Chunk {
+            x,
+            z,
+            timestamp,
+            data,
+        });
+    }
+
+    /* Write the chunks in the same order as the tables in the region file, so
+     * that the output doesn't depend on the order of the directory */
+    chunks.sort_by_key(|x| (x.z, x.x));
+
+    replace_output_file(output, "region file", |f| region::write_region(f, &chunks))?;
+
+    eprintln!("Wrote {} chunks to {}.", units.count(chunks.len()), output);
+
+    Ok(0)
+}
+
+/// Parse the coordinates out of a chunk file name of the form chunk.X.Z.nbt,
+/// returning None if it isn't one, and an error if the coordinates aren't
+/// within a region
+fn parse_chunk_name(name: &str) -> Result<Option<(u8, u8)>> {
+    let coordinates = match name
+        .strip_prefix("chunk.")
+        .and_then(|x| x.strip_suffix(".nbt"))
+    {
+        Some(x) => x,
+        None => return Ok(None),
+    };
+
+    let mut split = coordinates.splitn(2, '.');
+    let (x, z) = match (split.next(), split.next()) {
+        (Some(x), Some(z)) => (x, z),
+        _ => bail!("Invalid chunk file name {}, expected chunk.X.Z.nbt", name),
+    };
+    let parse = |x: &str| -> Result<u8> {
+        match x.parse::<u8>() {
+            Ok(x) if x < region::REGION_SIZE => Ok(x),
+            _ => bail!(
+                "Invalid chunk coordinate {} in {}, chunk coordinates must be from 0 to {}",
+                x,
+                name,
+                region::REGION_SIZE - 1
+            ),
+        }
+    };
+
+    Ok(Some((parse(x)?, parse(z)?)))
+}
+
+/// When the user wants to find the strings matching a regular expression
+///
+/// Returns an integer representing the program's exit status.
+#[cfg(feature = "grep")]
+fn grep(
+    input: &str,
+    output: &str,
+    options: &ReadOptions,
+    pattern: &str,
+    units: Units,
+) -> Result<i32> {
+    /* Match on the raw bytes, so that strings which aren't valid UTF-8 can
+     * still be searched */
+    let re = regex::bytes::Regex::new(pattern)
+        .context(format_err!("Invalid regular expression {}", pattern))?;
+
+    let nbt = read_input(input, options)?;
+    let found = nbt.root.find_strings(|x| re.is_match(x));
+
+    let mut buf = Vec::new();
+    for (path, val) in &found {
+        writeln!(buf, "{}: {}", path, String::from_utf8_lossy(val))?;
+    }
+    eprintln!("{} matching strings.", units.count(found.len()));
+
+    write_text_output(output, &buf)
+}
+
+#[cfg(not(feature = "grep"))]
+fn grep(
+    _input: &str,
+    _output: &str,
+    _options: &ReadOptions,
+    _pattern: &str,
+    _units: Units,
+) -> Result<i32> {
+    bail!("Unable to --grep, nbted was built without the grep feature");
+}
+
+/// When the user wants to apply one of the TRANSFORMS to a file without
+/// opening an editor
+///
+/// Returns an integer representing the program's exit status.
+#[allow(clippy::too_many_arguments)]
+fn transform(
+    input: &str,
+    output: &str,
+    options: &ReadOptions,
+    write_options: &WriteOptions,
+    overwrite: Overwrite,
+    matches: &Matches,
+    operands: &[String],
+    units: Units,
+) -> Result<i32> {
+    if let Some(x) = matches.opt_str("recompress") {
+        let compression = parse_compression(&x)?;
+        /* Recompressing a file with the compression it already has would
+         * only decompress and compress it again, so it is copied as it is,
+         * keeping its exact bytes. This needs the file to be peeked at, so
+         * stdin is always read in full. */
+        if input != "-"
+            && !options.recursive_decompress
+            && *write_options == WriteOptions::default()
+            && peek_input_compression(input, options)? == Some(compression.clone())
+        {
+            eprintln!(
+                "{} is already {} compressed, it is left as it is.",
+                input,
+                compression.to_str()
+            );
+            return copy_input(input, output, overwrite);
+        }
+    }
+
+    /* Converting the byte order is the only transformation that changes how
+     * the file is read and written rather than what is in it */
+    let mut options = *options;
+    let mut write_options = *write_options;
+    let convert_endian = match matches.opt_str("convert-endian") {
+        Some(x) => {
+            let target = parse_endianness(&x)?;
+            options.endianness = opposite_endianness(target);
+            write_options.endianness = target;
+            Some(target)
+        }
+        None => None,
+    };
+
+    let (prefix, mut nbt) = match read_input_with_prefix(input, &options) {
+        Ok(x) => x,
+        Err(e) => match convert_endian {
+            /* A file that can't be read in the source byte order but can in
+             * the target one was most likely already converted */
+            Some(target) => {
+                let target_options = ReadOptions {
+                    endianness: target,
+                    ..options
+                };
+                if read_input_with_prefix(input, &target_options).is_ok() {
+                    bail!(
+                        "{} appears to already be {} endian",
+                        input,
+                        endianness_to_str(target)
+                    );
+                }
+                return Err(e);
+            }
+            None => return Err(e),
+        },
+    };
+
+    if let Some(x) = matches.opt_str("apply-binary-patch") {
+        apply_binary_patch(&mut nbt, &x, units)?;
+    } else if let Some(x) = matches.opt_str("clamp") {
+        let (min, max) = parse_clamp_range(&operands[0], &operands[1])?;
+        let count = nbt.root.clamp_numeric(&PathFilter::new(&x), min, max)?;
+        eprintln!("Clamped {} values.", units.count(count));
+    } else if matches.opt_present("coerce-lists") {
+        let count = nbt.root.coerce_lists(matches.opt_present("force"))?;
+        eprintln!("Converted {} list elements.", units.count(count));
+    } else if let Some(target) = convert_endian {
+        warn_endian_conversion(&nbt, target);
+        eprintln!(
+            "Converting from {} to {} endian.",
+            endianness_to_str(options.endianness),
+            endianness_to_str(target)
+        );
+    } else if let Some(x) = matches.opt_str("copy") {
+        let from = match matches.opt_str("copy-from") {
+            Some(x) => Some(read_input(&x, &options)?),
+            None => None,
+        };
+        copy(&mut nbt, from.as_ref(), &x, &operands[0])?;
+    } else if let Some(x) = matches.opt_str("merge") {
+        let overlay = read_input(&x, &options)?;
+        merge(&mut nbt, input, &overlay, &x, units)?;
+    } else if let Some(x) = matches.opt_str("recompress") {
+        let compression = parse_compression(&x)?;
+        eprintln!(
+            "Recompressing from {} to {}.",
+            nbt.compression.to_str(),
+            compression.to_str()
+        );
+        if nbt.compression != compression {
+            nbt.compression = compression;
+            nbt.compression_level = None;
+        }
+    } else if let Some(x) = matches.opt_str("remove") {
+        remove(&mut nbt, &x, units)?;
+    } else if let Some(x) = matches.opt_str("rename-key") {
+        rename_keys(&mut nbt, &x, false, matches.opt_present("strict"), units)?;
+    } else if let Some(x) = matches.opt_str("rename-key-prefix") {
+        rename_keys(&mut nbt, &x, true, matches.opt_present("strict"), units)?;
+    } else if let Some(x) = matches.opt_str("set") {
+        set(&mut nbt, &x)?;
+    } else {
+        bail!("Internal error: No transformation selected. (Please report this.)");
+    }
+
+    write_output(output, &prefix, &nbt, &write_options, overwrite)
+}
+
+/// Parse the ORDER argument of --convert-endian
+fn parse_endianness(arg: &str) -> Result<Endianness> {
+    match arg.to_lowercase().as_str() {
+        "little" => Ok(Endianness::Little),
+        "big" => Ok(Endianness::Big),
+        _ => bail!(
+            "Invalid --convert-endian {}, expected either little or big",
+            arg
+        ),
+    }
+}
+
+fn opposite_endianness(endianness: Endianness) -> Endianness {
+    match endianness {
+        Endianness::Big => Endianness::Little,
+        Endianness::Little => Endianness::Big,
+    }
+}
+
+fn endianness_to_str(endianness: Endianness) -> &'static str {
+    match endianness {
+        Endianness::Big => "big",
+        Endianness::Little => "little",
+    }
+}
+
+/// Warn about what converting the byte order of the file doesn't change,
+/// but which the other edition may not read the same way
+fn warn_endian_conversion(nbt: &NBTFile, target: Endianness) {
+    if target == Endianness::Little && nbt.compression != Compression::None {
+        eprintln!(
+            "Warning: Bedrock Edition files are normally uncompressed, but the file is kept {} compressed.",
+            nbt.compression.to_str()
+        );
+    }
+
+    /* Java Edition stores strings in modified UTF-8, which encodes NUL and
+     * characters outside of the BMP differently from the plain UTF-8 of
+     * Bedrock Edition. Strings are copied byte for byte, so these would be
+     * read as something else. */
+    let modified = nbt.root.find_strings(|x| {
+        x.windows(2)
+            .any(|w| w == [0xc0, 0x80] || (w[0] == 0xed && (0xa0..=0xbf).contains(&w[1])))
+    });
+    if let Some((path, _)) = modified.first() {
+        eprintln!(
+            "Warning: {} strings, such as the one at {}, contain NUL or characters outside of the Basic Multilingual Plane, which are encoded differently by Java and Bedrock Edition and are copied as they are.",
+            modified.len(),
+            path
+        );
+    }
+
+    if target == Endianness::Little {
+        eprintln!("Warning: Bedrock Edition's level.dat also starts with an 8 byte header, which is not added.");
+    }
+}
+
+/// Apply the binary patch in the given file
+fn apply_binary_patch(nbt: &mut NBTFile, patch_file: &str, units: Units) -> Result<()> {
+    let f = open_input_file(patch_file)?;
+    let ops = patch::read_patch(&mut BufReader::new(f))
+        .context(format_err!("Unable to read binary patch {}", patch_file))?;
+    patch::apply_patch(&mut nbt.root, &ops)
+        .context(format_err!("Unable to apply binary patch {}", patch_file))?;
+    eprintln!("Applied {} operations.", units.count(ops.len()));
+
+    Ok(())
+}
+
+/// Parse the SCHEME argument of --recompress
+fn parse_compression(arg: &str) -> Result<Compression> {
+    match arg.to_lowercase().as_str() {
+        "none" => Ok(Compression::None),
+        "gzip" => Ok(Compression::Gzip),
+        "zlib" => Ok(Compression::Zlib),
+        "zstd" => Ok(Compression::Zstd),
+        "lz4" => Ok(Compression::Lz4),
+        _ => bail!(
+            "Invalid --recompress {}, expected one of none, gzip, zlib, zstd and lz4",
+            arg
+        ),
+    }
+}
+
+/// Find the compression of the input file from its first byte, after any
+/// bytes skipped with --skip-leading, without reading the rest of it
+fn peek_input_compression(input: &str, options: &ReadOptions) -> Result<Option<Compression>> {
+    let mut f = BufReader::new(open_input_file(input)?);
+    let _: Vec<u8> = read::read_leading(&mut f, options.skip_leading)?;
+    let first = f
+        .fill_buf()
+        .context(format_err!("Unable to read {}", input))?
+        .first()
+        .cloned();
+
+    Ok(first.and_then(Compression::from_first_byte))
+}
+
+/// Copy the input file to the output unchanged, where - means stdout,
+/// treating the file it replaces as given by overwrite. Nothing is done if
+/// they're the same file, even if given by different paths.
+///
+/// Returns an integer representing the program's exit status.
+fn copy_input(input: &str, output: &str, overwrite: Overwrite) -> Result<i32> {
+    if output == "-" {
+        let f = io::stdout();
+        let mut f = f.lock();
+        /* As with write_output, errors writing to stdout just exit with 1 */
+        if io::copy(&mut open_input_file(input)?, &mut f).is_err() {
+            return Ok(1);
+        }
+    } else if !is_same_file(input, output) {
+        let mtime = output_mtime(output, overwrite)?;
+        backup_output(output, overwrite.backup)?;
+        let mut f = open_input_file(input)?;
+        replace_output_file(output, "output NBT file", |w| {
+            let _: u64 = io::copy(&mut f, w).context(format_err!(
+                "Unable to copy {} to {}",
+                input,
+                output
+            ))?;
+            if let Some(mtime) = mtime {
+                w.flush()?;
+                w.get_ref().set_modified(mtime)?;
+            }
+            Ok(())
+        })?;
+    }
+
+    Ok(0)
+}
+
+/// Whether the two paths are the same existing file, such as h.nbt and
+/// ./h.nbt, or a symlink and what it points to
+fn is_same_file(a: &str, b: &str) -> bool {
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+/// Parse the MIN and MAX values of --clamp
+fn parse_clamp_range(min: &str, max: &str) -> Result<(i64, i64)> {
+    let min = min
+        .parse::<i64>()
+        .context(format_err!("Invalid minimum {} for --clamp", min))?;
+    let max = max
+        .parse::<i64>()
+        .context(format_err!("Invalid maximum {} for --clamp", max))?;
+
+    Ok((min, max))
+}
+
+/// Copy the tag at src to dst, from the file given by --copy-from if any
+fn copy(nbt: &mut NBTFile, from: Option<&NBTFile>, src: &str, dst: &str) -> Result<()> {
+    if dst.is_empty() {
+        bail!("Unable to replace the root of the file");
+    }
+
+    let from = from.unwrap_or(nbt);
+    let tag = match from.root.extract_path(&path::split(src)) {
+        Some(x) => x,
+        None => bail!("No tag at {} to copy", src),
+    };
+    let type_string = tag.type_string().to_string();
+    nbt.root.graft_path(&path::split(dst), tag)?;
+    eprintln!("Copied {} {} to {}.", type_string, src, dst);
+
+    Ok(())
+}
+
+/// Merge the root compound of overlay into that of nbt, see NBT::merge. The
+/// root compounds are merged whatever their names, and the name of nbt's is
+/// kept.
+fn merge(
+    nbt: &mut NBTFile,
+    name: &str,
+    overlay: &NBTFile,
+    overlay_name: &str,
+    units: Units,
+) -> Result<()> {
+    let overlay_root = root_compound(&overlay.root, overlay_name)?;
+    root_compound(&nbt.root, name)?;
+    let root = match nbt.root {
+        NBT::Compound(ref mut x) => &mut x[0].1,
+        _ => unreachable!(),
+    };
+
+    let count = root.merge(overlay_root)?;
+    eprintln!("Merged {} tags from {}.", units.count(count), overlay_name);
+
+    Ok(())
+}
+
+/// The single root compound of a file, failing if it has more than one root
+/// tag or its root tag isn't a compound
+fn root_compound<'a>(root: &'a NBT, name: &str) -> Result<&'a NBT> {
+    let roots = match root {
+        NBT::Compound(x) => x,
+        x => bail!(
+            "Unable to merge {}, its root is of type {} rather than Compound",
+            name,
+            x.type_string()
+        ),
+    };
+    match roots.as_slice() {
+        [(_, x)] if x.type_byte() == 10 => Ok(x),
+        [(_, x)] => bail!(
+            "Unable to merge {}, its root is of type {} rather than Compound",
+            name,
+            x.type_string()
+        ),
+        _ => bail!(
+            "Unable to merge {}, it has {} root tags rather than one",
+            name,
+            roots.len()
+        ),
+    }
+}
+
+/// Remove the tag at the given path, reporting how much smaller the file got
+fn remove(nbt: &mut NBTFile, arg: &str, units: Units) -> Result<()> {
+    if arg.is_empty() {
+        bail!("Unable to remove the root of the file");
+    }
+    let mut components = path::split(arg);
+    /* split always returns at least one component */
+    let last = components.pop().unwrap();
+
+    let parent = nbt.root.get_path_mut(&components)?;
+    /* Tags in a compound also have their type and name */
+    let header = match parent {
+        NBT::Compound(_) => 3 + last.len() as u64,
+        _ => 0,
+    };
+    let removed = parent.remove(last)?;
+    let size = match parent {
+        NBT::ByteArray(_) => 1,
+        NBT::IntArray(_) => 4,
+        NBT::LongArray(_) => 8,
+        _ => header + removed.payload_len()?,
+    };
+    eprintln!("Removed {} ({} uncompressed).", arg, units.bytes(size));
+
+    Ok(())
+}
+
+/// Set the tag at the path given as PATH=VALUE, reporting its old value
+fn set(nbt: &mut NBTFile, arg: &str) -> Result<()> {
+    /* Split from the left, since a String value may itself contain = */
+    let (path, value) = match arg.find('=') {
+        Some(i) => (&arg[..i], &arg[i + 1..]),
+        None => bail!("--set expects an argument of the form PATH=VALUE"),
+    };
+
+    let tag = nbt.root.get_path_mut(&path::split(path))?;
+    let old = tag.clone();
+    tag.set_from_str(value)
+        .context(format_err!("Unable to set {}", path))?;
+
+    let mut old_text = Vec::new();
+    string_write::write_value(&mut old_text, &old, &TextFormat::default())?;
+    eprintln!(
+        "Set {} {} from {} to {}.",
+        tag.type_string(),
+        path,
+        String::from_utf8_lossy(&old_text).trim_end(),
+        value
+    );
+
+    Ok(())
+}
+
+/// Rename the keys given as OLD=NEW, reporting what was done
+fn rename_keys(
+    nbt: &mut NBTFile,
+    arg: &str,
+    prefix: bool,
+    strict: bool,
+    units: Units,
+) -> Result<()> {
+    let (from, to) = match arg.find('=') {
+        Some(i) => (&arg[..i], &arg[i + 1..]),
+        None => bail!("Renaming keys expects an argument of the form OLD=NEW"),
+    };
+
+    let renamed = nbt
+        .root
+        .rename_keys(from.as_bytes(), to.as_bytes(), prefix, strict)?;
+    for path in &renamed.skipped {
+        eprintln!(
+            "Warning: Not renaming {}, since the new key already exists there",
+            path
+        );
+    }
+    eprintln!("Renamed {} keys.", units.count(renamed.count));
+
+    Ok(())
+}
+
+/// Read a binary NBT file from the input, where - means stdin and http://
+/// or https:// means downloading it
+fn read_input(input: &str, options: &ReadOptions) -> Result<NBTFile> {
+    Ok(read_input_with_prefix(input, options)?.1)
+}
+
+/// Read the input like read_input, also returning the leading bytes skipped
+/// because of --skip-leading, so that they can be written back
+#[cfg_attr(feature = "trace", tracing::instrument(skip(options)))]
+fn read_input_with_prefix(input: &str, options: &ReadOptions) -> Result<(Vec<u8>, NBTFile)> {
+    let (prefix, nbt, layers) = read_input_with(input, |f| {
+        let prefix = read::read_leading(f, options.skip_leading)?;
+        let options = &ReadOptions {
+            skip_leading: 0,
+            ..*options
+        };
+        if options.recursive_decompress {
+            let (nbt, layers) = read::read_file_recursive_decompress(f, options)?;
+            Ok((prefix, nbt, layers))
+        } else {
+            Ok((prefix, read::read_file_with_options(f, options)?, 1))
+        }
+    })?;
+
+    if layers > 1 {
+        eprintln!(
+            "{} was compressed {} times, it will be written back compressed once.",
+            input, layers
+        );
+    }
+
+    Ok((prefix, nbt))
+}
+
+/// Open the input the same way as read_input, but parse it with the given
+/// function
+fn read_input_with<T, F>(input: &str, read: F) -> Result<T>
+where
+    F: FnOnce(&mut Box<dyn BufRead>) -> Result<T>,
+{
+    if is_url(input) {
+        let buf = fetch_url(input)?;
+        let mut f: Box<dyn BufRead> = Box::new(io::Cursor::new(buf));
+        Ok(read(&mut f).context(format_err!(
+            "Unable to parse {}, are you sure it's an NBT file?",
+            input
+        ))?)
+    } else if input == "-" {
+        let mut f: Box<dyn BufRead> = Box::new(io::stdin().lock());
+        Ok(read(&mut f).context("Unable to parse any NBT files from stdin")?)
+    } else {
+        let f = open_input_file(input)?;
+        let mut f: Box<dyn BufRead> = Box::new(BufReader::new(f));
+
+        Ok(read(&mut f).context(format_err!(
+            "Unable to parse {}, are you sure it's an NBT file?",
+            input
+        ))?)
+    }
+}
+
+/// Open a file for reading, with a clear error if it's a directory, which
+/// would otherwise only fail with a confusing error once it's read from
+fn open_input_file(input: &str) -> Result<File> {
+    if Path::new(input).is_dir() {
+        bail!(
+            "Expected a file but {} is a directory (did you mean --check --recursive?)",
+            input
+        );
+    }
+
+    Ok(File::open(input).context(format!("Unable to open file {}", input))?)
+}
+
+fn is_url(input: &str) -> bool {
+    input.starts_with("http://") || input.starts_with("https://")
+}
+
+/// Download the file at the given URL into memory
+#[cfg(feature = "http")]
+fn fetch_url(url: &str) -> Result<Vec<u8>> {
+    let response = match ureq::get(url).call() {
+        Ok(x) => x,
+        Err(ureq::Error::Status(code, _)) => {
+            bail!(
+                "Unable to download {}, server responded with HTTP {}",
+                url,
+                code
+            )
+        }
+        /* ureq's own errors already name the URL */
+        Err(e) => bail!("Unable to download: {}", e),
+    };
+    if response.status() != 200 {
+        bail!(
+            "Unable to download {}, server responded with HTTP {}",
+            url,
+            response.status()
+        );
+    }
+
+    let mut buf = Vec::new();
+    let _: usize = response
+        .into_reader()
+        .read_to_end(&mut buf)
+        .context(format_err!("Error downloading {}", url))?;
+    Ok(buf)
+}
+
+#[cfg(not(feature = "http"))]
+fn fetch_url(url: &str) -> Result<Vec<u8>> {
+    bail!(
+        "Unable to download {}, nbted was built without the http feature",
+        url
+    );
+}
+
+/// Write already formatted text, or other bytes that aren't an NBT file, to
+/// the output, where - means stdout
+///
+/// Returns an integer representing the program's exit status.
+fn write_text_output(output: &str, text: &[u8]) -> Result<i32> {
+    if output == "-" {
+        let f = io::stdout();
+        let mut f = f.lock();
+        /* If we get an error writing to stdout, we want to just silently exit
+         * with exit code 1 */
+        if f.write_all(text).and_then(|()| f.flush()).is_err() {
+            return Ok(1);
+        }
+    } else {
+        replace_output_file(output, "output file", |f| Ok(f.write_all(text)?))?;
+    }
+
+    Ok(0)
+}
+
+/// Write the NBT file in binary form to the output, where - means stdout,
+/// preceded by the leading bytes that were skipped with --skip-leading,
+/// treating the file it replaces as given by overwrite
+///
+/// Returns an integer representing the program's exit status.
+#[cfg_attr(feature = "trace", tracing::instrument(skip_all, fields(output = %output)))]
+fn write_output(
+    output: &str,
+    prefix: &[u8],
+    nbt: &NBTFile,
+    options: &WriteOptions,
+    overwrite: Overwrite,
+) -> Result<i32> {
+    if output == "-" {
+        let f = io::stdout();
+        let mut f = f.lock();
+        /* If we get an error writing to stdout, we want to just silently exit
+         * with exit code 1. (It can generally be assumed that nbted will not
+         * error in serializing the data, so any error here would be because of
+         * writing to stdout) */
+        let written = f
+            .write_all(prefix)
+            .map_err(From::from)
+            .and_then(|()| write::write_file_with_options(&mut f, nbt, options));
+        match written {
+            Ok(()) => (),
+            Err(_) => return Ok(1),
+        }
+    } else {
+        let mtime = output_mtime(output, overwrite)?;
+        backup_output(output, overwrite.backup)?;
+        /* The file is replaced only once it's been written in full, so a
+         * crash or a full disk can't leave a truncated file behind */
+        replace_output_file(output, "output NBT file", |f| {
+            f.write_all(prefix)?;
+            write::write_file_with_options(f, nbt, options)?;
+            /* Set once everything is written, since writing changes it */
+            if let Some(mtime) = mtime {
+                f.flush()?;
+                f.get_ref().set_modified(mtime)?;
+            }
+            Ok(())
+        })?;
+    }
+
+    Ok(0)
+}
+
+/// Replace the output file with what write writes, see
+/// io::write_file_atomically, with an error that says whether the file was
+/// changed. what describes the file in the error, like "output file".
+fn replace_output_file<F>(output: &str, what: &str, write: F) -> Result<()>
+where
+    F: FnOnce(&mut BufWriter<File>) -> Result<()>,
+{
+    io_util::write_file_atomically(Path::new(output), write).map_err(|e| {
+        let message = if e.downcast_ref::<io_util::DirSyncError>().is_some() {
+            format!(
+                "Wrote {} {}, but it may be lost or corrupt after a crash",
+                what, output
+            )
+        } else {
+            format!(
+                "Unable to write to {} {}. Nothing was changed",
+                what, output
+            )
+        };
+        e.context(message).into()
+    })
+}
+
+/// The modification time to give the output file once it's replaced, if
+/// asked to keep it and there is a file to keep it from
+fn output_mtime(output: &str, overwrite: Overwrite) -> Result<Option<SystemTime>> {
+    match fs::metadata(output) {
+        Ok(ref x) if overwrite.keep_mtime && x.is_file() => Ok(Some(x.modified()?)),
+        _ => Ok(None),
+    }
+}
+
+/// Copy the output file to its backup, if asked to and there is a file to
+/// back up. Nothing is written to the output if this fails.
+fn backup_output(output: &str, backup: Option<Backup>) -> Result<()> {
+    let backup = match backup {
+        Some(x) => x,
+        None => return Ok(()),
+    };
+    match fs::metadata(output) {
+        Ok(ref x) if x.is_file() => (),
+        _ => return Ok(()),
+    }
+
+    let path = match backup {
+        Backup::Simple => format!("{}.bak", output),
+        Backup::Numbered => {
+            let time = UNIX_EPOCH.elapsed().map(|x| x.as_secs()).unwrap_or(0);
+            /* Two backups in the same second are told apart by a counter */
+            let mut path = format!("{}.{}.bak", output, time);
+            let mut n = 1;
+            while Path::new(&path).exists() {
+                path = format!("{}.{}-{}.bak", output, time, n);
+                n += 1;
+            }
+            path
+        }
+    };
+    let _: u64 = fs::copy(output, &path).context(format_err!(
+        "Unable to back up {} to {}. Nothing was changed",
+        output,
+        path
+    ))?;
+    File::open(&path)
+        .and_then(|f| f.sync_all())
+        .context(format_err!(
+            "Unable to back up {} to {}. Nothing was changed",
+            output,
+            path
+        ))?;
+    eprintln!("Backed up {} to {}.", output, path);
+
+    Ok(())
+}
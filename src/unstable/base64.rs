@@ -0,0 +1,55 @@
+//! Standard base64 (RFC 4648) with padding, as used to write ByteArrays in
+//! the text format with ByteArrayEncoding::Base64.
+
+use crate::Result;
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Encode the bytes as base64, padded with = to a multiple of 4 characters
+pub fn encode(bytes: &[u8]) -> String {
+    let mut ret = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let mut group = [0; 3];
+        group[..chunk.len()].copy_from_slice(chunk);
+        let bits = (u32::from(group[0]) << 16) | (u32::from(group[1]) << 8) | u32::from(group[2]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                ret.push(ALPHABET[(bits >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                ret.push('=');
+            }
+        }
+    }
+    ret
+}
+
+/// Decode base64 written by encode. The padding is required, and characters
+/// outside of the alphabet, including whitespace, are an error.
+pub fn decode(text: &str) -> Result<Vec<u8>> {
+    let text = text.as_bytes();
+    if !text.len().is_multiple_of(4) {
+        bail!(
+            "Invalid base64, its length {} isn't a multiple of 4",
+            text.len()
+        );
+    }
+    let mut ret = Vec::with_capacity(text.len() / 4 * 3);
+    for (i, chunk) in text.chunks(4).enumerate() {
+        let last = (i + 1) * 4 == text.len();
+        let padding = chunk.iter().rev().take_while(|x| **x == b'=').count();
+        if padding > 2 || (padding > 0 && !last) {
+            bail!("Invalid base64, = is only allowed at the end as padding");
+        }
+        let mut bits = 0;
+        for &c in &chunk[..4 - padding] {
+            let val = match ALPHABET.iter().position(|x| *x == c) {
+                Some(x) => x as u32,
+                None => bail!("Invalid base64 character {:?}", c as char),
+            };
+            bits = (bits << 6) | val;
+        }
+        bits <<= 6 * padding;
+        ret.extend_from_slice(&bits.to_be_bytes()[1..4 - padding]);
+    }
+    Ok(ret)
+}
@@ -0,0 +1,139 @@
+//! Checksums that some custom formats append after the NBT data.
+
+use std::io::{self, Read};
+
+use flate2::Crc;
+
+/// The length of the checksum after the file
+pub(crate) const TRAILER_LEN: usize = 4;
+
+/// The algorithm used for a checksum following an NBT file
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Checksum {
+    Crc32,
+    Adler32,
+}
+impl Checksum {
+    /// Compute the checksum of the data
+    pub fn compute(self, data: &[u8]) -> u32 {
+        let mut digest = Digest::new(self);
+        digest.update(data);
+        digest.sum()
+    }
+}
+
+/// A checksum computed over data given to it a piece at a time, which gives
+/// the same sum as computing it over all of the data at once
+pub struct Digest {
+    state: State,
+}
+enum State {
+    Crc32(Crc),
+    /// The two running sums of Adler-32, as used by zlib
+    Adler32 {
+        a: u32,
+        b: u32,
+    },
+}
+impl Digest {
+    pub fn new(checksum: Checksum) -> Self {
+        let state = match checksum {
+            Checksum::Crc32 => State::Crc32(Crc::new()),
+            Checksum::Adler32 => State::Adler32 { a: 1, b: 0 },
+        };
+        Digest { state }
+    }
+
+    /// Add the data to the checksum
+    pub fn update(&mut self, data: &[u8]) {
+        match self.state {
+            State::Crc32(ref mut crc) => crc.update(data),
+            State::Adler32 {
+                ref mut a,
+                ref mut b,
+            } => {
+                const MOD: u32 = 65521;
+                /* 5552 is the most bytes that can be summed before b might
+                 * overflow */
+                for chunk in data.chunks(5552) {
+                    for x in chunk {
+                        *a += u32::from(*x);
+                        *b += *a;
+                    }
+                    *a %= MOD;
+                    *b %= MOD;
+                }
+            }
+        }
+    }
+
+    /// The checksum of all the data given so far
+    pub fn sum(&self) -> u32 {
+        match self.state {
+            State::Crc32(ref crc) => crc.sum(),
+            State::Adler32 { a, b } => (b << 16) | a,
+        }
+    }
+}
+
+/// A reader for a file followed by a 4 byte checksum, which computes the
+/// checksum of everything read through it. The last 4 bytes are held back
+/// rather than read, so that they can be compared with the checksum once
+/// everything before them has been read.
+pub(crate) struct ChecksumReader<R> {
+    inner: R,
+    digest: Digest,
+    /// The last bytes read from inner, up to 4 of them, which are the
+    /// trailer if inner is at its end
+    held: Vec<u8>,
+}
+impl<R: Read> ChecksumReader<R> {
+    pub fn new(inner: R, checksum: Checksum) -> Self {
+        ChecksumReader {
+            inner,
+            digest: Digest::new(checksum),
+            held: Vec::with_capacity(TRAILER_LEN),
+        }
+    }
+
+    /// The checksum of what was read, and the trailer after it, once
+    /// everything has been read. The trailer is None if the whole file was
+    /// shorter than one.
+    pub fn finish(self) -> (u32, Option<[u8; TRAILER_LEN]>) {
+        let mut trailer = [0; TRAILER_LEN];
+        if self.held.len() < TRAILER_LEN {
+            return (self.digest.sum(), None);
+        }
+        trailer.copy_from_slice(&self.held);
+        (self.digest.sum(), Some(trailer))
+    }
+}
+impl<R: Read> Read for ChecksumReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        let mut tmp = [0; 8192];
+        let mut filled = self.held.len();
+        tmp[..filled].copy_from_slice(&self.held);
+        loop {
+            let end = tmp.len().min(filled + buf.len());
+            let n = self.inner.read(&mut tmp[filled..end])?;
+            if n == 0 {
+                return Ok(0);
+            }
+            filled += n;
+
+            /* Anything but the last 4 bytes can't be part of the trailer */
+            let len = filled.saturating_sub(TRAILER_LEN);
+            self.held.clear();
+            self.held.extend_from_slice(&tmp[len..filled]);
+            if len > 0 {
+                buf[..len].copy_from_slice(&tmp[..len]);
+                self.digest.update(&buf[..len]);
+                return Ok(len);
+            }
+        }
+    }
+}
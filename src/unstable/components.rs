@@ -0,0 +1,117 @@
+//! The item components of 1.20.5+ network packets, which are framed
+//! differently from both NBT files and other NBT in packets.
+//!
+//! An item stack in a packet carries a patch of its components: a VarInt
+//! count of added components, a VarInt count of removed components, then the
+//! type id and value of each added component, then the type id of each
+//! removed component. Only components whose value is sent as NBT, such as
+//! minecraft:custom_data, can be read; each is a nameless network NBT tag.
+//!
+//! To be edited like any other file, a patch is represented as an NBTFile
+//! with an "added" compound, mapping each type id in decimal to its value,
+//! and a "removed" list of the Int type ids. This is only a view of the
+//! packet, and is never written as an NBT file.
+
+use crate::data::{Compression, NBTFile, NBT};
+use crate::network::{read_nameless, read_varint, write_nameless, write_varint};
+use crate::Result;
+
+use std::convert::TryFrom;
+use std::io::{Read, Write};
+
+/// Read the component patch of an item stack
+pub fn read_components<R: Read>(reader: &mut R) -> Result<NBTFile> {
+    let added_count = read_count(reader, "added")?;
+    let removed_count = read_count(reader, "removed")?;
+
+    let mut added = Vec::new();
+    for i in 0..added_count {
+        let type_id = read_varint(reader)?;
+        let value = read_nameless(reader).map_err(|e| {
+            format_err!(
+                "Unable to read added component {} of type {}: {}",
+                i,
+                type_id,
+                e
+            )
+        })?;
+        added.push((type_id.to_string().into_bytes(), value));
+    }
+
+    let mut removed = Vec::new();
+    for _ in 0..removed_count {
+        removed.push(NBT::Int(read_varint(reader)?));
+    }
+
+    let mut rest = Vec::new();
+    let _: usize = reader.read_to_end(&mut rest)?;
+    if !rest.is_empty() {
+        bail!("Found {} bytes after the end of the components", rest.len());
+    }
+
+    Ok(NBTFile {
+        root: NBT::Compound(vec![
+            (b"added".to_vec(), NBT::Compound(added)),
+            (
+                b"removed".to_vec(),
+                NBT::List {
+                    element_type: 3,
+                    items: removed,
+                },
+            ),
+        ]),
+        compression: Compression::None,
+        compression_level: None,
+    })
+}
+
+/// Write the component patch of an item stack, from an NBTFile in the form
+/// given by read_components
+pub fn write_components<W: Write>(w: &mut W, file: &NBTFile) -> Result<()> {
+    if file.compression != Compression::None {
+        bail!("Item components can't be compressed");
+    }
+
+    let (added, removed) = match (file.root.get("added"), file.root.get("removed")) {
+        (Some(NBT::Compound(added)), Some(NBT::List { items: removed, .. })) => (added, removed),
+        _ => bail!("Item components must be an \"added\" Compound and a \"removed\" List"),
+    };
+
+    write_count(w, added.len())?;
+    write_count(w, removed.len())?;
+    for (key, value) in added {
+        let type_id = String::from_utf8_lossy(key);
+        let type_id = type_id.parse::<i32>().map_err(|_| {
+            format_err!(
+                "Invalid added component {}, expected a type id number",
+                type_id
+            )
+        })?;
+        write_varint(w, type_id)?;
+        write_nameless(w, value)?;
+    }
+    for x in removed {
+        match x {
+            NBT::Int(type_id) => write_varint(w, *type_id)?,
+            x => bail!(
+                "Invalid removed component {}, expected an Int type id",
+                x.type_string()
+            ),
+        }
+    }
+
+    Ok(())
+}
+
+fn read_count<R: Read>(reader: &mut R, what: &str) -> Result<usize> {
+    let count = read_varint(reader)?;
+    usize::try_from(count)
+        .map_err(|_| format_err!("Invalid number of {} components {}", what, count))
+}
+
+fn write_count<W: Write>(w: &mut W, count: usize) -> Result<()> {
+    match i32::try_from(count) {
+        Ok(x) => write_varint(w, x),
+        Err(_) => bail!("Too many components to write"),
+    }
+}
@@ -1,5 +1,19 @@
+use crate::dot;
+use crate::io::CountingWriter;
+use crate::json::{self, TypeMap};
+use crate::mutf8;
+use crate::path::{NbtPath, PathFilter, Segment};
+use crate::visit::{self, Visitor, VisitorMut};
+use crate::write;
 use crate::Result;
 
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashSet};
+use std::io;
+
+use failure::ResultExt;
+use serde_json::Value;
+
 /// Represents a single NBT tag
 #[derive(Clone, PartialEq, Debug)]
 pub enum NBT {
@@ -12,12 +26,33 @@ pub enum NBT {
     Double(f64),
     ByteArray(Vec<i8>),
     String(Vec<u8>),
-    List(Vec<NBT>),
+    /// A list of tags of the same type. element_type is the type id the
+    /// list was declared with, which is what tells an empty list of
+    /// Compounds apart from an empty list of End. A list with elements is
+    /// written with the type of its first element.
+    List {
+        element_type: u8,
+        items: Vec<NBT>,
+    },
     Compound(Vec<(Vec<u8>, NBT)>),
     IntArray(Vec<i32>),
     LongArray(Vec<i64>),
 }
 impl NBT {
+    /// Start building a compound, see NbtCompound
+    pub fn compound() -> NbtCompound {
+        NbtCompound::new()
+    }
+
+    /// A list of the given tags, which should all be of the same type,
+    /// declared with the type of the first one, or End if it's empty
+    pub fn list(items: Vec<NBT>) -> NBT {
+        NBT::List {
+            element_type: items.first().map_or(0, NBT::type_byte),
+            items,
+        }
+    }
+
     pub fn get<S: AsRef<[u8]>>(&self, val: S) -> Option<&NBT> {
         let s = match self {
             NBT::Compound(s) => s,
@@ -25,7 +60,7 @@ impl NBT {
         };
 
         for (i, v) in s {
-            if i == &val.as_ref() {
+            if i == val.as_ref() {
                 return Some(v);
             }
         }
@@ -42,6 +77,288 @@ impl NBT {
             .ok_or_else(|| format_err!("No value in compound {}", String::from_utf8_lossy(val)))
     }
 
+    /// Returns the tag at the given path below this one, such as
+    /// Data/Player/Pos/1, or None if there is no such tag. Components are
+    /// keys inside of compounds, and indices inside of lists and arrays. An
+    /// empty path returns the tag itself.
+    ///
+    /// The elements of arrays aren't stored as tags, so an element of an
+    /// array is returned as an owned tag of the element's type, such as an
+    /// Int for an IntArray. Every other tag is borrowed.
+    pub fn get_path<S: AsRef<str>>(&self, path: S) -> Option<Cow<'_, NBT>> {
+        self.get_path_with(path, '/')
+    }
+
+    /// Like get_path, but with components separated by the given character
+    /// instead of /
+    pub fn get_path_with<S: AsRef<str>>(&self, path: S, separator: char) -> Option<Cow<'_, NBT>> {
+        let path = path.as_ref();
+        if path.is_empty() {
+            return Some(Cow::Borrowed(self));
+        }
+
+        let mut cur = self;
+        let mut components = path.split(separator);
+        while let Some(component) = components.next() {
+            let component = component.as_bytes();
+            cur = match cur {
+                NBT::Compound(_) => cur.get(component)?,
+                NBT::List { items: x, .. } => x.get(parse_index(component)?)?,
+                array => {
+                    let i = parse_index(component)?;
+                    let element = match array {
+                        NBT::ByteArray(x) => NBT::Byte(*x.get(i)?),
+                        NBT::IntArray(x) => NBT::Int(*x.get(i)?),
+                        NBT::LongArray(x) => NBT::Long(*x.get(i)?),
+                        _ => return None,
+                    };
+                    /* There is nothing below an element of an array */
+                    return match components.next() {
+                        Some(_) => None,
+                        None => Some(Cow::Owned(element)),
+                    };
+                }
+            };
+        }
+
+        Some(Cow::Borrowed(cur))
+    }
+
+    /// Returns the tag at the given path components below this one, where
+    /// components inside of a compound are keys and components inside of a
+    /// list are indices. An empty path returns the tag itself.
+    pub fn get_path_mut(&mut self, path: &[&[u8]]) -> Result<&mut NBT> {
+        let mut cur = self;
+        for (i, component) in path.iter().enumerate() {
+            cur = match cur {
+                NBT::Compound(x) => match x.iter_mut().find(|(k, _)| k == component) {
+                    Some((_, v)) => v,
+                    None => bail!(
+                        "No value in compound {}",
+                        String::from_utf8_lossy(&path[..=i].join(&b'/'))
+                    ),
+                },
+                NBT::List { items: x, .. } => {
+                    let len = x.len();
+                    match parse_index(component) {
+                        Some(n) if n < len => &mut x[n],
+                        _ => bail!(
+                            "No element {} in list of length {}",
+                            String::from_utf8_lossy(&path[..=i].join(&b'/')),
+                            len
+                        ),
+                    }
+                }
+                x => bail!(
+                    "Unable to look up {} in {}, only compounds and lists contain tags",
+                    String::from_utf8_lossy(&path[..=i].join(&b'/')),
+                    x.type_string()
+                ),
+            };
+        }
+
+        Ok(cur)
+    }
+
+    /// Remove and return the tag with the given key from a compound, or the
+    /// element at the given index from a list or array. Later elements are
+    /// shifted down to fill the gap. Array elements are returned as the tag
+    /// type of the element, so an element of an IntArray is returned as an
+    /// Int.
+    pub fn remove(&mut self, component: &[u8]) -> Result<NBT> {
+        let index = |len: usize| match parse_index(component) {
+            Some(x) if x < len => Ok(x),
+            _ => Err(format_err!(
+                "No element {} in array or list of length {}",
+                String::from_utf8_lossy(component),
+                len
+            )),
+        };
+
+        Ok(match self {
+            NBT::Compound(x) => match x.iter().position(|(k, _)| k == component) {
+                Some(i) => x.remove(i).1,
+                None => bail!(
+                    "No value in compound {}",
+                    String::from_utf8_lossy(component)
+                ),
+            },
+            NBT::List { items: x, .. } => x.remove(index(x.len())?),
+            NBT::ByteArray(x) => NBT::Byte(x.remove(index(x.len())?)),
+            NBT::IntArray(x) => NBT::Int(x.remove(index(x.len())?)),
+            NBT::LongArray(x) => NBT::Long(x.remove(index(x.len())?)),
+            x => bail!(
+                "Unable to remove {} from {}, it contains no tags",
+                String::from_utf8_lossy(component),
+                x.type_string()
+            ),
+        })
+    }
+
+    /// Replace the value of a number or String with the given text, parsed
+    /// as the type of the tag, so that a Float is parsed as an f32 and a
+    /// Long as an i64. A String is set to the text encoded as modified UTF-8.
+    /// The tag's type
+    /// is never changed, and other tags can't be set this way.
+    pub fn set_from_str(&mut self, value: &str) -> Result<()> {
+        let type_string = self.type_string().to_string();
+        let invalid = || format_err!("Invalid value {} for a {}", value, type_string);
+        match self {
+            NBT::Byte(x) => *x = value.parse::<i8>().context(invalid())?,
+            NBT::Short(x) => *x = value.parse::<i16>().context(invalid())?,
+            NBT::Int(x) => *x = value.parse::<i32>().context(invalid())?,
+            NBT::Long(x) => *x = value.parse::<i64>().context(invalid())?,
+            NBT::Float(x) => *x = value.parse::<f32>().context(invalid())?,
+            NBT::Double(x) => *x = value.parse::<f64>().context(invalid())?,
+            NBT::String(x) => *x = mutf8::encode(value),
+            _ => bail!(
+                "Unable to set a {}, only numbers and Strings can be set",
+                type_string
+            ),
+        }
+        Ok(())
+    }
+
+    /// Returns a copy of the tag at the given path components below this
+    /// one, looked up like get_path_mut, or None if there is no such tag.
+    pub fn extract_path(&self, path: &[&[u8]]) -> Option<NBT> {
+        let mut cur = self;
+        for component in path {
+            cur = match cur {
+                NBT::Compound(_) => cur.get(component)?,
+                NBT::List { items: x, .. } => x.get(parse_index(component)?)?,
+                _ => return None,
+            };
+        }
+
+        Some(cur.clone())
+    }
+
+    /// Put the tag at the given path components below this one, replacing
+    /// any tag already there.
+    ///
+    /// Compounds along the path that don't have the next key get an empty
+    /// compound added under it. Lists can't be extended this way, so their
+    /// elements along the path must already exist. The last component may be
+    /// a key in a compound, or an index in a list up to its length, where
+    /// an index of the length adds the tag to the end of the list. A tag put
+    /// in a list must be of the same type as the other elements, if there are
+    /// any other than the one it replaces.
+    pub fn graft_path(&mut self, path: &[&[u8]], value: NBT) -> Result<()> {
+        let (last, parents) = match path.split_last() {
+            Some(x) => x,
+            None => bail!("Unable to replace the root of the file"),
+        };
+
+        let mut cur = self;
+        for (i, component) in parents.iter().enumerate() {
+            if let NBT::Compound(x) = cur {
+                if !x.iter().any(|(k, _)| k == component) {
+                    x.push((component.to_vec(), NBT::Compound(Vec::new())));
+                }
+            }
+            cur = cur.get_path_mut(&[component]).map_err(|e| {
+                format_err!(
+                    "Unable to graft onto {}: {}",
+                    String::from_utf8_lossy(&path[..=i].join(&b'/')),
+                    e
+                )
+            })?;
+        }
+
+        match cur {
+            NBT::Compound(x) => match x.iter_mut().find(|(k, _)| k == last) {
+                Some((_, v)) => *v = value,
+                None => x.push((last.to_vec(), value)),
+            },
+            NBT::List {
+                element_type,
+                items: x,
+            } => {
+                let index = match parse_index(last) {
+                    Some(n) if n <= x.len() => n,
+                    _ => bail!(
+                        "No element {} in list of length {}",
+                        String::from_utf8_lossy(&path.join(&b'/')),
+                        x.len()
+                    ),
+                };
+                /* The tag only has to match the elements it doesn't
+                 * replace, so the only element can be replaced by any tag */
+                if let Some((_, other)) = x.iter().enumerate().find(|(i, _)| *i != index) {
+                    if other.type_byte() != value.type_byte() {
+                        bail!(
+                            "Unable to put {} in a list of {}",
+                            value.type_string(),
+                            other.type_string()
+                        );
+                    }
+                }
+                *element_type = value.type_byte();
+                if index < x.len() {
+                    x[index] = value;
+                } else {
+                    x.push(value);
+                }
+            }
+            x => bail!(
+                "Unable to graft onto {}, only compounds and lists contain tags",
+                x.type_string()
+            ),
+        }
+
+        Ok(())
+    }
+
+    /// Merge the overlay compound into this one. Keys that are only in the
+    /// overlay are added after the others, and keys in both get the
+    /// overlay's tag, except where both tags are compounds, which are merged
+    /// the same way. Lists are replaced as a whole, since there is no telling
+    /// which of their elements correspond. Returns the number of tags taken
+    /// from the overlay. Fails unless both tags are compounds.
+    pub fn merge(&mut self, overlay: &NBT) -> Result<usize> {
+        let (base, overlay) = match (self, overlay) {
+            (NBT::Compound(a), NBT::Compound(b)) => (a, b),
+            (a, b) => bail!(
+                "Unable to merge {} into {}, only compounds can be merged",
+                b.type_string(),
+                a.type_string()
+            ),
+        };
+
+        let mut count = 0;
+        for (key, val) in overlay {
+            match base.iter_mut().find(|(k, _)| k == key) {
+                Some((_, x)) if x.type_byte() == 10 && val.type_byte() == 10 => {
+                    count += x.merge(val)?;
+                }
+                Some((_, x)) => {
+                    *x = val.clone();
+                    count += 1;
+                }
+                None => {
+                    base.push((key.clone(), val.clone()));
+                    count += 1;
+                }
+            }
+        }
+
+        Ok(count)
+    }
+
+    /// Returns how many bytes the payload of this tag takes up in a binary
+    /// NBT file, which excludes its type and name.
+    pub fn payload_len(&self) -> Result<u64> {
+        let mut w = CountingWriter::new(io::sink());
+        match self {
+            /* Only found in lists of End, where it takes up no space */
+            NBT::End => (),
+            x => write::write_tag(&mut w, x)?,
+        }
+
+        Ok(w.count())
+    }
+
     /// Returns the type of the tag as an English string
     pub fn type_string(&self) -> &str {
         match self {
@@ -54,7 +371,7 @@ impl NBT {
             NBT::Double(..) => "Double",
             NBT::ByteArray(..) => "ByteArray",
             NBT::String(..) => "String",
-            NBT::List(..) => "List",
+            NBT::List { .. } => "List",
             NBT::Compound(..) => "Compound",
             NBT::IntArray(..) => "IntArray",
             NBT::LongArray(..) => "LongArray",
@@ -72,12 +389,650 @@ impl NBT {
             NBT::Double(..) => 6,
             NBT::ByteArray(..) => 7,
             NBT::String(..) => 8,
-            NBT::List(..) => 9,
+            NBT::List { .. } => 9,
             NBT::Compound(..) => 10,
             NBT::IntArray(..) => 11,
             NBT::LongArray(..) => 12,
         }
     }
+
+    /// Compare two tags, comparing Floats and Doubles by their bit patterns
+    /// rather than numerically.
+    ///
+    /// Unlike ==, this considers a NaN equal to itself (if the bits are the
+    /// same), and 0.0 different from -0.0, which is what you want when asking
+    /// whether two trees have the same content.
+    pub fn bitwise_eq(&self, other: &NBT) -> bool {
+        match (self, other) {
+            (NBT::Float(a), NBT::Float(b)) => a.to_bits() == b.to_bits(),
+            (NBT::Double(a), NBT::Double(b)) => a.to_bits() == b.to_bits(),
+            (
+                NBT::List {
+                    element_type: ta,
+                    items: a,
+                },
+                NBT::List {
+                    element_type: tb,
+                    items: b,
+                },
+            ) => ta == tb && a.len() == b.len() && a.iter().zip(b).all(|(a, b)| a.bitwise_eq(b)),
+            (NBT::Compound(a), NBT::Compound(b)) => {
+                a.len() == b.len()
+                    && a.iter()
+                        .zip(b)
+                        .all(|((ka, va), (kb, vb))| ka == kb && va.bitwise_eq(vb))
+            }
+            (a, b) => a == b,
+        }
+    }
+
+    /// Convert the tag to plain JSON, where all numbers are just numbers, along
+    /// with the NBT type of every tag so that the exact tree can be restored
+    /// with from_json_with_types after editing the JSON.
+    pub fn to_json_with_types(&self) -> Result<(Value, TypeMap)> {
+        json::to_json_with_types(self)
+    }
+
+    /// Rebuild a tree from JSON and the types recorded by to_json_with_types
+    pub fn from_json_with_types(value: &Value, types: &TypeMap) -> Result<NBT> {
+        json::from_json_with_types(value, types)
+    }
+
+    /// Convert the tag to JSON that keeps the type of every tag, as objects
+    /// such as {"Int": 5}, see json::to_tagged_json
+    pub fn to_tagged_json(&self) -> Result<Value> {
+        json::to_tagged_json(self)
+    }
+
+    /// Rebuild a tree from JSON written by to_tagged_json
+    pub fn from_tagged_json(value: &Value) -> Result<NBT> {
+        json::from_tagged_json(value)
+    }
+
+    /// Draw the tree as a Graphviz DOT graph, see the dot module
+    pub fn to_dot(&self) -> String {
+        dot::to_dot(self)
+    }
+
+    /// Returns the path and contents of every String in the tree for which
+    /// the predicate returns true, in the order they appear.
+    pub fn find_strings<F>(&self, predicate: F) -> Vec<(NbtPath, &[u8])>
+    where
+        F: Fn(&[u8]) -> bool,
+    {
+        let mut finder = StringFinder {
+            predicate,
+            found: Vec::new(),
+        };
+        /* The finder never errors */
+        visit::visit(self, &mut finder).expect("StringFinder errored");
+
+        finder.found
+    }
+
+    /// Returns the path of every tag in the tree for which the predicate
+    /// returns true, in the order they appear. The predicate is also given
+    /// the tree itself, whose path is empty.
+    pub fn find_value<F>(&self, predicate: F) -> Vec<NbtPath>
+    where
+        F: Fn(&NBT) -> bool,
+    {
+        let mut finder = ValueFinder {
+            predicate,
+            found: Vec::new(),
+        };
+        /* The finder never errors */
+        visit::visit(self, &mut finder).expect("ValueFinder errored");
+
+        finder.found
+    }
+
+    /// Count the distinct values of every String in the tree that is stored
+    /// under one of the given compound keys, such as id, for finding which
+    /// items or blocks a file refers to. Strings that aren't valid UTF-8 are
+    /// counted with the invalid bytes replaced.
+    pub fn count_ids(&self, keys: &[&[u8]]) -> BTreeMap<String, usize> {
+        let mut counter = IdCounter {
+            keys,
+            ids: BTreeMap::new(),
+        };
+        /* The counter never errors */
+        visit::visit(self, &mut counter).expect("IdCounter errored");
+
+        counter.ids
+    }
+
+    /// Returns the path of every tag in the tree, the tag itself and how many
+    /// bytes it takes up in an uncompressed binary NBT file, in the order they
+    /// appear.
+    /// The size of a tag inside of a compound includes its type and name, and
+    /// the size of a compound or list includes everything inside of it.
+    pub fn size_breakdown(&self) -> Result<Vec<(NbtPath, &NBT, u64)>> {
+        let mut counter = SizeCounter { sizes: Vec::new() };
+        visit::visit(self, &mut counter)?;
+
+        Ok(counter.sizes)
+    }
+
+    /// Rename every compound key in the tree that is exactly from to to, or
+    /// if prefix is set, replace the from prefix of every key starting with
+    /// it.
+    ///
+    /// A rename that would give a compound two identical keys is skipped and
+    /// reported in the result, or is an error if strict is set.
+    pub fn rename_keys(
+        &mut self,
+        from: &[u8],
+        to: &[u8],
+        prefix: bool,
+        strict: bool,
+    ) -> Result<Renamed> {
+        let mut renamer = KeyRenamer {
+            from,
+            to,
+            prefix,
+            strict,
+            renamed: Renamed {
+                count: 0,
+                skipped: Vec::new(),
+            },
+        };
+        visit::visit_mut(self, &mut renamer)?;
+
+        Ok(renamer.renamed)
+    }
+
+    /// Clamp every Byte, Short, Int and Long whose path is matched by the
+    /// filter into the range min..=max, including the elements of arrays
+    /// (addressed by their index.) Values that can't be represented in their
+    /// own type are limited to the nearest value that can be.
+    ///
+    /// Returns the number of values that were changed.
+    pub fn clamp_numeric(&mut self, filter: &PathFilter, min: i64, max: i64) -> Result<usize> {
+        if min > max {
+            bail!("Minimum {} is greater than maximum {}", min, max);
+        }
+
+        let mut clamper = Clamper {
+            filter,
+            min,
+            max,
+            count: 0,
+        };
+        visit::visit_mut(self, &mut clamper)?;
+
+        Ok(clamper.count)
+    }
+
+    /// Convert every element of every list in the tree to the type of the
+    /// first element of that list. Integers are widened or narrowed, and
+    /// Floats and Doubles converted into each other.
+    ///
+    /// It is an error if a value doesn't fit in the new type, unless force is
+    /// set, in which case it is limited to the nearest value that does (or
+    /// rounded, for Doubles becoming Floats.) Elements that aren't numbers of
+    /// the same kind as the first element can't be converted at all.
+    ///
+    /// Returns the number of elements that were converted. Nothing is
+    /// converted if any element can't be.
+    pub fn coerce_lists(&mut self, force: bool) -> Result<usize> {
+        /* Every element is checked before any is converted, so that an error
+         * doesn't leave the tree half converted */
+        visit::visit(self, &mut ListCoercer { force, count: 0 })?;
+
+        let mut coercer = ListCoercer { force, count: 0 };
+        visit::visit_mut(self, &mut coercer)?;
+
+        Ok(coercer.count)
+    }
+}
+
+/// Walk the whole tree depth-first, calling f on every tag, including the
+/// root. Containers are given to f before their contents, so any changes made
+/// to them are seen when walking their contents. Use visit::visit_mut instead
+/// when the path of each tag is needed.
+pub fn walk_mut<F: FnMut(&mut NBT)>(tag: &mut NBT, f: &mut F) {
+    /* The walker only fails if the visitor does */
+    visit::visit_mut(tag, &mut Walker(f)).unwrap();
+}
+
+/// Parse a path component as a list index. Only plain decimal numbers are
+/// accepted, so that +1 and 01 aren't taken to mean 1.
+fn parse_index(component: &[u8]) -> Option<usize> {
+    if component.is_empty()
+        || !component.iter().all(u8::is_ascii_digit)
+        || (component.len() > 1 && component[0] == b'0')
+    {
+        return None;
+    }
+    std::str::from_utf8(component).ok()?.parse().ok()
+}
+
+struct Walker<'f, F>(&'f mut F);
+impl<'f, F: FnMut(&mut NBT)> VisitorMut for Walker<'f, F> {
+    fn visit_tag(&mut self, _path: &NbtPath, tag: &mut NBT) -> Result<()> {
+        (self.0)(tag);
+        Ok(())
+    }
+}
+
+struct StringFinder<'a, F> {
+    predicate: F,
+    found: Vec<(NbtPath, &'a [u8])>,
+}
+impl<'a, F: Fn(&[u8]) -> bool> Visitor<'a> for StringFinder<'a, F> {
+    fn visit_tag(&mut self, path: &NbtPath, tag: &'a NBT) -> Result<()> {
+        if let NBT::String(x) = tag {
+            if (self.predicate)(x) {
+                self.found.push((path.clone(), x));
+            }
+        }
+        Ok(())
+    }
+}
+
+struct IdCounter<'k> {
+    keys: &'k [&'k [u8]],
+    ids: BTreeMap<String, usize>,
+}
+impl<'a, 'k> Visitor<'a> for IdCounter<'k> {
+    fn visit_tag(&mut self, path: &NbtPath, tag: &'a NBT) -> Result<()> {
+        if let (NBT::String(x), Some(Segment::Key(key))) = (tag, path.segments().last()) {
+            if self.keys.contains(&&key[..]) {
+                *self
+                    .ids
+                    .entry(String::from_utf8_lossy(x).into_owned())
+                    .or_insert(0) += 1;
+            }
+        }
+        Ok(())
+    }
+}
+
+struct SizeCounter<'a> {
+    sizes: Vec<(NbtPath, &'a NBT, u64)>,
+}
+impl<'a> Visitor<'a> for SizeCounter<'a> {
+    fn visit_tag(&mut self, path: &NbtPath, tag: &'a NBT) -> Result<()> {
+        /* The type id and the length prefixed name */
+        let header = match path.segments().last() {
+            Some(Segment::Key(x)) => 3 + x.len() as u64,
+            _ => 0,
+        };
+
+        self.sizes
+            .push((path.clone(), tag, header + tag.payload_len()?));
+        Ok(())
+    }
+}
+
+struct ValueFinder<F> {
+    predicate: F,
+    found: Vec<NbtPath>,
+}
+impl<'a, F: Fn(&NBT) -> bool> Visitor<'a> for ValueFinder<F> {
+    fn visit_tag(&mut self, path: &NbtPath, tag: &'a NBT) -> Result<()> {
+        if (self.predicate)(tag) {
+            self.found.push(path.clone());
+        }
+        Ok(())
+    }
+}
+
+/// The name of the type with the given type id, as given by
+/// NBT::type_string
+pub(crate) fn type_name(type_byte: u8) -> &'static str {
+    match type_byte {
+        0 => "End",
+        1 => "Byte",
+        2 => "Short",
+        3 => "Int",
+        4 => "Long",
+        5 => "Float",
+        6 => "Double",
+        7 => "ByteArray",
+        8 => "String",
+        9 => "List",
+        10 => "Compound",
+        11 => "IntArray",
+        12 => "LongArray",
+        _ => "an unknown type",
+    }
+}
+
+/// The keys of a compound seen so far, for finding a key that is in it more
+/// than once without comparing every key with every other one
+#[derive(Default)]
+pub(crate) struct KeySet<'a> {
+    keys: HashSet<Cow<'a, [u8]>>,
+}
+impl<'a> KeySet<'a> {
+    /// Add the key, returning false if it was already there
+    pub(crate) fn insert<K: Into<Cow<'a, [u8]>>>(&mut self, key: K) -> bool {
+        self.keys.insert(key.into())
+    }
+
+    /// Add the key of a tag of the given type that was just read, failing if
+    /// it was already there
+    pub(crate) fn check(&mut self, key: &[u8], type_byte: u8) -> Result<()> {
+        if !self.insert(key.to_vec()) {
+            bail!(
+                "Duplicate key {} in compound, the second one is a {}",
+                String::from_utf8_lossy(key),
+                type_name(type_byte)
+            );
+        }
+        Ok(())
+    }
+}
+
+/// The result of NBT::rename_keys
+#[derive(Clone, PartialEq, Debug)]
+pub struct Renamed {
+    /// The number of keys that were renamed
+    pub count: usize,
+    /// The paths of the keys that weren't renamed, because the compound
+    /// already had a key with the new name
+    pub skipped: Vec<NbtPath>,
+}
+
+struct KeyRenamer<'a> {
+    from: &'a [u8],
+    to: &'a [u8],
+    prefix: bool,
+    strict: bool,
+    renamed: Renamed,
+}
+impl<'a> VisitorMut for KeyRenamer<'a> {
+    fn visit_compound_key(
+        &mut self,
+        path: &NbtPath,
+        map: &mut [(Vec<u8>, NBT)],
+        index: usize,
+    ) -> Result<()> {
+        let key = &map[index].0;
+        let new = if self.prefix && key.starts_with(self.from) {
+            let mut new = self.to.to_vec();
+            new.extend_from_slice(&key[self.from.len()..]);
+            new
+        } else if !self.prefix && key.as_slice() == self.from {
+            self.to.to_vec()
+        } else {
+            return Ok(());
+        };
+
+        if &new == key {
+            return Ok(());
+        }
+
+        if map.iter().any(|(k, _)| k == &new) {
+            let path = path.join(Segment::Key(key.clone()));
+            if self.strict {
+                bail!(
+                    "Renaming {} to {} would create a duplicate key",
+                    path,
+                    String::from_utf8_lossy(&new)
+                );
+            }
+            self.renamed.skipped.push(path);
+            return Ok(());
+        }
+
+        map[index].0 = new;
+        self.renamed.count += 1;
+        Ok(())
+    }
+}
+
+struct Clamper<'a> {
+    filter: &'a PathFilter,
+    min: i64,
+    max: i64,
+    count: usize,
+}
+impl<'a> Clamper<'a> {
+    /// Clamp a single value to both the requested range and the range of T
+    fn clamp<T: Integer>(&mut self, val: &mut T) {
+        let wide: i64 = (*val).into();
+        let new = wide.max(self.min).min(self.max).max(T::MIN).min(T::MAX);
+        if new != wide {
+            *val = T::from_i64(new);
+            self.count += 1;
+        }
+    }
+
+    fn clamp_array<T: Integer>(&mut self, path: &NbtPath, vals: &mut [T]) {
+        for (i, val) in vals.iter_mut().enumerate() {
+            if self.filter.matches(&path.join(Segment::Index(i))) {
+                self.clamp(val);
+            }
+        }
+    }
+}
+impl<'a> VisitorMut for Clamper<'a> {
+    fn visit_tag(&mut self, path: &NbtPath, tag: &mut NBT) -> Result<()> {
+        match tag {
+            NBT::ByteArray(x) => self.clamp_array(path, x),
+            NBT::IntArray(x) => self.clamp_array(path, x),
+            NBT::LongArray(x) => self.clamp_array(path, x),
+            _ if !self.filter.matches(path) => (),
+            NBT::Byte(x) => self.clamp(x),
+            NBT::Short(x) => self.clamp(x),
+            NBT::Int(x) => self.clamp(x),
+            NBT::Long(x) => self.clamp(x),
+            _ => (),
+        }
+        Ok(())
+    }
+}
+
+struct ListCoercer {
+    force: bool,
+    count: usize,
+}
+impl ListCoercer {
+    /// Convert a single value to the type of target, or None if that would
+    /// change the value and force isn't set
+    fn coerce(&self, val: &NBT, target: &NBT) -> Result<Option<NBT>> {
+        let wide: Option<i64> = match *val {
+            NBT::Byte(x) => Some(x.into()),
+            NBT::Short(x) => Some(x.into()),
+            NBT::Int(x) => Some(x.into()),
+            NBT::Long(x) => Some(x),
+            _ => None,
+        };
+
+        Ok(match (wide, val, target) {
+            (Some(x), _, NBT::Byte(_)) => self.narrow(x).map(NBT::Byte),
+            (Some(x), _, NBT::Short(_)) => self.narrow(x).map(NBT::Short),
+            (Some(x), _, NBT::Int(_)) => self.narrow(x).map(NBT::Int),
+            (Some(x), _, NBT::Long(_)) => Some(NBT::Long(x)),
+            (_, NBT::Float(x), NBT::Double(_)) => Some(NBT::Double((*x).into())),
+            (_, NBT::Double(x), NBT::Float(_)) => {
+                let new = *x as f32;
+                if f64::from(new).to_bits() == x.to_bits() || self.force {
+                    Some(NBT::Float(new))
+                } else {
+                    None
+                }
+            }
+            _ => bail!(
+                "{} can't be converted to {}",
+                val.type_string(),
+                target.type_string()
+            ),
+        })
+    }
+
+    /// Convert the element at the index of the list at path, failing if
+    /// that would change its value and force isn't set
+    fn convert(&self, path: &NbtPath, index: usize, val: &NBT, target: &NBT) -> Result<NBT> {
+        let elem = path.join(Segment::Index(index));
+        match self
+            .coerce(val, target)
+            .context(format_err!("Unable to convert {}", elem))?
+        {
+            Some(x) => Ok(x),
+            None => bail!(
+                "Unable to convert {} to {} without changing its value: {:?}",
+                elem,
+                target.type_string(),
+                val
+            ),
+        }
+    }
+
+    fn narrow<T: Integer>(&self, val: i64) -> Option<T> {
+        if (T::MIN..=T::MAX).contains(&val) || self.force {
+            Some(T::from_i64(val.max(T::MIN).min(T::MAX)))
+        } else {
+            None
+        }
+    }
+}
+impl<'a> Visitor<'a> for ListCoercer {
+    fn visit_tag(&mut self, path: &NbtPath, tag: &'a NBT) -> Result<()> {
+        if let NBT::List { items: x, .. } = tag {
+            for (i, val) in write::mixed_elements(x) {
+                let _: NBT = self.convert(path, i, val, &x[0])?;
+            }
+        }
+        Ok(())
+    }
+}
+impl VisitorMut for ListCoercer {
+    fn visit_tag(&mut self, path: &NbtPath, tag: &mut NBT) -> Result<()> {
+        let list = match tag {
+            NBT::List { items: x, .. } if !x.is_empty() => x,
+            _ => return Ok(()),
+        };
+
+        let (first, rest) = list.split_at_mut(1);
+        let target = &first[0];
+        for (i, val) in rest.iter_mut().enumerate() {
+            if val.type_byte() != target.type_byte() {
+                *val = self.convert(path, i + 1, val, target)?;
+                self.count += 1;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The integer types used in NBT, with their bounds widened to i64
+trait Integer: Copy + Into<i64> {
+    const MIN: i64;
+    const MAX: i64;
+    /// Convert from an i64 that is known to be within MIN..=MAX
+    fn from_i64(x: i64) -> Self;
+}
+macro_rules! impl_integer {
+    ($t:ty) => {
+        #[allow(trivial_numeric_casts)]
+        impl Integer for $t {
+            const MIN: i64 = <$t>::MIN as i64;
+            const MAX: i64 = <$t>::MAX as i64;
+            fn from_i64(x: i64) -> Self {
+                x as $t
+            }
+        }
+    };
+}
+impl_integer!(i8);
+impl_integer!(i16);
+impl_integer!(i32);
+impl_integer!(i64);
+
+/// Builds an NBT::Compound without spelling out every key and tag, such as
+/// NBT::compound().string("LevelName", "world").child("Player", |c|
+/// c.float("Health", 20.0)).build().
+///
+/// The entries are kept in the order they are added. Adding a key that is
+/// already there replaces its tag where it is, since a compound can't hold
+/// the same key twice.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct NbtCompound {
+    entries: Vec<(Vec<u8>, NBT)>,
+}
+impl NbtCompound {
+    pub fn new() -> Self {
+        NbtCompound::default()
+    }
+
+    /// Add any tag under the given key
+    pub fn tag<K: AsRef<[u8]>>(mut self, key: K, tag: NBT) -> Self {
+        let key = key.as_ref();
+        match self.entries.iter_mut().find(|(k, _)| k == key) {
+            Some((_, v)) => *v = tag,
+            None => self.entries.push((key.to_vec(), tag)),
+        }
+        self
+    }
+
+    pub fn byte<K: AsRef<[u8]>>(self, key: K, val: i8) -> Self {
+        self.tag(key, NBT::Byte(val))
+    }
+
+    pub fn short<K: AsRef<[u8]>>(self, key: K, val: i16) -> Self {
+        self.tag(key, NBT::Short(val))
+    }
+
+    pub fn int<K: AsRef<[u8]>>(self, key: K, val: i32) -> Self {
+        self.tag(key, NBT::Int(val))
+    }
+
+    pub fn long<K: AsRef<[u8]>>(self, key: K, val: i64) -> Self {
+        self.tag(key, NBT::Long(val))
+    }
+
+    pub fn float<K: AsRef<[u8]>>(self, key: K, val: f32) -> Self {
+        self.tag(key, NBT::Float(val))
+    }
+
+    pub fn double<K: AsRef<[u8]>>(self, key: K, val: f64) -> Self {
+        self.tag(key, NBT::Double(val))
+    }
+
+    /// Add a String, encoded as modified UTF-8
+    pub fn string<K: AsRef<[u8]>, S: AsRef<str>>(self, key: K, val: S) -> Self {
+        self.tag(key, NBT::String(mutf8::encode(val.as_ref())))
+    }
+
+    pub fn byte_array<K: AsRef<[u8]>>(self, key: K, val: Vec<i8>) -> Self {
+        self.tag(key, NBT::ByteArray(val))
+    }
+
+    pub fn int_array<K: AsRef<[u8]>>(self, key: K, val: Vec<i32>) -> Self {
+        self.tag(key, NBT::IntArray(val))
+    }
+
+    pub fn long_array<K: AsRef<[u8]>>(self, key: K, val: Vec<i64>) -> Self {
+        self.tag(key, NBT::LongArray(val))
+    }
+
+    /// Add a list of the given tags, which should all be of the same type
+    pub fn list<K: AsRef<[u8]>>(self, key: K, val: Vec<NBT>) -> Self {
+        self.tag(key, NBT::list(val))
+    }
+
+    /// Add a compound built by the given function, which is passed an empty
+    /// builder
+    pub fn child<K, F>(self, key: K, build: F) -> Self
+    where
+        K: AsRef<[u8]>,
+        F: FnOnce(NbtCompound) -> NbtCompound,
+    {
+        self.tag(key, build(NbtCompound::new()).build())
+    }
+
+    pub fn build(self) -> NBT {
+        NBT::Compound(self.entries)
+    }
+}
+impl From<NbtCompound> for NBT {
+    fn from(compound: NbtCompound) -> NBT {
+        compound.build()
+    }
 }
 
 /// Represents the different compression formats NBT files can be in
@@ -86,6 +1041,10 @@ pub enum Compression {
     None,
     Gzip,
     Zlib,
+    /// Zstandard, which some third party tools use, see the zstd module
+    Zstd,
+    /// The LZ4 frame format, see the lz4 module
+    Lz4,
 }
 impl Compression {
     /// Returns the type of compression as an English string
@@ -94,6 +1053,8 @@ impl Compression {
             Compression::None => "None",
             Compression::Gzip => "Gzip",
             Compression::Zlib => "Zlib",
+            Compression::Zstd => "Zstd",
+            Compression::Lz4 => "Lz4",
         }
     }
     /// Given the name of a type of compression, return the corresponding
@@ -105,6 +1066,8 @@ impl Compression {
             "None" => Some(Compression::None),
             "Gzip" => Some(Compression::Gzip),
             "Zlib" => Some(Compression::Zlib),
+            "Zstd" => Some(Compression::Zstd),
+            "Lz4" => Some(Compression::Lz4),
             _ => None,
         }
     }
@@ -116,13 +1079,31 @@ impl Compression {
          * at the first byte in the file, with the following meanings: */
         match byte {
             0x0a => Some(Compression::None),
+            /* An uncompressed file with an empty root compound consists of
+             * just an End tag */
+            0x00 => Some(Compression::None),
             0x1f => Some(Compression::Gzip),
             0x78 => Some(Compression::Zlib),
+            /* The first byte of the 28 B5 2F FD magic number */
+            0x28 => Some(Compression::Zstd),
+            /* The first byte of the 04 22 4D 18 magic number. Uncompressed
+             * files always start with a Compound or End tag, so this can't be
+             * mistaken for one, even though 04 is the type id of Long. */
+            0x04 => Some(Compression::Lz4),
             _ => None,
         }
     }
 }
 
+/// The byte order numbers in an NBT file are stored in. Java Edition uses
+/// big endian, while Bedrock Edition uses little endian.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum Endianness {
+    #[default]
+    Big,
+    Little,
+}
+
 /// Represents a single NBT file, that is all the NBT data, as well as a
 /// compression type.
 ///
@@ -131,4 +1112,16 @@ impl Compression {
 pub struct NBTFile {
     pub root: NBT,
     pub compression: Compression,
+    /// The level to compress the file with when writing it, from 0 to 9, or
+    /// None for write::DEFAULT_COMPRESSION_LEVEL. When reading a zlib compressed file this
+    /// is taken from its header, so that it is written back the same way.
+    pub compression_level: Option<u32>,
+}
+impl NBTFile {
+    /// Compare two files, with the root compared using NBT::bitwise_eq
+    pub fn bitwise_eq(&self, other: &NBTFile) -> bool {
+        self.compression == other.compression
+            && self.compression_level == other.compression_level
+            && self.root.bitwise_eq(&other.root)
+    }
 }
@@ -0,0 +1,152 @@
+//! Gzip and zlib compression with a choice of deflate strategy, which flate2
+//! doesn't offer. It uses the same compressor as flate2, so files written
+//! with the default strategy are the same either way.
+
+use crate::Result;
+
+use std::io::{self, Write};
+
+use byteorder::{LittleEndian, WriteBytesExt};
+
+use flate2::Crc;
+
+use miniz_oxide::deflate::core::{
+    compress_to_output, create_comp_flags_from_zip_params, CompressionStrategy, CompressorOxide,
+    TDEFLFlush, TDEFLStatus,
+};
+
+/// The window size of deflate, as a power of 2. The window bits are
+/// negative for raw deflate, and positive for deflate with a zlib header.
+const WINDOW_BITS: i32 = 15;
+
+/// How the compressor looks for repeated data, which can make particular
+/// kinds of data compress better or faster
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum Strategy {
+    /// Look for any repeated data, as normal
+    #[default]
+    Default,
+    /// Only use matches of at least 5 bytes, for data that is mostly small
+    /// random values
+    Filtered,
+    /// Don't look for repeated data at all, only Huffman code the bytes
+    Huffman,
+    /// Only look for runs of the same byte
+    Rle,
+}
+impl Strategy {
+    pub fn to_str(&self) -> &str {
+        match self {
+            Strategy::Default => "default",
+            Strategy::Filtered => "filtered",
+            Strategy::Huffman => "huffman",
+            Strategy::Rle => "rle",
+        }
+    }
+
+    /// Returns the Strategy of the given name, or None if there is no such
+    /// Strategy
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(string: &str) -> Option<Self> {
+        match string {
+            "default" => Some(Strategy::Default),
+            "filtered" => Some(Strategy::Filtered),
+            "huffman" => Some(Strategy::Huffman),
+            "rle" => Some(Strategy::Rle),
+            _ => None,
+        }
+    }
+}
+
+/// Compresses what is written to it as gzip or zlib, writing the compressed
+/// data to the inner writer as it goes. finish must be called to write the
+/// end of the stream.
+pub struct Encoder<W: Write> {
+    w: W,
+    compressor: Box<CompressorOxide>,
+    /// The checksum of the uncompressed data for the gzip trailer, or None
+    /// for zlib, where the compressor writes the trailer itself
+    crc: Option<Crc>,
+    buf: Vec<u8>,
+}
+impl<W: Write> Encoder<W> {
+    /// Create an encoder writing a gzip file, with a header like that of
+    /// flate2's GzEncoder
+    pub fn gzip(mut w: W, level: u32, strategy: Strategy) -> Result<Self> {
+        let extra_flags = match level {
+            9 => 2,
+            1 => 4,
+            _ => 0,
+        };
+        w.write_all(&[0x1f, 0x8b, 8, 0, 0, 0, 0, 0, extra_flags, 255])?;
+        Ok(Self::new(
+            w,
+            level,
+            -WINDOW_BITS,
+            strategy,
+            Some(Crc::new()),
+        ))
+    }
+
+    /// Create an encoder writing a zlib file
+    pub fn zlib(w: W, level: u32, strategy: Strategy) -> Self {
+        Self::new(w, level, WINDOW_BITS, strategy, None)
+    }
+
+    fn new(w: W, level: u32, window_bits: i32, strategy: Strategy, crc: Option<Crc>) -> Self {
+        let strategy = match strategy {
+            Strategy::Default => CompressionStrategy::Default,
+            Strategy::Filtered => CompressionStrategy::Filtered,
+            Strategy::Huffman => CompressionStrategy::HuffmanOnly,
+            Strategy::Rle => CompressionStrategy::RLE,
+        };
+        let flags = create_comp_flags_from_zip_params(level as i32, window_bits, strategy as i32);
+        Encoder {
+            w,
+            compressor: Box::new(CompressorOxide::new(flags)),
+            crc,
+            buf: Vec::new(),
+        }
+    }
+
+    /// Compress the data, writing out everything that the compressor gives
+    /// back
+    fn compress(&mut self, data: &[u8], flush: TDEFLFlush) -> io::Result<usize> {
+        let buf = &mut self.buf;
+        let (status, consumed) = compress_to_output(&mut self.compressor, data, flush, |x| {
+            buf.extend_from_slice(x);
+            true
+        });
+        match status {
+            TDEFLStatus::Okay | TDEFLStatus::Done => (),
+            x => return Err(io::Error::other(format!("Unable to compress: {:?}", x))),
+        }
+
+        if let Some(crc) = &mut self.crc {
+            crc.update(&data[..consumed]);
+        }
+        self.w.write_all(&self.buf)?;
+        self.buf.clear();
+
+        Ok(consumed)
+    }
+
+    /// Write the end of the stream, returning the inner writer
+    pub fn finish(mut self) -> Result<W> {
+        let _: usize = self.compress(&[], TDEFLFlush::Finish)?;
+        if let Some(crc) = &self.crc {
+            self.w.write_u32::<LittleEndian>(crc.sum())?;
+            self.w.write_u32::<LittleEndian>(crc.amount())?;
+        }
+        Ok(self.w)
+    }
+}
+impl<W: Write> Write for Encoder<W> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.compress(data, TDEFLFlush::None)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.w.flush()
+    }
+}
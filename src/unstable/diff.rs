@@ -0,0 +1,287 @@
+use crate::data::NBT;
+use crate::path::{NbtPath, Segment};
+use crate::string_write;
+use crate::Result;
+
+use std::io::Write;
+
+/// A single difference between two NBT trees
+#[derive(Clone, PartialEq, Debug)]
+pub enum NbtChange {
+    /// A tag that only exists in the new tree
+    Added { path: NbtPath, new: NBT },
+    /// A tag that only exists in the old tree
+    Removed { path: NbtPath, old: NBT },
+    /// A tag that exists in both trees, but with a different value or type.
+    /// Compounds and lists are never Changed as a whole unless their type
+    /// changed, or an empty list changed the type it was declared with,
+    /// instead their contents are compared.
+    Changed { path: NbtPath, old: NBT, new: NBT },
+}
+impl NbtChange {
+    pub fn path(&self) -> &NbtPath {
+        match self {
+            NbtChange::Added { path, .. } => path,
+            NbtChange::Removed { path, .. } => path,
+            NbtChange::Changed { path, .. } => path,
+        }
+    }
+}
+
+/// Find the differences between two NBT trees, in the order they appear in
+/// the trees.
+///
+/// Compounds are compared by key, with removed keys reported where they were
+/// in the old compound, and added keys after everything else in the compound.
+/// Lists are compared by index.
+pub fn diff(old: &NBT, new: &NBT) -> Vec<NbtChange> {
+    let mut ret = Vec::new();
+    let mut path = NbtPath::new();
+    diff_tag(old, new, None, &mut path, &mut ret);
+    ret
+}
+
+/// Like diff, but lists of compounds that all have the given key, such as
+/// UUID for entities, are compared by the value of that key instead of by
+/// index. An element inserted in the middle of such a list is then reported
+/// as a single added tag, and elements that only moved aren't reported at
+/// all.
+///
+/// Matched and added elements are given by their index in the new list, and
+/// removed ones by their index in the old list. Added elements come after
+/// everything else in the list. Lists where the key is missing from some
+/// element or has the same value twice are compared by index as in diff.
+pub fn diff_by_key(old: &NBT, new: &NBT, list_key: &[u8]) -> Vec<NbtChange> {
+    let mut ret = Vec::new();
+    let mut path = NbtPath::new();
+    diff_tag(old, new, Some(list_key), &mut path, &mut ret);
+    ret
+}
+
+fn diff_tag(
+    old: &NBT,
+    new: &NBT,
+    list_key: Option<&[u8]>,
+    path: &mut NbtPath,
+    ret: &mut Vec<NbtChange>,
+) {
+    match (old, new) {
+        (NBT::Compound(a), NBT::Compound(b)) => {
+            for (key, val) in a {
+                path.push(Segment::Key(key.clone()));
+                match new.get(key) {
+                    Some(x) => diff_tag(val, x, list_key, path, ret),
+                    None => ret.push(NbtChange::Removed {
+                        path: path.clone(),
+                        old: val.clone(),
+                    }),
+                }
+                let _: Option<Segment> = path.pop();
+            }
+            for (key, val) in b {
+                if old.get(key).is_none() {
+                    ret.push(NbtChange::Added {
+                        path: path.join(Segment::Key(key.clone())),
+                        new: val.clone(),
+                    });
+                }
+            }
+        }
+        (
+            NBT::List {
+                element_type: ta,
+                items: a,
+            },
+            NBT::List {
+                element_type: tb,
+                items: b,
+            },
+        ) if (a.is_empty() || b.is_empty()) && ta != tb => ret.push(NbtChange::Changed {
+            path: path.clone(),
+            old: old.clone(),
+            new: new.clone(),
+        }),
+        (NBT::List { items: a, .. }, NBT::List { items: b, .. }) => {
+            if let Some(key) = list_key {
+                if let (Some(a_ids), Some(b_ids)) = (list_ids(a, key), list_ids(b, key)) {
+                    diff_list_by_key(a, b, &a_ids, &b_ids, key, path, ret);
+                    return;
+                }
+            }
+            for i in 0..a.len().max(b.len()) {
+                path.push(Segment::Index(i));
+                match (a.get(i), b.get(i)) {
+                    (Some(x), Some(y)) => diff_tag(x, y, list_key, path, ret),
+                    (Some(x), None) => ret.push(NbtChange::Removed {
+                        path: path.clone(),
+                        old: x.clone(),
+                    }),
+                    (None, Some(y)) => ret.push(NbtChange::Added {
+                        path: path.clone(),
+                        new: y.clone(),
+                    }),
+                    (None, None) => unreachable!(),
+                }
+                let _: Option<Segment> = path.pop();
+            }
+        }
+        (a, b) => {
+            if !a.bitwise_eq(b) {
+                ret.push(NbtChange::Changed {
+                    path: path.clone(),
+                    old: a.clone(),
+                    new: b.clone(),
+                });
+            }
+        }
+    }
+}
+
+/// The value of the key in every element of the list, or None if any
+/// element isn't a compound with the key, or two have the same value
+fn list_ids<'a>(list: &'a [NBT], key: &[u8]) -> Option<Vec<&'a NBT>> {
+    let mut ret: Vec<&NBT> = Vec::with_capacity(list.len());
+    for val in list {
+        let id = val.get(key)?;
+        if ret.iter().any(|x| x.bitwise_eq(id)) {
+            return None;
+        }
+        ret.push(id);
+    }
+    Some(ret)
+}
+
+fn diff_list_by_key(
+    a: &[NBT],
+    b: &[NBT],
+    a_ids: &[&NBT],
+    b_ids: &[&NBT],
+    key: &[u8],
+    path: &mut NbtPath,
+    ret: &mut Vec<NbtChange>,
+) {
+    for (i, (val, id)) in a.iter().zip(a_ids).enumerate() {
+        match b_ids.iter().position(|x| x.bitwise_eq(id)) {
+            Some(j) => {
+                path.push(Segment::Index(j));
+                diff_tag(val, &b[j], Some(key), path, ret);
+                let _: Option<Segment> = path.pop();
+            }
+            None => ret.push(NbtChange::Removed {
+                path: path.join(Segment::Index(i)),
+                old: val.clone(),
+            }),
+        }
+    }
+    for (i, (val, id)) in b.iter().zip(b_ids).enumerate() {
+        if !a_ids.iter().any(|x| x.bitwise_eq(id)) {
+            ret.push(NbtChange::Added {
+                path: path.join(Segment::Index(i)),
+                new: val.clone(),
+            });
+        }
+    }
+}
+
+/// Write the changes one per line, prefixed with - for removed tags, + for
+/// added tags and ~ for changed tags. Values are given in the pretty text
+/// format, with compounds and lists abbreviated to their type.
+pub fn write_changes<W: Write>(w: &mut W, changes: &[NbtChange]) -> Result<()> {
+    for change in changes {
+        match change {
+            NbtChange::Removed { path, old } => {
+                writeln!(w, "- {}: {}", path, short_value(old))?;
+            }
+            NbtChange::Added { path, new } => {
+                writeln!(w, "+ {}: {}", path, short_value(new))?;
+            }
+            NbtChange::Changed { path, old, new } => {
+                writeln!(
+                    w,
+                    "~ {}: {} -> {}",
+                    path,
+                    short_value(old),
+                    short_value(new)
+                )?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Write the changes in a format resembling a unified diff, with a hunk
+/// for each change, headed by the path of the change. Removed and added tags
+/// are given in full in the pretty text format, with each line prefixed by
+/// - or + respectively.
+pub fn write_unified<W: Write>(
+    w: &mut W,
+    old_name: &str,
+    new_name: &str,
+    changes: &[NbtChange],
+) -> Result<()> {
+    if changes.is_empty() {
+        return Ok(());
+    }
+
+    writeln!(w, "--- {}", old_name)?;
+    writeln!(w, "+++ {}", new_name)?;
+    for change in changes {
+        writeln!(w, "@@ {} @@", change.path())?;
+        let name = match change.path().segments().last() {
+            Some(Segment::Key(x)) => Some(x.as_slice()),
+            _ => None,
+        };
+        match change {
+            NbtChange::Removed { old, .. } => write_prefixed(w, "-", name, old)?,
+            NbtChange::Added { new, .. } => write_prefixed(w, "+", name, new)?,
+            NbtChange::Changed { old, new, .. } => {
+                write_prefixed(w, "-", name, old)?;
+                write_prefixed(w, "+", name, new)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Write the tag in the pretty text format, with every line prefixed
+fn write_prefixed<W: Write>(w: &mut W, prefix: &str, name: Option<&[u8]>, tag: &NBT) -> Result<()> {
+    let mut buf = Vec::new();
+    string_write::write_single_tag(&mut buf, name, tag)?;
+    for line in buf.split(|x| *x == b'\n') {
+        /* The text ends with a newline, so the last line is always empty */
+        if line.is_empty() {
+            continue;
+        }
+        w.write_all(prefix.as_bytes())?;
+        w.write_all(line)?;
+        writeln!(w)?;
+    }
+    Ok(())
+}
+
+/// A one line description of a tag, its value for atomic tags and its type
+/// for the rest
+fn short_value(tag: &NBT) -> String {
+    match tag {
+        NBT::Byte(_)
+        | NBT::Short(_)
+        | NBT::Int(_)
+        | NBT::Long(_)
+        | NBT::Float(_)
+        | NBT::Double(_)
+        | NBT::String(_) => {
+            let mut buf = Vec::new();
+            string_write::write_single_tag(&mut buf, None, tag).expect("writing to a Vec failed");
+            let s = String::from_utf8_lossy(&buf);
+            format!("{} {}", tag.type_string(), s.trim_end())
+        }
+        NBT::ByteArray(x) => format!("ByteArray of length {}", x.len()),
+        NBT::IntArray(x) => format!("IntArray of length {}", x.len()),
+        NBT::LongArray(x) => format!("LongArray of length {}", x.len()),
+        NBT::List { items: x, .. } => format!("List of length {}", x.len()),
+        NBT::Compound(x) => format!("Compound with {} tags", x.len()),
+        NBT::End => "End".to_string(),
+    }
+}
@@ -0,0 +1,150 @@
+//! Drawing an NBT tree as a Graphviz DOT graph, such as with
+//! `dot -Tpng`, for seeing how a file is structured.
+//!
+//! Every tag is a node, numbered in the order they appear in the file so
+//! that the same tree always gives the same graph. Compounds and lists are
+//! labelled with their type and point to their contents, with the edges
+//! labelled by key or index, while other tags show their value. Large parts
+//! of the tree are cut short to keep the graph readable.
+
+use std::fmt::Write;
+
+use crate::data::NBT;
+
+/// The most elements of an array shown before the rest are left out
+pub const MAX_ARRAY_ELEMENTS: usize = 8;
+/// The most tags inside of a compound or list drawn before the rest are
+/// replaced by a single node saying how many were left out
+pub const MAX_CHILDREN: usize = 32;
+/// The most characters of a String or key shown before it is cut short
+pub const MAX_STRING_CHARS: usize = 40;
+
+/// Returns the DOT graph of the tree
+pub fn to_dot(tag: &NBT) -> String {
+    let mut graph = Graph {
+        out: String::new(),
+        next_id: 0,
+    };
+    graph.out.push_str("digraph nbt {\n\tnode [shape=box];\n");
+    let _: usize = graph.node(tag);
+    graph.out.push_str("}\n");
+
+    graph.out
+}
+
+struct Graph {
+    out: String,
+    next_id: usize,
+}
+impl Graph {
+    /// Add the node for the tag and everything inside of it, returning its id
+    fn node(&mut self, tag: &NBT) -> usize {
+        let id = self.add_node(&label(tag));
+
+        match tag {
+            NBT::Compound(x) => {
+                for (key, val) in x.iter().take(MAX_CHILDREN) {
+                    let child = self.node(val);
+                    self.add_edge(id, child, &shorten(&String::from_utf8_lossy(key)));
+                }
+                self.add_left_out(id, x.len());
+            }
+            NBT::List { items: x, .. } => {
+                for (i, val) in x.iter().enumerate().take(MAX_CHILDREN) {
+                    let child = self.node(val);
+                    self.add_edge(id, child, &i.to_string());
+                }
+                self.add_left_out(id, x.len());
+            }
+            _ => (),
+        }
+
+        id
+    }
+
+    fn add_node(&mut self, label: &str) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        /* Writing to a String can't fail */
+        writeln!(self.out, "\tn{} [label=\"{}\"];", id, escape(label)).unwrap();
+        id
+    }
+
+    fn add_edge(&mut self, from: usize, to: usize, label: &str) {
+        writeln!(
+            self.out,
+            "\tn{} -> n{} [label=\"{}\"];",
+            from,
+            to,
+            escape(label)
+        )
+        .unwrap();
+    }
+
+    /// Add a node saying how many tags of a container of the given length
+    /// weren't drawn, if any
+    fn add_left_out(&mut self, parent: usize, len: usize) {
+        if len > MAX_CHILDREN {
+            let child = self.add_node(&format!("{} more", len - MAX_CHILDREN));
+            self.add_edge(parent, child, "...");
+        }
+    }
+}
+
+/// The label of the node of a tag, its type and its value if it has one
+fn label(tag: &NBT) -> String {
+    match tag {
+        NBT::End => "End".to_string(),
+        NBT::Byte(x) => format!("Byte {}", x),
+        NBT::Short(x) => format!("Short {}", x),
+        NBT::Int(x) => format!("Int {}", x),
+        NBT::Long(x) => format!("Long {}", x),
+        NBT::Float(x) => format!("Float {}", x),
+        NBT::Double(x) => format!("Double {}", x),
+        NBT::ByteArray(x) => format!("ByteArray {}", array(x)),
+        NBT::String(x) => format!("String \"{}\"", shorten(&String::from_utf8_lossy(x))),
+        NBT::List { items: x, .. } => format!("List ({})", x.len()),
+        NBT::Compound(x) => format!("Compound ({})", x.len()),
+        NBT::IntArray(x) => format!("IntArray {}", array(x)),
+        NBT::LongArray(x) => format!("LongArray {}", array(x)),
+    }
+}
+
+/// Format the first elements of an array
+fn array<T: ToString>(x: &[T]) -> String {
+    let mut elements: Vec<String> = x
+        .iter()
+        .take(MAX_ARRAY_ELEMENTS)
+        .map(ToString::to_string)
+        .collect();
+    if x.len() > MAX_ARRAY_ELEMENTS {
+        elements.push(format!("... {} more", x.len() - MAX_ARRAY_ELEMENTS));
+    }
+    format!("[{}]", elements.join(", "))
+}
+
+/// Cut a string short to MAX_STRING_CHARS characters
+fn shorten(x: &str) -> String {
+    if x.chars().count() > MAX_STRING_CHARS {
+        let mut ret: String = x.chars().take(MAX_STRING_CHARS).collect();
+        ret.push_str("...");
+        ret
+    } else {
+        x.to_string()
+    }
+}
+
+/// Escape a string to be put between double quotes in the DOT language
+fn escape(x: &str) -> String {
+    let mut ret = String::with_capacity(x.len());
+    for c in x.chars() {
+        match c {
+            '"' => ret.push_str("\\\""),
+            '\\' => ret.push_str("\\\\"),
+            '\n' => ret.push_str("\\n"),
+            '\r' => (),
+            c => ret.push(c),
+        }
+    }
+    ret
+}
@@ -0,0 +1,36 @@
+//! Formatting of sizes and counts for people to read, for the reports that
+//! nbted prints.
+
+const UNITS: &[&str] = &["KiB", "MiB", "GiB", "TiB", "PiB", "EiB"];
+
+/// Format a number of bytes in binary units with one decimal, such as
+/// 17.7 MiB. Sizes below 1024 bytes are given exactly, such as 512 B.
+pub fn bytes(n: u64) -> String {
+    if n < 1024 {
+        return format!("{} B", n);
+    }
+
+    let mut value = n as f64 / 1024.0;
+    let mut unit = 0;
+    /* Move up a unit before the value would be rounded to 1024.0 */
+    while value >= 1023.95 && unit + 1 < UNITS.len() {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+/// Format a count with a comma between every three digits, such as
+/// 18,532,864
+pub fn count(n: u64) -> String {
+    let digits = n.to_string();
+    let mut ret = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            ret.push(',');
+        }
+        ret.push(c);
+    }
+    ret
+}
@@ -0,0 +1,249 @@
+//! Adapters for keeping track of how many bytes are read or written, and for
+//! putting a limit on it, and replacing files without leaving them half
+//! written.
+
+use crate::Result;
+
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufWriter, Read, Write};
+use std::path::Path;
+
+use failure::Fail;
+use tempdir::TempDir;
+
+/// The error returned by the limiting adapters when the limit is exceeded
+fn limit_exceeded(limit: u64) -> io::Error {
+    io::Error::other(format!("exceeded the limit of {} bytes", limit))
+}
+
+/// The error from write_file_atomically when the file was replaced, but the
+/// directory it's in couldn't be synced. The new file is in place, but might
+/// not be after a crash.
+#[derive(Debug)]
+pub struct DirSyncError {
+    path: String,
+    error: io::Error,
+}
+impl fmt::Display for DirSyncError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Unable to sync the directory of {} to disk", self.path)
+    }
+}
+impl Fail for DirSyncError {
+    fn cause(&self) -> Option<&dyn Fail> {
+        Some(&self.error)
+    }
+}
+
+/// A reader that counts the number of bytes read through it
+pub struct CountingReader<R> {
+    inner: R,
+    count: u64,
+}
+impl<R> CountingReader<R> {
+    pub fn new(inner: R) -> Self {
+        CountingReader { inner, count: 0 }
+    }
+
+    /// The number of bytes read so far
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.count += n as u64;
+        Ok(n)
+    }
+}
+impl<R: BufRead> BufRead for CountingReader<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.count += amt as u64;
+        self.inner.consume(amt);
+    }
+}
+
+/// A reader that fails once more than a given number of bytes would be read
+/// through it.
+///
+/// Unlike Read::take, which just stops at the limit as if the reader was EOF,
+/// this returns an error if there is more to read, so that a file that is too
+/// large isn't mistaken for a truncated one.
+pub struct LimitingReader<R> {
+    inner: R,
+    limit: u64,
+    remaining: u64,
+}
+impl<R> LimitingReader<R> {
+    pub fn new(inner: R, limit: u64) -> Self {
+        LimitingReader {
+            inner,
+            limit,
+            remaining: limit,
+        }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+impl<R: Read> Read for LimitingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        if self.remaining == 0 {
+            /* Only an error if there actually is more to read */
+            let mut extra: [u8; 1] = [0];
+            return match self.inner.read(&mut extra)? {
+                0 => Ok(0),
+                _ => Err(limit_exceeded(self.limit)),
+            };
+        }
+
+        let max = buf.len().min(self.remaining as usize);
+        let n = self.inner.read(&mut buf[..max])?;
+        self.remaining -= n as u64;
+        Ok(n)
+    }
+}
+
+/// A writer that counts the number of bytes written through it
+pub struct CountingWriter<W> {
+    inner: W,
+    count: u64,
+}
+impl<W> CountingWriter<W> {
+    pub fn new(inner: W) -> Self {
+        CountingWriter { inner, count: 0 }
+    }
+
+    /// The number of bytes written so far
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.count += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// A writer that fails once more than a given number of bytes would be
+/// written through it. Everything up to the limit is still written.
+pub struct LimitingWriter<W> {
+    inner: W,
+    limit: u64,
+    remaining: u64,
+}
+impl<W> LimitingWriter<W> {
+    pub fn new(inner: W, limit: u64) -> Self {
+        LimitingWriter {
+            inner,
+            limit,
+            remaining: limit,
+        }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+impl<W: Write> Write for LimitingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        if self.remaining == 0 {
+            return Err(limit_exceeded(self.limit));
+        }
+
+        let max = buf.len().min(self.remaining as usize);
+        let n = self.inner.write(&buf[..max])?;
+        self.remaining -= n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Write the file at the path by giving write a temporary file in the same
+/// directory, syncing that to disk and renaming it over the path. Whether or
+/// not writing fails or is interrupted, the path is left with either the old
+/// file or the whole new one. The new file keeps the permissions of the old
+/// one, and a symlink is written through rather than replaced. Something that
+/// isn't a regular file, like /dev/null or a pipe, is just written to. If
+/// only syncing the directory fails, the error is a DirSyncError.
+pub fn write_file_atomically<F>(path: &Path, write: F) -> Result<()>
+where
+    F: FnOnce(&mut BufWriter<File>) -> Result<()>,
+{
+    let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let metadata = fs::metadata(&path).ok();
+    if let Some(ref metadata) = metadata {
+        if !metadata.is_file() {
+            let mut f = BufWriter::new(File::create(&path)?);
+            write(&mut f)?;
+            return Ok(f.flush()?);
+        }
+    }
+    let dir = match path.parent() {
+        Some(x) if !x.as_os_str().is_empty() => x,
+        _ => Path::new("."),
+    };
+    let name = match path.file_name() {
+        Some(x) => x,
+        None => bail!("{} is not a file name", path.display()),
+    };
+
+    /* The temporary file is in a directory of its own, which is removed with
+     * whatever is in it if anything fails */
+    let tmpdir = TempDir::new_in(dir, ".nbted")?;
+    let tmp_path = tmpdir.path().join(name);
+    {
+        let mut f = BufWriter::new(File::create(&tmp_path)?);
+        write(&mut f)?;
+        let f = f.into_inner().map_err(|e| e.into_error())?;
+        if let Some(metadata) = metadata {
+            f.set_permissions(metadata.permissions())?;
+        }
+        f.sync_all()?;
+    }
+
+    /* On Windows rename also replaces an existing file, as long as nothing
+     * else has it open */
+    fs::rename(&tmp_path, &path)?;
+    /* The rename is only durable once the directory is synced */
+    #[cfg(unix)]
+    File::open(dir)
+        .and_then(|f| f.sync_all())
+        .map_err(|e| DirSyncError {
+            path: path.display().to_string(),
+            error: e,
+        })?;
+
+    Ok(())
+}
@@ -0,0 +1,460 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+
+use crate::data::{type_name, NBT};
+use crate::mutf8;
+use crate::path::{NbtPath, Segment};
+use crate::Result;
+
+use serde_json::{Map, Number, Value};
+
+/// The NBT type of every tag in a tree converted to JSON, by path. The
+/// elements of arrays aren't recorded, since the array's type covers them.
+/// The declared element type of every list is recorded as well, which is
+/// what an empty list gets back.
+///
+/// Tags that aren't in the map, such as ones added while the JSON was
+/// edited, get the natural type of their JSON value when converted back.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct TypeMap {
+    types: HashMap<NbtPath, u8>,
+    element_types: HashMap<NbtPath, u8>,
+}
+impl TypeMap {
+    /// Returns the type byte recorded for the given path
+    pub fn get(&self, path: &NbtPath) -> Option<u8> {
+        self.types.get(path).cloned()
+    }
+
+    /// Returns the element type recorded for the list at the given path
+    pub fn element_type(&self, path: &NbtPath) -> Option<u8> {
+        self.element_types.get(path).cloned()
+    }
+
+    pub fn len(&self) -> usize {
+        self.types.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.types.is_empty()
+    }
+}
+
+/// Convert the tag to the natural JSON for it, recording the type of every
+/// tag in the TypeMap. Fails if a string or key isn't valid modified UTF-8,
+/// since it couldn't be converted back to the same bytes.
+pub fn to_json_with_types(tag: &NBT) -> Result<(Value, TypeMap)> {
+    let mut types = TypeMap::default();
+    let mut path = NbtPath::new();
+    let value = to_json(tag, &mut path, &mut types)?;
+    Ok((value, types))
+}
+
+fn to_json(tag: &NBT, path: &mut NbtPath, types: &mut TypeMap) -> Result<Value> {
+    let _: Option<u8> = types.types.insert(path.clone(), tag.type_byte());
+
+    Ok(match tag {
+        NBT::End => Value::Null,
+        NBT::Byte(x) => Value::from(*x),
+        NBT::Short(x) => Value::from(*x),
+        NBT::Int(x) => Value::from(*x),
+        NBT::Long(x) => Value::from(*x),
+        NBT::Float(x) => float(f64::from(*x), path)?,
+        NBT::Double(x) => float(*x, path)?,
+        NBT::ByteArray(x) => Value::Array(x.iter().map(|x| Value::from(*x)).collect()),
+        NBT::IntArray(x) => Value::Array(x.iter().map(|x| Value::from(*x)).collect()),
+        NBT::LongArray(x) => Value::Array(x.iter().map(|x| Value::from(*x)).collect()),
+        NBT::String(x) => Value::String(utf8(x, path)?),
+        NBT::List {
+            element_type,
+            items: x,
+        } => {
+            let _: Option<u8> = types.element_types.insert(path.clone(), *element_type);
+            let mut ret = Vec::with_capacity(x.len());
+            for (i, val) in x.iter().enumerate() {
+                path.push(Segment::Index(i));
+                ret.push(to_json(val, path, types)?);
+                let _: Option<Segment> = path.pop();
+            }
+            Value::Array(ret)
+        }
+        NBT::Compound(x) => {
+            let mut ret = Map::with_capacity(x.len());
+            for (key, val) in x {
+                path.push(Segment::Key(key.clone()));
+                insert(&mut ret, utf8(key, path)?, to_json(val, path, types)?, path)?;
+                let _: Option<Segment> = path.pop();
+            }
+            Value::Object(ret)
+        }
+    })
+}
+
+/// A JSON number can't be NaN or infinite, so those fail
+fn float(x: f64, path: &NbtPath) -> Result<Value> {
+    match Number::from_f64(x) {
+        Some(x) => Ok(Value::Number(x)),
+        None => bail!("{} at {} can't be converted to JSON", x, location(path)),
+    }
+}
+
+/// Add the value of a compound's tag to the JSON object for the compound,
+/// failing if the key is already in it rather than losing one of the tags
+fn insert(
+    object: &mut Map<String, Value>,
+    key: String,
+    value: Value,
+    path: &NbtPath,
+) -> Result<()> {
+    if object.insert(key, value).is_some() {
+        bail!(
+            "Duplicate key at {}, so its compound can't be converted to JSON",
+            location(path)
+        );
+    }
+    Ok(())
+}
+
+/// Decode the modified UTF-8 of a string or key. Strings are encoded as
+/// modified UTF-8 again when converted back, so anything else, even if it's
+/// valid UTF-8, fails rather than coming back as different bytes.
+fn utf8(bytes: &[u8], path: &NbtPath) -> Result<String> {
+    match mutf8::decode(bytes) {
+        Ok(x) => Ok(x),
+        Err(_) => bail!(
+            "String at {} isn't valid modified UTF-8, so it can't be converted to JSON",
+            location(path)
+        ),
+    }
+}
+
+/// Convert JSON back to NBT, giving each value the type recorded for its
+/// path in the TypeMap. Values without a recorded type get their natural
+/// type: integers are Ints (or Longs if they don't fit), other numbers are
+/// Doubles, booleans are Bytes, arrays are Lists and objects are Compounds.
+pub fn from_json_with_types(value: &Value, types: &TypeMap) -> Result<NBT> {
+    let mut path = NbtPath::new();
+    from_json(value, &mut path, types)
+}
+
+fn from_json(value: &Value, path: &mut NbtPath, types: &TypeMap) -> Result<NBT> {
+    let type_byte = match types.get(path) {
+        Some(x) => x,
+        None => natural_type(value, path)?,
+    };
+
+    Ok(match (type_byte, value) {
+        (0, Value::Null) => NBT::End,
+        (1, Value::Number(x)) => NBT::Byte(fit(x, path, type_byte)?),
+        (1, Value::Bool(x)) => NBT::Byte(*x as i8),
+        (2, Value::Number(x)) => NBT::Short(fit(x, path, type_byte)?),
+        (3, Value::Number(x)) => NBT::Int(fit(x, path, type_byte)?),
+        (4, Value::Number(x)) => NBT::Long(fit(x, path, type_byte)?),
+        (5, Value::Number(x)) => NBT::Float(number(x) as f32),
+        (6, Value::Number(x)) => NBT::Double(number(x)),
+        (7, Value::Array(x)) => NBT::ByteArray(array_elements(x, path, type_byte)?),
+        (8, Value::String(x)) => NBT::String(mutf8::encode(x)),
+        (9, Value::Array(x)) => {
+            let mut ret: Vec<NBT> = Vec::with_capacity(x.len());
+            for (i, val) in x.iter().enumerate() {
+                path.push(Segment::Index(i));
+                let tag = from_json(val, path, types)?;
+                if let Some(first) = ret.first() {
+                    if first.type_byte() != tag.type_byte() {
+                        bail!(
+                            "{} at {} in a list of {}, all elements of a list must have the same type",
+                            tag.type_string(),
+                            location(path),
+                            first.type_string()
+                        );
+                    }
+                }
+                let _: Option<Segment> = path.pop();
+                ret.push(tag);
+            }
+            /* An empty list has nothing to take its type from */
+            match (ret.is_empty(), types.element_type(path)) {
+                (true, Some(element_type)) => NBT::List {
+                    element_type,
+                    items: ret,
+                },
+                _ => NBT::list(ret),
+            }
+        }
+        (10, Value::Object(x)) => {
+            let mut ret = Vec::with_capacity(x.len());
+            for (key, val) in x {
+                let key = mutf8::encode(key);
+                path.push(Segment::Key(key.clone()));
+                ret.push((key, from_json(val, path, types)?));
+                let _: Option<Segment> = path.pop();
+            }
+            NBT::Compound(ret)
+        }
+        (11, Value::Array(x)) => NBT::IntArray(array_elements(x, path, type_byte)?),
+        (12, Value::Array(x)) => NBT::LongArray(array_elements(x, path, type_byte)?),
+        _ => bail!(
+            "Expected {} at {} to be {}, since it was {}",
+            json_type(value),
+            location(path),
+            json_type_of(type_byte),
+            type_name(type_byte)
+        ),
+    })
+}
+
+/// Convert the tag to JSON where every tag is an object holding its value
+/// under the name of its type, such as {"Int": 5} or {"Compound": {...}}.
+/// A list holds its elements under the name of their type, without naming it
+/// again for each element, such as {"List": {"Int": [1, 2]}}, so that an
+/// empty list keeps its declared type. NaN and infinite Floats and Doubles,
+/// which JSON numbers can't be, are given as a string of their bits in hex,
+/// such as {"Float": "0x7fc00000"}. Unlike to_json_with_types the JSON
+/// holds the types itself, so it can be converted back by from_tagged_json
+/// without anything else.
+pub fn to_tagged_json(tag: &NBT) -> Result<Value> {
+    let mut path = NbtPath::new();
+    to_tagged(tag, &mut path)
+}
+
+fn to_tagged(tag: &NBT, path: &mut NbtPath) -> Result<Value> {
+    let mut ret = Map::with_capacity(1);
+    let _: Option<Value> = ret.insert(tag.type_string().to_string(), to_tagged_value(tag, path)?);
+    Ok(Value::Object(ret))
+}
+
+/// The value a tag is held under in the tagged JSON
+fn to_tagged_value(tag: &NBT, path: &mut NbtPath) -> Result<Value> {
+    Ok(match tag {
+        NBT::List {
+            element_type,
+            items: x,
+        } => {
+            let element_type = match x.first() {
+                Some(first) => first.type_byte(),
+                None => *element_type,
+            };
+            let mut ret = Vec::with_capacity(x.len());
+            for (i, val) in x.iter().enumerate() {
+                path.push(Segment::Index(i));
+                if val.type_byte() != element_type {
+                    bail!(
+                        "{} at {} in a list of {}, so the list can't be converted to JSON",
+                        val.type_string(),
+                        location(path),
+                        type_name(element_type)
+                    );
+                }
+                ret.push(to_tagged_value(val, path)?);
+                let _: Option<Segment> = path.pop();
+            }
+            let mut list = Map::with_capacity(1);
+            let _: Option<Value> =
+                list.insert(type_name(element_type).to_string(), Value::Array(ret));
+            Value::Object(list)
+        }
+        NBT::Compound(x) => {
+            let mut ret = Map::with_capacity(x.len());
+            for (key, val) in x {
+                path.push(Segment::Key(key.clone()));
+                insert(&mut ret, utf8(key, path)?, to_tagged(val, path)?, path)?;
+                let _: Option<Segment> = path.pop();
+            }
+            Value::Object(ret)
+        }
+        NBT::Float(x) if !x.is_finite() => Value::String(format!("{:#010x}", x.to_bits())),
+        NBT::Double(x) if !x.is_finite() => Value::String(format!("{:#018x}", x.to_bits())),
+        /* Everything else has the same value as in the plain JSON */
+        _ => to_json(tag, path, &mut TypeMap::default())?,
+    })
+}
+
+/// Convert JSON written by to_tagged_json back to NBT
+pub fn from_tagged_json(value: &Value) -> Result<NBT> {
+    let mut path = NbtPath::new();
+    from_tagged(value, &mut path)
+}
+
+fn from_tagged(value: &Value, path: &mut NbtPath) -> Result<NBT> {
+    let (type_byte, inner) = tagged_type(value, path)?;
+    from_tagged_value(type_byte, inner, path)
+}
+
+/// The type named by the single key of an object in the tagged JSON, and the
+/// value under it
+fn tagged_type<'a>(value: &'a Value, path: &NbtPath) -> Result<(u8, &'a Value)> {
+    let (name, inner) = match value {
+        Value::Object(x) if x.len() == 1 => x.iter().next().unwrap(),
+        x => bail!(
+            "Expected {} at {} to be an object with a single key naming its type",
+            json_type(x),
+            location(path)
+        ),
+    };
+    match (0..=12).find(|x| type_name(*x) == name) {
+        Some(x) => Ok((x, inner)),
+        None => bail!("Unknown type {} at {}", name, location(path)),
+    }
+}
+
+/// Convert the value a tag of the given type is held under in the tagged
+/// JSON back to the tag
+fn from_tagged_value(type_byte: u8, value: &Value, path: &mut NbtPath) -> Result<NBT> {
+    match (type_byte, value) {
+        (9, Value::Object(_)) => {
+            let (element_type, elements) = tagged_type(value, path)?;
+            let elements = match elements {
+                Value::Array(x) => x,
+                x => bail!(
+                    "Expected {} at {} to be an array of the elements of the list",
+                    json_type(x),
+                    location(path)
+                ),
+            };
+            let mut ret: Vec<NBT> = Vec::with_capacity(elements.len());
+            for (i, val) in elements.iter().enumerate() {
+                path.push(Segment::Index(i));
+                ret.push(from_tagged_value(element_type, val, path)?);
+                let _: Option<Segment> = path.pop();
+            }
+            Ok(NBT::List {
+                element_type,
+                items: ret,
+            })
+        }
+        (10, Value::Object(x)) => {
+            let mut ret = Vec::with_capacity(x.len());
+            for (key, val) in x {
+                let key = mutf8::encode(key);
+                path.push(Segment::Key(key.clone()));
+                ret.push((key, from_tagged(val, path)?));
+                let _: Option<Segment> = path.pop();
+            }
+            Ok(NBT::Compound(ret))
+        }
+        (5, Value::String(x)) => {
+            let bits = float_bits(x, path, type_byte)?;
+            match u32::try_from(bits) {
+                Ok(bits) => Ok(NBT::Float(f32::from_bits(bits))),
+                Err(_) => bail!("{} at {} doesn't fit in a Float", x, location(path)),
+            }
+        }
+        (6, Value::String(x)) => Ok(NBT::Double(f64::from_bits(float_bits(x, path, type_byte)?))),
+        /* Everything else has the same value as in the plain JSON, and is
+         * converted like a value with a recorded type */
+        (9, x) | (10, x) => bail!(
+            "Expected {} at {} to be an object, since it was {}",
+            json_type(x),
+            location(path),
+            type_name(type_byte)
+        ),
+        (_, x) => {
+            let mut types = TypeMap::default();
+            let _: Option<u8> = types.types.insert(path.clone(), type_byte);
+            from_json(x, path, &types)
+        }
+    }
+}
+
+/// Parse the hex bits that a Float or Double is given as in the tagged JSON
+fn float_bits(x: &str, path: &NbtPath, type_byte: u8) -> Result<u64> {
+    match x.strip_prefix("0x").map(|x| u64::from_str_radix(x, 16)) {
+        Some(Ok(bits)) => Ok(bits),
+        _ => bail!(
+            "Expected {} at {} to be a number or its bits in hex, since it was {}",
+            x,
+            location(path),
+            type_name(type_byte)
+        ),
+    }
+}
+
+/// Where the path is, for error messages, since the path of the root is
+/// empty
+fn location(path: &NbtPath) -> String {
+    if path.segments().is_empty() {
+        "the root".to_string()
+    } else {
+        path.to_string()
+    }
+}
+
+/// The type a JSON value gets when the TypeMap has nothing for it
+fn natural_type(value: &Value, path: &NbtPath) -> Result<u8> {
+    Ok(match value {
+        Value::Null => bail!("Unable to convert null at {} to NBT", location(path)),
+        Value::Bool(_) => 1,
+        Value::Number(x) => match x.as_i64() {
+            Some(x) if i32::try_from(x).is_ok() => 3,
+            Some(_) => 4,
+            None => 6,
+        },
+        Value::String(_) => 8,
+        Value::Array(_) => 9,
+        Value::Object(_) => 10,
+    })
+}
+
+/// Convert the JSON number to the integer type of the given tag type,
+/// failing if it doesn't fit or isn't an integer
+fn fit<T: TryFrom<i64>>(x: &Number, path: &NbtPath, type_byte: u8) -> Result<T> {
+    match x.as_i64().map(T::try_from) {
+        Some(Ok(x)) => Ok(x),
+        _ => bail!(
+            "{} at {} doesn't fit in a {}",
+            x,
+            location(path),
+            type_name(type_byte)
+        ),
+    }
+}
+
+/// The value of the JSON number as a float, which every number has
+fn number(x: &Number) -> f64 {
+    /* Only arbitrary precision numbers can fail, which aren't enabled */
+    x.as_f64().unwrap_or(f64::NAN)
+}
+
+fn array_elements<T: TryFrom<i64>>(
+    values: &[Value],
+    path: &mut NbtPath,
+    type_byte: u8,
+) -> Result<Vec<T>> {
+    let mut ret = Vec::with_capacity(values.len());
+    for (i, val) in values.iter().enumerate() {
+        path.push(Segment::Index(i));
+        match val {
+            Value::Number(x) => ret.push(fit(x, path, type_byte)?),
+            x => bail!(
+                "Expected {} at {} to be a number, since it's in a {}",
+                json_type(x),
+                location(path),
+                type_name(type_byte)
+            ),
+        }
+        let _: Option<Segment> = path.pop();
+    }
+    Ok(ret)
+}
+
+fn json_type(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "an object",
+    }
+}
+
+/// The kind of JSON value a tag of the given type is converted to
+fn json_type_of(type_byte: u8) -> &'static str {
+    match type_byte {
+        0 => "null",
+        1..=6 => "a number",
+        8 => "a string",
+        10 => "an object",
+        _ => "an array",
+    }
+}
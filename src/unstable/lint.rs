@@ -0,0 +1,174 @@
+//! Checks for problems in files that parse, but which were probably written
+//! by a buggy tool and may not be read the way the tool meant by Minecraft.
+
+use crate::data::{type_name, KeySet, NBT};
+use crate::mutf8;
+use crate::path::{NbtPath, Segment};
+use crate::read::{self, ReadOptions};
+use crate::visit::{self, Visitor};
+use crate::write;
+use crate::Result;
+
+use std::fmt;
+use std::io::BufRead;
+
+/// The largest x or z block coordinate that is inside of the world border
+const MAX_HORIZONTAL: f64 = 30_000_000.0;
+/// The largest y coordinate an entity can be at
+const MAX_VERTICAL: f64 = 20_000_000.0;
+/// The largest x or z chunk coordinate that is inside of the world border
+const MAX_CHUNK: f64 = MAX_HORIZONTAL / 16.0;
+
+/// A single kind of problem found by lint_file
+#[derive(Clone, PartialEq, Debug)]
+pub enum IssueKind {
+    /// A list whose elements don't all have the type of its first element,
+    /// which can't be written to a file
+    MixedList { expected: u8, found: u8 },
+    /// An empty list that declares a type other than End for its elements
+    TypedEmptyList { type_id: u8 },
+    /// A key that occurs more than once in the same compound. The path is
+    /// that of the later occurrence.
+    DuplicateKey,
+    /// A key or String that isn't valid modified UTF-8
+    InvalidUtf8,
+    /// A coordinate that is outside of the world, or isn't a number
+    Coordinate,
+}
+
+/// A problem found by lint_file, at the path of the tag it was found in
+#[derive(Clone, PartialEq, Debug)]
+pub struct Issue {
+    pub path: NbtPath,
+    pub kind: IssueKind,
+}
+
+impl fmt::Display for IssueKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IssueKind::MixedList { expected, found } => write!(
+                f,
+                "List of {} also contains a {}",
+                type_name(*expected),
+                type_name(*found)
+            ),
+            IssueKind::TypedEmptyList { type_id } => write!(
+                f,
+                "Empty List declares elements of type {} instead of End",
+                type_name(*type_id)
+            ),
+            IssueKind::DuplicateKey => write!(f, "Duplicate key in Compound"),
+            IssueKind::InvalidUtf8 => write!(f, "Not valid modified UTF-8"),
+            IssueKind::Coordinate => write!(f, "Coordinate is outside of the world"),
+        }
+    }
+}
+
+/// Read an NBT file under the limits in the options and return every problem
+/// found in it, in the order they appear in the file, where the problems with
+/// the keys of a compound come before those inside of it. An error is only
+/// returned if the file doesn't parse.
+pub fn lint_file<R: BufRead>(reader: &mut R, options: &ReadOptions) -> Result<Vec<Issue>> {
+    /* Duplicate keys are one of the problems reported */
+    let options = ReadOptions {
+        forbid_duplicate_keys: false,
+        ..*options
+    };
+    let file = read::read_file_with_options(reader, &options)?;
+
+    Ok(lint_tree(&file.root))
+}
+
+/// Return every problem found in the tree
+pub fn lint_tree(tag: &NBT) -> Vec<Issue> {
+    let mut linter = Linter { issues: Vec::new() };
+    /* The linter never errors */
+    visit::visit(tag, &mut linter).expect("Linter errored");
+
+    linter.issues
+}
+
+struct Linter {
+    issues: Vec<Issue>,
+}
+impl Linter {
+    fn push(&mut self, path: NbtPath, kind: IssueKind) {
+        self.issues.push(Issue { path, kind });
+    }
+
+    /// Check the tag if its key, or the key of the list it's in, names a
+    /// coordinate
+    fn check_coordinate(&mut self, path: &NbtPath, tag: &NBT) {
+        let axis: &[u8] = match path.segments() {
+            [.., Segment::Key(key)] => key,
+            /* Entities store their position as a list of x, y and z */
+            [.., Segment::Key(key), Segment::Index(i)] if key == b"Pos" && *i < 3 => {
+                [b"x", b"y", b"z"][*i]
+            }
+            _ => return,
+        };
+        let limit = match axis {
+            b"x" | b"z" | b"X" | b"Z" => MAX_HORIZONTAL,
+            b"y" | b"Y" => MAX_VERTICAL,
+            b"xPos" | b"zPos" => MAX_CHUNK,
+            _ => return,
+        };
+        let value = match *tag {
+            NBT::Int(x) => f64::from(x),
+            NBT::Float(x) => f64::from(x),
+            NBT::Double(x) => x,
+            _ => return,
+        };
+        if value.is_nan() || value.abs() > limit {
+            self.push(path.clone(), IssueKind::Coordinate);
+        }
+    }
+}
+impl<'a> Visitor<'a> for Linter {
+    fn visit_tag(&mut self, path: &NbtPath, tag: &'a NBT) -> Result<()> {
+        match tag {
+            NBT::List {
+                element_type,
+                items: x,
+            } => {
+                if x.is_empty() && *element_type != 0x0 {
+                    self.push(
+                        path.clone(),
+                        IssueKind::TypedEmptyList {
+                            type_id: *element_type,
+                        },
+                    );
+                }
+                for (i, val) in write::mixed_elements(x) {
+                    self.push(
+                        path.join(Segment::Index(i)),
+                        IssueKind::MixedList {
+                            expected: x[0].type_byte(),
+                            found: val.type_byte(),
+                        },
+                    );
+                }
+            }
+            NBT::Compound(x) => {
+                let mut keys = KeySet::default();
+                for (key, _) in x {
+                    let key_path = path.join(Segment::Key(key.clone()));
+                    if !keys.insert(&key[..]) {
+                        self.push(key_path.clone(), IssueKind::DuplicateKey);
+                    }
+                    if mutf8::decode(key).is_err() {
+                        self.push(key_path, IssueKind::InvalidUtf8);
+                    }
+                }
+            }
+            NBT::String(x) => {
+                if mutf8::decode(x).is_err() {
+                    self.push(path.clone(), IssueKind::InvalidUtf8);
+                }
+            }
+            _ => self.check_coordinate(path, tag),
+        }
+
+        Ok(())
+    }
+}
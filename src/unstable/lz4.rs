@@ -0,0 +1,98 @@
+//! The LZ4 frame format, which some servers use for NBT embedded in their own
+//! data.
+//!
+//! Frames are read and written with lz4_flex, which is only built with the
+//! lz4 feature. Without it, LZ4 files are still recognized, but reading or
+//! writing them fails. Frames are written with independent 64 KiB blocks and
+//! a content checksum, which is read by anything that reads LZ4 frames.
+
+use crate::Result;
+
+use std::io::{Read, Write};
+
+/// The magic number every frame starts with, stored little endian as
+/// 04 22 4D 18
+pub const MAGIC: u32 = 0x184d_2204;
+
+/// A reader that decompresses the frame read from the given reader as it's
+/// read from. Like a gzip decoder, anything after the frame that isn't
+/// another frame, such as padding, is ignored.
+#[cfg(feature = "lz4")]
+pub fn decoder<'a, R: Read + 'a>(reader: R) -> Result<Box<dyn Read + 'a>> {
+    Ok(Box::new(Decoder {
+        inner: lz4_flex::frame::FrameDecoder::new(reader),
+        started: false,
+    }))
+}
+
+/// Wraps lz4_flex's decoder, which reads frames until the end of the input
+#[cfg(feature = "lz4")]
+struct Decoder<R: Read> {
+    inner: lz4_flex::frame::FrameDecoder<R>,
+    /// Whether anything has been decompressed yet, after which a missing
+    /// magic number can only be where a following frame would start
+    started: bool,
+}
+#[cfg(feature = "lz4")]
+impl<R: Read> Read for Decoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self.inner.read(buf) {
+            Ok(n) => {
+                self.started |= n > 0;
+                Ok(n)
+            }
+            Err(ref e) if self.started && is_wrong_magic(e) => Ok(0),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+#[cfg(feature = "lz4")]
+fn is_wrong_magic(e: &std::io::Error) -> bool {
+    matches!(
+        e.get_ref().and_then(|x| x.downcast_ref()),
+        Some(lz4_flex::frame::Error::WrongMagicNumber)
+    )
+}
+
+#[cfg(not(feature = "lz4"))]
+pub fn decoder<'a, R: Read + 'a>(_reader: R) -> Result<Box<dyn Read + 'a>> {
+    Err(unsupported())
+}
+
+/// Write a frame to the writer, compressing everything write writes as it's
+/// written, so that the uncompressed data is never held in memory
+#[cfg(feature = "lz4")]
+pub fn compress_with<W, F>(w: W, write: F) -> Result<()>
+where
+    W: Write,
+    F: FnOnce(&mut dyn Write) -> Result<()>,
+{
+    use lz4_flex::frame::{BlockMode, BlockSize, FrameEncoder, FrameInfo};
+
+    let info = FrameInfo::new()
+        .block_size(BlockSize::Max64KB)
+        .block_mode(BlockMode::Independent)
+        .content_checksum(true);
+    let mut encoder = FrameEncoder::with_frame_info(info, w);
+    write(&mut encoder)?;
+    let _: W = encoder.finish()?;
+
+    Ok(())
+}
+
+#[cfg(not(feature = "lz4"))]
+pub fn compress_with<W, F>(_w: W, _write: F) -> Result<()>
+where
+    W: Write,
+    F: FnOnce(&mut dyn Write) -> Result<()>,
+{
+    Err(unsupported())
+}
+
+/// The error given when reading or writing an LZ4 file without the lz4
+/// feature
+#[cfg(not(feature = "lz4"))]
+fn unsupported() -> failure::Error {
+    format_err!("LZ4 compressed files are recognized but not supported, nbted was built without the lz4 feature")
+}
@@ -1,9 +1,31 @@
+pub mod base64;
+pub mod checksum;
+pub mod components;
 pub mod data;
+pub mod deflate;
+pub mod diff;
+pub mod dot;
+pub mod humanize;
+pub mod io;
 pub mod iter_replacer;
+pub mod json;
+pub mod lint;
+pub mod lz4;
+pub mod mutf8;
+pub mod network;
+pub mod patch;
+pub mod path;
 pub mod read;
+pub mod region;
+pub mod repro;
+pub mod snbt_read;
+pub mod snbt_write;
+pub mod stream;
 pub mod string_read;
 pub mod string_write;
+pub mod visit;
 pub mod write;
+pub mod zstd;
 
 #[cfg(test)]
 mod tests;
@@ -0,0 +1,100 @@
+//! Java's modified UTF-8, which is how NBT strings are encoded.
+//!
+//! It differs from UTF-8 in two ways: NUL is written as the two bytes 0xc0
+//! 0x80, so that the encoded string never contains a zero byte, and characters
+//! outside of the Basic Multilingual Plane, such as emoji, are written as a
+//! UTF-16 surrogate pair with each half encoded on its own in three bytes
+//! (also known as CESU-8), rather than in four bytes.
+
+use crate::Result;
+
+/// Decode the character at the start of the bytes, returning it and the
+/// number of bytes it took up. Returns None if the bytes don't start with a
+/// character as Java would encode it, such as a zero byte, a four byte UTF-8
+/// sequence, an overlong encoding other than that of NUL, or half of a
+/// surrogate pair.
+pub fn decode_char(bytes: &[u8]) -> Option<(char, usize)> {
+    let (unit, len) = decode_unit(bytes)?;
+    match unit {
+        0xd800..=0xdbff => {
+            let (low, low_len) = decode_unit(&bytes[len..])?;
+            if !(0xdc00..=0xdfff).contains(&low) {
+                return None;
+            }
+            let c = 0x10000 + ((unit - 0xd800) << 10) + (low - 0xdc00);
+            Some((char::from_u32(c)?, len + low_len))
+        }
+        _ => Some((char::from_u32(unit)?, len)),
+    }
+}
+
+/// Decode a single UTF-16 code unit at the start of the bytes
+fn decode_unit(bytes: &[u8]) -> Option<(u32, usize)> {
+    let cont = |i: usize| match bytes.get(i) {
+        Some(x) if x & 0xc0 == 0x80 => Some(u32::from(x & 0x3f)),
+        _ => None,
+    };
+    match *bytes.first()? {
+        0x00 => None,
+        x @ 0x01..=0x7f => Some((u32::from(x), 1)),
+        /* NUL is the only character allowed an overlong encoding */
+        0xc0 if bytes.get(1) == Some(&0x80) => Some((0, 2)),
+        x @ 0xc2..=0xdf => Some(((u32::from(x & 0x1f) << 6) | cont(1)?, 2)),
+        x @ 0xe0..=0xef => {
+            let unit = (u32::from(x & 0x0f) << 12) | (cont(1)? << 6) | cont(2)?;
+            if unit < 0x800 {
+                return None;
+            }
+            Some((unit, 3))
+        }
+        _ => None,
+    }
+}
+
+/// Decode the bytes of an NBT string, failing if they aren't modified UTF-8
+pub fn decode(bytes: &[u8]) -> Result<String> {
+    let mut ret = String::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match decode_char(&bytes[i..]) {
+            Some((c, len)) => {
+                ret.push(c);
+                i += len;
+            }
+            None => bail!(
+                "Invalid modified UTF-8 at byte {} of the string, 0x{:02x}",
+                i,
+                bytes[i]
+            ),
+        }
+    }
+    Ok(ret)
+}
+
+/// Encode the character as modified UTF-8, appending it to the bytes
+pub fn encode_char(c: char, bytes: &mut Vec<u8>) {
+    let mut units = [0; 2];
+    for &unit in c.encode_utf16(&mut units).iter() {
+        let unit = u32::from(unit);
+        match unit {
+            0x01..=0x7f => bytes.push(unit as u8),
+            0x00..=0x7ff => {
+                bytes.extend_from_slice(&[0xc0 | (unit >> 6) as u8, 0x80 | (unit & 0x3f) as u8])
+            }
+            _ => bytes.extend_from_slice(&[
+                0xe0 | (unit >> 12) as u8,
+                0x80 | ((unit >> 6) & 0x3f) as u8,
+                0x80 | (unit & 0x3f) as u8,
+            ]),
+        }
+    }
+}
+
+/// Encode the string as modified UTF-8, as the bytes of an NBT string
+pub fn encode(s: &str) -> Vec<u8> {
+    let mut ret = Vec::with_capacity(s.len());
+    for c in s.chars() {
+        encode_char(c, &mut ret);
+    }
+    ret
+}
@@ -0,0 +1,65 @@
+//! The building blocks of NBT as it is sent in Minecraft network packets, as
+//! opposed to NBT files.
+//!
+//! Since 1.20.2, NBT in packets has a nameless root: the type id of the root
+//! tag is followed directly by its value, without the name files have. Packets
+//! also frame their contents with VarInts, the variable length integers of
+//! the Minecraft protocol.
+
+use crate::data::NBT;
+use crate::read;
+use crate::write;
+use crate::Result;
+
+use std::io::{Read, Write};
+
+use byteorder::{ReadBytesExt, WriteBytesExt};
+
+/// The most bytes a VarInt can take up
+const MAX_VARINT_BYTES: usize = 5;
+
+/// Read a VarInt, which stores 7 bits of the number in each byte, least
+/// significant first, with the high bit set on every byte but the last
+pub fn read_varint<R: Read>(reader: &mut R) -> Result<i32> {
+    let mut ret: u32 = 0;
+    for i in 0..MAX_VARINT_BYTES {
+        let byte = reader.read_u8()?;
+        ret |= u32::from(byte & 0x7f) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Ok(ret as i32);
+        }
+    }
+    bail!("VarInt is longer than {} bytes", MAX_VARINT_BYTES);
+}
+
+/// Write a VarInt, as read by read_varint. Negative numbers always take up
+/// all 5 bytes.
+pub fn write_varint<W: Write>(w: &mut W, val: i32) -> Result<()> {
+    let mut val = val as u32;
+    loop {
+        if val & !0x7f == 0 {
+            w.write_u8(val as u8)?;
+            return Ok(());
+        }
+        w.write_u8((val & 0x7f) as u8 | 0x80)?;
+        val >>= 7;
+    }
+}
+
+/// Read a tag with a nameless root, which may be of any type. An End tag in
+/// place of the root, which the game sends for no value, is an error.
+pub fn read_nameless<R: Read>(reader: &mut R) -> Result<NBT> {
+    match reader.read_u8()? {
+        0x00 => bail!("Expected a network NBT tag, but got an End tag"),
+        type_id => read::read_value(reader, type_id),
+    }
+}
+
+/// Write a tag with a nameless root, as read by read_nameless
+pub fn write_nameless<W: Write>(w: &mut W, tag: &NBT) -> Result<()> {
+    if let NBT::End = tag {
+        bail!("Unable to write End tag");
+    }
+    w.write_u8(tag.type_byte())?;
+    write::write_tag(w, tag)
+}
@@ -0,0 +1,409 @@
+//! A compact binary format for the changes between two NBT trees, for
+//! storing edits to large files without keeping a copy of every version.
+//!
+//! A patch file starts with the magic bytes NBTPATCH and a version byte,
+//! followed by the number of operations as a u32 and then the operations
+//! themselves. Every operation starts with a byte giving its kind (1 for
+//! set, 2 for insert and 3 for remove) and the path it applies to, which is
+//! the number of segments as a u32 followed by each segment: a 0 byte and a
+//! string for a compound key, or a 1 byte and a u32 for a list index. Set and
+//! insert then give the position (inserts only, as a u32) and the new tag as
+//! its type id and payload, in the same encoding as in NBT files. All numbers
+//! are big endian, like in NBT files.
+
+use crate::data::NBT;
+use crate::path::{NbtPath, Segment};
+use crate::read;
+use crate::write;
+use crate::Result;
+
+use std::io::{Read, Write};
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+
+const MAGIC: &[u8] = b"NBTPATCH";
+const VERSION: u8 = 1;
+
+/// A single operation of a patch
+#[derive(Clone, PartialEq, Debug)]
+pub enum PatchOp {
+    /// Replace the tag at the path, which must exist, with the value. The
+    /// empty path replaces the whole tree.
+    Set { path: NbtPath, value: NBT },
+    /// Add a tag to a compound or list. The last segment of the path gives
+    /// the key of the new tag in a compound, and the position is where in the
+    /// compound it goes. In a list the last segment is the index the new tag
+    /// goes at, which is also the position.
+    Insert {
+        path: NbtPath,
+        position: usize,
+        value: NBT,
+    },
+    /// Remove the tag at the path, shifting later elements of a list down
+    Remove { path: NbtPath },
+}
+
+/// Work out the operations that turn old into new.
+///
+/// Compounds and lists are patched piece by piece where possible, so that
+/// small edits give small patches. A compound whose remaining keys changed
+/// order is replaced as a whole, since operations can't move tags.
+pub fn make_patch(old: &NBT, new: &NBT) -> Vec<PatchOp> {
+    let mut ret = Vec::new();
+    let mut path = NbtPath::new();
+    make_tag(old, new, &mut path, &mut ret);
+    ret
+}
+
+fn make_tag(old: &NBT, new: &NBT, path: &mut NbtPath, ret: &mut Vec<PatchOp>) {
+    match (old, new) {
+        (NBT::Compound(a), NBT::Compound(b)) if same_key_order(a, b) => {
+            /* Removals first, so that the positions of the inserts are the
+             * positions in the new compound */
+            for (key, _) in a {
+                if new.get(key).is_none() {
+                    ret.push(PatchOp::Remove {
+                        path: path.join(Segment::Key(key.clone())),
+                    });
+                }
+            }
+            for (key, val) in a {
+                if let Some(x) = new.get(key) {
+                    path.push(Segment::Key(key.clone()));
+                    make_tag(val, x, path, ret);
+                    let _: Option<Segment> = path.pop();
+                }
+            }
+            for (position, (key, val)) in b.iter().enumerate() {
+                if old.get(key).is_none() {
+                    ret.push(PatchOp::Insert {
+                        path: path.join(Segment::Key(key.clone())),
+                        position,
+                        value: val.clone(),
+                    });
+                }
+            }
+        }
+        (
+            NBT::List {
+                element_type: ta,
+                items: a,
+            },
+            NBT::List {
+                element_type: tb,
+                items: b,
+            },
+        ) if same_element_type(*ta, a, *tb, b) => {
+            /* Remove from the end, so that the indices stay valid */
+            for i in (b.len()..a.len()).rev() {
+                ret.push(PatchOp::Remove {
+                    path: path.join(Segment::Index(i)),
+                });
+            }
+            for (i, (x, y)) in a.iter().zip(b).enumerate() {
+                path.push(Segment::Index(i));
+                make_tag(x, y, path, ret);
+                let _: Option<Segment> = path.pop();
+            }
+            for (i, val) in b.iter().enumerate().skip(a.len()) {
+                ret.push(PatchOp::Insert {
+                    path: path.join(Segment::Index(i)),
+                    position: i,
+                    value: val.clone(),
+                });
+            }
+        }
+        (a, b) => {
+            if !a.bitwise_eq(b) {
+                ret.push(PatchOp::Set {
+                    path: path.clone(),
+                    value: b.clone(),
+                });
+            }
+        }
+    }
+}
+
+/// Whether the keys the two compounds have in common are in the same order
+/// in both
+fn same_key_order(a: &[(Vec<u8>, NBT)], b: &[(Vec<u8>, NBT)]) -> bool {
+    let common_a = a.iter().filter(|(k, _)| b.iter().any(|(x, _)| x == k));
+    let common_b = b.iter().filter(|(k, _)| a.iter().any(|(x, _)| x == k));
+    common_a.map(|(k, _)| k).eq(common_b.map(|(k, _)| k))
+}
+
+/// Whether the elements of the lists have the same type, so that they can be
+/// patched element by element without the list ever being mixed. An empty
+/// list keeps the type it was declared with when its elements are removed,
+/// so it must already be the same.
+fn same_element_type(ta: u8, a: &[NBT], tb: u8, b: &[NBT]) -> bool {
+    match (a.first(), b.first()) {
+        (Some(x), Some(y)) => x.type_byte() == y.type_byte(),
+        (None, Some(_)) => true,
+        (_, None) => ta == tb,
+    }
+}
+
+/// Apply the operations to the tree in order
+pub fn apply_patch(root: &mut NBT, ops: &[PatchOp]) -> Result<()> {
+    for op in ops {
+        apply_op(root, op)?;
+    }
+    Ok(())
+}
+
+fn apply_op(root: &mut NBT, op: &PatchOp) -> Result<()> {
+    let path = match op {
+        PatchOp::Set { path, .. } | PatchOp::Insert { path, .. } | PatchOp::Remove { path } => path,
+    };
+    let (last, parent_path) = match path.segments().split_last() {
+        Some(x) => x,
+        None => {
+            return match op {
+                PatchOp::Set { value, .. } => {
+                    *root = value.clone();
+                    Ok(())
+                }
+                _ => bail!("Unable to insert or remove the root of the file"),
+            };
+        }
+    };
+    let parent = get_mut(root, parent_path)?;
+
+    match (op, parent, last) {
+        (PatchOp::Set { value, .. }, NBT::Compound(x), Segment::Key(key)) => {
+            match x.iter_mut().find(|(k, _)| k == key) {
+                Some((_, v)) => *v = value.clone(),
+                None => bail!("Unable to set {}, it doesn't exist", path),
+            }
+        }
+        (
+            PatchOp::Set { value, .. },
+            NBT::List {
+                element_type,
+                items: x,
+            },
+            Segment::Index(i),
+        ) => {
+            check_list_type(x, Some(*i), value, path)?;
+            match x.get_mut(*i) {
+                Some(v) => *v = value.clone(),
+                None => bail!("Unable to set {}, it doesn't exist", path),
+            }
+            *element_type = value.type_byte();
+        }
+        (
+            PatchOp::Insert {
+                position, value, ..
+            },
+            NBT::Compound(x),
+            Segment::Key(key),
+        ) => {
+            if x.iter().any(|(k, _)| k == key) {
+                bail!("Unable to insert {}, it already exists", path);
+            }
+            if *position > x.len() {
+                bail!(
+                    "Unable to insert {} at position {} in a compound with {} tags",
+                    path,
+                    position,
+                    x.len()
+                );
+            }
+            x.insert(*position, (key.clone(), value.clone()));
+        }
+        (
+            PatchOp::Insert { value, .. },
+            NBT::List {
+                element_type,
+                items: x,
+            },
+            Segment::Index(i),
+        ) => {
+            if *i > x.len() {
+                bail!("Unable to insert {} in a list of length {}", path, x.len());
+            }
+            check_list_type(x, None, value, path)?;
+            x.insert(*i, value.clone());
+            *element_type = value.type_byte();
+        }
+        (PatchOp::Remove { .. }, NBT::Compound(x), Segment::Key(key)) => {
+            match x.iter().position(|(k, _)| k == key) {
+                Some(i) => {
+                    let _: (Vec<u8>, NBT) = x.remove(i);
+                }
+                None => bail!("Unable to remove {}, it doesn't exist", path),
+            }
+        }
+        (PatchOp::Remove { .. }, NBT::List { items: x, .. }, Segment::Index(i)) => {
+            if *i >= x.len() {
+                bail!("Unable to remove {}, it doesn't exist", path);
+            }
+            let _: NBT = x.remove(*i);
+        }
+        (_, x, _) => bail!(
+            "Unable to apply the patch to {}, it's in a {}",
+            path,
+            x.type_string()
+        ),
+    }
+
+    Ok(())
+}
+
+/// Returns the tag at the path, which must consist of keys inside of
+/// compounds and indices inside of lists
+fn get_mut<'a>(root: &'a mut NBT, segments: &[Segment]) -> Result<&'a mut NBT> {
+    let mut cur = root;
+    for segment in segments {
+        cur = match (cur, segment) {
+            (NBT::Compound(x), Segment::Key(key)) => match x.iter_mut().find(|(k, _)| k == key) {
+                Some((_, v)) => v,
+                None => bail!("No value in compound {}", String::from_utf8_lossy(key)),
+            },
+            (NBT::List { items: x, .. }, Segment::Index(i)) => {
+                let len = x.len();
+                match x.get_mut(*i) {
+                    Some(v) => v,
+                    None => bail!("No element {} in list of length {}", i, len),
+                }
+            }
+            (x, _) => bail!(
+                "Unable to look inside of {} while applying patch",
+                x.type_string()
+            ),
+        };
+    }
+    Ok(cur)
+}
+
+/// Make sure the value can go in the list, where all elements must have the
+/// same type. When replacing an element, the element being replaced is left
+/// out, so the only element of a list may be replaced with any type.
+fn check_list_type(
+    list: &[NBT],
+    replacing: Option<usize>,
+    value: &NBT,
+    path: &NbtPath,
+) -> Result<()> {
+    let other = list
+        .iter()
+        .enumerate()
+        .find(|(i, x)| Some(*i) != replacing && x.type_byte() != value.type_byte());
+    match other {
+        Some((_, x)) => bail!(
+            "Unable to put {} at {} in a list of {}",
+            value.type_string(),
+            path,
+            x.type_string()
+        ),
+        None => Ok(()),
+    }
+}
+
+/// Write the operations in the binary patch format
+pub fn write_patch<W: Write>(w: &mut W, ops: &[PatchOp]) -> Result<()> {
+    w.write_all(MAGIC)?;
+    w.write_u8(VERSION)?;
+    w.write_u32::<BigEndian>(ops.len() as u32)?;
+
+    for op in ops {
+        match op {
+            PatchOp::Set { path, value } => {
+                w.write_u8(1)?;
+                write_path(w, path)?;
+                write_value(w, value)?;
+            }
+            PatchOp::Insert {
+                path,
+                position,
+                value,
+            } => {
+                w.write_u8(2)?;
+                write_path(w, path)?;
+                w.write_u32::<BigEndian>(*position as u32)?;
+                write_value(w, value)?;
+            }
+            PatchOp::Remove { path } => {
+                w.write_u8(3)?;
+                write_path(w, path)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn write_path<W: Write>(w: &mut W, path: &NbtPath) -> Result<()> {
+    w.write_u32::<BigEndian>(path.segments().len() as u32)?;
+    for segment in path.segments() {
+        match segment {
+            Segment::Key(x) => {
+                w.write_u8(0)?;
+                write::write_string(w, x)?;
+            }
+            Segment::Index(x) => {
+                w.write_u8(1)?;
+                w.write_u32::<BigEndian>(*x as u32)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn write_value<W: Write>(w: &mut W, value: &NBT) -> Result<()> {
+    w.write_u8(value.type_byte())?;
+    write::write_tag(w, value)
+}
+
+/// Read a patch written by write_patch
+pub fn read_patch<R: Read>(reader: &mut R) -> Result<Vec<PatchOp>> {
+    let mut magic = [0; 8];
+    reader.read_exact(&mut magic)?;
+    if magic != MAGIC {
+        bail!("Not an NBT patch, it doesn't start with NBTPATCH");
+    }
+    match reader.read_u8()? {
+        VERSION => (),
+        x => bail!("Unsupported NBT patch version {}", x),
+    }
+
+    let count = reader.read_u32::<BigEndian>()?;
+    let mut ret = Vec::new();
+    for i in 0..count {
+        let kind = reader.read_u8()?;
+        let path = read_path(reader)?;
+        ret.push(match kind {
+            1 => PatchOp::Set {
+                path,
+                value: read_value(reader)?,
+            },
+            2 => PatchOp::Insert {
+                path,
+                position: reader.read_u32::<BigEndian>()? as usize,
+                value: read_value(reader)?,
+            },
+            3 => PatchOp::Remove { path },
+            x => bail!("Unknown kind of operation {} in operation {}", x, i),
+        });
+    }
+
+    Ok(ret)
+}
+
+fn read_path<R: Read>(reader: &mut R) -> Result<NbtPath> {
+    let mut path = NbtPath::new();
+    for _ in 0..reader.read_u32::<BigEndian>()? {
+        path.push(match reader.read_u8()? {
+            0 => Segment::Key(read::read_key(reader)?),
+            1 => Segment::Index(reader.read_u32::<BigEndian>()? as usize),
+            x => bail!("Unknown kind of path segment {}", x),
+        });
+    }
+    Ok(path)
+}
+
+fn read_value<R: Read>(reader: &mut R) -> Result<NBT> {
+    let type_id = reader.read_u8()?;
+    read::read_value(reader, type_id)
+}
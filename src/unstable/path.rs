@@ -0,0 +1,111 @@
+use std::fmt;
+
+/// A single component of an NbtPath
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub enum Segment {
+    /// The name of a tag inside of a compound
+    Key(Vec<u8>),
+    /// The index of an element inside of a list or array
+    Index(usize),
+}
+impl Segment {
+    /// Returns whether this segment is matched by the given textual path
+    /// component. Keys match byte for byte, indices match their decimal form.
+    pub fn matches(&self, component: &[u8]) -> bool {
+        match self {
+            Segment::Key(x) => x.as_slice() == component,
+            Segment::Index(i) => i.to_string().as_bytes() == component,
+        }
+    }
+}
+
+/// The location of a tag inside of an NBT tree, given as the compound keys
+/// and list indices leading to it.
+///
+/// Paths are written with the components separated by /, so the doubleTest
+/// in bigtest is at Level/doubleTest. Note that most Minecraft files have a
+/// root compound with an empty name, so their paths begin with a /, such as
+/// /Data/Player in level.dat.
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Default)]
+pub struct NbtPath {
+    segments: Vec<Segment>,
+}
+impl NbtPath {
+    pub fn new() -> Self {
+        NbtPath {
+            segments: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, segment: Segment) {
+        self.segments.push(segment);
+    }
+
+    pub fn pop(&mut self) -> Option<Segment> {
+        self.segments.pop()
+    }
+
+    pub fn segments(&self) -> &[Segment] {
+        &self.segments
+    }
+
+    /// Returns a copy of this path with the given segment appended
+    pub fn join(&self, segment: Segment) -> Self {
+        let mut ret = self.clone();
+        ret.push(segment);
+        ret
+    }
+}
+impl fmt::Display for NbtPath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, segment) in self.segments.iter().enumerate() {
+            if i > 0 {
+                write!(f, "/")?;
+            }
+            match segment {
+                Segment::Key(x) => write!(f, "{}", String::from_utf8_lossy(x))?,
+                Segment::Index(x) => write!(f, "{}", x)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Split a textual path into its components
+pub fn split(path: &str) -> Vec<&[u8]> {
+    path.as_bytes().split(|x| *x == b'/').collect()
+}
+
+/// A pattern that selects tags by their path.
+///
+/// Written like a path, except that a component consisting of just * matches
+/// any single key or index, so Level/*/value matches both
+/// Level/ham/value and Level/egg/value.
+#[derive(Clone, PartialEq, Debug)]
+pub struct PathFilter {
+    components: Vec<Option<Vec<u8>>>,
+}
+impl PathFilter {
+    pub fn new(pattern: &str) -> Self {
+        let components = split(pattern)
+            .into_iter()
+            .map(|x| if x == b"*" { None } else { Some(x.to_vec()) })
+            .collect();
+        PathFilter { components }
+    }
+
+    /// Returns whether the given path is selected by this filter
+    pub fn matches(&self, path: &NbtPath) -> bool {
+        if self.components.len() != path.segments.len() {
+            return false;
+        }
+
+        self.components
+            .iter()
+            .zip(path.segments.iter())
+            .all(|(c, s)| match c {
+                None => true,
+                Some(c) => s.matches(c),
+            })
+    }
+}
@@ -1,121 +1,760 @@
-use crate::data::{Compression, NBTFile, NBT};
+use crate::checksum::{Checksum, ChecksumReader};
+use crate::data::{Compression, Endianness, KeySet, NBTFile, NBT};
+use crate::io::CountingReader;
+use crate::lz4;
+#[cfg(feature = "trace")]
+use crate::path::NbtPath;
+use crate::path::Segment;
+use crate::zstd;
 use crate::Result;
 
+use std::cmp;
 use std::io::{self, BufRead, Read};
 
-use byteorder::{BigEndian, ReadBytesExt};
+use byteorder::{BigEndian, LittleEndian, ReadBytesExt};
 
 use flate2::read::{GzDecoder, ZlibDecoder};
 
+/// Limits on how much reading a file may produce, so that reading an
+/// untrusted file can't exhaust memory, and how to treat damaged files. The
+/// default is to have no limits other than DEFAULT_MAX_DEPTH, and read files
+/// as they are.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ReadOptions {
+    /// The maximum number of tags in the file, counting every tag inside of a
+    /// compound or list, but not the elements of arrays
+    pub max_tags: Option<usize>,
+    /// The maximum combined length in bytes of every string in the file,
+    /// including the names of tags
+    pub max_string_bytes: Option<usize>,
+    /// The maximum number of compounds and lists a tag may be nested in.
+    /// Tags are read recursively, so without a limit a deeply nested file
+    /// overflows the stack. The text and SNBT readers have the same limit.
+    pub max_depth: Option<usize>,
+    /// Decompress files that were accidentally compressed more than once,
+    /// such as a gzipped zlib stream, up to MAX_COMPRESSION_LAYERS times. The
+    /// file is given the outermost compression, so it's written back
+    /// compressed only once.
+    pub recursive_decompress: bool,
+    /// The number of bytes of junk before the NBT data, such as a version
+    /// byte, which are skipped before parsing. Skipping the wrong number of
+    /// bytes makes the rest of the file unreadable, or worse, readable as
+    /// something else. Use read_leading to keep the skipped bytes.
+    pub skip_leading: usize,
+    /// The byte order of the numbers in the file, including the lengths of
+    /// strings, lists and arrays
+    pub endianness: Endianness,
+    /// The byte order of the elements of IntArrays and LongArrays, for the
+    /// rare formats that store them differently from the other numbers. This
+    /// is an advanced option, None means the same as endianness.
+    pub array_endianness: Option<Endianness>,
+    /// Fail on a compound that has the same key more than once. Files like
+    /// that can be read and written, but they are almost always a mistake,
+    /// and most programs only see one of the tags.
+    pub forbid_duplicate_keys: bool,
+}
+impl Default for ReadOptions {
+    fn default() -> Self {
+        ReadOptions {
+            max_tags: None,
+            max_string_bytes: None,
+            max_depth: Some(DEFAULT_MAX_DEPTH),
+            recursive_decompress: false,
+            skip_leading: 0,
+            endianness: Endianness::Big,
+            array_endianness: None,
+            forbid_duplicate_keys: false,
+        }
+    }
+}
+impl ReadOptions {
+    /// The byte order of the elements of arrays
+    fn array_endianness(&self) -> Endianness {
+        self.array_endianness.unwrap_or(self.endianness)
+    }
+}
+
+/// Read a number of the given byte order with the byteorder method of the
+/// given name, such as read_i32
+macro_rules! read_number {
+    ($reader:expr, $method:ident, $endianness:expr) => {
+        match $endianness {
+            Endianness::Big => $reader.$method::<BigEndian>(),
+            Endianness::Little => $reader.$method::<LittleEndian>(),
+        }
+    };
+}
+
+/// The most layers of compression read_file_recursive_decompress removes
+/// before giving up
+pub const MAX_COMPRESSION_LAYERS: usize = 4;
+
+/// The default ReadOptions::max_depth, the same as Minecraft's
+pub const DEFAULT_MAX_DEPTH: usize = 512;
+
+/// The most elements of an array or list allocated for before reading them
+const MAX_PREALLOCATE: usize = 4096;
+
+/// Keeps count of what has been read so far, to check it against the
+/// ReadOptions
+struct Budget {
+    options: ReadOptions,
+    tags: usize,
+    string_bytes: usize,
+    /// The number of compounds and lists the tag being read is in
+    depth: usize,
+    /// The path of the tag being read, for the traces
+    #[cfg(feature = "trace")]
+    path: NbtPath,
+}
+impl Budget {
+    fn new(options: &ReadOptions) -> Self {
+        Budget {
+            options: *options,
+            tags: 0,
+            string_bytes: 0,
+            depth: 0,
+            #[cfg(feature = "trace")]
+            path: NbtPath::new(),
+        }
+    }
+
+    /// Note that the tag with the given key or index is about to be read,
+    /// failing if it's nested too deeply, and so that traces can say where in
+    /// the file they are. Without the trace feature the segment isn't even
+    /// made.
+    #[cfg_attr(not(feature = "trace"), allow(unused_variables))]
+    fn enter<F: FnOnce() -> Segment>(&mut self, segment: F) -> Result<()> {
+        self.depth += 1;
+        check_depth(self.depth, self.options.max_depth)?;
+        #[cfg(feature = "trace")]
+        self.path.push(segment());
+        Ok(())
+    }
+
+    /// Note that the tag given to enter has been read
+    fn leave(&mut self) {
+        self.depth -= 1;
+        #[cfg(feature = "trace")]
+        let _: Option<Segment> = self.path.pop();
+    }
+
+    /// Count a tag about to be read
+    fn add_tag(&mut self) -> Result<()> {
+        self.tags += 1;
+        match self.options.max_tags {
+            Some(max) if self.tags > max => bail!("exceeded maximum tag count {}", max),
+            _ => Ok(()),
+        }
+    }
+
+    /// Count a string of the given length about to be read
+    fn add_string(&mut self, length: usize) -> Result<()> {
+        self.string_bytes += length;
+        match self.options.max_string_bytes {
+            Some(max) if self.string_bytes > max => {
+                bail!("exceeded maximum total string length {}", max)
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
 /// Read an NBT file from the given reader
-pub fn read_file<R: BufRead>(mut reader: &mut R) -> Result<NBTFile> {
-    /* Peek into the first byte of the reader, which is used to determine the
-     * compression */
-    let peek = match reader.fill_buf()? {
-        x if !x.is_empty() => x[0],
-        _ => bail!("Error peaking first byte in read::read_file, file was EOF"),
+pub fn read_file<R: BufRead>(reader: &mut R) -> Result<NBTFile> {
+    read_file_with_options(reader, &ReadOptions::default())
+}
+
+/// Read an NBT file with numbers of the given byte order from the given
+/// reader, such as a little endian Bedrock Edition file. The 8 byte header of
+/// Bedrock's level.dat isn't part of the NBT data, so it has to be skipped
+/// first, for example with ReadOptions::skip_leading.
+pub fn read_file_with<R: BufRead>(reader: &mut R, endianness: Endianness) -> Result<NBTFile> {
+    let options = ReadOptions {
+        endianness,
+        ..Default::default()
+    };
+    read_file_with_options(reader, &options)
+}
+
+/// Read an NBT file from the given reader, failing if the file goes beyond
+/// the limits in the options
+pub fn read_file_with_options<R: BufRead>(
+    reader: &mut R,
+    options: &ReadOptions,
+) -> Result<NBTFile> {
+    let _: Vec<u8> = read_leading(reader, options.skip_leading)?;
+    let options = &ReadOptions {
+        skip_leading: 0,
+        ..*options
     };
 
-    let compression = match Compression::from_first_byte(peek) {
+    if options.recursive_decompress {
+        return Ok(read_file_recursive_decompress(reader, options)?.0);
+    }
+
+    let compression = peek_compression(reader)?;
+    let compression_level = peek_compression_level(reader, &compression)?;
+    read_compressed(reader, compression, compression_level, options)
+}
+
+/// Read an NBT file from a reader that isn't buffered, such as a decoder for
+/// part of a stream, without having to wrap it in a BufReader. The first
+/// byte, which gives the compression, is read rather than peeked at, and put
+/// back in front of the rest.
+pub fn read_file_from<R: Read>(reader: &mut R) -> Result<NBTFile> {
+    let mut header = [0; 2];
+    if reader.read_exact(&mut header[..1]).is_err() {
+        bail!("Error reading first byte in read::read_file_from, file was EOF");
+    }
+    let compression = match Compression::from_first_byte(header[0]) {
         Some(x) => x,
-        None => bail!("Unknown compression format where first byte is {}", peek),
+        None => bail!(
+            "Unknown compression format where first byte is {}",
+            header[0]
+        ),
+    };
+
+    /* The level of a zlib file is in its second byte */
+    let (header, compression_level) = match compression {
+        Compression::Zlib => {
+            reader.read_exact(&mut header[1..])?;
+            let level = peek_compression_level(&mut &header[..], &compression)?;
+            (&header[..], level)
+        }
+        _ => (&header[..1], None),
     };
 
+    read_compressed(
+        &mut header.chain(reader),
+        compression,
+        compression_level,
+        &ReadOptions::default(),
+    )
+}
+
+/// Read an NBT file with the given compression, which has already been
+/// found from its first bytes
+pub(crate) fn read_compressed<R: Read>(
+    reader: &mut R,
+    compression: Compression,
+    compression_level: Option<u32>,
+    options: &ReadOptions,
+) -> Result<NBTFile> {
+    debug!("reading {} compressed file", compression.to_str());
+
+    let mut reader = CountingReader::new(reader);
+    let budget = &mut Budget::new(options);
     let root = match compression {
-        Compression::None => read_compound(&mut reader)?,
-        Compression::Gzip => read_compound(&mut GzDecoder::new(reader))?,
-        Compression::Zlib => read_compound(&mut ZlibDecoder::new(reader))?,
+        Compression::None => read_compound(&mut reader, budget)?,
+        Compression::Gzip => read_compound(&mut GzDecoder::new(&mut reader), budget)?,
+        Compression::Zlib => read_compound(&mut ZlibDecoder::new(&mut reader), budget)?,
+        Compression::Zstd => read_compound(&mut zstd::decoder(&mut reader)?, budget)?,
+        Compression::Lz4 => read_compound(&mut lz4::decoder(&mut reader)?, budget)?,
     };
+    debug!(
+        "read {} bytes of {} compressed data, with {} tags",
+        reader.count(),
+        compression.to_str(),
+        budget.tags
+    );
+
+    Ok(NBTFile {
+        root,
+        compression,
+        compression_level,
+    })
+}
+
+/// Read an NBT file that may have been compressed several times over,
+/// decompressing it until the uncompressed NBT data is reached. The file is
+/// given the outermost compression.
+///
+/// Returns the file along with the number of layers of compression that were
+/// removed. It is an error for there to be more than MAX_COMPRESSION_LAYERS.
+pub fn read_file_recursive_decompress<R: Read>(
+    reader: &mut R,
+    options: &ReadOptions,
+) -> Result<(NBTFile, usize)> {
+    let _: Vec<u8> = read_leading(reader, options.skip_leading)?;
+
+    let mut decompressed = decompress(reader, MAX_COMPRESSION_LAYERS)?;
+    if decompressed.layers == MAX_COMPRESSION_LAYERS
+        && peek_compression(&mut decompressed.reader)? != Compression::None
+    {
+        bail!(
+            "File is still compressed after removing {} layers of compression",
+            decompressed.layers
+        );
+    }
 
-    Ok(NBTFile { root, compression })
+    let nbt = read_compressed(&mut decompressed.reader, Compression::None, None, options)?;
+
+    Ok((
+        NBTFile {
+            root: nbt.root,
+            compression: decompressed.compression,
+            compression_level: decompressed.compression_level,
+        },
+        decompressed.layers,
+    ))
 }
 
-/// Reads an NBT compound. I.e. assumes that the first byte from the Reader is
-/// the byte that determines the NBT type of the first value INSIDE whatever
-/// compound we're in.
+/// The data inside of the compression of a file, decompressed as it's read
+pub(crate) struct Decompressed<'a> {
+    pub reader: Box<dyn BufRead + 'a>,
+    /// The outermost compression, and its level if it's known
+    pub compression: Compression,
+    pub compression_level: Option<u32>,
+    /// The number of layers of compression removed
+    pub layers: usize,
+}
+
+/// Remove up to max_layers layers of compression from the file. Each layer is
+/// decompressed as the layer inside of it is read, so nothing is kept other
+/// than what the caller reads, and the caller's limits apply.
+pub(crate) fn decompress<'a, R: Read + 'a>(
+    reader: R,
+    max_layers: usize,
+) -> Result<Decompressed<'a>> {
+    let mut reader: Box<dyn BufRead + 'a> = Box::new(io::BufReader::new(reader));
+    let mut outermost = None;
+    let mut layers = 0;
+    while layers < max_layers {
+        if layers > 0 {
+            let _: &[u8] = reader
+                .fill_buf()
+                .map_err(|e| format_err!("Unable to decompress layer {}: {}", layers, e))?;
+        }
+        let compression = peek_compression(&mut reader)?;
+        if compression == Compression::None {
+            break;
+        }
+        if outermost.is_none() {
+            outermost = Some((
+                compression.clone(),
+                peek_compression_level(&mut reader, &compression)?,
+            ));
+        }
+
+        debug!(
+            "decompressing {} layer {}",
+            compression.to_str(),
+            layers + 1
+        );
+        let decoder: Box<dyn Read + 'a> = match compression {
+            Compression::Gzip => Box::new(GzDecoder::new(reader)),
+            Compression::Zlib => Box::new(ZlibDecoder::new(reader)),
+            Compression::Lz4 => lz4::decoder(reader)?,
+            Compression::Zstd => zstd::decoder(reader)?,
+            Compression::None => unreachable!(),
+        };
+        reader = Box::new(io::BufReader::new(decoder));
+
+        layers += 1;
+    }
+
+    let (compression, compression_level) = outermost.unwrap_or((Compression::None, None));
+    Ok(Decompressed {
+        reader,
+        compression,
+        compression_level,
+        layers,
+    })
+}
+
+/// Read an NBT file that is followed by a 4 byte big endian checksum of the
+/// file as stored, that is of the compressed data if it is compressed.
 ///
-/// This will always return an NBT::Compound, never any other type of NBT.
-fn read_compound<R: Read>(reader: &mut R) -> Result<NBT> {
+/// Returns an error if the checksum doesn't match the file.
+pub fn read_file_with_checksum<R: Read>(reader: &mut R, checksum: Checksum) -> Result<NBTFile> {
+    /* An uncompressed file is only ended by the end of the data, so the
+     * checksum is only known to be the last 4 bytes once everything has been
+     * read. The file is checked before anything read from it is returned. */
+    let mut reader = ChecksumReader::new(reader, checksum);
+    let nbt = {
+        let mut buffered = io::BufReader::new(&mut reader);
+        let nbt = read_file(&mut buffered);
+        let _: u64 = io::copy(&mut buffered, &mut io::sink())?;
+        nbt
+    };
+
+    let (got, trailer) = reader.finish();
+    let expected = match trailer {
+        Some(x) => u32::from_be_bytes(x),
+        None => bail!("File is too short to contain a checksum"),
+    };
+    if expected != got {
+        bail!(
+            "checksum mismatch: expected {:08x} got {:08x}",
+            expected,
+            got
+        );
+    }
+
+    nbt
+}
+
+/// Read only the beginning of an NBT file from the given reader, for quickly
+/// seeing what kind of file it is.
+///
+/// Only the first limit tags inside of each root compound are read, the rest
+/// are skipped over without being kept in memory. Returns the file along with
+/// the number of tags that were skipped. Skipped tags don't count towards the
+/// limits in the options.
+pub fn read_file_preview<R: BufRead>(
+    mut reader: &mut R,
+    limit: usize,
+    options: &ReadOptions,
+) -> Result<(NBTFile, usize)> {
+    let _: Vec<u8> = read_leading(reader, options.skip_leading)?;
+    let compression = peek_compression(reader)?;
+    let compression_level = peek_compression_level(reader, &compression)?;
+
+    let budget = &mut Budget::new(options);
+    let mut skipped = 0;
+    let root = match compression {
+        Compression::None => read_root_preview(&mut reader, limit, &mut skipped, budget)?,
+        Compression::Gzip => {
+            read_root_preview(&mut GzDecoder::new(reader), limit, &mut skipped, budget)?
+        }
+        Compression::Zlib => {
+            read_root_preview(&mut ZlibDecoder::new(reader), limit, &mut skipped, budget)?
+        }
+        Compression::Zstd => {
+            read_root_preview(&mut zstd::decoder(reader)?, limit, &mut skipped, budget)?
+        }
+        Compression::Lz4 => {
+            read_root_preview(&mut lz4::decoder(reader)?, limit, &mut skipped, budget)?
+        }
+    };
+
+    Ok((
+        NBTFile {
+            root,
+            compression,
+            compression_level,
+        },
+        skipped,
+    ))
+}
+
+/// Read the given number of bytes of junk from before the NBT data, as given
+/// by ReadOptions::skip_leading, so that they can be written back in front of
+/// the file. The read_file functions skip them on their own, so the options
+/// given to them afterwards should have skip_leading set to 0.
+pub fn read_leading<R: Read>(reader: &mut R, len: usize) -> Result<Vec<u8>> {
+    /* The length is given by the user, so only what is actually there is
+     * allocated */
+    let mut buf = Vec::new();
+    let _: usize = reader.take(len as u64).read_to_end(&mut buf)?;
+    if buf.len() < len {
+        bail!("File is shorter than the {} leading bytes to skip", len);
+    }
+    Ok(buf)
+}
+
+/// Peek into the first byte of the reader, which is used to determine the
+/// compression
+pub(crate) fn peek_compression<R: BufRead>(reader: &mut R) -> Result<Compression> {
+    let peek = match reader.fill_buf()? {
+        x if !x.is_empty() => x[0],
+        _ => bail!("Error peaking first byte in read::read_file, file was EOF"),
+    };
+
+    match Compression::from_first_byte(peek) {
+        Some(x) => Ok(x),
+        None => bail!("Unknown compression format where first byte is {}", peek),
+    }
+}
+
+/// Find the level a zlib compressed file was compressed with from the FLEVEL
+/// bits of its header, without consuming any of it.
+///
+/// The header only says roughly how hard the compressor tried, so this is the
+/// level in that range that gives the same header when written back. Files
+/// compressed with the default level, and other kinds of compression which
+/// don't record a level, give None.
+fn peek_compression_level<R: BufRead>(
+    reader: &mut R,
+    compression: &Compression,
+) -> Result<Option<u32>> {
+    if *compression != Compression::Zlib {
+        return Ok(None);
+    }
+
+    let header = reader.fill_buf()?;
+    if header.len() < 2 {
+        return Ok(None);
+    }
+    Ok(match header[1] >> 6 {
+        0 => Some(1),
+        1 => Some(2),
+        2 => None,
+        _ => Some(9),
+    })
+}
+
+/// Like read_compound for the implicit root compound, except that the
+/// compounds inside of it are read with read_compound_preview
+fn read_root_preview<R: Read>(
+    reader: &mut R,
+    limit: usize,
+    skipped: &mut usize,
+    budget: &mut Budget,
+) -> Result<NBT> {
     let mut map = Vec::new();
 
-    loop {
-        let mut buf: [u8; 1] = [0];
+    while let Some(type_id) = read_type_id(reader)? {
+        budget.add_tag()?;
+        let name = read_name(reader, budget)?;
+        budget.enter(|| Segment::Key(name.clone()))?;
+        let val = if type_id == 0x0a {
+            read_compound_preview(reader, limit, skipped, budget)?
+        } else {
+            read_tag(reader, type_id, budget)?
+        };
+        budget.leave();
+        map.push((name, val));
+    }
 
-        /* If unable to read anything, then we're done */
-        match reader.read_exact(&mut buf) {
-            Ok(()) => (),
-            Err(ref e) if e.kind() == io::ErrorKind::UnexpectedEof => {
-                break;
-            }
-            Err(e) => {
-                return Err(e.into());
+    Ok(NBT::Compound(map))
+}
+
+/// Like read_compound, except that only the first limit tags are read, and the
+/// rest are skipped and counted.
+fn read_compound_preview<R: Read>(
+    reader: &mut R,
+    limit: usize,
+    skipped: &mut usize,
+    budget: &mut Budget,
+) -> Result<NBT> {
+    let mut map = Vec::new();
+    let mut keys = duplicate_key_check(budget);
+
+    while let Some(type_id) = read_type_id(reader)? {
+        if map.len() < limit {
+            budget.add_tag()?;
+            let name = read_name(reader, budget)?;
+            if let Some(ref mut keys) = keys {
+                keys.check(&name, type_id)?;
             }
+            budget.enter(|| Segment::Key(name.clone()))?;
+            let val = read_tag(reader, type_id, budget)?;
+            budget.leave();
+            map.push((name, val));
+        } else {
+            let endianness = budget.options.endianness;
+            skip_string(reader, endianness)?;
+            let max_depth = budget.options.max_depth;
+            skip_tag_with(reader, type_id, endianness, budget.depth + 1, max_depth)?;
+            *skipped += 1;
         }
+    }
 
-        /* If we've got a TAG_end now, then the compound list is done */
-        if buf[0] == 0x0 {
-            break;
-        }
+    Ok(NBT::Compound(map))
+}
+
+/// Read the type id of the next tag in a compound, returning None if the
+/// compound is done, either because of an End tag or because the reader is
+/// EOF.
+pub(crate) fn read_type_id<R: Read>(reader: &mut R) -> Result<Option<u8>> {
+    let mut buf: [u8; 1] = [0];
+
+    match reader.read_exact(&mut buf) {
+        Ok(()) => (),
+        Err(ref e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    }
+
+    if buf[0] == 0x0 {
+        Ok(None)
+    } else {
+        Ok(Some(buf[0]))
+    }
+}
+
+/// Read the value of a tag with the given type id
+fn read_tag<R: Read>(reader: &mut R, type_id: u8, budget: &mut Budget) -> Result<NBT> {
+    let endianness = budget.options.endianness;
+    let array_endianness = budget.options.array_endianness();
+    match type_id {
+        0x01 => read_byte(reader),
+        0x02 => read_short(reader, endianness),
+        0x03 => read_int(reader, endianness),
+        0x04 => read_long(reader, endianness),
+        0x05 => read_float(reader, endianness),
+        0x06 => read_double(reader, endianness),
+        0x07 => read_byte_array(reader, endianness),
+        0x08 => read_string(reader, budget),
+        0x09 => read_list(reader, budget),
+        0x0a => read_compound(reader, budget),
+        0x0b => read_int_array(reader, endianness, array_endianness),
+        0x0c => read_long_array(reader, endianness, array_endianness),
+        x => bail!("Got unknown type id {:x} trying to read NBT tag", x),
+    }
+}
 
-        map.push((
-            match read_string(reader)? {
-                NBT::String(val) => val,
-                _ => unreachable!(),
-            },
-            match buf[0] {
-                0x01 => read_byte(reader)?,
-                0x02 => read_short(reader)?,
-                0x03 => read_int(reader)?,
-                0x04 => read_long(reader)?,
-                0x05 => read_float(reader)?,
-                0x06 => read_double(reader)?,
-                0x07 => read_byte_array(reader)?,
-                0x08 => read_string(reader)?,
-                0x09 => read_list(reader)?,
-                0x0a => read_compound(reader)?,
-                0x0b => read_int_array(reader)?,
-                0x0c => read_long_array(reader)?,
-                x => {
-                    bail!("Got unknown type id {:x} trying to read NBT compound", x);
+/// Advance the reader past the value of a tag with the given type id, without
+/// keeping it in memory.
+pub fn skip_tag<R: Read>(reader: &mut R, type_id: u8) -> Result<()> {
+    skip_tag_with(reader, type_id, Endianness::Big, 0, Some(DEFAULT_MAX_DEPTH))
+}
+
+/// Like skip_tag, for a file with numbers of the given byte order, and a tag
+/// nested in depth compounds and lists
+fn skip_tag_with<R: Read>(
+    reader: &mut R,
+    type_id: u8,
+    endianness: Endianness,
+    depth: usize,
+    max_depth: Option<usize>,
+) -> Result<()> {
+    check_depth(depth, max_depth)?;
+    match type_id {
+        0x01 => skip_bytes(reader, 1),
+        0x02 => skip_bytes(reader, 2),
+        0x03 => skip_bytes(reader, 4),
+        0x04 => skip_bytes(reader, 8),
+        0x05 => skip_bytes(reader, 4),
+        0x06 => skip_bytes(reader, 8),
+        0x07 => {
+            let length = read_length(reader, endianness)?;
+            skip_bytes(reader, length)
+        }
+        0x08 => skip_string(reader, endianness),
+        0x09 => {
+            let mut type_id: [u8; 1] = [0];
+            reader.read_exact(&mut type_id)?;
+            let length = read_length(reader, endianness)?;
+            /* Lists of End have no contents at all */
+            if type_id[0] != 0x0 {
+                for _ in 0..length {
+                    skip_tag_with(reader, type_id[0], endianness, depth + 1, max_depth)?;
                 }
-            },
-        ));
+            }
+            Ok(())
+        }
+        0x0a => {
+            while let Some(type_id) = read_type_id(reader)? {
+                skip_string(reader, endianness)?;
+                skip_tag_with(reader, type_id, endianness, depth + 1, max_depth)?;
+            }
+            Ok(())
+        }
+        0x0b => {
+            let length = read_length(reader, endianness)?;
+            skip_bytes(reader, length * 4)
+        }
+        0x0c => {
+            let length = read_length(reader, endianness)?;
+            skip_bytes(reader, length * 8)
+        }
+        x => bail!("Got unknown type id {:x} trying to skip NBT tag", x),
+    }
+}
+
+fn skip_string<R: Read>(reader: &mut R, endianness: Endianness) -> Result<()> {
+    let length = read_number!(reader, read_u16, endianness)?;
+    skip_bytes(reader, u64::from(length))
+}
+
+/// Fail if a tag nested in depth compounds and lists is too deep
+pub(crate) fn check_depth(depth: usize, max_depth: Option<usize>) -> Result<()> {
+    match max_depth {
+        Some(max) if depth > max => bail!("NBT nesting exceeds max depth {}", max),
+        _ => Ok(()),
+    }
+}
+
+/// Read the length prefix of an array or list
+pub(crate) fn read_length<R: Read>(reader: &mut R, endianness: Endianness) -> Result<u64> {
+    let length = read_number!(reader, read_i32, endianness)?;
+    if length < 0 {
+        bail!("Got negative length {}", length);
+    }
+    Ok(length as u64)
+}
+
+/// Allocate room for an array or list of the given length, up to
+/// MAX_PREALLOCATE elements. The length comes from the file, so a corrupt one
+/// mustn't be able to allocate gigabytes before we find out there's no data.
+fn preallocate<T>(length: usize) -> Vec<T> {
+    Vec::with_capacity(cmp::min(length, MAX_PREALLOCATE))
+}
+
+fn skip_bytes<R: Read>(reader: &mut R, count: u64) -> Result<()> {
+    let skipped = io::copy(&mut reader.take(count), &mut io::sink())?;
+    if skipped != count {
+        return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+    }
+    Ok(())
+}
+
+/// Reads an NBT compound. I.e. assumes that the first byte from the Reader is
+/// the byte that determines the NBT type of the first value INSIDE whatever
+/// compound we're in.
+///
+/// This will always return an NBT::Compound, never any other type of NBT.
+fn read_compound<R: Read>(reader: &mut R, budget: &mut Budget) -> Result<NBT> {
+    trace!("reading compound at '{}'", budget.path);
+    let mut map = Vec::new();
+    let mut keys = duplicate_key_check(budget);
+
+    /* If unable to read anything, or if we get a TAG_end, then the compound
+     * is done */
+    while let Some(type_id) = read_type_id(reader)? {
+        budget.add_tag()?;
+        let name = read_name(reader, budget)?;
+        if let Some(ref mut keys) = keys {
+            keys.check(&name, type_id)?;
+        }
+        budget.enter(|| Segment::Key(name.clone()))?;
+        let val = read_tag(reader, type_id, budget)?;
+        budget.leave();
+        map.push((name, val));
     }
 
     Ok(NBT::Compound(map))
 }
 
+/// The keys of a compound being read, if the options forbid duplicate keys
+fn duplicate_key_check(budget: &Budget) -> Option<KeySet<'static>> {
+    if budget.options.forbid_duplicate_keys {
+        Some(KeySet::default())
+    } else {
+        None
+    }
+}
+
 fn read_byte<R: Read>(reader: &mut R) -> Result<NBT> {
     Ok(NBT::Byte(reader.read_i8()?))
 }
 
-fn read_short<R: Read>(reader: &mut R) -> Result<NBT> {
-    Ok(NBT::Short(reader.read_i16::<BigEndian>()?))
+fn read_short<R: Read>(reader: &mut R, endianness: Endianness) -> Result<NBT> {
+    Ok(NBT::Short(read_number!(reader, read_i16, endianness)?))
 }
 
-fn read_int<R: Read>(reader: &mut R) -> Result<NBT> {
-    Ok(NBT::Int(reader.read_i32::<BigEndian>()?))
+fn read_int<R: Read>(reader: &mut R, endianness: Endianness) -> Result<NBT> {
+    Ok(NBT::Int(read_number!(reader, read_i32, endianness)?))
 }
 
-fn read_long<R: Read>(reader: &mut R) -> Result<NBT> {
-    Ok(NBT::Long(reader.read_i64::<BigEndian>()?))
+fn read_long<R: Read>(reader: &mut R, endianness: Endianness) -> Result<NBT> {
+    Ok(NBT::Long(read_number!(reader, read_i64, endianness)?))
 }
 
-fn read_float<R: Read>(reader: &mut R) -> Result<NBT> {
-    Ok(NBT::Float(reader.read_f32::<BigEndian>()?))
+fn read_float<R: Read>(reader: &mut R, endianness: Endianness) -> Result<NBT> {
+    Ok(NBT::Float(read_number!(reader, read_f32, endianness)?))
 }
 
-fn read_double<R: Read>(reader: &mut R) -> Result<NBT> {
-    Ok(NBT::Double(reader.read_f64::<BigEndian>()?))
+fn read_double<R: Read>(reader: &mut R, endianness: Endianness) -> Result<NBT> {
+    Ok(NBT::Double(read_number!(reader, read_f64, endianness)?))
 }
 
-fn read_byte_array<R: Read>(reader: &mut R) -> Result<NBT> {
-    let length = match read_int(reader)? {
-        NBT::Int(val) => val as usize,
-        _ => unreachable!(),
-    };
+fn read_byte_array<R: Read>(reader: &mut R, endianness: Endianness) -> Result<NBT> {
+    let length = read_length(reader, endianness)? as usize;
 
-    let mut ret: Vec<i8> = Vec::with_capacity(length);
+    let mut ret: Vec<i8> = preallocate(length);
 
     for _ in 0..length {
         ret.push(match read_byte(reader)? {
@@ -127,10 +766,11 @@ fn read_byte_array<R: Read>(reader: &mut R) -> Result<NBT> {
     Ok(NBT::ByteArray(ret))
 }
 
-fn read_string<R: Read>(reader: &mut R) -> Result<NBT> {
+fn read_string<R: Read>(reader: &mut R, budget: &mut Budget) -> Result<NBT> {
     /* Apparently the length of a string is given unsigned unlike everything
      * else in NBT */
-    let length = reader.read_u16::<BigEndian>()?;
+    let length = read_number!(reader, read_u16, budget.options.endianness)?;
+    budget.add_string(length as usize)?;
 
     let mut buf = Vec::with_capacity(length as usize);
     let tmp = reader.take(length as u64).read_to_end(&mut buf)?;
@@ -141,48 +781,68 @@ fn read_string<R: Read>(reader: &mut R) -> Result<NBT> {
     Ok(NBT::String(buf))
 }
 
-fn read_list<R: Read>(reader: &mut R) -> Result<NBT> {
+/// Read the value of a tag with the given type id, without any limits
+pub(crate) fn read_value<R: Read>(reader: &mut R, type_id: u8) -> Result<NBT> {
+    read_tag(reader, type_id, &mut Budget::new(&ReadOptions::default()))
+}
+
+/// Read the name of a tag inside of a compound, without any limits
+pub(crate) fn read_key<R: Read>(reader: &mut R) -> Result<Vec<u8>> {
+    read_name(reader, &mut Budget::new(&ReadOptions::default()))
+}
+
+/// Read the name of a tag inside of a compound
+fn read_name<R: Read>(reader: &mut R, budget: &mut Budget) -> Result<Vec<u8>> {
+    match read_string(reader, budget)? {
+        NBT::String(val) => Ok(val),
+        _ => unreachable!(),
+    }
+}
+
+fn read_list<R: Read>(reader: &mut R, budget: &mut Budget) -> Result<NBT> {
     let mut type_id: [u8; 1] = [0];
     reader.read_exact(&mut type_id)?;
 
-    let length = match read_int(reader)? {
-        NBT::Int(val) => val as usize,
-        _ => unreachable!(),
-    };
+    let length = read_length(reader, budget.options.endianness)? as usize;
+    /* End tags take up no bytes, so nothing else stops a bogus length here */
+    if type_id[0] == 0x0 && length != 0 {
+        bail!("Got a list of {} End tags", length);
+    }
 
-    let mut ret: Vec<NBT> = Vec::new();
-    for _ in 0..length {
-        ret.push(match type_id[0] {
-            0x0 => NBT::End,
-            0x1 => read_byte(reader)?,
-            0x2 => read_short(reader)?,
-            0x3 => read_int(reader)?,
-            0x4 => read_long(reader)?,
-            0x5 => read_float(reader)?,
-            0x6 => read_double(reader)?,
-            0x7 => read_byte_array(reader)?,
-            0x8 => read_string(reader)?,
-            0x9 => read_list(reader)?,
-            0xa => read_compound(reader)?,
-            0xb => read_int_array(reader)?,
-            0xc => read_long_array(reader)?,
-            x => bail!("Got unknown type id {:x} trying to read NBT list", x),
-        });
+    trace!(
+        "reading list of {} tags of type {} at '{}'",
+        length,
+        type_id[0],
+        budget.path
+    );
+
+    let mut ret: Vec<NBT> = preallocate(length);
+    for i in 0..length {
+        budget.add_tag()?;
+        budget.enter(|| Segment::Index(i))?;
+        ret.push(read_tag(reader, type_id[0], budget)?);
+        budget.leave();
     }
 
-    Ok(NBT::List(ret))
+    Ok(NBT::List {
+        element_type: type_id[0],
+        items: ret,
+    })
 }
 
-fn read_int_array<R: Read>(reader: &mut R) -> Result<NBT> {
-    let length = match read_int(reader)? {
-        NBT::Int(val) => val as usize,
-        _ => unreachable!(),
-    };
+/// Read an IntArray, whose length is given in the byte order of the file and
+/// whose elements are given in array_endianness
+fn read_int_array<R: Read>(
+    reader: &mut R,
+    endianness: Endianness,
+    array_endianness: Endianness,
+) -> Result<NBT> {
+    let length = read_length(reader, endianness)? as usize;
 
-    let mut ret: Vec<i32> = Vec::new();
+    let mut ret: Vec<i32> = preallocate(length);
 
     for _ in 0..length {
-        ret.push(match read_int(reader)? {
+        ret.push(match read_int(reader, array_endianness)? {
             NBT::Int(val) => val,
             _ => unreachable!(),
         });
@@ -191,16 +851,18 @@ fn read_int_array<R: Read>(reader: &mut R) -> Result<NBT> {
     Ok(NBT::IntArray(ret))
 }
 
-fn read_long_array<R: Read>(reader: &mut R) -> Result<NBT> {
-    let length = match read_int(reader)? {
-        NBT::Int(val) => val as usize,
-        _ => unreachable!(),
-    };
+/// Like read_int_array, for a LongArray
+fn read_long_array<R: Read>(
+    reader: &mut R,
+    endianness: Endianness,
+    array_endianness: Endianness,
+) -> Result<NBT> {
+    let length = read_length(reader, endianness)? as usize;
 
-    let mut ret: Vec<i64> = Vec::new();
+    let mut ret: Vec<i64> = preallocate(length);
 
     for _ in 0..length {
-        ret.push(match read_long(reader)? {
+        ret.push(match read_long(reader, array_endianness)? {
             NBT::Long(val) => val,
             _ => unreachable!(),
         });
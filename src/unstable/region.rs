@@ -0,0 +1,388 @@
+//! Reading and writing Minecraft region files (.mca), which contain up to
+//! 32x32 chunks, each of which is an NBT file of its own.
+//!
+//! A region file starts with a table of where each chunk is stored, given as
+//! the offset and length in 4096 byte sectors, followed by a table of when
+//! each chunk was last saved. The chunks follow, each stored as its length,
+//! a byte giving its compression and then the NBT file itself.
+
+use crate::data::{Compression, NBTFile};
+use crate::read::{self, ReadOptions};
+use crate::write;
+use crate::Result;
+
+use std::io::{Read, Seek, SeekFrom, Write};
+
+use byteorder::{BigEndian, ByteOrder, ReadBytesExt, WriteBytesExt};
+
+const SECTOR: usize = 4096;
+/// The number of chunks along each side of a region
+pub const REGION_SIZE: u8 = 32;
+
+/// The position of a chunk within its region
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct ChunkPos {
+    /// The x coordinate of the chunk within the region, from 0 to 31
+    pub x: u8,
+    /// The z coordinate of the chunk within the region, from 0 to 31
+    pub z: u8,
+}
+impl ChunkPos {
+    /// The position of the chunk at the given index in the tables at the
+    /// start of the region file
+    fn from_index(index: usize) -> Self {
+        ChunkPos {
+            x: (index % usize::from(REGION_SIZE)) as u8,
+            z: (index / usize::from(REGION_SIZE)) as u8,
+        }
+    }
+
+    /// The index of the chunk in the tables at the start of the region file
+    fn index(self) -> usize {
+        usize::from(self.x) + usize::from(self.z) * usize::from(REGION_SIZE)
+    }
+}
+
+/// A single chunk in a region file
+#[derive(Clone, PartialEq, Debug)]
+pub struct Chunk {
+    /// The x coordinate of the chunk within the region, from 0 to 31
+    pub x: u8,
+    /// The z coordinate of the chunk within the region, from 0 to 31
+    pub z: u8,
+    /// When the chunk was last saved, in seconds since the Unix epoch
+    pub timestamp: u32,
+    /// The NBT file of the chunk exactly as stored in the region, in its
+    /// original compression
+    pub data: Vec<u8>,
+}
+impl Chunk {
+    /// Make a chunk of the NBT file, such as one that was read from a region
+    /// and edited, to be written with write_region. The chunk is stored
+    /// with the compression of the file.
+    pub fn from_nbt(pos: ChunkPos, timestamp: u32, file: &NBTFile) -> Result<Self> {
+        let mut data = Vec::new();
+        write::write_file(&mut data, file)?;
+
+        Ok(Chunk {
+            x: pos.x,
+            z: pos.z,
+            timestamp,
+            data,
+        })
+    }
+
+    /// Parse the NBT file of the chunk
+    pub fn read_nbt(&self) -> Result<NBTFile> {
+        read::read_file(&mut &self.data[..])
+    }
+
+    /// The position of the chunk within the region
+    pub fn pos(&self) -> ChunkPos {
+        ChunkPos {
+            x: self.x,
+            z: self.z,
+        }
+    }
+
+    /// The index of the chunk in the tables at the start of the region file
+    fn index(&self) -> usize {
+        self.pos().index()
+    }
+}
+
+/// Read every chunk that is present in the region file, ordered by their
+/// position in the tables at the start of the file (by z, then by x).
+pub fn read_region<R: Read>(reader: &mut R) -> Result<Vec<Chunk>> {
+    let mut buf = Vec::new();
+    let _: usize = reader.read_to_end(&mut buf)?;
+    if buf.len() < 2 * SECTOR {
+        bail!("Region file is too short to contain the chunk tables");
+    }
+
+    let mut ret = Vec::new();
+    for index in 0..usize::from(REGION_SIZE) * usize::from(REGION_SIZE) {
+        let location = BigEndian::read_u32(&buf[index * 4..]);
+        if location == 0 {
+            continue;
+        }
+        let timestamp = BigEndian::read_u32(&buf[SECTOR + index * 4..]);
+
+        let pos = ChunkPos::from_index(index);
+        let (offset, sectors) = check_location(pos, location)?;
+        if offset + sectors * SECTOR > buf.len() {
+            bail!(
+                "Chunk {}, {} has an invalid location in the region file",
+                pos.x,
+                pos.z
+            );
+        }
+        let length = BigEndian::read_u32(&buf[offset..]) as usize;
+        check_length(pos, length, sectors)?;
+
+        let compression = buf[offset + 4];
+        let data = buf[offset + 5..offset + 4 + length].to_vec();
+        check_compression(pos, compression, &data)?;
+
+        ret.push(Chunk {
+            x: pos.x,
+            z: pos.z,
+            timestamp,
+            data,
+        });
+    }
+
+    Ok(ret)
+}
+
+/// Iterates over the chunks in a region file one at a time, seeking to each
+/// chunk and reading it only when it is reached, so that only the tables at
+/// the start of the file and a single chunk are in memory at once.
+///
+/// Chunks are given in the same order as read_region. A chunk that can't be
+/// read gives an error for that chunk, and iteration continues with the next.
+pub struct ChunkIter<R: Read + Seek> {
+    reader: R,
+    locations: Vec<u32>,
+    timestamps: Vec<u32>,
+    index: usize,
+    options: ReadOptions,
+    since: u32,
+}
+impl<R: Read + Seek> ChunkIter<R> {
+    /// Read the tables at the start of the region file, without reading any
+    /// of the chunks
+    pub fn new(reader: R) -> Result<Self> {
+        ChunkIter::with_options(reader, &ReadOptions::default())
+    }
+
+    /// Like new, reading each chunk under the limits in the options
+    pub fn with_options(mut reader: R, options: &ReadOptions) -> Result<Self> {
+        let mut tables = vec![0u32; 2 * usize::from(REGION_SIZE) * usize::from(REGION_SIZE)];
+        if reader.read_u32_into::<BigEndian>(&mut tables).is_err() {
+            bail!("Region file is too short to contain the chunk tables");
+        }
+        let timestamps = tables.split_off(tables.len() / 2);
+
+        Ok(ChunkIter {
+            reader,
+            locations: tables,
+            timestamps,
+            index: 0,
+            options: *options,
+            since: 0,
+        })
+    }
+
+    /// Only give the chunks that were saved at or after the given time, in
+    /// seconds since the Unix epoch. The others aren't read at all.
+    pub fn since(mut self, since: u32) -> Self {
+        self.since = since;
+        self
+    }
+
+    /// The number of chunks in the region, including those left out by since
+    pub fn chunk_count(&self) -> usize {
+        self.locations.iter().filter(|x| **x != 0).count()
+    }
+
+    /// When the chunk was last saved, in seconds since the Unix epoch, or
+    /// None if the region doesn't contain it. This doesn't read the chunk.
+    pub fn timestamp(&self, pos: ChunkPos) -> Option<u32> {
+        match self.locations.get(pos.index()) {
+            Some(0) | None => None,
+            Some(_) => Some(self.timestamps[pos.index()]),
+        }
+    }
+
+    /// Read the next chunk as it is stored in the region, without parsing
+    /// it, such as to copy it somewhere else unchanged. This advances the
+    /// same as next.
+    pub fn next_raw(&mut self) -> Option<(ChunkPos, Result<Chunk>)> {
+        let (pos, location) = self.next_location()?;
+        let chunk = self.read_data(pos, location).map(|data| Chunk {
+            x: pos.x,
+            z: pos.z,
+            timestamp: self.timestamps[pos.index()],
+            data,
+        });
+        Some((pos, chunk))
+    }
+
+    /// Find the location of the next chunk to give, skipping the positions
+    /// without one and the chunks that were saved too early
+    fn next_location(&mut self) -> Option<(ChunkPos, u32)> {
+        while self.index < self.locations.len() {
+            let index = self.index;
+            self.index += 1;
+
+            let location = self.locations[index];
+            if location != 0 && self.timestamps[index] >= self.since {
+                return Some((ChunkPos::from_index(index), location));
+            }
+        }
+        None
+    }
+
+    /// Read the NBT file of a chunk as it is stored, in its compression
+    fn read_data(&mut self, pos: ChunkPos, location: u32) -> Result<Vec<u8>> {
+        let (offset, sectors) = check_location(pos, location)?;
+        let _: u64 = self.reader.seek(SeekFrom::Start(offset as u64))?;
+
+        let length = self.reader.read_u32::<BigEndian>()? as usize;
+        check_length(pos, length, sectors)?;
+        let compression = self.reader.read_u8()?;
+        let mut data = vec![0; length - 1];
+        self.reader.read_exact(&mut data)?;
+        check_compression(pos, compression, &data)?;
+
+        Ok(data)
+    }
+
+    fn read_chunk(&mut self, pos: ChunkPos, location: u32) -> Result<NBTFile> {
+        let data = self.read_data(pos, location)?;
+        read::read_file_with_options(&mut &data[..], &self.options)
+    }
+}
+impl<R: Read + Seek> Iterator for ChunkIter<R> {
+    type Item = (ChunkPos, Result<NBTFile>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (pos, location) = self.next_location()?;
+        Some((pos, self.read_chunk(pos, location)))
+    }
+}
+
+/// Call the function with every chunk in the region file, reading them one
+/// at a time with ChunkIter. Stops at the first error returned by the
+/// function.
+pub fn for_each_chunk<R, F>(reader: R, mut f: F) -> Result<()>
+where
+    R: Read + Seek,
+    F: FnMut(ChunkPos, Result<NBTFile>) -> Result<()>,
+{
+    for (pos, nbt) in ChunkIter::new(reader)? {
+        f(pos, nbt)?;
+    }
+    Ok(())
+}
+
+/// Split the location of a chunk from the table into its offset in bytes and
+/// its length in sectors, checking that it doesn't overlap the tables
+fn check_location(pos: ChunkPos, location: u32) -> Result<(usize, usize)> {
+    let offset = (location >> 8) as usize * SECTOR;
+    let sectors = (location & 0xff) as usize;
+    if offset < 2 * SECTOR || sectors == 0 {
+        bail!(
+            "Chunk {}, {} has an invalid location in the region file",
+            pos.x,
+            pos.z
+        );
+    }
+    Ok((offset, sectors))
+}
+
+/// Check that the length at the start of a chunk, which includes the
+/// compression byte, fits in the sectors given by its location
+fn check_length(pos: ChunkPos, length: usize, sectors: usize) -> Result<()> {
+    if length == 0 || length + 4 > sectors * SECTOR {
+        bail!(
+            "Chunk {}, {} has an invalid length {}",
+            pos.x,
+            pos.z,
+            length
+        );
+    }
+    Ok(())
+}
+
+/// Check that the compression byte of a chunk is one that is supported, and
+/// that it agrees with the data
+fn check_compression(pos: ChunkPos, compression: u8, data: &[u8]) -> Result<()> {
+    match (
+        compression,
+        data.first().cloned().and_then(Compression::from_first_byte),
+    ) {
+        (1, Some(Compression::Gzip))
+        | (2, Some(Compression::Zlib))
+        | (3, Some(Compression::None)) => Ok(()),
+        (compression, _) if compression & 0x80 != 0 => bail!(
+            "Chunk {}, {} is stored in a separate file, which is not supported",
+            pos.x,
+            pos.z
+        ),
+        (compression, _) => bail!(
+            "Chunk {}, {} has unsupported compression type {}",
+            pos.x,
+            pos.z,
+            compression
+        ),
+    }
+}
+
+/// Write the chunks to a region file, one after the other in the order given.
+pub fn write_region<W: Write>(w: &mut W, chunks: &[Chunk]) -> Result<()> {
+    let mut locations = vec![0u32; usize::from(REGION_SIZE) * usize::from(REGION_SIZE)];
+    let mut timestamps = locations.clone();
+    let mut body = Vec::new();
+
+    for chunk in chunks {
+        if chunk.x >= REGION_SIZE || chunk.z >= REGION_SIZE {
+            bail!(
+                "Chunk coordinates {}, {} are outside of the region, they must be from 0 to {}",
+                chunk.x,
+                chunk.z,
+                REGION_SIZE - 1
+            );
+        }
+        if locations[chunk.index()] != 0 {
+            bail!("Chunk {}, {} is given more than once", chunk.x, chunk.z);
+        }
+
+        let compression = match chunk
+            .data
+            .first()
+            .cloned()
+            .and_then(Compression::from_first_byte)
+        {
+            Some(Compression::Gzip) => 1,
+            Some(Compression::Zlib) => 2,
+            Some(Compression::None) => 3,
+            Some(x @ Compression::Zstd) | Some(x @ Compression::Lz4) => bail!(
+                "Chunk {}, {} is {} compressed, which region files can't store",
+                chunk.x,
+                chunk.z,
+                x.to_str()
+            ),
+            None => bail!("Chunk {}, {} is not an NBT file", chunk.x, chunk.z),
+        };
+
+        /* The length includes the compression byte, and every chunk is padded
+         * to a whole number of sectors */
+        let length = chunk.data.len() + 1;
+        let sectors = (length + 4).div_ceil(SECTOR);
+        if sectors > 0xff {
+            bail!(
+                "Chunk {}, {} is too large to be stored in a region file",
+                chunk.x,
+                chunk.z
+            );
+        }
+
+        let offset = 2 + body.len() / SECTOR;
+        locations[chunk.index()] = ((offset as u32) << 8) | sectors as u32;
+        timestamps[chunk.index()] = chunk.timestamp;
+
+        body.write_u32::<BigEndian>(length as u32)?;
+        body.write_u8(compression)?;
+        body.extend_from_slice(&chunk.data);
+        body.resize((offset - 2 + sectors) * SECTOR, 0);
+    }
+
+    for x in locations.iter().chain(timestamps.iter()) {
+        w.write_u32::<BigEndian>(*x)?;
+    }
+    w.write_all(&body)?;
+
+    Ok(())
+}
@@ -0,0 +1,160 @@
+//! Cutting the bytes around the point where a file fails to parse out of
+//! the file, so that a bug report about it can include the part that matters
+//! without the whole file.
+
+use crate::data::Compression;
+use crate::read::{self, ReadOptions, MAX_COMPRESSION_LAYERS};
+use crate::Result;
+
+use std::io::{self, BufRead, Read, Write};
+use std::vec::Drain;
+
+/// How many bytes before and after the point of failure are kept
+pub const CONTEXT: usize = 64;
+
+/// Where and why a file failed to parse, with the bytes around that point
+#[derive(Debug)]
+pub struct ParseFailure {
+    /// The offset in the uncompressed NBT data of the first byte that
+    /// hadn't been read when parsing failed. The byte at fault may come a
+    /// little before it, since a tag's name is read before its type is
+    /// checked.
+    pub offset: u64,
+    /// The length of the uncompressed NBT data
+    pub len: u64,
+    pub compression: Compression,
+    pub error: failure::Error,
+    /// The offset of the first byte of data
+    pub start: u64,
+    /// Up to CONTEXT bytes on either side of offset
+    pub data: Vec<u8>,
+}
+
+/// Read an NBT file under the limits in the options, returning where it
+/// failed to parse, or None if it parsed. The file is decompressed as it's
+/// read so that the offset is in the NBT data, and only the bytes around the
+/// point of failure are kept. An error is only returned if the file can't be
+/// decompressed.
+pub fn extract_error<R: BufRead>(
+    reader: &mut R,
+    options: &ReadOptions,
+) -> Result<Option<ParseFailure>> {
+    let _: Vec<u8> = read::read_leading(reader, options.skip_leading)?;
+    let max_layers = if options.recursive_decompress {
+        MAX_COMPRESSION_LAYERS
+    } else {
+        1
+    };
+    let decompressed = read::decompress(reader, max_layers)?;
+    let compression = decompressed.compression;
+
+    let options = ReadOptions {
+        skip_leading: 0,
+        recursive_decompress: false,
+        ..*options
+    };
+    let mut reader = TailReader {
+        inner: decompressed.reader,
+        tail: Vec::new(),
+        count: 0,
+    };
+    let error = match read::read_compressed(&mut reader, Compression::None, None, &options) {
+        Ok(_) => return Ok(None),
+        Err(e) => e,
+    };
+
+    let offset = reader.count;
+    let start = offset - reader.tail.len() as u64;
+    let mut data = reader.tail;
+    let mut rest = reader.inner;
+    let after = (&mut rest).take(CONTEXT as u64).read_to_end(&mut data)?;
+    let len = offset + after as u64 + io::copy(&mut rest, &mut io::sink())?;
+    Ok(Some(ParseFailure {
+        offset,
+        len,
+        compression,
+        error,
+        start,
+        data,
+    }))
+}
+
+/// A reader that counts the bytes read through it, and keeps the last
+/// CONTEXT of them
+struct TailReader<R> {
+    inner: R,
+    tail: Vec<u8>,
+    count: u64,
+}
+impl<R: Read> Read for TailReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.count += n as u64;
+        self.tail.extend_from_slice(&buf[..n]);
+        if self.tail.len() > CONTEXT {
+            let excess = self.tail.len() - CONTEXT;
+            let _: Drain<u8> = self.tail.drain(..excess);
+        }
+        Ok(n)
+    }
+}
+
+/// Write a description of the failure followed by its bytes as a hex dump
+/// in the format of xxd, with the offsets of the uncompressed data. The lines
+/// of the dump can be turned back into the bytes with xxd -r.
+pub fn write_report<W: Write>(w: &mut W, name: &str, failure: &ParseFailure) -> Result<()> {
+    let compression = match &failure.compression {
+        Compression::None => "uncompressed".to_string(),
+        x => format!("{} compressed", x.to_str()),
+    };
+    writeln!(
+        w,
+        "nbted {} failed to parse {}, which is {}",
+        env!("CARGO_PKG_VERSION"),
+        name,
+        compression
+    )?;
+    for (i, e) in failure.error.iter_chain().enumerate() {
+        if i == 0 {
+            writeln!(w, "Error: {}", e)?;
+        } else {
+            writeln!(w, "\tcaused by: {}", e)?;
+        }
+    }
+    writeln!(
+        w,
+        "Parsing stopped at offset 0x{:x} of 0x{:x} bytes of uncompressed NBT data",
+        failure.offset, failure.len
+    )?;
+    writeln!(
+        w,
+        "Bytes 0x{:x} to 0x{:x}:",
+        failure.start,
+        failure.start + failure.data.len() as u64
+    )?;
+
+    for (i, line) in failure.data.chunks(16).enumerate() {
+        write!(w, "{:08x}:", failure.start + i as u64 * 16)?;
+        for j in 0..16 {
+            if j % 2 == 0 {
+                write!(w, " ")?;
+            }
+            match line.get(j) {
+                Some(x) => write!(w, "{:02x}", x)?,
+                None => write!(w, "  ")?,
+            }
+        }
+        write!(w, "  ")?;
+        for x in line {
+            let c = if x.is_ascii_graphic() || *x == b' ' {
+                *x as char
+            } else {
+                '.'
+            };
+            write!(w, "{}", c)?;
+        }
+        writeln!(w)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,373 @@
+use crate::data::NBT;
+use crate::mutf8;
+use crate::read::DEFAULT_MAX_DEPTH;
+use crate::Result;
+
+/// Returns whether the given text looks like SNBT, the format used for NBT in
+/// Minecraft commands, rather than nbted's own text format. SNBT files start
+/// with the { of a compound, while nbted's text format starts with the name of
+/// the compression, or a comment.
+pub fn looks_like_snbt(text: &[u8]) -> bool {
+    text.iter().find(|x| !x.is_ascii_whitespace()) == Some(&b'{')
+}
+
+/// Parse a single tag written in SNBT, such as {Count:1b,id:"minecraft:stone"}
+pub fn read_snbt(s: &str) -> Result<NBT> {
+    read_snbt_with_max_depth(s, Some(DEFAULT_MAX_DEPTH))
+}
+
+/// Parse a single tag written in SNBT like read_snbt, failing if a tag is
+/// nested in more than max_depth compounds and lists, as with
+/// read::ReadOptions::max_depth
+pub fn read_snbt_with_max_depth(s: &str, max_depth: Option<usize>) -> Result<NBT> {
+    let mut parser = Parser {
+        s,
+        pos: 0,
+        depth: 0,
+        max_depth,
+    };
+    let tag = parser.read_value()?;
+
+    parser.skip_whitespace();
+    if parser.pos != s.len() {
+        return Err(parser.error("Unexpected data after the end of the tag"));
+    }
+
+    Ok(tag)
+}
+
+/// Walks the bytes of the SNBT text. Since all of the syntax is ASCII, pos
+/// always stays on a character boundary.
+struct Parser<'a> {
+    s: &'a str,
+    pos: usize,
+    /// The number of compounds and lists the tag being read is in
+    depth: usize,
+    max_depth: Option<usize>,
+}
+impl<'a> Parser<'a> {
+    /// Create an error, saying at which character it happened
+    fn error(&self, msg: &str) -> failure::Error {
+        format_err!(
+            "{} at character {}",
+            msg,
+            self.s[..self.pos].chars().count()
+        )
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.s.as_bytes().get(self.pos).cloned()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(|x| x.is_ascii_whitespace()) {
+            self.pos += 1;
+        }
+    }
+
+    /// Skip whitespace, and then consume the given character
+    fn expect(&mut self, c: u8) -> Result<()> {
+        self.skip_whitespace();
+        if self.peek() != Some(c) {
+            return Err(self.error(&format!("Expected {}", c as char)));
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    /// Skip whitespace, and then consume the given character if it's next
+    fn accept(&mut self, c: u8) -> bool {
+        self.skip_whitespace();
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Read a value inside of a compound, list or array, failing if it's
+    /// nested too deeply
+    fn read_nested_value(&mut self) -> Result<NBT> {
+        self.depth += 1;
+        match self.max_depth {
+            Some(max) if self.depth > max => {
+                return Err(self.error(&format!("NBT nesting exceeds max depth {}", max)))
+            }
+            _ => (),
+        }
+        let ret = self.read_value();
+        self.depth -= 1;
+        ret
+    }
+
+    fn read_value(&mut self) -> Result<NBT> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(b'{') => self.read_compound(),
+            Some(b'[') => self.read_list(),
+            Some(b'"') | Some(b'\'') => Ok(NBT::String(self.read_quoted()?)),
+            Some(_) => {
+                let literal = self.read_unquoted();
+                if literal.is_empty() {
+                    return Err(self.error("Expected a value"));
+                }
+                Ok(parse_literal(literal))
+            }
+            None => Err(self.error("Unexpected end of input, expected a value")),
+        }
+    }
+
+    fn read_compound(&mut self) -> Result<NBT> {
+        self.expect(b'{')?;
+        let mut map: Vec<(Vec<u8>, NBT)> = Vec::new();
+        if self.accept(b'}') {
+            return Ok(NBT::Compound(map));
+        }
+
+        loop {
+            self.skip_whitespace();
+            /* Only quoted keys may be empty */
+            let key = match self.peek() {
+                Some(b'"') | Some(b'\'') => self.read_quoted()?,
+                _ => {
+                    let key = self.read_unquoted();
+                    if key.is_empty() {
+                        return Err(self.error("Expected a key"));
+                    }
+                    key.as_bytes().to_vec()
+                }
+            };
+            if map.iter().any(|(k, _)| *k == key) {
+                return Err(self.error(&format!("Duplicate key {}", String::from_utf8_lossy(&key))));
+            }
+            self.expect(b':')?;
+            map.push((key, self.read_nested_value()?));
+
+            if self.accept(b'}') {
+                return Ok(NBT::Compound(map));
+            }
+            self.expect(b',')?;
+        }
+    }
+
+    fn read_list(&mut self) -> Result<NBT> {
+        self.expect(b'[')?;
+
+        /* A typed array starts with B;, I; or L; */
+        let bytes = self.s.as_bytes();
+        if bytes.get(self.pos + 1) == Some(&b';') {
+            let kind = bytes[self.pos];
+            if let b'B' | b'I' | b'L' = kind {
+                self.pos += 2;
+                return self.read_array(kind);
+            }
+        }
+
+        let mut list: Vec<NBT> = Vec::new();
+        if self.accept(b']') {
+            return Ok(NBT::list(list));
+        }
+
+        loop {
+            self.skip_whitespace();
+            let start = self.pos;
+            let tag = self.read_nested_value()?;
+            if let Some(first) = list.first() {
+                if first.type_byte() != tag.type_byte() {
+                    self.pos = start;
+                    return Err(self.error(&format!(
+                        "{} in a list of {}, all elements of a list must have the same type",
+                        tag.type_string(),
+                        first.type_string()
+                    )));
+                }
+            }
+            list.push(tag);
+
+            if self.accept(b']') {
+                return Ok(NBT::list(list));
+            }
+            self.expect(b',')?;
+        }
+    }
+
+    /// Read the elements of a typed array, after the [B; that starts it.
+    /// Elements may be given as any integer type, as long as the value fits.
+    fn read_array(&mut self, kind: u8) -> Result<NBT> {
+        let mut vals: Vec<i64> = Vec::new();
+        let (min, max) = match kind {
+            b'B' => (i64::from(i8::MIN), i64::from(i8::MAX)),
+            b'I' => (i64::from(i32::MIN), i64::from(i32::MAX)),
+            _ => (i64::MIN, i64::MAX),
+        };
+
+        if !self.accept(b']') {
+            loop {
+                self.skip_whitespace();
+                let start = self.pos;
+                let val = match self.read_nested_value()? {
+                    NBT::Byte(x) => i64::from(x),
+                    NBT::Short(x) => i64::from(x),
+                    NBT::Int(x) => i64::from(x),
+                    NBT::Long(x) => x,
+                    x => {
+                        self.pos = start;
+                        return Err(self.error(&format!(
+                            "{} in a {}; array, expected an integer",
+                            x.type_string(),
+                            kind as char
+                        )));
+                    }
+                };
+                if val < min || val > max {
+                    self.pos = start;
+                    return Err(
+                        self.error(&format!("{} doesn't fit in a {}; array", val, kind as char))
+                    );
+                }
+                vals.push(val);
+
+                if self.accept(b']') {
+                    break;
+                }
+                self.expect(b',')?;
+            }
+        }
+
+        /* The values were checked to fit above */
+        Ok(match kind {
+            b'B' => NBT::ByteArray(vals.into_iter().map(|x| x as i8).collect()),
+            b'I' => NBT::IntArray(vals.into_iter().map(|x| x as i32).collect()),
+            _ => NBT::LongArray(vals),
+        })
+    }
+
+    /// Read a string in either single or double quotes, encoded as modified
+    /// UTF-8. A backslash escapes the next backslash or quote, \n, \t and \r
+    /// are a newline, tab and carriage return, and \xNN is the byte with the
+    /// given hex value, as written by snbt_write.
+    fn read_quoted(&mut self) -> Result<Vec<u8>> {
+        let quote = self.peek().expect("read_quoted called without a quote");
+        self.pos += 1;
+
+        let mut ret = Vec::new();
+        loop {
+            let rest = &self.s[self.pos..];
+            let c = match rest.chars().next() {
+                Some(x) => x,
+                None => return Err(self.error("Unexpected end of input in a string")),
+            };
+            self.pos += c.len_utf8();
+
+            if c == quote as char {
+                return Ok(ret);
+            } else if c == '\\' {
+                let escaped = match self.peek() {
+                    Some(x) if x == b'\\' || x == b'"' || x == b'\'' => x,
+                    Some(b'n') => b'\n',
+                    Some(b't') => b'\t',
+                    Some(b'r') => b'\r',
+                    Some(b'x') => {
+                        let val = self
+                            .s
+                            .get(self.pos + 1..self.pos + 3)
+                            .filter(|x| x.bytes().all(|x| x.is_ascii_hexdigit()))
+                            .and_then(|x| u8::from_str_radix(x, 16).ok());
+                        match val {
+                            Some(x) => {
+                                self.pos += 2;
+                                x
+                            }
+                            None => {
+                                return Err(self.error(
+                                    r"Invalid string, \x must be followed by two hex digits giving a byte",
+                                ))
+                            }
+                        }
+                    }
+                    _ => return Err(self.error("Invalid escape sequence in a string")),
+                };
+                ret.push(escaped);
+                self.pos += 1;
+            } else {
+                mutf8::encode_char(c, &mut ret);
+            }
+        }
+    }
+
+    /// Read the characters allowed in an unquoted key or value
+    fn read_unquoted(&mut self) -> &'a str {
+        let start = self.pos;
+        while let Some(x) = self.peek() {
+            if x.is_ascii_alphanumeric() || b"_-.+".contains(&x) {
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+        &self.s[start..self.pos]
+    }
+}
+
+/// Work out the tag an unquoted value stands for, following the rules of
+/// Minecraft. Numbers are typed by their suffix in either case, with plain
+/// integers being Ints and plain decimals (which need a decimal point)
+/// Doubles, true and false are Bytes, and anything else is a String.
+/// Numbers that don't fit in their type, and integers with leading zeros,
+/// are also read as Strings.
+fn parse_literal(s: &str) -> NBT {
+    match s {
+        "true" => return NBT::Byte(1),
+        "false" => return NBT::Byte(0),
+        _ => (),
+    }
+
+    let (body, suffix) = match s.char_indices().last() {
+        Some((i, c)) if c.is_ascii_alphabetic() => (&s[..i], Some(c.to_ascii_lowercase())),
+        _ => (s, None),
+    };
+
+    let tag = match suffix {
+        Some('b') if is_integer(body) => body.parse().ok().map(NBT::Byte),
+        Some('s') if is_integer(body) => body.parse().ok().map(NBT::Short),
+        Some('l') if is_integer(body) => body.parse().ok().map(NBT::Long),
+        Some('f') if is_decimal(body) => body.parse().ok().map(NBT::Float),
+        Some('d') if is_decimal(body) => body.parse().ok().map(NBT::Double),
+        None if is_integer(body) => body.parse().ok().map(NBT::Int),
+        None if is_decimal(body) && body.contains('.') => body.parse().ok().map(NBT::Double),
+        _ => None,
+    };
+
+    tag.unwrap_or_else(|| NBT::String(s.as_bytes().to_vec()))
+}
+
+/// An optional sign followed by digits, where the only number that may
+/// start with a 0 is 0 itself
+fn is_integer(s: &str) -> bool {
+    let digits = s.strip_prefix(['-', '+']).unwrap_or(s);
+    is_digits(digits) && (digits == "0" || !digits.starts_with('0'))
+}
+
+fn is_digits(s: &str) -> bool {
+    !s.is_empty() && s.bytes().all(|x| x.is_ascii_digit())
+}
+
+/// An optional sign, digits with at most one decimal point, and an optional
+/// exponent
+fn is_decimal(s: &str) -> bool {
+    let s = s.strip_prefix(['-', '+']).unwrap_or(s);
+    let (mantissa, exponent) = match s.find(['e', 'E']) {
+        Some(i) => (&s[..i], Some(&s[i + 1..])),
+        None => (s, None),
+    };
+
+    let mut parts = mantissa.splitn(2, '.');
+    let int = parts.next().unwrap_or("");
+    let frac = parts.next().unwrap_or("");
+    let valid_mantissa = (!int.is_empty() || !frac.is_empty())
+        && int.bytes().all(|x| x.is_ascii_digit())
+        && frac.bytes().all(|x| x.is_ascii_digit());
+
+    valid_mantissa && exponent.is_none_or(|x| is_digits(x.strip_prefix(['-', '+']).unwrap_or(x)))
+}
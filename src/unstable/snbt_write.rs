@@ -0,0 +1,143 @@
+//! Writing SNBT, the format used for NBT in Minecraft commands such as
+//! /data merge, as read by snbt_read.
+
+use crate::data::NBT;
+use crate::mutf8;
+use crate::read::{self, DEFAULT_MAX_DEPTH};
+use crate::Result;
+
+use std::io::Write;
+
+/// Write a single tag as SNBT, such as {Count:1b,id:"minecraft:stone"}, on
+/// a single line without any whitespace. Fails on tags nested deeper than
+/// read::DEFAULT_MAX_DEPTH, which couldn't be read back.
+pub fn write_snbt<W: Write>(w: &mut W, tag: &NBT) -> Result<()> {
+    write_tag(w, tag, 0)
+}
+
+/// Write a tag nested in depth compounds and lists
+fn write_tag<W: Write>(w: &mut W, tag: &NBT, depth: usize) -> Result<()> {
+    match tag {
+        NBT::End => bail!("Unable to write End tag"),
+        NBT::Byte(x) => write!(w, "{}b", x)?,
+        NBT::Short(x) => write!(w, "{}s", x)?,
+        NBT::Int(x) => write!(w, "{}", x)?,
+        NBT::Long(x) => write!(w, "{}L", x)?,
+        NBT::Float(x) => write_decimal(w, f64::from(*x), &format!("{:?}", x), 'f')?,
+        NBT::Double(x) => write_decimal(w, *x, &format!("{:?}", x), 'd')?,
+        NBT::ByteArray(x) => write_array(w, "B", "b", x)?,
+        NBT::String(x) => write_string(w, x)?,
+        NBT::List { items: x, .. } => {
+            write!(w, "[")?;
+            for (i, val) in x.iter().enumerate() {
+                if i > 0 {
+                    write!(w, ",")?;
+                }
+                read::check_depth(depth + 1, Some(DEFAULT_MAX_DEPTH))?;
+                write_tag(w, val, depth + 1)?;
+            }
+            write!(w, "]")?;
+        }
+        NBT::Compound(x) => {
+            write!(w, "{{")?;
+            for (i, (key, val)) in x.iter().enumerate() {
+                if i > 0 {
+                    write!(w, ",")?;
+                }
+                write_key(w, key)?;
+                write!(w, ":")?;
+                read::check_depth(depth + 1, Some(DEFAULT_MAX_DEPTH))?;
+                write_tag(w, val, depth + 1)?;
+            }
+            write!(w, "}}")?;
+        }
+        NBT::IntArray(x) => write_array(w, "I", "", x)?,
+        NBT::LongArray(x) => write_array(w, "L", "L", x)?,
+    }
+
+    Ok(())
+}
+
+/// Write a Float or Double, given as text that always has a decimal point or
+/// an exponent, like Minecraft writes them as 1.0f rather than 1f.
+fn write_decimal<W: Write>(w: &mut W, val: f64, text: &str, suffix: char) -> Result<()> {
+    if val.is_nan() {
+        bail!("Unable to write NaN as SNBT, it has no SNBT form");
+    } else if val.is_infinite() {
+        let exponent = if suffix == 'f' { 39 } else { 309 };
+        let sign = if val < 0.0 { "-" } else { "" };
+        write!(w, "{}1e{}{}", sign, exponent, suffix)?;
+    } else {
+        write!(w, "{}{}", text, suffix)?;
+    }
+    Ok(())
+}
+
+/// Write an array as [B;1b,2b], with the given type letter and the suffix
+/// of every element
+fn write_array<W: Write, T: std::fmt::Display>(
+    w: &mut W,
+    kind: &str,
+    suffix: &str,
+    vals: &[T],
+) -> Result<()> {
+    write!(w, "[{};", kind)?;
+    for (i, x) in vals.iter().enumerate() {
+        if i > 0 {
+            write!(w, ",")?;
+        }
+        write!(w, "{}{}", x, suffix)?;
+    }
+    write!(w, "]")?;
+    Ok(())
+}
+
+/// Write a key of a compound, which is left unquoted if it only has the
+/// characters snbt_read allows in an unquoted key
+fn write_key<W: Write>(w: &mut W, key: &[u8]) -> Result<()> {
+    let simple = !key.is_empty()
+        && key
+            .iter()
+            .all(|x| x.is_ascii_alphanumeric() || b"_-.+".contains(x));
+    if simple {
+        w.write_all(key)?;
+        Ok(())
+    } else {
+        write_string(w, key)
+    }
+}
+
+/// Write a string in double quotes. The string is decoded from modified
+/// UTF-8, and quotes, backslashes, newlines, tabs and carriage returns are
+/// escaped with a backslash. Other control characters, NUL, and bytes that
+/// aren't part of a character Java would have written are written as \xNN
+/// escapes of their bytes, as in the text format.
+fn write_string<W: Write>(w: &mut W, val: &[u8]) -> Result<()> {
+    write!(w, r#"""#)?;
+    let mut i = 0;
+    while i < val.len() {
+        match mutf8::decode_char(&val[i..]) {
+            Some((c, len)) => {
+                match c {
+                    '\\' | '"' => write!(w, "\\{}", c)?,
+                    '\n' => write!(w, r"\n")?,
+                    '\t' => write!(w, r"\t")?,
+                    '\r' => write!(w, r"\r")?,
+                    _ if c.is_control() => {
+                        for b in &val[i..i + len] {
+                            write!(w, r"\x{:02x}", b)?;
+                        }
+                    }
+                    _ => write!(w, "{}", c)?,
+                }
+                i += len;
+            }
+            None => {
+                write!(w, r"\x{:02x}", val[i])?;
+                i += 1;
+            }
+        }
+    }
+    write!(w, r#"""#)?;
+    Ok(())
+}
@@ -0,0 +1,370 @@
+//! Reading and writing NBT files one tag at a time, without ever keeping the
+//! whole file in memory.
+
+use crate::data::{Compression, Endianness, NBT};
+use crate::lz4;
+use crate::read;
+use crate::write;
+use crate::zstd;
+use crate::Result;
+
+use std::io::{BufRead, BufWriter, Read, Write};
+
+use byteorder::WriteBytesExt;
+
+use flate2::read::{GzDecoder, ZlibDecoder};
+use flate2::write::ZlibEncoder;
+
+/// A single step in reading an NBT file. Compounds and lists are given as a
+/// start event, followed by the events of their contents, followed by an end
+/// event. All other tags are given as a single Value event.
+///
+/// The name is Some for tags inside of a compound, and None for the elements
+/// of a list.
+#[derive(Clone, PartialEq, Debug)]
+pub enum NbtEvent {
+    CompoundStart {
+        name: Option<Vec<u8>>,
+    },
+    CompoundEnd,
+    ListStart {
+        name: Option<Vec<u8>>,
+        /// The type id of the elements of the list
+        type_id: u8,
+        length: usize,
+    },
+    ListEnd,
+    Value {
+        name: Option<Vec<u8>>,
+        value: NBT,
+    },
+}
+
+/// What transform_file should do with an event after passing it to the
+/// closure
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Action {
+    /// Write the event, including any changes the closure made to it
+    Keep,
+    /// Leave the tag out of the output. For start events, the whole compound
+    /// or list is left out, without its contents being passed to the closure.
+    Drop,
+}
+
+/// The kind of container that the reader or writer is currently inside of
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Frame {
+    Compound,
+    List { type_id: u8, remaining: usize },
+}
+
+/// Reads the events of an uncompressed NBT file one at a time.
+///
+/// The implicit root compound doesn't have any events of its own, so the
+/// first event is that of the first named tag in the file, and reading stops
+/// at the end of the implicit compound.
+pub struct EventReader<R: Read> {
+    reader: R,
+    stack: Vec<Frame>,
+}
+impl<R: Read> EventReader<R> {
+    pub fn new(reader: R) -> Self {
+        EventReader {
+            reader,
+            stack: vec![Frame::Compound],
+        }
+    }
+
+    /// Read the next event, returning None once the whole file has been
+    /// read
+    pub fn next_event(&mut self) -> Result<Option<NbtEvent>> {
+        let frame = match self.stack.last_mut() {
+            Some(x) => x,
+            None => return Ok(None),
+        };
+
+        match frame {
+            Frame::Compound => match read::read_type_id(&mut self.reader)? {
+                Some(type_id) => {
+                    let name = read::read_key(&mut self.reader)?;
+                    self.read_tag(type_id, Some(name)).map(Some)
+                }
+                None => {
+                    let _: Option<Frame> = self.stack.pop();
+                    if self.stack.is_empty() {
+                        Ok(None)
+                    } else {
+                        Ok(Some(NbtEvent::CompoundEnd))
+                    }
+                }
+            },
+            Frame::List { remaining: 0, .. } => {
+                let _: Option<Frame> = self.stack.pop();
+                Ok(Some(NbtEvent::ListEnd))
+            }
+            Frame::List { type_id, remaining } => {
+                *remaining -= 1;
+                let type_id = *type_id;
+                self.read_tag(type_id, None).map(Some)
+            }
+        }
+    }
+
+    /// Skip the rest of the compound or list that was started by the last
+    /// event, so that the next event is the one following its end event.
+    pub fn skip_container(&mut self) -> Result<()> {
+        match self.stack.pop() {
+            Some(Frame::Compound) => read::skip_tag(&mut self.reader, 0x0a),
+            Some(Frame::List { type_id, remaining }) => {
+                if type_id != 0x0 {
+                    for _ in 0..remaining {
+                        read::skip_tag(&mut self.reader, type_id)?;
+                    }
+                }
+                Ok(())
+            }
+            None => bail!("Tried to skip a container while not inside of one"),
+        }
+    }
+
+    fn read_tag(&mut self, type_id: u8, name: Option<Vec<u8>>) -> Result<NbtEvent> {
+        match type_id {
+            0x0 => Ok(NbtEvent::Value {
+                name,
+                value: NBT::End,
+            }),
+            0x09 => {
+                let mut element_type: [u8; 1] = [0];
+                self.reader.read_exact(&mut element_type)?;
+                let length = read::read_length(&mut self.reader, Endianness::Big)? as usize;
+                self.stack.push(Frame::List {
+                    type_id: element_type[0],
+                    remaining: length,
+                });
+                Ok(NbtEvent::ListStart {
+                    name,
+                    type_id: element_type[0],
+                    length,
+                })
+            }
+            0x0a => {
+                self.stack.push(Frame::Compound);
+                Ok(NbtEvent::CompoundStart { name })
+            }
+            x => Ok(NbtEvent::Value {
+                name,
+                value: read::read_value(&mut self.reader, x)?,
+            }),
+        }
+    }
+}
+
+/// Writes events to an uncompressed NBT file, checking that they make up a
+/// valid file.
+pub struct EventWriter<W: Write> {
+    writer: W,
+    stack: Vec<Frame>,
+    empty: bool,
+}
+impl<W: Write> EventWriter<W> {
+    pub fn new(writer: W) -> Self {
+        EventWriter {
+            writer,
+            stack: vec![Frame::Compound],
+            empty: true,
+        }
+    }
+
+    pub fn write_event(&mut self, event: &NbtEvent) -> Result<()> {
+        self.empty = false;
+        match event {
+            NbtEvent::CompoundStart { name } => {
+                self.write_header(0x0a, name)?;
+                self.stack.push(Frame::Compound);
+            }
+            NbtEvent::CompoundEnd => match self.stack.pop() {
+                Some(Frame::Compound) if !self.stack.is_empty() => self.writer.write_all(&[0])?,
+                _ => bail!("Got the end of a compound while not inside of one"),
+            },
+            NbtEvent::ListStart {
+                name,
+                type_id,
+                length,
+            } => {
+                self.write_header(0x09, name)?;
+                self.writer.write_all(&[*type_id])?;
+                write::write_int(&mut self.writer, *length as i32)?;
+                self.stack.push(Frame::List {
+                    type_id: *type_id,
+                    remaining: *length,
+                });
+            }
+            NbtEvent::ListEnd => match self.stack.pop() {
+                Some(Frame::List { remaining: 0, .. }) => (),
+                Some(Frame::List { remaining, .. }) => {
+                    bail!(
+                        "Got the end of a list that is missing {} elements",
+                        remaining
+                    )
+                }
+                _ => bail!("Got the end of a list while not inside of one"),
+            },
+            NbtEvent::Value { name, value } => {
+                if let (NBT::End, Some(_)) = (value, name) {
+                    bail!("Got an End tag inside of a compound");
+                }
+                self.write_header(value.type_byte(), name)?;
+                match value {
+                    /* Only lists of End contain End tags, and they're empty */
+                    NBT::End => (),
+                    x => write::write_tag(&mut self.writer, x)?,
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check that the whole file has been written, and return the writer
+    pub fn finish(mut self) -> Result<W> {
+        if self.stack.len() != 1 {
+            bail!("The file ended inside of a compound or list");
+        }
+        /* Like write::write_file, an empty file is written as just an End
+         * tag */
+        if self.empty {
+            self.writer.write_all(&[0])?;
+        }
+        Ok(self.writer)
+    }
+
+    /// Write what comes before the value of a tag, the type id and name if
+    /// it's inside of a compound, or nothing if it's inside of a list
+    fn write_header(&mut self, type_id: u8, name: &Option<Vec<u8>>) -> Result<()> {
+        match (self.stack.last_mut(), name) {
+            (Some(Frame::Compound), Some(name)) => {
+                self.writer.write_u8(type_id)?;
+                write::write_string(&mut self.writer, name)
+            }
+            (Some(Frame::Compound), None) => bail!("Got a tag without a name inside of a compound"),
+            (
+                Some(Frame::List {
+                    type_id: expected,
+                    remaining,
+                }),
+                None,
+            ) => {
+                if *expected != type_id {
+                    bail!(
+                        "Got a tag with type id {:x} inside of a list with type id {:x}",
+                        type_id,
+                        expected
+                    );
+                }
+                if *remaining == 0 {
+                    bail!("Got more elements than the length of the list");
+                }
+                *remaining -= 1;
+                Ok(())
+            }
+            (Some(Frame::List { .. }), Some(_)) => bail!("Got a tag with a name inside of a list"),
+            (None, _) => bail!("Got a tag after the end of the file"),
+        }
+    }
+}
+
+/// Read an NBT file from the reader one tag at a time, passing every event
+/// to the closure, and write the result to the writer with the same
+/// compression, without keeping the whole file in memory.
+///
+/// The closure may change the names and values of tags, but not the type or
+/// length of lists. Tags inside of compounds may be dropped, but elements of
+/// lists may not, as the length of the list has already been written.
+pub fn transform_file<R, W, F>(reader: &mut R, writer: &mut W, f: F) -> Result<()>
+where
+    R: BufRead,
+    W: Write,
+    F: FnMut(&mut NbtEvent) -> Action,
+{
+    match read::peek_compression(reader)? {
+        Compression::None => transform_events(reader, writer, f),
+        /* As in write_file, the encoders are given larger pieces than
+         * single tags */
+        Compression::Gzip => {
+            let mut w = BufWriter::new(write::gzip_encoder(
+                writer,
+                flate2::Compression::new(write::DEFAULT_COMPRESSION_LEVEL),
+            ));
+            transform_events(GzDecoder::new(reader), &mut w, f)?;
+            let _: &mut W = write::finish_buffered(w)?.finish()?;
+            Ok(())
+        }
+        Compression::Zlib => {
+            let mut w = BufWriter::new(ZlibEncoder::new(
+                writer,
+                flate2::Compression::new(write::DEFAULT_COMPRESSION_LEVEL),
+            ));
+            transform_events(ZlibDecoder::new(reader), &mut w, f)?;
+            let _: &mut W = write::finish_buffered(w)?.finish()?;
+            Ok(())
+        }
+        Compression::Zstd => {
+            zstd::compress_with(writer, |w| transform_events(zstd::decoder(reader)?, w, f))
+        }
+        Compression::Lz4 => {
+            lz4::compress_with(writer, |w| transform_events(lz4::decoder(reader)?, w, f))
+        }
+    }
+}
+
+fn transform_events<R, W, F>(reader: R, writer: W, mut f: F) -> Result<()>
+where
+    R: Read,
+    W: Write,
+    F: FnMut(&mut NbtEvent) -> Action,
+{
+    let mut reader = EventReader::new(reader);
+    let mut writer = EventWriter::new(writer);
+
+    while let Some(mut event) = reader.next_event()? {
+        let original = match event {
+            NbtEvent::ListStart {
+                type_id, length, ..
+            } => Some((type_id, length)),
+            _ => None,
+        };
+
+        let action = f(&mut event);
+
+        if let NbtEvent::ListStart {
+            type_id, length, ..
+        } = event
+        {
+            if original != Some((type_id, length)) {
+                bail!("The type and length of a list can't be changed while streaming");
+            }
+        }
+
+        match action {
+            Action::Keep => writer.write_event(&event)?,
+            Action::Drop => match event {
+                NbtEvent::CompoundStart { name: None }
+                | NbtEvent::ListStart { name: None, .. }
+                | NbtEvent::Value { name: None, .. } => {
+                    bail!("Elements of a list can't be dropped while streaming")
+                }
+                NbtEvent::CompoundStart { .. } | NbtEvent::ListStart { .. } => {
+                    reader.skip_container()?
+                }
+                NbtEvent::Value { .. } => (),
+                NbtEvent::CompoundEnd | NbtEvent::ListEnd => {
+                    bail!("The end of a compound or list can't be dropped")
+                }
+            },
+        }
+    }
+
+    let _: W = writer.finish()?;
+
+    Ok(())
+}
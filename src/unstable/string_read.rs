@@ -1,11 +1,17 @@
-use crate::data::{Compression, NBTFile, NBT};
+use crate::base64;
+use crate::data::{type_name, Compression, KeySet, NBTFile, NBT};
+use crate::mutf8;
+use crate::read::DEFAULT_MAX_DEPTH;
 use crate::Result;
 
 use std::borrow::Cow;
+use std::fmt;
 use std::io::Read;
+use std::num::{IntErrorKind, ParseIntError};
 use std::str;
+use std::str::FromStr;
 
-use failure::ResultExt;
+use failure::{Backtrace, Fail, ResultExt};
 
 /// A struct for iterating over the tokens in a given file
 ///
@@ -13,33 +19,155 @@ use failure::ResultExt;
 /// such as a tag or a value. This will /almost/ only be space-separated values
 /// but unfortunately strings are an exception, as strings can contain any
 /// character, including newline.
+///
+/// Comments, from a # at the start of a token to the end of the line, are
+/// skipped like whitespace.
 struct Tokens<'a> {
     file: &'a [u8],
     a: usize,
     b: usize,
+    /// Where the last token began, or the end of the file once there are no
+    /// more tokens, which is where errors are reported
+    start: usize,
+    options: TextReadOptions,
+    /// The number of compounds and lists the tag being read is in
+    depth: usize,
 }
 impl<'a> Tokens<'a> {
-    fn new(file: &'a [u8]) -> Self {
-        Tokens { file, a: 0, b: 0 }
+    fn new(file: &'a [u8], options: &TextReadOptions) -> Self {
+        Tokens {
+            file,
+            a: 0,
+            b: 0,
+            start: 0,
+            options: *options,
+            depth: 0,
+        }
+    }
+
+    /// Note that a tag inside of a compound or list is about to be read,
+    /// failing if it's nested too deeply
+    fn enter(&mut self) -> Result<()> {
+        self.depth += 1;
+        match self.options.max_depth {
+            Some(max) if self.depth > max => bail!("NBT nesting exceeds max depth {}", max),
+            _ => Ok(()),
+        }
+    }
+
+    /// Note that the tag given to enter has been read
+    fn leave(&mut self) {
+        self.depth -= 1;
+    }
+
+    /// Return the next token without moving past it
+    fn peek(&mut self) -> Option<Result<Cow<'a, str>>> {
+        let (a, b, start) = (self.a, self.b, self.start);
+        let ret = self.next();
+        self.a = a;
+        self.b = b;
+        self.start = start;
+        ret
+    }
+
+    /// Give the error the line and column of the given offset in the file,
+    /// unless it already has them
+    fn locate_at(&self, error: failure::Error, offset: usize) -> failure::Error {
+        if error.downcast_ref::<Located>().is_some() {
+            return error;
+        }
+        let before = &self.file[..offset];
+        let line_start = match before.iter().rposition(|x| *x == b'\n') {
+            Some(x) => x + 1,
+            None => 0,
+        };
+        Located {
+            line: before.iter().filter(|x| **x == b'\n').count() + 1,
+            column: String::from_utf8_lossy(&before[line_start..])
+                .chars()
+                .count()
+                + 1,
+            error,
+        }
+        .into()
+    }
+
+    /// Give the error the line and column of the last token
+    fn locate(&self, error: failure::Error) -> failure::Error {
+        self.locate_at(error, self.start)
+    }
+}
+
+/// An error reading a file in the text format, with the line and column
+/// where it happened, counting from 1. It is displayed as the original error
+/// followed by where it happened, and the original error's causes are kept.
+#[derive(Debug)]
+struct Located {
+    error: failure::Error,
+    line: usize,
+    column: usize,
+}
+impl fmt::Display for Located {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} at line {}, column {}",
+            self.error, self.line, self.column
+        )
+    }
+}
+impl Fail for Located {
+    fn cause(&self) -> Option<&dyn Fail> {
+        self.error.as_fail().cause()
+    }
+
+    fn backtrace(&self) -> Option<&Backtrace> {
+        Some(self.error.backtrace())
     }
 }
 impl<'a> Iterator for Tokens<'a> {
     type Item = Result<Cow<'a, str>>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        while self.file.get(self.a)?.is_whitespace() {
-            self.a += 1;
+        Some(self.next_bytes()?.and_then(|x| match x {
+            Cow::Borrowed(x) => Ok(Cow::Borrowed(str::from_utf8(x)?)),
+            Cow::Owned(x) => Ok(Cow::Owned(String::from_utf8(x)?)),
+        }))
+    }
+}
+impl<'a> Tokens<'a> {
+    /// Like next, but without checking that the token is UTF-8, for strings,
+    /// which can contain any bytes given as \xNN escapes
+    fn next_bytes(&mut self) -> Option<Result<Cow<'a, [u8]>>> {
+        self.start = self.file.len();
+        loop {
+            while self.file.get(self.a)?.is_whitespace() {
+                self.a += 1;
+            }
+
+            /* 0x23 = #, which starts a comment lasting until the end of the
+             * line */
+            if *self.file.get(self.a)? != 0x23 {
+                break;
+            }
+            while *self.file.get(self.a)? != 0x0a {
+                self.a += 1;
+            }
         }
         /* a now matches the beginning of the next token */
+        self.start = self.a;
 
         if *self.file.get(self.a)? == 0x22 {
             /* The next token is a string */
             self.a += 1; /* So we don't include the beginning " */
-                         
+
             self.b = self.a;
 
             let mut escape: bool = false;
             let mut ret: Vec<u8> = Vec::new();
+            /* The characters since the last \x escape, which are converted
+             * to modified UTF-8 once they're all there */
+            let mut literal: Vec<u8> = Vec::new();
 
             loop {
                 /* 0x22 = "
@@ -47,38 +175,74 @@ impl<'a> Iterator for Tokens<'a> {
                 match self.file.get(self.b)? {
                     0x22 => {
                         if escape {
-                            ret.push(0x22);
+                            literal.push(0x22);
                             escape = false;
                         } else {
                             self.b += 1;
                             break;
                         }
-                    },
+                    }
                     0x5c => {
                         if escape {
-                            ret.push(0x5c);
+                            literal.push(0x5c);
                             escape = false;
                         } else {
                             escape = true;
                         }
-                    },
+                    }
+                    /* \n, \t and \r, as written with
+                     * TextFormat::escape_whitespace */
+                    0x6e if escape => {
+                        literal.push(b'\n');
+                        escape = false;
+                    }
+                    0x74 if escape => {
+                        literal.push(b'\t');
+                        escape = false;
+                    }
+                    0x72 if escape => {
+                        literal.push(b'\r');
+                        escape = false;
+                    }
+                    /* 0x78 = x, followed by two hex digits giving a byte, as
+                     * written for control characters with
+                     * TextFormat::escape_control, and for bytes that aren't
+                     * UTF-8 */
+                    0x78 if escape => {
+                        let digits = self.file.get(self.b + 1..self.b + 3)?;
+                        let val = str::from_utf8(digits)
+                            .ok()
+                            .filter(|x| x.bytes().all(|x| x.is_ascii_hexdigit()))
+                            .and_then(|x| u8::from_str_radix(x, 16).ok());
+                        match val {
+                            Some(x) => {
+                                push_literal(&mut ret, &literal);
+                                literal.clear();
+                                ret.push(x);
+                            }
+                            None => {
+                                return Some(Err(format_err!(
+                                    r"Invalid string, \x must be followed by two hex digits giving a byte"
+                                )))
+                            }
+                        }
+                        escape = false;
+                        self.b += 2;
+                    }
                     x if escape => {
-                        return Some(Err(
-                            format_err!(r#"Invalid string, tried to escape the character {} which cannot be escaped (to enter a literal \, write \\)"#, x)))
-                    },
-                    x => ret.push(*x),
+                        return Some(Err(format_err!(
+                            r#"Invalid string, tried to escape the character {} which cannot be escaped (to enter a literal \, write \\)"#,
+                            describe_byte(*x)
+                        )))
+                    }
+                    x => literal.push(*x),
                 }
                 self.b += 1;
             }
-
-            let ret: String = match String::from_utf8(ret) {
-                Ok(x) => x,
-                Err(e) => return Some(Err(e.into())),
-            };
-            let ret: Cow<str> = Cow::Owned(ret);
+            push_literal(&mut ret, &literal);
 
             self.a = self.b;
-            Some(Ok(ret))
+            Some(Ok(Cow::Owned(ret)))
         } else {
             /* The next token is not a string */
             self.b = self.a;
@@ -91,10 +255,7 @@ impl<'a> Iterator for Tokens<'a> {
                 }
             }
 
-            let ret = match str::from_utf8(self.file.get(self.a..self.b)?) {
-                Ok(x) => x,
-                Err(e) => return Some(Err(e.into())),
-            };
+            let ret = self.file.get(self.a..self.b)?;
 
             self.a = self.b;
             Some(Ok(Cow::Borrowed(ret)))
@@ -102,6 +263,18 @@ impl<'a> Iterator for Tokens<'a> {
     }
 }
 
+/// Add characters written in a string as they are to its bytes, in modified
+/// UTF-8 like Java would encode them. Anything that isn't UTF-8 is kept as it
+/// is.
+fn push_literal(ret: &mut Vec<u8>, literal: &[u8]) {
+    for chunk in literal.utf8_chunks() {
+        for c in chunk.valid().chars() {
+            mutf8::encode_char(c, ret);
+        }
+        ret.extend_from_slice(chunk.invalid());
+    }
+}
+
 trait IsWhitespace {
     fn is_whitespace(&self) -> bool;
 }
@@ -119,30 +292,161 @@ impl IsWhitespace for u8 {
     }
 }
 
+/// Format a byte for an error message, as a quoted character if it's
+/// printable ASCII and as \xNN otherwise
+fn describe_byte(x: u8) -> String {
+    if x.is_ascii_graphic() || x == b' ' {
+        format!("'{}'", x as char)
+    } else {
+        format!(r"\x{:02x}", x)
+    }
+}
+
+/// How to read a file in the pretty text format. The default is the format
+/// as written by string_write::write_file, read as permissively as the binary
+/// format is.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TextReadOptions {
+    /// Whether the contents of containers are between { and }, as written
+    /// with TextFormat::braces
+    pub braces: bool,
+    /// Fail on a compound that has the same key more than once, as with
+    /// read::ReadOptions::forbid_duplicate_keys
+    pub forbid_duplicate_keys: bool,
+    /// The maximum number of compounds and lists a tag may be nested in, as
+    /// with read::ReadOptions::max_depth
+    pub max_depth: Option<usize>,
+}
+impl Default for TextReadOptions {
+    fn default() -> Self {
+        TextReadOptions {
+            braces: false,
+            forbid_duplicate_keys: false,
+            max_depth: Some(DEFAULT_MAX_DEPTH),
+        }
+    }
+}
+
 /// Read an NBT file from the reader, in the pretty text format
 pub fn read_file<R: Read>(reader: &mut R) -> Result<NBTFile> {
+    read_file_with_options(reader, &TextReadOptions::default())
+}
+
+/// Read an NBT file from the reader, in the variant of the pretty text format
+/// where the contents of containers are between { and }, as written with
+/// TextFormat::braces
+pub fn read_file_braces<R: Read>(reader: &mut R) -> Result<NBTFile> {
+    let options = TextReadOptions {
+        braces: true,
+        ..Default::default()
+    };
+    read_file_with_options(reader, &options)
+}
+
+/// Read an NBT file from the reader, in the pretty text format or its
+/// variant with braces, as given by the options
+pub fn read_file_with_options<R: Read>(
+    reader: &mut R,
+    options: &TextReadOptions,
+) -> Result<NBTFile> {
     let mut buf = Vec::new();
     let _: usize = reader.read_to_end(&mut buf)?;
 
-    let mut tokens = Tokens::new(&buf);
+    let mut tokens = Tokens::new(&buf, options);
 
-    let compression = {
-        let tmp = match tokens.next() {
-            Some(x) => x?,
-            None => bail!("NBT file in text format does not contain any tags at all"),
-        };
+    let first = match tokens.next() {
+        Some(x) => x,
+        None => bail!("NBT file in text format does not contain any tags at all"),
+    };
+    read_file_from(&mut tokens, first).map_err(|e| tokens.locate(e))
+}
 
+/// Read the rest of the file, after the first token, which should be the
+/// compression
+fn read_file_from(tokens: &mut Tokens, first: Result<Cow<str>>) -> Result<NBTFile> {
+    let compression = {
+        let tmp = first?;
         match Compression::from_str(&tmp) {
             Some(x) => x,
             None => bail!("Unknown compression format {}", tmp),
         }
     };
 
-    let root = read_compound(&mut tokens)?;
+    let root = read_compound(tokens)?;
+
+    /* Anything left is most likely a mistake, such as two files pasted
+     * together, so don't quietly drop it */
+    if let Some(x) = tokens.next() {
+        bail!(
+            "Unexpected tokens after the root compound, starting with {}",
+            x?
+        );
+    }
+
+    /* The text format doesn't record the compression level, so files read
+     * from it are written with the default level */
+    Ok(NBTFile {
+        root,
+        compression,
+        compression_level: None,
+    })
+}
+
+/// If the file has braces, read the { that starts the contents of a container
+fn read_open_brace(tokens: &mut Tokens) -> Result<()> {
+    if tokens.options.braces {
+        match tokens.next() {
+            Some(x) => {
+                let x = x?;
+                if x != "{" {
+                    bail!(
+                        "Expected {{ to start the contents of a container, found {}",
+                        x
+                    );
+                }
+            }
+            None => bail!("EOF when trying to read the {{ starting a container"),
+        }
+    }
+    Ok(())
+}
 
-    Ok(NBTFile { root, compression })
+/// If the file has braces, read the } that ends the contents of a list or
+/// array
+fn read_close_brace(tokens: &mut Tokens) -> Result<()> {
+    if tokens.options.braces {
+        match tokens.next() {
+            Some(x) => {
+                let x = x?;
+                if x != "}" {
+                    bail!(
+                        "Expected }} after the last element of a list or array, found {}",
+                        x
+                    );
+                }
+            }
+            None => bail!("EOF when trying to read the }} ending a list or array"),
+        }
+    }
+    Ok(())
 }
 
+/// The names of the tag types that read_tag can read
+const TAG_TYPES: &[&str] = &[
+    "Byte",
+    "Short",
+    "Int",
+    "Long",
+    "Float",
+    "Double",
+    "ByteArray",
+    "String",
+    "List",
+    "Compound",
+    "IntArray",
+    "LongArray",
+];
+
 fn read_tag(tokens: &mut Tokens, tag_type: &str) -> Result<NBT> {
     match tag_type {
         "Byte" => read_byte(tokens),
@@ -166,7 +470,7 @@ fn read_byte(tokens: &mut Tokens) -> Result<NBT> {
         Some(x) => x?,
         None => bail!("EOF when trying to read a byte"),
     };
-    let val = val.parse::<i8>().context(format!("Invalid Byte {}", val))?;
+    let val = parse_integer(&val, "Byte", i8::MIN.into(), i8::MAX.into())?;
     Ok(NBT::Byte(val))
 }
 
@@ -175,9 +479,7 @@ fn read_short(tokens: &mut Tokens) -> Result<NBT> {
         Some(x) => x?,
         None => bail!("EOF when trying to read a short"),
     };
-    let val = val
-        .parse::<i16>()
-        .context(format!("Invalid Short {}", val))?;
+    let val = parse_integer(&val, "Short", i16::MIN.into(), i16::MAX.into())?;
     Ok(NBT::Short(val))
 }
 
@@ -186,7 +488,7 @@ fn read_int(tokens: &mut Tokens) -> Result<NBT> {
         Some(x) => x?,
         None => bail!("EOF when trying to read an int"),
     };
-    let val = val.parse::<i32>().context(format!("Invalid Int {}", val))?;
+    let val = parse_integer(&val, "Int", i32::MIN.into(), i32::MAX.into())?;
     Ok(NBT::Int(val))
 }
 
@@ -195,20 +497,51 @@ fn read_long(tokens: &mut Tokens) -> Result<NBT> {
         Some(x) => x?,
         None => bail!("EOF when trying to read a long"),
     };
-    let val = val
-        .parse::<i64>()
-        .context(format!("Invalid Long {}", val))?;
+    let val = parse_integer(&val, "Long", i64::MIN, i64::MAX)?;
     Ok(NBT::Long(val))
 }
 
+/// Parse an integer of the given type, where a number that doesn't fit in the
+/// type gets an error giving the range of the type, so that the user knows to
+/// use a larger one
+fn parse_integer<T>(val: &str, type_name: &str, min: i64, max: i64) -> Result<T>
+where
+    T: FromStr<Err = ParseIntError>,
+{
+    match val.parse::<T>() {
+        Ok(x) => Ok(x),
+        Err(e) => match e.kind() {
+            IntErrorKind::PosOverflow | IntErrorKind::NegOverflow => bail!(
+                "{} value {} out of range ({}..={})",
+                type_name,
+                val,
+                min,
+                max
+            ),
+            _ => Err(e)
+                .context(format!("Invalid {} {}", type_name, val))
+                .map_err(From::from),
+        },
+    }
+}
+
 fn read_float(tokens: &mut Tokens) -> Result<NBT> {
     let val = match tokens.next() {
         Some(x) => x?,
         None => bail!("EOF when trying to read a float"),
     };
-    let val = val
-        .parse::<f32>()
-        .context(format!("Invalid Float {}", val))?;
+    let invalid = || format!("Invalid Float {}", val);
+    let val = match &*val {
+        "NaN" => f32::NAN,
+        "Infinity" => f32::INFINITY,
+        "-Infinity" => f32::NEG_INFINITY,
+        _ => match nan_bits(&val) {
+            Some(x) => f32::from_bits(u32::from_str_radix(x, 16).context(invalid())?),
+            /* Files written by earlier versions have inf for Infinity, which
+             * parse also accepts */
+            None => val.parse::<f32>().context(invalid())?,
+        },
+    };
     Ok(NBT::Float(val))
 }
 
@@ -217,17 +550,56 @@ fn read_double(tokens: &mut Tokens) -> Result<NBT> {
         Some(x) => x?,
         None => bail!("EOF when trying to read a double"),
     };
-    let val = val
-        .parse::<f64>()
-        .context(format!("Invalid Double {}", val))?;
+    let invalid = || format!("Invalid Double {}", val);
+    let val = match &*val {
+        "NaN" => f64::NAN,
+        "Infinity" => f64::INFINITY,
+        "-Infinity" => f64::NEG_INFINITY,
+        _ => match nan_bits(&val) {
+            Some(x) => f64::from_bits(u64::from_str_radix(x, 16).context(invalid())?),
+            None => val.parse::<f64>().context(invalid())?,
+        },
+    };
     Ok(NBT::Double(val))
 }
 
+/// Returns the hexadecimal bits of a NaN written as NaN(0x...), which is how
+/// the NaNs other than the usual one are written so that they keep their sign
+/// and payload. The bits aren't checked to be a NaN, so that any value read
+/// back is the one that was written.
+fn nan_bits(val: &str) -> Option<&str> {
+    val.strip_prefix("NaN(0x")?.strip_suffix(')')
+}
+
 fn read_byte_array(tokens: &mut Tokens) -> Result<NBT> {
     let len = match read_int(tokens)? {
         NBT::Int(x) => x,
         _ => unreachable!(),
     };
+
+    /* The bytes may be a single token in hex or base64, as written with
+     * TextFormat::byte_array_encoding */
+    let encoded = match tokens.peek() {
+        Some(Ok(x)) if x.starts_with("0x") || x.starts_with("base64:") => Some(x),
+        _ => None,
+    };
+    if let Some(x) = encoded {
+        let _: Option<Result<Cow<str>>> = tokens.next();
+        let bytes = match x.strip_prefix("0x") {
+            Some(hex) => decode_hex(hex)?,
+            None => base64::decode(&x["base64:".len()..])?,
+        };
+        if bytes.len() as i64 != i64::from(len) {
+            bail!(
+                "ByteArray has length {}, but {} bytes were given",
+                len,
+                bytes.len()
+            );
+        }
+        return Ok(NBT::ByteArray(bytes.into_iter().map(|x| x as i8).collect()));
+    }
+
+    read_open_brace(tokens)?;
     let mut tmp = Vec::with_capacity(len as usize);
     for _ in 0..len {
         tmp.push(match read_byte(tokens)? {
@@ -235,15 +607,27 @@ fn read_byte_array(tokens: &mut Tokens) -> Result<NBT> {
             _ => unreachable!(),
         });
     }
+    read_close_brace(tokens)?;
     Ok(NBT::ByteArray(tmp))
 }
 
+/// Decode the hex digits of a ByteArray written with ByteArrayEncoding::Hex
+fn decode_hex(hex: &str) -> Result<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) || !hex.bytes().all(|x| x.is_ascii_hexdigit()) {
+        bail!("Invalid ByteArray, 0x must be followed by pairs of hex digits");
+    }
+    Ok((0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+        .collect())
+}
+
 fn read_string(tokens: &mut Tokens) -> Result<NBT> {
-    let val = match tokens.next() {
+    let val = match tokens.next_bytes() {
         Some(x) => x?,
         None => bail!("EOF when trying to read a string"),
     };
-    Ok(NBT::String(val.into_owned().into_bytes()))
+    Ok(NBT::String(val.into_owned()))
 }
 
 fn read_list(tokens: &mut Tokens) -> Result<NBT> {
@@ -255,16 +639,33 @@ fn read_list(tokens: &mut Tokens) -> Result<NBT> {
         NBT::Int(x) => x,
         _ => unreachable!(),
     };
+    read_open_brace(tokens)?;
     let mut tmp = Vec::with_capacity(len as usize);
     for _ in 0..len {
+        tokens.enter()?;
         tmp.push(read_tag(tokens, &list_type)?);
+        tokens.leave();
     }
+    read_close_brace(tokens)?;
 
-    Ok(NBT::List(tmp))
+    let element_type = match (0..=12).find(|x| type_name(*x) == list_type) {
+        Some(x) => x,
+        None => bail!("Unknown tag type {}", list_type),
+    };
+    Ok(NBT::List {
+        element_type,
+        items: tmp,
+    })
 }
 
 fn read_compound(tokens: &mut Tokens) -> Result<NBT> {
     let mut map = Vec::new();
+    let mut keys = if tokens.options.forbid_duplicate_keys {
+        Some(KeySet::default())
+    } else {
+        None
+    };
+    read_open_brace(tokens)?;
 
     loop {
         let tag_type = match tokens.next() {
@@ -272,21 +673,39 @@ fn read_compound(tokens: &mut Tokens) -> Result<NBT> {
             None => bail!("EOF when trying to read the next item in a compound"),
         };
 
-        /* If we get an End tag then the compound is done */
-        if &tag_type == "End" {
+        /* If we get an End tag (or the closing brace) then the compound is
+         * done */
+        let end = if tokens.options.braces { "}" } else { "End" };
+        if tag_type == end {
             break;
         }
+        if tag_type == "End" {
+            bail!("Found End in a compound, but compounds end with }} when reading with braces");
+        }
+        /* Checked before reading the name, so the error points at the type */
+        if !TAG_TYPES.contains(&&*tag_type) {
+            bail!("Unknown tag type {}", tag_type);
+        }
 
-        let name = match tokens.next() {
-            Some(x) => x?,
+        let name = match tokens.next_bytes() {
+            Some(x) => x?.into_owned(),
             None => bail!(
                 "EOF when trying to read the name of a {} tag in a compound",
                 tag_type
             ),
         };
+        let name_start = tokens.start;
+        tokens.enter()?;
         let nbt = read_tag(tokens, &tag_type)?;
+        tokens.leave();
+        if let Some(ref mut keys) = keys {
+            /* The error is about the name, rather than the end of the tag */
+            if let Err(e) = keys.check(&name, nbt.type_byte()) {
+                return Err(tokens.locate_at(e, name_start));
+            }
+        }
 
-        map.push((name.into_owned().into_bytes(), nbt));
+        map.push((name, nbt));
     }
 
     Ok(NBT::Compound(map))
@@ -297,6 +716,7 @@ fn read_int_array(tokens: &mut Tokens) -> Result<NBT> {
         NBT::Int(x) => x,
         _ => unreachable!(),
     };
+    read_open_brace(tokens)?;
     let mut tmp = Vec::with_capacity(len as usize);
     for _ in 0..len {
         tmp.push(match read_int(tokens)? {
@@ -304,6 +724,7 @@ fn read_int_array(tokens: &mut Tokens) -> Result<NBT> {
             _ => unreachable!(),
         });
     }
+    read_close_brace(tokens)?;
     Ok(NBT::IntArray(tmp))
 }
 
@@ -312,6 +733,7 @@ fn read_long_array(tokens: &mut Tokens) -> Result<NBT> {
         NBT::Int(x) => x,
         _ => unreachable!(),
     };
+    read_open_brace(tokens)?;
     let mut tmp = Vec::with_capacity(len as usize);
     for _ in 0..len {
         tmp.push(match read_long(tokens)? {
@@ -319,5 +741,6 @@ fn read_long_array(tokens: &mut Tokens) -> Result<NBT> {
             _ => unreachable!(),
         });
     }
+    read_close_brace(tokens)?;
     Ok(NBT::LongArray(tmp))
 }
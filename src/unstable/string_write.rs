@@ -1,20 +1,258 @@
-use crate::data::{NBTFile, NBT};
-use crate::iter_replacer::ReplacerExt;
+use crate::base64;
+use crate::data::{type_name, NBTFile, NBT};
+use crate::mutf8;
+use crate::write;
 use crate::Result;
 
-use byteorder::WriteBytesExt;
-
 use std::io::Write;
 
+/// How write_file_with lays out the pretty text format. The default gives
+/// the same output as write_file, and every variation can still be read back
+/// by string_read (with read_file_braces if braces is set.)
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct TextFormat {
+    /// What each level of nesting is indented with
+    pub indent: IndentStyle,
+    /// Write control characters in strings as \xNN escapes, instead of
+    /// writing them to the file as they are
+    pub escape_control: bool,
+    /// Write newlines, tabs and carriage returns in strings as \n, \t and \r,
+    /// so that a string with several lines stays on one line of the text.
+    /// Takes precedence over escape_control for these characters.
+    pub escape_whitespace: bool,
+    /// How the elements of arrays and short lists of atomic tags are laid out
+    pub arrays: ArrayFormat,
+    /// How the bytes of ByteArrays are written
+    pub byte_array_encoding: ByteArrayEncoding,
+    /// Write the tags of every compound sorted by their names instead of in
+    /// the order they are stored. Reading the text back gives the sorted
+    /// order.
+    pub sort_keys: bool,
+    /// Add a comment with the UUID string to every IntArray of length 4, which
+    /// is how Minecraft stores UUIDs. The comments are ignored when reading
+    /// the file back in.
+    pub uuid_strings: bool,
+    /// Write the contents of compounds, lists and arrays between { and }
+    /// instead of ending compounds with End, so that the nesting is explicit.
+    /// Files written like this have to be read with string_read::read_file_braces.
+    pub braces: bool,
+    /// Write Floats and Doubles as the shortest text that reads back as the
+    /// same value, always with a decimal point or an exponent, such as 1.0
+    /// and 1e300, instead of as 1 and 1 followed by 300 zeros
+    pub canonical_floats: bool,
+}
+impl TextFormat {
+    /// The format of --canonical, for dumps that are kept in version
+    /// control. Keys are sorted and every element of an array is on a line
+    /// of its own, so the text only depends on the tags and a change to a
+    /// tag only changes its own lines. This format won't change in later
+    /// versions of nbted.
+    pub fn canonical() -> Self {
+        TextFormat {
+            indent: IndentStyle::Tabs,
+            escape_control: true,
+            escape_whitespace: false,
+            arrays: ArrayFormat::OnePerLine,
+            byte_array_encoding: ByteArrayEncoding::Decimal,
+            sort_keys: true,
+            uuid_strings: false,
+            braces: false,
+            canonical_floats: true,
+        }
+    }
+}
+
+/// What TextFormat indents each level of nesting with
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum IndentStyle {
+    /// A single tab
+    #[default]
+    Tabs,
+    /// The given number of spaces
+    Spaces(u8),
+}
+
+/// How TextFormat writes the bytes of ByteArrays
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum ByteArrayEncoding {
+    /// Every byte as a signed number, laid out like the other arrays
+    #[default]
+    Decimal,
+    /// All of the bytes as a single token of hex digits after 0x, on the same
+    /// line as the length
+    Hex,
+    /// All of the bytes as a single token of base64 after base64:, on the
+    /// same line as the length
+    Base64,
+}
+impl ByteArrayEncoding {
+    /// Returns the ByteArrayEncoding of the given name, or None if there is
+    /// no such ByteArrayEncoding
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(string: &str) -> Option<Self> {
+        match string {
+            "decimal" => Some(ByteArrayEncoding::Decimal),
+            "hex" => Some(ByteArrayEncoding::Hex),
+            "base64" => Some(ByteArrayEncoding::Base64),
+            _ => None,
+        }
+    }
+}
+
+/// How TextFormat lays out arrays and short lists of atomic tags
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum ArrayFormat {
+    /// Every element on a line of its own
+    #[default]
+    OnePerLine,
+    /// All of the elements on the same line as the array, for lists only if
+    /// they have at most COMPACT_LIST_LENGTH elements
+    SingleLine,
+}
+
 /// Given an NBT file, write it to the writer in the pretty text format
 pub fn write_file<W: Write>(w: &mut W, file: &NBTFile) -> Result<()> {
+    write_file_with(w, file, &TextFormat::default())
+}
+
+/// Given an NBT file, write it to the writer in the pretty text format laid
+/// out according to the format
+pub fn write_file_with<W: Write>(w: &mut W, file: &NBTFile, format: &TextFormat) -> Result<()> {
     write!(w, "{}", file.compression.to_str())?;
-    write_tag(w, &file.root, 0, true)?;
+    /* The root is indented like any other compound when it has braces around
+     * it */
+    let indent = if format.braces { 1 } else { 0 };
+    write_tag(w, &file.root, indent, true, format)?;
 
     Ok(())
 }
 
-fn write_tag<W: Write>(w: &mut W, tag: &NBT, indent: u64, compound: bool) -> Result<()> {
+/// The longest list that is written on a single line with
+/// ArrayFormat::SingleLine
+const COMPACT_LIST_LENGTH: usize = 16;
+
+/// Write a single tag in the pretty text format, the way it appears inside
+/// of a compound if it has a name, or inside of a list if it doesn't.
+pub fn write_single_tag<W: Write>(w: &mut W, name: Option<&[u8]>, tag: &NBT) -> Result<()> {
+    match name {
+        Some(name) => {
+            w.write_all(tag.type_string().as_bytes())?;
+            write!(w, " ")?;
+            write_string(w, name, &TextFormat::default())?;
+            write_tag(w, tag, 1, true, &TextFormat::default())
+        }
+        None => write_tag(w, tag, 1, false, &TextFormat::default()),
+    }
+}
+
+/// Write just the value of a tag in the pretty text format, the way it
+/// appears as an element of a list but without any indentation, so that the
+/// contents of a compound are written like those of the root. Lists and
+/// arrays start with their type and length, such as Int 3.
+pub fn write_value<W: Write>(w: &mut W, tag: &NBT, format: &TextFormat) -> Result<()> {
+    let mut buf = Vec::new();
+    write_tag(&mut buf, tag, 0, false, format)?;
+    /* Lists and arrays are written after their name with a space between */
+    w.write_all(buf.strip_prefix(b" ").unwrap_or(&buf))?;
+    Ok(())
+}
+
+/// What write_tag has left to write. Nested tags are written by pushing
+/// their steps onto a stack rather than by recursing, so that any tree that
+/// could be read can be written back, however deep it is.
+enum Step<'a> {
+    /// Write the tag, as it appears inside of a compound if compound is set,
+    /// or inside of a list if not
+    Tag {
+        tag: &'a NBT,
+        indent: u64,
+        compound: bool,
+    },
+    /// Write the type and name of a tag inside of a compound, followed by
+    /// the tag itself
+    Entry {
+        key: &'a [u8],
+        tag: &'a NBT,
+        indent: u64,
+    },
+    /// Write an element of a list, on a line of its own
+    Element { tag: &'a NBT, indent: u64 },
+    /// Write the End or } that closes a compound
+    CompoundEnd { indent: u64 },
+    /// Write the } that closes a list, if the format has braces
+    ListEnd { indent: u64 },
+}
+
+fn write_tag<W: Write>(
+    w: &mut W,
+    tag: &NBT,
+    indent: u64,
+    compound: bool,
+    format: &TextFormat,
+) -> Result<()> {
+    let mut stack = vec![Step::Tag {
+        tag,
+        indent,
+        compound,
+    }];
+
+    while let Some(step) = stack.pop() {
+        match step {
+            Step::Tag {
+                tag,
+                indent,
+                compound,
+            } => write_tag_start(w, tag, indent, compound, format, &mut stack)?,
+            Step::Entry { key, tag, indent } => {
+                write_indent(w, indent, format)?;
+                w.write_all(tag.type_string().as_bytes())?;
+                write!(w, " ")?;
+                write_string(w, key, format)?;
+                stack.push(Step::Tag {
+                    tag,
+                    indent: indent + 1,
+                    compound: true,
+                });
+            }
+            Step::Element { tag, indent } => {
+                match tag {
+                    /* Compounds without braces start directly with their
+                     * contents, which are indented on their own */
+                    NBT::Compound(..) if !format.braces => (),
+                    _ => write_indent(w, indent, format)?,
+                }
+                stack.push(Step::Tag {
+                    tag,
+                    indent: indent + 1,
+                    compound: false,
+                });
+            }
+            Step::CompoundEnd { indent } => {
+                if format.braces {
+                    write_close_brace_line(w, indent, format)?;
+                } else {
+                    write_indent(w, indent, format)?;
+                    writeln!(w, "End")?;
+                }
+            }
+            Step::ListEnd { indent } => write_close_brace_line(w, indent, format)?,
+        }
+    }
+
+    Ok(())
+}
+
+/// Write a single tag, except for the contents of compounds and lists, which
+/// are pushed onto the stack to be written next, followed by their end
+fn write_tag_start<'a, W: Write>(
+    w: &mut W,
+    tag: &'a NBT,
+    indent: u64,
+    compound: bool,
+    format: &TextFormat,
+    stack: &mut Vec<Step<'a>>,
+) -> Result<()> {
+    let single_line = format.arrays == ArrayFormat::SingleLine;
     match *tag {
         NBT::End => (),
         NBT::Byte(x) => {
@@ -41,97 +279,307 @@ fn write_tag<W: Write>(w: &mut W, tag: &NBT, indent: u64, compound: bool) -> Res
             }
             writeln!(w, "{}", x)?;
         }
-        NBT::Float(x) => {
+        NBT::Float(_) | NBT::Double(_) => {
             if compound {
                 write!(w, " ")?;
             }
-            writeln!(w, "{}", x)?;
+            write_atomic(w, tag, format)?;
+            writeln!(w)?;
         }
-        NBT::Double(x) => {
-            if compound {
-                write!(w, " ")?;
+        NBT::ByteArray(ref x) if format.byte_array_encoding == ByteArrayEncoding::Hex => {
+            write!(w, " {} 0x", x.len())?;
+            for val in x {
+                write!(w, "{:02x}", *val as u8)?;
             }
-            writeln!(w, "{}", x)?;
+            writeln!(w)?;
+        }
+        NBT::ByteArray(ref x) if format.byte_array_encoding == ByteArrayEncoding::Base64 => {
+            let bytes: Vec<u8> = x.iter().map(|x| *x as u8).collect();
+            writeln!(w, " {} base64:{}", x.len(), base64::encode(&bytes))?;
+        }
+        NBT::ByteArray(ref x) if single_line => {
+            write!(w, " {}", x.len())?;
+            write_open_brace(w, format)?;
+            for val in x {
+                write!(w, " {}", val)?;
+            }
+            write_close_brace(w, format)?;
+            writeln!(w)?;
         }
         NBT::ByteArray(ref x) => {
-            writeln!(w, " {}", x.len())?;
+            write!(w, " {}", x.len())?;
+            write_open_brace(w, format)?;
+            writeln!(w)?;
             for val in x {
-                write_indent(w, indent)?;
+                write_indent(w, indent, format)?;
                 writeln!(w, "{}", val)?;
             }
+            write_close_brace_line(w, indent, format)?;
         }
         NBT::String(ref x) => {
             if compound {
                 write!(w, " ")?;
             }
-            write!(w, r#"""#)?;
-            /* Order is important here */
-            for b in x.iter().replacer(br"\", br"\\").replacer(br#"""#, br#"\""#) {
-                w.write_all(&[b])?;
-            }
-            writeln!(w, r#"""#)?;
-        }
-        NBT::List(ref x) => {
-            /* If the list has length 0, then it just defaults to type "End". */
-            let tag_type = if x.is_empty() {
-                "End"
-            } else {
-                x[0].type_string()
-            };
-            writeln!(w, " {} {}", tag_type, x.len())?;
+            write_string(w, x, format)?;
+            writeln!(w)?;
+        }
+        NBT::List {
+            element_type,
+            items: ref x,
+        } if single_line && is_short_list(x) => {
+            write::check_list_types(x)?;
+            write!(w, " {} {}", list_type(element_type, x), x.len())?;
+            write_open_brace(w, format)?;
             for val in x {
-                match val {
-                    NBT::Compound(..) => (),
-                    _ => write_indent(w, indent)?,
-                }
-                write_tag(w, val, indent + 1, false)?;
+                write!(w, " ")?;
+                write_atomic(w, val, format)?;
+            }
+            write_close_brace(w, format)?;
+            writeln!(w)?;
+        }
+        NBT::List {
+            element_type,
+            items: ref x,
+        } => {
+            write::check_list_types(x)?;
+            write!(w, " {} {}", list_type(element_type, x), x.len())?;
+            write_open_brace(w, format)?;
+            writeln!(w)?;
+            stack.push(Step::ListEnd { indent });
+            for val in x.iter().rev() {
+                stack.push(Step::Element { tag: val, indent });
             }
         }
         NBT::Compound(ref x) => {
-            if compound {
+            if format.braces {
+                if compound {
+                    write!(w, " ")?;
+                }
+                writeln!(w, "{{")?;
+            } else if compound {
                 writeln!(w)?;
             }
-            for &(ref key, ref val) in x {
-                write_indent(w, indent)?;
-                w.write_all(val.type_string().as_bytes())?;
-                write!(w, r#" ""#)?;
-                for x in key
-                    .iter()
-                    .replacer(br"\", br"\\")
-                    .replacer(br#"""#, br#"\""#)
-                {
-                    w.write_all(&[x])?;
-                }
-                write!(w, r#"""#)?;
-                write_tag(w, val, indent + 1, true)?;
+            let mut entries: Vec<&(Vec<u8>, NBT)> = x.iter().collect();
+            if format.sort_keys {
+                entries.sort_by(|a, b| a.0.cmp(&b.0));
             }
-
-            write_indent(w, indent)?;
-            writeln!(w, "End")?;
+            stack.push(Step::CompoundEnd { indent });
+            for (key, val) in entries.into_iter().rev() {
+                stack.push(Step::Entry {
+                    key,
+                    tag: val,
+                    indent,
+                });
+            }
+        }
+        NBT::IntArray(ref x) if single_line => {
+            write!(w, " {}", x.len())?;
+            write_open_brace(w, format)?;
+            for val in x {
+                write!(w, " {}", val)?;
+            }
+            write_close_brace(w, format)?;
+            write_uuid_comment(w, x, format)?;
+            writeln!(w)?;
         }
         NBT::IntArray(ref x) => {
-            writeln!(w, " {}", x.len())?;
+            write!(w, " {}", x.len())?;
+            write_open_brace(w, format)?;
+            write_uuid_comment(w, x, format)?;
+            writeln!(w)?;
             for val in x {
-                write_indent(w, indent)?;
+                write_indent(w, indent, format)?;
                 writeln!(w, "{}", val)?;
             }
+            write_close_brace_line(w, indent, format)?;
+        }
+        NBT::LongArray(ref x) if single_line => {
+            write!(w, " {}", x.len())?;
+            write_open_brace(w, format)?;
+            for val in x {
+                write!(w, " {}", val)?;
+            }
+            write_close_brace(w, format)?;
+            writeln!(w)?;
         }
         NBT::LongArray(ref x) => {
-            writeln!(w, " {}", x.len())?;
+            write!(w, " {}", x.len())?;
+            write_open_brace(w, format)?;
+            writeln!(w)?;
             for val in x {
-                write_indent(w, indent)?;
+                write_indent(w, indent, format)?;
                 writeln!(w, "{}", val)?;
             }
+            write_close_brace_line(w, indent, format)?;
         }
     }
 
     Ok(())
 }
 
-fn write_indent<W: Write>(w: &mut W, indent: u64) -> Result<()> {
-    for _ in 0..indent {
-        /* 9 = tab character */
-        w.write_u8(9)?;
+/// Write the { that starts the contents of a list or array, if the format
+/// asks for braces
+fn write_open_brace<W: Write>(w: &mut W, format: &TextFormat) -> Result<()> {
+    if format.braces {
+        write!(w, " {{")?;
     }
     Ok(())
 }
+
+/// Write the } that ends the contents of a list or array on the same line,
+/// if the format asks for braces
+fn write_close_brace<W: Write>(w: &mut W, format: &TextFormat) -> Result<()> {
+    if format.braces {
+        write!(w, " }}")?;
+    }
+    Ok(())
+}
+
+/// Write an infinite Float or Double as Infinity or -Infinity, rather than
+/// the inf that Rust writes, which means nothing to other programs
+fn write_infinity<W: Write>(w: &mut W, negative: bool) -> Result<()> {
+    if negative {
+        write!(w, "-")?;
+    }
+    write!(w, "Infinity")?;
+    Ok(())
+}
+
+/// Write the } that ends the contents of a container on a line of its own,
+/// indented one level less than the contents, if the format asks for braces
+fn write_close_brace_line<W: Write>(w: &mut W, indent: u64, format: &TextFormat) -> Result<()> {
+    if format.braces {
+        write_indent(w, indent.saturating_sub(1), format)?;
+        writeln!(w, "}}")?;
+    }
+    Ok(())
+}
+
+/// If the format asks for it and the array is a UUID, write the UUID as a
+/// comment
+fn write_uuid_comment<W: Write>(w: &mut W, array: &[i32], format: &TextFormat) -> Result<()> {
+    if format.uuid_strings && array.len() == 4 {
+        write!(w, " # {}", uuid_string(array))?;
+    }
+    Ok(())
+}
+
+/// Format a UUID stored as 4 Ints, most significant first, in the canonical
+/// xxxxxxxx-xxxx-xxxx-xxxx-xxxxxxxxxxxx form
+fn uuid_string(array: &[i32]) -> String {
+    let x: Vec<u32> = array.iter().map(|x| *x as u32).collect();
+    format!(
+        "{:08x}-{:04x}-{:04x}-{:04x}-{:04x}{:08x}",
+        x[0],
+        x[1] >> 16,
+        x[1] & 0xffff,
+        x[2] >> 16,
+        x[2] & 0xffff,
+        x[3]
+    )
+}
+
+/// The name of the type of the list's elements, which for an empty list is
+/// the type it was declared with. A type that has no name can only be
+/// written as End.
+fn list_type(element_type: u8, list: &[NBT]) -> &str {
+    match list.first() {
+        Some(x) => x.type_string(),
+        None if element_type <= 12 => type_name(element_type),
+        None => "End",
+    }
+}
+
+/// Whether the list only contains few enough atomic tags to be written on a
+/// single line
+fn is_short_list(list: &[NBT]) -> bool {
+    list.len() <= COMPACT_LIST_LENGTH
+        && list.iter().all(|x| {
+            matches!(
+                x,
+                NBT::Byte(_)
+                    | NBT::Short(_)
+                    | NBT::Int(_)
+                    | NBT::Long(_)
+                    | NBT::Float(_)
+                    | NBT::Double(_)
+                    | NBT::String(_)
+            )
+        })
+}
+
+/// Write the value of an atomic tag, without a newline
+fn write_atomic<W: Write>(w: &mut W, tag: &NBT, format: &TextFormat) -> Result<()> {
+    match *tag {
+        NBT::Byte(x) => write!(w, "{}", x)?,
+        NBT::Short(x) => write!(w, "{}", x)?,
+        NBT::Int(x) => write!(w, "{}", x)?,
+        NBT::Long(x) => write!(w, "{}", x)?,
+        /* Only the usual NaN is written as NaN, since any other is read back
+         * as that one, so the others are written by their bits */
+        NBT::Float(x) if x.is_nan() && x.to_bits() != f32::NAN.to_bits() => {
+            write!(w, "NaN(0x{:08x})", x.to_bits())?
+        }
+        NBT::Double(x) if x.is_nan() && x.to_bits() != f64::NAN.to_bits() => {
+            write!(w, "NaN(0x{:016x})", x.to_bits())?
+        }
+        NBT::Float(x) if x.is_infinite() => write_infinity(w, x.is_sign_negative())?,
+        NBT::Double(x) if x.is_infinite() => write_infinity(w, x.is_sign_negative())?,
+        NBT::Float(x) if format.canonical_floats => write!(w, "{:?}", x)?,
+        NBT::Double(x) if format.canonical_floats => write!(w, "{:?}", x)?,
+        NBT::Float(x) => write!(w, "{}", x)?,
+        NBT::Double(x) => write!(w, "{}", x)?,
+        NBT::String(ref x) => write_string(w, x, format)?,
+        _ => unreachable!(),
+    }
+    Ok(())
+}
+
+/// Write a string in quotes, escaping any quotes, backslashes and NULs within
+/// it, and other control characters if the format asks for it. The string is
+/// decoded from modified UTF-8, and any bytes that Java wouldn't have written,
+/// such as a four byte UTF-8 character, are escaped so that they're kept as
+/// they are.
+fn write_string<W: Write>(w: &mut W, val: &[u8], format: &TextFormat) -> Result<()> {
+    write!(w, r#"""#)?;
+    let mut i = 0;
+    while i < val.len() {
+        match mutf8::decode_char(&val[i..]) {
+            Some((c, len)) => {
+                let whitespace = match c {
+                    '\n' => Some('n'),
+                    '\t' => Some('t'),
+                    '\r' => Some('r'),
+                    _ => None,
+                };
+                if c == '\\' || c == '"' {
+                    write!(w, "\\{}", c)?;
+                } else if let Some(x) = whitespace.filter(|_| format.escape_whitespace) {
+                    write!(w, "\\{}", x)?;
+                } else if c == '\0' || (format.escape_control && c.is_ascii_control()) {
+                    for b in &val[i..i + len] {
+                        write!(w, r"\x{:02x}", b)?;
+                    }
+                } else {
+                    write!(w, "{}", c)?;
+                }
+                i += len;
+            }
+            None => {
+                write!(w, r"\x{:02x}", val[i])?;
+                i += 1;
+            }
+        }
+    }
+    write!(w, r#"""#)?;
+    Ok(())
+}
+
+fn write_indent<W: Write>(w: &mut W, indent: u64, format: &TextFormat) -> Result<()> {
+    let (c, width) = match format.indent {
+        IndentStyle::Tabs => (b'\t', 1),
+        IndentStyle::Spaces(n) => (b' ', u64::from(n)),
+    };
+    w.write_all(&vec![c; (indent * width) as usize])?;
+    Ok(())
+}
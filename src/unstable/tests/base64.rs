@@ -0,0 +1,34 @@
+use crate::base64::{decode, encode};
+
+#[test]
+fn padding() {
+    /* The examples from RFC 4648 */
+    let cases: &[(&[u8], &str)] = &[
+        (b"", ""),
+        (b"f", "Zg=="),
+        (b"fo", "Zm8="),
+        (b"foo", "Zm9v"),
+        (b"foob", "Zm9vYg=="),
+        (b"fooba", "Zm9vYmE="),
+        (b"foobar", "Zm9vYmFy"),
+    ];
+    for (bytes, text) in cases {
+        assert_eq!(encode(bytes), *text);
+        assert_eq!(decode(text).unwrap(), *bytes);
+    }
+
+    let all: Vec<u8> = (0..=255).collect();
+    assert_eq!(decode(&encode(&all)).unwrap(), all);
+}
+
+#[test]
+fn invalid() {
+    assert!(decode("Zm9").is_err());
+    assert!(decode("Zm9v YmFy").is_err());
+    assert!(decode("Zg==Zm9v").is_err());
+    assert!(decode("Z===").is_err());
+    assert_eq!(
+        decode("Zm9*").unwrap_err().to_string(),
+        "Invalid base64 character '*'"
+    );
+}
@@ -0,0 +1,91 @@
+use std::io::{self, Cursor, Read, Write};
+
+use crate::checksum::Checksum;
+use crate::read::read_file_with_checksum;
+use crate::write::write_file_with_checksum;
+
+use super::bigtest;
+
+#[test]
+fn known_values() {
+    assert_eq!(Checksum::Crc32.compute(b"123456789"), 0xcbf4_3926);
+    assert_eq!(Checksum::Adler32.compute(b"Wikipedia"), 0x11e6_0398);
+    assert_eq!(Checksum::Adler32.compute(b""), 1);
+
+    /* Long enough for the Adler-32 sums to be reduced along the way */
+    let long = vec![0xff; 100_000];
+    let mut zlib = Vec::new();
+    {
+        let mut w = flate2::write::ZlibEncoder::new(&mut zlib, flate2::Compression::default());
+        w.write_all(&long).unwrap();
+        let _: &mut Vec<u8> = w.finish().unwrap();
+    }
+    /* zlib ends with the big endian Adler-32 of the uncompressed data */
+    let trailer = &zlib[zlib.len() - 4..];
+    let expected = u32::from_be_bytes([trailer[0], trailer[1], trailer[2], trailer[3]]);
+    assert_eq!(Checksum::Adler32.compute(&long), expected);
+}
+
+#[test]
+fn checksum_loop() {
+    for checksum in &[Checksum::Crc32, Checksum::Adler32] {
+        let original = bigtest();
+        let mut buf = Vec::new();
+        write_file_with_checksum(&mut buf, &original, *checksum).unwrap();
+
+        let read = read_file_with_checksum(&mut Cursor::new(buf), *checksum).unwrap();
+        assert_eq!(read, original);
+    }
+}
+
+#[test]
+fn checksum_mismatch() {
+    let mut buf = Vec::new();
+    write_file_with_checksum(&mut buf, &bigtest(), Checksum::Crc32).unwrap();
+    let stored = Checksum::Crc32.compute(&buf[..buf.len() - 4]);
+
+    /* Corrupt a byte in the middle of the file */
+    let middle = buf.len() / 2;
+    buf[middle] ^= 0xff;
+    let computed = Checksum::Crc32.compute(&buf[..buf.len() - 4]);
+
+    let err = read_file_with_checksum(&mut Cursor::new(buf), Checksum::Crc32).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "checksum mismatch: expected {:08x} got {:08x}",
+            stored, computed
+        )
+    );
+}
+
+#[test]
+fn checksum_too_short() {
+    assert!(
+        read_file_with_checksum(&mut Cursor::new(vec![0x00, 0x01]), Checksum::Adler32).is_err()
+    );
+}
+
+/// A reader that gives one byte at a time, so that the checksum is split
+/// over several reads
+struct OneByte<'a>(&'a [u8]);
+impl<'a> Read for OneByte<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = buf.len().min(1);
+        self.0.read(&mut buf[..len])
+    }
+}
+
+#[test]
+fn checksum_split_reads() {
+    let original = bigtest();
+    let mut buf = Vec::new();
+    write_file_with_checksum(&mut buf, &original, Checksum::Adler32).unwrap();
+
+    let read = read_file_with_checksum(&mut OneByte(&buf), Checksum::Adler32).unwrap();
+    assert_eq!(read, original);
+
+    let len = buf.len();
+    buf[len - 1] ^= 1;
+    assert!(read_file_with_checksum(&mut OneByte(&buf), Checksum::Adler32).is_err());
+}
@@ -0,0 +1,798 @@
+/// Tests of the helper methods on the NBT type
+use std::io::Cursor;
+
+use crate::data::{walk_mut, NbtCompound, NBT};
+use crate::path::{NbtPath, PathFilter};
+use crate::visit::{self, Visitor};
+
+use super::bigtest;
+use super::tests_data;
+
+#[test]
+fn clamp_scalars() {
+    let mut nbt = bigtest();
+    let count = nbt
+        .root
+        .clamp_numeric(&PathFilter::new("Level/*"), -10, 10)
+        .unwrap();
+    /* longTest, shortTest, intTest and byteTest */
+    assert_eq!(count, 4);
+
+    let level = nbt.root.get("Level").unwrap();
+    assert_eq!(level.get("longTest"), Some(&NBT::Long(10)));
+    assert_eq!(level.get("shortTest"), Some(&NBT::Short(10)));
+    assert_eq!(level.get("intTest"), Some(&NBT::Int(10)));
+    assert_eq!(level.get("byteTest"), Some(&NBT::Byte(10)));
+}
+
+#[test]
+fn clamp_only_matching_paths() {
+    let mut nbt = bigtest();
+    let count = nbt
+        .root
+        .clamp_numeric(&PathFilter::new("Level/listTest (long)/*"), 0, 12)
+        .unwrap();
+    assert_eq!(count, 3);
+
+    let level = nbt.root.get("Level").unwrap();
+    let expected: Vec<NBT> = vec![11, 12, 12, 12, 12]
+        .into_iter()
+        .map(NBT::Long)
+        .collect();
+    assert_eq!(level.get("listTest (long)"), Some(&NBT::list(expected)));
+    assert_eq!(level.get("intTest"), Some(&NBT::Int(2147483647)));
+}
+
+#[test]
+fn clamp_array_elements() {
+    let mut nbt = bigtest();
+    let count = nbt
+        .root
+        .clamp_numeric(&PathFilter::new("Level/*/1"), 0, 50)
+        .unwrap();
+    /* The second element of the byte array is 62 */
+    assert_eq!(count, 1);
+
+    let level = nbt.root.get("Level").unwrap();
+    match level.get("byteArrayTest (the first 1000 values of (n*n*255+n*7)%100, starting with n=0 (0, 62, 34, 16, 8, ...))") {
+        Some(NBT::ByteArray(x)) => assert_eq!(&x[..3], &[0, 50, 34]),
+        x => panic!("Expected a ByteArray, got {:?}", x),
+    }
+}
+
+#[test]
+fn clamp_type_bounds() {
+    /* The range lies entirely outside of what a Byte can represent, so it is
+     * limited to the Byte's own maximum */
+    let mut nbt = NBT::Compound(vec![(b"a".to_vec(), NBT::Byte(5))]);
+    let count = nbt
+        .clamp_numeric(&PathFilter::new("a"), 1000, 2000)
+        .unwrap();
+    assert_eq!(count, 1);
+    assert_eq!(nbt.get("a"), Some(&NBT::Byte(127)));
+}
+
+#[test]
+fn clamp_invalid_range() {
+    let mut nbt = bigtest();
+    assert!(nbt
+        .root
+        .clamp_numeric(&PathFilter::new("*"), 10, -10)
+        .is_err());
+}
+
+#[test]
+fn compound_builder() {
+    let mut cursor = Cursor::new(&tests_data::HELLO_WORLD[..]);
+    let hello_world = crate::read::read_file(&mut cursor).unwrap();
+    let built = NBT::compound()
+        .child("hello world", |c| c.string("name", "Bananrama"))
+        .build();
+    assert_eq!(built, hello_world.root);
+
+    let built: NBT = NbtCompound::new()
+        .byte("b", 1)
+        .int_array("a", vec![1, 2])
+        .list(b"l", vec![NBT::Short(3)])
+        .double("d", 0.5)
+        .into();
+    assert_eq!(
+        built,
+        NBT::Compound(vec![
+            (b"b".to_vec(), NBT::Byte(1)),
+            (b"a".to_vec(), NBT::IntArray(vec![1, 2])),
+            (b"l".to_vec(), NBT::list(vec![NBT::Short(3)])),
+            (b"d".to_vec(), NBT::Double(0.5)),
+        ])
+    );
+
+    /* A key added again is replaced where it is */
+    let built = NBT::compound().int("x", 1).int("y", 2).long("x", 3).build();
+    assert_eq!(
+        built,
+        NBT::Compound(vec![
+            (b"x".to_vec(), NBT::Long(3)),
+            (b"y".to_vec(), NBT::Int(2)),
+        ])
+    );
+
+    /* Strings are encoded as modified UTF-8 */
+    let built = NBT::compound().string("s", "\0\u{1f600}").build();
+    assert_eq!(
+        built.get("s"),
+        Some(&NBT::String(vec![
+            0xc0, 0x80, 0xed, 0xa0, 0xbd, 0xed, 0xb8, 0x80
+        ]))
+    );
+}
+
+#[test]
+fn bitwise_eq_nan() {
+    let a = NBT::list(vec![NBT::Float(f32::NAN), NBT::Double(f64::NAN)]);
+    assert!(a != a.clone());
+    assert!(a.bitwise_eq(&a.clone()));
+}
+
+#[test]
+fn bitwise_eq_signed_zero() {
+    assert_eq!(NBT::Double(0.0), NBT::Double(-0.0));
+    assert!(!NBT::Double(0.0).bitwise_eq(&NBT::Double(-0.0)));
+}
+
+#[test]
+fn bitwise_eq_compound() {
+    let nbt = bigtest();
+    assert!(nbt.bitwise_eq(&bigtest()));
+
+    let mut other = bigtest();
+    let _: usize = other
+        .root
+        .clamp_numeric(&PathFilter::new("Level/intTest"), 0, 0)
+        .unwrap();
+    assert!(!nbt.bitwise_eq(&other));
+}
+
+#[test]
+fn rename_keys_exact() {
+    let mut nbt = bigtest();
+    let renamed = nbt
+        .root
+        .rename_keys(b"name", b"title", false, false)
+        .unwrap();
+    /* ham, egg and the two compounds in the compound list */
+    assert_eq!(renamed.count, 4);
+    assert!(renamed.skipped.is_empty());
+
+    let ham = nbt
+        .root
+        .get("Level")
+        .unwrap()
+        .get("nested compound test")
+        .unwrap()
+        .get("ham")
+        .unwrap();
+    assert_eq!(ham.get("title"), Some(&NBT::String(b"Hampus".to_vec())));
+    assert_eq!(ham.get("name"), None);
+}
+
+#[test]
+fn rename_keys_prefix() {
+    let mut nbt = bigtest();
+    let renamed = nbt
+        .root
+        .rename_keys(b"list", b"array", true, false)
+        .unwrap();
+    assert_eq!(renamed.count, 2);
+
+    let level = nbt.root.get("Level").unwrap();
+    assert!(level.get("arrayTest (long)").is_some());
+    assert!(level.get("arrayTest (compound)").is_some());
+}
+
+#[test]
+fn rename_keys_duplicate() {
+    let mut nbt = NBT::Compound(vec![
+        (b"a".to_vec(), NBT::Byte(1)),
+        (b"b".to_vec(), NBT::Byte(2)),
+    ]);
+
+    assert!(nbt.clone().rename_keys(b"a", b"b", false, true).is_err());
+
+    let renamed = nbt.rename_keys(b"a", b"b", false, false).unwrap();
+    assert_eq!(renamed.count, 0);
+    assert_eq!(renamed.skipped.len(), 1);
+    assert_eq!(renamed.skipped[0].to_string(), "a");
+    assert_eq!(nbt.get("a"), Some(&NBT::Byte(1)));
+}
+
+#[test]
+fn find_strings() {
+    let nbt = bigtest();
+    let found = nbt.root.find_strings(|x| x.starts_with(b"Compound tag"));
+    let paths: Vec<String> = found.iter().map(|(p, _)| p.to_string()).collect();
+    assert_eq!(
+        paths,
+        &[
+            "Level/listTest (compound)/0/name",
+            "Level/listTest (compound)/1/name"
+        ]
+    );
+    assert_eq!(found[1].1, b"Compound tag #1");
+}
+
+#[test]
+fn find_strings_not_utf8() {
+    let nbt = NBT::list(vec![
+        NBT::String(vec![0xff, 0xfe]),
+        NBT::String(b"abc".to_vec()),
+    ]);
+    let found = nbt.find_strings(|x| x.contains(&0xff));
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].0.to_string(), "0");
+}
+
+#[test]
+fn find_value() {
+    let nbt = bigtest();
+    let paths: Vec<String> = nbt
+        .root
+        .find_value(|x| *x == NBT::Long(1_264_099_775_885))
+        .iter()
+        .map(|x| x.to_string())
+        .collect();
+    assert_eq!(
+        paths,
+        &[
+            "Level/listTest (compound)/0/created-on",
+            "Level/listTest (compound)/1/created-on"
+        ]
+    );
+
+    let found = nbt.root.find_value(|x| *x == NBT::Int(2_147_483_647));
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].to_string(), "Level/intTest");
+
+    assert!(nbt.root.find_value(|x| *x == NBT::Int(12345)).is_empty());
+}
+
+#[test]
+fn size_breakdown() {
+    let nbt = bigtest();
+    let sizes = nbt.root.size_breakdown().unwrap();
+
+    /* The root has no header, and its direct children make up the whole file */
+    assert_eq!(sizes[0].0.to_string(), "");
+    let total: u64 = sizes
+        .iter()
+        .filter(|(path, _, _)| path.segments().len() == 1)
+        .map(|(_, _, size)| size)
+        .sum();
+    assert_eq!(total, tests_data::BIGTEST_UNCOMPRESSED.len() as u64);
+
+    let size = |path: &str| {
+        sizes
+            .iter()
+            .find(|(x, _, _)| x.to_string() == path)
+            .map(|(_, _, size)| *size)
+            .unwrap()
+    };
+    /* The type, the name and the value */
+    assert_eq!(size("Level/intTest"), 1 + 2 + 7 + 4);
+    assert_eq!(size("Level/nested compound test/egg/value"), 1 + 2 + 5 + 4);
+    /* The header, the 4 byte length and the 1000 bytes */
+    assert_eq!(
+        size("Level/byteArrayTest (the first 1000 values of (n*n*255+n*7)%100, starting with n=0 (0, 62, 34, 16, 8, ...))"),
+        1108
+    );
+    assert_eq!(size("Level"), 1544);
+}
+
+#[test]
+fn get_path() {
+    let nbt = bigtest();
+    let level = nbt.root.get("Level").unwrap();
+
+    let value = level.get_path("nested compound test/egg/value").unwrap();
+    assert_eq!(&*value, &NBT::Float(0.5));
+    let name = level.get_path("listTest (compound)/1/name").unwrap();
+    assert_eq!(&*name, &NBT::String(b"Compound tag #1".to_vec()));
+    assert_eq!(&*level.get_path("").unwrap(), level);
+
+    /* Array elements are given as tags of the element's type */
+    let key = "byteArrayTest (the first 1000 values of (n*n*255+n*7)%100, starting with n=0 (0, 62, 34, 16, 8, ...))";
+    assert_eq!(
+        level.get_path(format!("{}/1", key)).unwrap().into_owned(),
+        NBT::Byte(62)
+    );
+    let arrays = NBT::Compound(vec![
+        (b"i".to_vec(), NBT::IntArray(vec![1, 2])),
+        (b"l".to_vec(), NBT::LongArray(vec![3])),
+    ]);
+    assert_eq!(&*arrays.get_path("i/1").unwrap(), &NBT::Int(2));
+    assert_eq!(&*arrays.get_path("l/0").unwrap(), &NBT::Long(3));
+
+    /* Type mismatches and indices out of range */
+    assert!(level.get_path("nope").is_none());
+    assert!(level.get_path("listTest (long)/5").is_none());
+    assert!(level.get_path("listTest (long)/01").is_none());
+    assert!(level.get_path("listTest (long)/x").is_none());
+    assert!(level.get_path("intTest/0").is_none());
+    assert!(arrays.get_path("i/2").is_none());
+    assert!(arrays.get_path("i/0/0").is_none());
+    assert!(arrays.get_path("i/-1").is_none());
+
+    assert_eq!(
+        &*level
+            .get_path_with("nested compound test.ham.name", '.')
+            .unwrap(),
+        &NBT::String(b"Hampus".to_vec())
+    );
+}
+
+#[test]
+fn get_path_mut() {
+    let mut nbt = bigtest();
+    let egg = nbt
+        .root
+        .get_path_mut(&[b"Level", b"nested compound test", b"egg"])
+        .unwrap();
+    assert_eq!(egg.get("value"), Some(&NBT::Float(0.5)));
+
+    let name = nbt
+        .root
+        .get_path_mut(&[b"Level", b"listTest (compound)", b"1", b"name"])
+        .unwrap();
+    assert_eq!(name, &NBT::String(b"Compound tag #1".to_vec()));
+
+    assert!(nbt.root.get_path_mut(&[b"Level", b"nope"]).is_err());
+    assert!(nbt
+        .root
+        .get_path_mut(&[b"Level", b"listTest (long)", b"5"])
+        .is_err());
+    assert!(nbt
+        .root
+        .get_path_mut(&[b"Level", b"listTest (long)", b"01"])
+        .is_err());
+    assert!(nbt
+        .root
+        .get_path_mut(&[b"Level", b"intTest", b"0"])
+        .is_err());
+}
+
+#[test]
+fn remove() {
+    let mut nbt = bigtest();
+    let level = nbt.root.get_path_mut(&[b"Level"]).unwrap();
+    assert_eq!(level.remove(b"intTest").unwrap(), NBT::Int(2_147_483_647));
+    assert_eq!(level.get("intTest"), None);
+    assert!(level.remove(b"intTest").is_err());
+
+    /* Later elements move down to fill the gap */
+    let list = level.get_path_mut(&[b"listTest (long)"]).unwrap();
+    assert_eq!(list.remove(b"1").unwrap(), NBT::Long(12));
+    assert_eq!(
+        list,
+        &NBT::list(vec![
+            NBT::Long(11),
+            NBT::Long(13),
+            NBT::Long(14),
+            NBT::Long(15)
+        ])
+    );
+    assert!(list.remove(b"4").is_err());
+
+    let mut array = NBT::IntArray(vec![1, 2, 3]);
+    assert_eq!(array.remove(b"0").unwrap(), NBT::Int(1));
+    assert_eq!(array, NBT::IntArray(vec![2, 3]));
+
+    assert!(NBT::Int(1).remove(b"0").is_err());
+}
+
+#[test]
+fn set_from_str() {
+    let mut nbt = bigtest();
+    let level = nbt.root.get_path_mut(&[b"Level"]).unwrap();
+
+    let byte = level.get_path_mut(&[b"byteTest"]).unwrap();
+    byte.set_from_str("-5").unwrap();
+    assert_eq!(byte, &NBT::Byte(-5));
+    /* The value must fit in the tag's type, which is left unchanged */
+    assert!(byte.set_from_str("300").is_err());
+    assert!(byte.set_from_str("1.5").is_err());
+    assert_eq!(byte, &NBT::Byte(-5));
+
+    let float = level.get_path_mut(&[b"floatTest"]).unwrap();
+    float.set_from_str("1.5").unwrap();
+    assert_eq!(float, &NBT::Float(1.5));
+
+    let string = level.get_path_mut(&[b"stringTest"]).unwrap();
+    string.set_from_str("a=b").unwrap();
+    assert_eq!(string, &NBT::String(b"a=b".to_vec()));
+    /* Characters outside of the BMP are encoded as a surrogate pair */
+    string.set_from_str("\u{1f600}").unwrap();
+    assert_eq!(
+        string,
+        &NBT::String(vec![0xed, 0xa0, 0xbd, 0xed, 0xb8, 0x80])
+    );
+
+    let element = level.get_path_mut(&[b"listTest (long)", b"0"]).unwrap();
+    element.set_from_str("-3").unwrap();
+    assert_eq!(element, &NBT::Long(-3));
+
+    /* Only numbers and Strings can be set */
+    assert!(level.set_from_str("1").is_err());
+    let list = level.get_path_mut(&[b"listTest (long)"]).unwrap();
+    assert!(list.set_from_str("1").is_err());
+}
+
+#[test]
+fn coerce_lists() {
+    let mut nbt = NBT::Compound(vec![
+        (
+            b"ints".to_vec(),
+            NBT::list(vec![NBT::Int(1), NBT::Byte(2), NBT::Long(3)]),
+        ),
+        (
+            b"doubles".to_vec(),
+            NBT::list(vec![NBT::Double(0.5), NBT::Float(1.5)]),
+        ),
+        (b"empty".to_vec(), NBT::list(Vec::new())),
+    ]);
+    assert_eq!(nbt.coerce_lists(false).unwrap(), 3);
+    assert_eq!(
+        nbt.get("ints"),
+        Some(&NBT::list(vec![NBT::Int(1), NBT::Int(2), NBT::Int(3)]))
+    );
+    assert_eq!(
+        nbt.get("doubles"),
+        Some(&NBT::list(vec![NBT::Double(0.5), NBT::Double(1.5)]))
+    );
+
+    /* Already homogeneous lists are left alone */
+    assert_eq!(nbt.coerce_lists(false).unwrap(), 0);
+}
+
+#[test]
+fn coerce_lists_lossy() {
+    let original = NBT::list(vec![NBT::Byte(1), NBT::Int(300), NBT::Int(-300)]);
+
+    let mut nbt = original.clone();
+    let err = nbt.coerce_lists(false).unwrap_err();
+    assert!(err.to_string().contains("Int(300)"), "{}", err);
+    /* The list is left as it was */
+    assert_eq!(nbt, original);
+
+    let mut nbt = original;
+    assert_eq!(nbt.coerce_lists(true).unwrap(), 2);
+    assert_eq!(
+        nbt,
+        NBT::list(vec![NBT::Byte(1), NBT::Byte(127), NBT::Byte(-128)])
+    );
+
+    let mut nbt = NBT::list(vec![NBT::Float(0.0), NBT::Double(0.1)]);
+    assert!(nbt.coerce_lists(false).is_err());
+    assert_eq!(nbt.coerce_lists(true).unwrap(), 1);
+    assert_eq!(nbt, NBT::list(vec![NBT::Float(0.0), NBT::Float(0.1)]));
+
+    /* Exactly representable Doubles don't need force */
+    let mut nbt = NBT::list(vec![NBT::Float(0.0), NBT::Double(0.5)]);
+    assert_eq!(nbt.coerce_lists(false).unwrap(), 1);
+}
+
+#[test]
+fn coerce_lists_incompatible() {
+    let mut nbt = NBT::list(vec![NBT::Int(1), NBT::String(b"2".to_vec())]);
+    assert!(nbt.coerce_lists(true).is_err());
+
+    let mut nbt = NBT::list(vec![NBT::Int(1), NBT::Float(2.0)]);
+    assert!(nbt.coerce_lists(true).is_err());
+}
+
+/// Nothing is converted if any element can't be, even in another list
+#[test]
+fn coerce_lists_all_or_nothing() {
+    let original = NBT::Compound(vec![
+        (b"a".to_vec(), NBT::list(vec![NBT::Int(1), NBT::Byte(2)])),
+        (
+            b"b".to_vec(),
+            NBT::list(vec![NBT::Byte(1), NBT::Short(2), NBT::Int(300)]),
+        ),
+    ]);
+    let mut nbt = original.clone();
+    let err = nbt.coerce_lists(false).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Unable to convert b/2 to Byte without changing its value: Int(300)"
+    );
+    assert_eq!(nbt, original);
+}
+
+#[test]
+fn count_ids() {
+    let nbt = NBT::Compound(vec![
+        (
+            b"Inventory".to_vec(),
+            NBT::list(vec![
+                NBT::Compound(vec![
+                    (b"id".to_vec(), NBT::String(b"minecraft:stone".to_vec())),
+                    (b"Count".to_vec(), NBT::Byte(64)),
+                ]),
+                NBT::Compound(vec![
+                    (b"id".to_vec(), NBT::String(b"minecraft:dirt".to_vec())),
+                    (b"Count".to_vec(), NBT::Byte(1)),
+                ]),
+                NBT::Compound(vec![(
+                    b"id".to_vec(),
+                    NBT::String(b"minecraft:stone".to_vec()),
+                )]),
+            ]),
+        ),
+        (
+            b"Block".to_vec(),
+            NBT::Compound(vec![
+                (b"Name".to_vec(), NBT::String(b"mod:machine".to_vec())),
+                (b"id".to_vec(), NBT::Int(3)),
+                (b"type".to_vec(), NBT::String(b"minecraft:dirt".to_vec())),
+            ]),
+        ),
+    ]);
+
+    let ids: Vec<(String, usize)> = nbt.count_ids(&[b"id", b"Name"]).into_iter().collect();
+    assert_eq!(
+        ids,
+        vec![
+            ("minecraft:dirt".to_string(), 1),
+            ("minecraft:stone".to_string(), 2),
+            ("mod:machine".to_string(), 1),
+        ]
+    );
+
+    /* Extra keys are counted together with the rest */
+    let ids = nbt.count_ids(&[b"id", b"type"]);
+    assert_eq!(ids.get("minecraft:dirt"), Some(&2));
+    assert_eq!(ids.get("mod:machine"), None);
+}
+
+fn path_tree() -> NBT {
+    NBT::Compound(vec![(
+        b"Level".to_vec(),
+        NBT::Compound(vec![
+            (b"name".to_vec(), NBT::String(b"world".to_vec())),
+            (
+                b"items".to_vec(),
+                NBT::list(vec![
+                    NBT::Compound(vec![(b"id".to_vec(), NBT::Int(1))]),
+                    NBT::Compound(vec![(b"id".to_vec(), NBT::Int(2))]),
+                ]),
+            ),
+        ]),
+    )])
+}
+
+#[test]
+fn extract_path() {
+    let nbt = path_tree();
+    assert_eq!(
+        nbt.extract_path(&[b"Level", b"items", b"1", b"id"]),
+        Some(NBT::Int(2))
+    );
+    assert_eq!(nbt.extract_path(&[]), Some(nbt.clone()));
+    assert_eq!(nbt.extract_path(&[b"Level", b"items", b"2"]), None);
+    assert_eq!(nbt.extract_path(&[b"Level", b"missing"]), None);
+    assert_eq!(nbt.extract_path(&[b"Level", b"name", b"0"]), None);
+}
+
+#[test]
+fn graft_path() {
+    let mut nbt = path_tree();
+
+    /* Replacing a key, and creating the compounds on the way to a new one */
+    nbt.graft_path(&[b"Level", b"name"], NBT::String(b"copy".to_vec()))
+        .unwrap();
+    nbt.graft_path(&[b"Level", b"new", b"deep", b"id"], NBT::Int(3))
+        .unwrap();
+    assert_eq!(
+        nbt.extract_path(&[b"Level", b"name"]),
+        Some(NBT::String(b"copy".to_vec()))
+    );
+    assert_eq!(
+        nbt.extract_path(&[b"Level", b"new"]),
+        Some(NBT::Compound(vec![(
+            b"deep".to_vec(),
+            NBT::Compound(vec![(b"id".to_vec(), NBT::Int(3))]),
+        )]))
+    );
+
+    /* Replacing an element of a list, and adding one to the end */
+    let item = nbt.extract_path(&[b"Level", b"items", b"0"]).unwrap();
+    nbt.graft_path(&[b"Level", b"items", b"1"], item.clone())
+        .unwrap();
+    nbt.graft_path(&[b"Level", b"items", b"2"], item.clone())
+        .unwrap();
+    assert_eq!(
+        nbt.extract_path(&[b"Level", b"items"]),
+        Some(NBT::list(vec![item.clone(), item.clone(), item]))
+    );
+}
+
+/// The only element of a list can be replaced by a tag of any type, which
+/// changes the type of the list
+#[test]
+fn graft_path_only_element() {
+    let mut nbt = NBT::Compound(vec![(b"l".to_vec(), NBT::list(vec![NBT::Int(1)]))]);
+    nbt.graft_path(&[b"l", b"0"], NBT::String(b"a".to_vec()))
+        .unwrap();
+    assert_eq!(
+        nbt.get("l"),
+        Some(&NBT::list(vec![NBT::String(b"a".to_vec())]))
+    );
+
+    /* Once there are two, they have to match */
+    nbt.graft_path(&[b"l", b"1"], NBT::String(b"b".to_vec()))
+        .unwrap();
+    assert!(nbt.graft_path(&[b"l", b"0"], NBT::Int(1)).is_err());
+}
+
+#[test]
+fn graft_path_invalid() {
+    let original = path_tree();
+    let mut nbt = original.clone();
+
+    /* Past the end of a list, and the elements of lists aren't created */
+    assert!(nbt
+        .graft_path(&[b"Level", b"items", b"5"], NBT::Compound(Vec::new()))
+        .is_err());
+    assert!(nbt
+        .graft_path(&[b"Level", b"items", b"5", b"id"], NBT::Int(1))
+        .is_err());
+    /* A list can't get an element of another type */
+    assert!(nbt
+        .graft_path(&[b"Level", b"items", b"0"], NBT::Int(1))
+        .is_err());
+    /* Only compounds and lists contain tags */
+    assert!(nbt
+        .graft_path(&[b"Level", b"name", b"x"], NBT::Int(1))
+        .is_err());
+    assert!(nbt.graft_path(&[], NBT::Int(1)).is_err());
+    assert_eq!(nbt, original);
+}
+
+#[test]
+fn merge_compounds() {
+    let mut base = NBT::compound()
+        .child("Data", |c| {
+            c.byte("Difficulty", 2)
+                .child("Player", |c| c.int("Score", 10).string("Name", "a"))
+                .list(b"Pos", vec![NBT::Double(1.0), NBT::Double(2.0)])
+        })
+        .build();
+    let overlay = NBT::compound()
+        .child("Data", |c| {
+            c.byte("Difficulty", 3)
+                .child("Player", |c| c.int("XpLevel", 5).int("Score", 20))
+                .list(b"Pos", vec![NBT::Double(3.0)])
+                .string("LevelName", "b")
+        })
+        .build();
+
+    assert_eq!(base.merge(&overlay).unwrap(), 5);
+    /* Scalars and lists are replaced where they are, nested compounds are
+     * merged, and new keys are added at the end */
+    let expected = NBT::compound()
+        .child("Data", |c| {
+            c.byte("Difficulty", 3)
+                .child("Player", |c| {
+                    c.int("Score", 20).string("Name", "a").int("XpLevel", 5)
+                })
+                .list(b"Pos", vec![NBT::Double(3.0)])
+                .string("LevelName", "b")
+        })
+        .build();
+    assert_eq!(base, expected);
+}
+
+#[test]
+fn merge_replaces_other_types() {
+    /* A compound in the overlay replaces a tag of another type, and the
+     * other way around */
+    let mut base = NBT::compound()
+        .int("a", 1)
+        .child("b", |c| c.int("x", 1))
+        .build();
+    let overlay = NBT::compound()
+        .child("a", |c| c.int("x", 2))
+        .int("b", 2)
+        .build();
+    assert_eq!(base.merge(&overlay).unwrap(), 2);
+    assert_eq!(base, overlay);
+}
+
+#[test]
+fn merge_not_compound() {
+    let mut base = NBT::Int(1);
+    let err = base.merge(&NBT::compound().build()).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Unable to merge Compound into Int, only compounds can be merged"
+    );
+    assert!(NBT::compound()
+        .build()
+        .merge(&NBT::list(Vec::new()))
+        .is_err());
+    assert_eq!(base, NBT::Int(1));
+}
+
+#[test]
+fn walk_mut_visits_every_tag() {
+    let mut nbt = bigtest();
+    let mut types = Vec::new();
+    walk_mut(&mut nbt.root, &mut |tag: &mut NBT| {
+        types.push(tag.type_byte())
+    });
+
+    /* The same tags in the same order as the read-only visitor */
+    struct TypeLister(Vec<u8>);
+    impl<'a> Visitor<'a> for TypeLister {
+        fn visit_tag(&mut self, _path: &NbtPath, tag: &'a NBT) -> crate::Result<()> {
+            self.0.push(tag.type_byte());
+            Ok(())
+        }
+    }
+    let mut lister = TypeLister(Vec::new());
+    visit::visit(&nbt.root, &mut lister).unwrap();
+    assert_eq!(types, lister.0);
+
+    /* The root of the file, the root compound and the scalars in Level */
+    assert_eq!(&types[..3], &[10, 10, 4]);
+    /* Every type in bigtest is there */
+    for x in &[1, 2, 3, 4, 5, 6, 7, 8, 9, 10] {
+        assert!(types.contains(x), "no tag of type {}", x);
+    }
+}
+
+#[test]
+fn walk_mut_changes_ints() {
+    let mut nbt = NBT::compound()
+        .child("Level", |c| {
+            c.int("xPos", 3)
+                .int("zPos", -2)
+                .int("Other", 7)
+                .list(b"Entities", vec![NBT::compound().int("xPos", 1).build()])
+        })
+        .build();
+
+    /* Move the chunk by 32 along both axes, as after relocating a region */
+    walk_mut(&mut nbt, &mut |tag: &mut NBT| {
+        if let NBT::Compound(map) = tag {
+            for (key, val) in map.iter_mut() {
+                if let (b"xPos" | b"zPos", NBT::Int(x)) = (key.as_slice(), val) {
+                    *x += 32;
+                }
+            }
+        }
+    });
+
+    let expected = NBT::compound()
+        .child("Level", |c| {
+            c.int("xPos", 35)
+                .int("zPos", 30)
+                .int("Other", 7)
+                .list(b"Entities", vec![NBT::compound().int("xPos", 33).build()])
+        })
+        .build();
+    assert_eq!(nbt, expected);
+
+    /* Changes made to a container are seen by the walk of its contents */
+    let mut nbt = NBT::list(vec![NBT::Int(1)]);
+    let mut seen = Vec::new();
+    walk_mut(&mut nbt, &mut |tag: &mut NBT| {
+        if let NBT::List { items, .. } = tag {
+            items.push(NBT::Int(2));
+        } else if let NBT::Int(x) = tag {
+            *x *= 10;
+            seen.push(*x);
+        }
+    });
+    assert_eq!(seen, vec![10, 20]);
+    assert_eq!(nbt, NBT::list(vec![NBT::Int(10), NBT::Int(20)]));
+}
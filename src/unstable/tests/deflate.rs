@@ -0,0 +1,79 @@
+use std::io::{Cursor, Write};
+
+use crate::data::Compression;
+use crate::deflate::{Encoder, Strategy};
+use crate::read::read_file;
+use crate::write::{write_file, write_file_with_options, WriteOptions};
+
+use super::{assert_bitwise_eq, tests_data};
+
+const STRATEGIES: [Strategy; 4] = [
+    Strategy::Default,
+    Strategy::Filtered,
+    Strategy::Huffman,
+    Strategy::Rle,
+];
+
+/// Every strategy writes a file that reads back the same
+#[test]
+fn strategies_read_back() {
+    for data in [
+        &tests_data::BIGTEST_COMPRESSED[..],
+        &tests_data::PLAYER_FILE[..],
+    ] {
+        for compression in [Compression::Gzip, Compression::Zlib] {
+            let mut nbtfile = read_file(&mut Cursor::new(data)).unwrap();
+            nbtfile.compression = compression.clone();
+
+            for &strategy in &STRATEGIES {
+                let options = WriteOptions {
+                    strategy,
+                    ..Default::default()
+                };
+                let mut tmp = Vec::new();
+                write_file_with_options(&mut tmp, &nbtfile, &options).unwrap();
+
+                let read = read_file(&mut Cursor::new(&tmp)).unwrap();
+                assert_bitwise_eq(&read, &nbtfile);
+            }
+        }
+    }
+}
+
+/// The encoder uses the same compressor as flate2, so with the default
+/// strategy it writes the same bytes
+#[test]
+fn default_strategy_matches_flate2() {
+    let nbtfile = read_file(&mut Cursor::new(&tests_data::BIGTEST_COMPRESSED[..])).unwrap();
+    let mut uncompressed = Vec::new();
+    let mut plain = crate::data::NBTFile {
+        root: nbtfile.root.clone(),
+        compression: Compression::None,
+        compression_level: None,
+    };
+    write_file(&mut uncompressed, &plain).unwrap();
+
+    for compression in [Compression::Gzip, Compression::Zlib] {
+        for level in [1, 6, 9] {
+            plain.compression = compression.clone();
+            plain.compression_level = Some(level);
+            let mut expected = Vec::new();
+            write_file(&mut expected, &plain).unwrap();
+
+            let mut encoder = match compression {
+                Compression::Gzip => Encoder::gzip(Vec::new(), level, Strategy::Default).unwrap(),
+                _ => Encoder::zlib(Vec::new(), level, Strategy::Default),
+            };
+            encoder.write_all(&uncompressed).unwrap();
+            assert_eq!(encoder.finish().unwrap(), expected);
+        }
+    }
+}
+
+#[test]
+fn strategy_names() {
+    for &strategy in &STRATEGIES {
+        assert_eq!(Strategy::from_str(strategy.to_str()), Some(strategy));
+    }
+    assert_eq!(Strategy::from_str("fast"), None);
+}
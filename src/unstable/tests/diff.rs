@@ -0,0 +1,203 @@
+/// Tests of the diffing of NBT trees
+use crate::data::NBT;
+use crate::diff::{self, NbtChange};
+use crate::path::{NbtPath, Segment};
+
+fn compound(entries: Vec<(&str, NBT)>) -> NBT {
+    NBT::Compound(
+        entries
+            .into_iter()
+            .map(|(k, v)| (k.as_bytes().to_vec(), v))
+            .collect(),
+    )
+}
+
+#[test]
+fn identical() {
+    let a = compound(vec![("a", NBT::Int(1)), ("b", NBT::Float(f32::NAN))]);
+    assert!(diff::diff(&a, &a.clone()).is_empty());
+}
+
+#[test]
+fn lists_positional() {
+    let a = NBT::list(vec![NBT::Int(1), NBT::Int(2)]);
+    let b = NBT::list(vec![NBT::Int(1), NBT::Int(3), NBT::Int(4)]);
+    let changes = diff::diff(&a, &b);
+
+    let paths: Vec<String> = changes.iter().map(|x| x.path().to_string()).collect();
+    assert_eq!(paths, &["1", "2"]);
+    match &changes[1] {
+        NbtChange::Added { new, .. } => assert_eq!(new, &NBT::Int(4)),
+        x => panic!("Expected an addition, got {:?}", x),
+    }
+}
+
+/// An empty list that was declared with another type is a change, even though
+/// it has no elements to compare
+#[test]
+fn empty_list_type() {
+    let empty = |element_type| NBT::List {
+        element_type,
+        items: Vec::new(),
+    };
+    assert!(diff::diff(&empty(3), &empty(3)).is_empty());
+    assert_eq!(
+        diff::diff(&empty(0), &empty(10)),
+        &[NbtChange::Changed {
+            path: NbtPath::new(),
+            old: empty(0),
+            new: empty(10),
+        }]
+    );
+}
+
+fn entity(id: i32, health: f32) -> NBT {
+    compound(vec![("id", NBT::Int(id)), ("Health", NBT::Float(health))])
+}
+
+#[test]
+fn lists_by_key() {
+    let a = compound(vec![(
+        "Entities",
+        NBT::list(vec![entity(1, 20.0), entity(2, 20.0), entity(3, 20.0)]),
+    )]);
+    let b = compound(vec![(
+        "Entities",
+        NBT::list(vec![
+            entity(4, 20.0),
+            entity(1, 20.0),
+            entity(3, 10.0),
+            entity(5, 20.0),
+        ]),
+    )]);
+
+    /* By index every element looks changed */
+    assert_eq!(diff::diff(&a, &b).len(), 4);
+
+    let changes = diff::diff_by_key(&a, &b, b"id");
+    let paths: Vec<String> = changes.iter().map(|x| x.path().to_string()).collect();
+    assert_eq!(
+        paths,
+        &[
+            "Entities/1",
+            "Entities/2/Health",
+            "Entities/0",
+            "Entities/3"
+        ]
+    );
+    match &changes[0] {
+        NbtChange::Removed { old, .. } => assert_eq!(old, &entity(2, 20.0)),
+        x => panic!("Expected a removal, got {:?}", x),
+    }
+    match &changes[2] {
+        NbtChange::Added { new, .. } => assert_eq!(new, &entity(4, 20.0)),
+        x => panic!("Expected an addition, got {:?}", x),
+    }
+}
+
+#[test]
+fn lists_by_key_fallback() {
+    /* A duplicate id, or an element without one, means the list is
+     * compared by index */
+    let a = NBT::list(vec![entity(1, 20.0), entity(2, 20.0)]);
+    let duplicate = NBT::list(vec![entity(2, 20.0), entity(2, 20.0)]);
+    let missing = NBT::list(vec![compound(vec![]), entity(1, 20.0), entity(2, 20.0)]);
+
+    assert_eq!(
+        diff::diff_by_key(&a, &duplicate, b"id"),
+        diff::diff(&a, &duplicate)
+    );
+    assert_eq!(
+        diff::diff_by_key(&a, &missing, b"id"),
+        diff::diff(&a, &missing)
+    );
+    assert!(diff::diff_by_key(&a, &a.clone(), b"id").is_empty());
+}
+
+#[test]
+fn type_change() {
+    let a = compound(vec![("a", NBT::Int(1))]);
+    let b = compound(vec![("a", NBT::Long(1))]);
+    assert_eq!(
+        diff::diff(&a, &b),
+        &[NbtChange::Changed {
+            path: NbtPath::new().join(Segment::Key(b"a".to_vec())),
+            old: NBT::Int(1),
+            new: NBT::Long(1),
+        }]
+    );
+}
+
+/// Added and removed keys and changed values, at any depth, are each a line
+/// with the path of the tag
+#[test]
+fn changes() {
+    let level = |difficulty, extra: Option<(&'static str, NBT)>| {
+        let mut data = vec![
+            ("Difficulty", NBT::Byte(difficulty)),
+            (
+                "Player",
+                compound(vec![(
+                    "Pos",
+                    NBT::list(vec![NBT::Double(1.0), NBT::Double(64.0)]),
+                )]),
+            ),
+        ];
+        data.extend(extra);
+        compound(vec![("Data", compound(data))])
+    };
+    let a = level(2, Some(("LevelName", NBT::String(b"old".to_vec()))));
+    let b = level(3, Some(("WanderingTraderId", NBT::Int(7))));
+
+    let mut out = Vec::new();
+    diff::write_changes(&mut out, &diff::diff(&a, &b)).unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "~ Data/Difficulty: Byte 2 -> Byte 3\n- Data/LevelName: String \"old\"\n+ Data/WanderingTraderId: Int 7\n"
+    );
+
+    /* Changes inside of lists are given by index */
+    let mut moved = b.clone();
+    *moved
+        .get_path_mut(&[b"Data", b"Player", b"Pos", b"1"])
+        .unwrap() = NBT::Double(70.0);
+    let mut out = Vec::new();
+    diff::write_changes(&mut out, &diff::diff(&b, &moved)).unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "~ Data/Player/Pos/1: Double 64 -> Double 70\n"
+    );
+}
+
+#[test]
+fn unified() {
+    let a = compound(vec![
+        ("a", NBT::Int(1)),
+        ("b", compound(vec![("c", NBT::String(b"x".to_vec()))])),
+    ]);
+    let b = compound(vec![("a", NBT::Int(2))]);
+
+    let mut out = Vec::new();
+    diff::write_unified(&mut out, "old", "new", &diff::diff(&a, &b)).unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "--- old
++++ new
+@@ a @@
+-Int \"a\" 1
++Int \"a\" 2
+@@ b @@
+-Compound \"b\"
+-\tString \"c\" \"x\"
+-\tEnd
+"
+    );
+}
+
+#[test]
+fn unified_identical_is_empty() {
+    let a = compound(vec![("a", NBT::Int(1))]);
+    let mut out = Vec::new();
+    diff::write_unified(&mut out, "old", "new", &diff::diff(&a, &a)).unwrap();
+    assert!(out.is_empty());
+}
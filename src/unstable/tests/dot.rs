@@ -0,0 +1,56 @@
+/// Tests of drawing trees as DOT graphs
+use crate::data::NBT;
+use crate::dot::{MAX_ARRAY_ELEMENTS, MAX_CHILDREN};
+
+#[test]
+fn small_tree() {
+    let nbt = NBT::Compound(vec![(
+        b"hello world".to_vec(),
+        NBT::Compound(vec![
+            (b"name".to_vec(), NBT::String(b"Say \"hi\"".to_vec())),
+            (
+                b"list".to_vec(),
+                NBT::list(vec![NBT::Short(1), NBT::Short(2)]),
+            ),
+            (b"ints".to_vec(), NBT::IntArray(vec![1, 2, 3])),
+        ]),
+    )]);
+
+    assert_eq!(
+        nbt.to_dot(),
+        "digraph nbt {
+\tnode [shape=box];
+\tn0 [label=\"Compound (1)\"];
+\tn1 [label=\"Compound (3)\"];
+\tn2 [label=\"String \\\"Say \\\"hi\\\"\\\"\"];
+\tn1 -> n2 [label=\"name\"];
+\tn3 [label=\"List (2)\"];
+\tn4 [label=\"Short 1\"];
+\tn3 -> n4 [label=\"0\"];
+\tn5 [label=\"Short 2\"];
+\tn3 -> n5 [label=\"1\"];
+\tn1 -> n3 [label=\"list\"];
+\tn6 [label=\"IntArray [1, 2, 3]\"];
+\tn1 -> n6 [label=\"ints\"];
+\tn0 -> n1 [label=\"hello world\"];
+}
+"
+    );
+}
+
+/// Large containers and arrays are cut short
+#[test]
+fn truncated() {
+    let len = MAX_CHILDREN + 5;
+    let nbt = NBT::list((0..len).map(|x| NBT::Int(x as i32)).collect());
+    let dot = nbt.to_dot();
+    assert!(dot.contains(&format!("[label=\"List ({})\"]", len)));
+    assert!(dot.contains(&format!("[label=\"Int {}\"]", MAX_CHILDREN - 1)));
+    assert!(!dot.contains(&format!("[label=\"Int {}\"]", MAX_CHILDREN)));
+    assert!(dot.contains("[label=\"5 more\"]"));
+
+    let nbt = NBT::ByteArray(vec![7; MAX_ARRAY_ELEMENTS + 3]);
+    assert!(nbt
+        .to_dot()
+        .contains("[label=\"ByteArray [7, 7, 7, 7, 7, 7, 7, 7, ... 3 more]\"]"));
+}
@@ -0,0 +1,34 @@
+/// Tests of the formatting of sizes and counts
+use crate::humanize;
+
+#[test]
+fn bytes() {
+    assert_eq!(humanize::bytes(0), "0 B");
+    assert_eq!(humanize::bytes(1023), "1023 B");
+    assert_eq!(humanize::bytes(1024), "1.0 KiB");
+    assert_eq!(humanize::bytes(1536), "1.5 KiB");
+    assert_eq!(humanize::bytes(18_532_864), "17.7 MiB");
+    assert_eq!(humanize::bytes(1 << 30), "1.0 GiB");
+}
+
+/// Values just below the next unit are given in it, rather than as 1024.0
+#[test]
+fn bytes_rounding_up() {
+    assert_eq!(humanize::bytes(1024 * 1024 - 1), "1.0 MiB");
+    assert_eq!(humanize::bytes(1024 * 1023), "1023.0 KiB");
+}
+
+#[test]
+fn bytes_huge() {
+    assert_eq!(humanize::bytes(u64::MAX), "16.0 EiB");
+}
+
+#[test]
+fn count() {
+    assert_eq!(humanize::count(0), "0");
+    assert_eq!(humanize::count(999), "999");
+    assert_eq!(humanize::count(1000), "1,000");
+    assert_eq!(humanize::count(1024), "1,024");
+    assert_eq!(humanize::count(18_532_864), "18,532,864");
+    assert_eq!(humanize::count(u64::MAX), "18,446,744,073,709,551,615");
+}
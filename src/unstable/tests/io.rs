@@ -0,0 +1,159 @@
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+
+use tempdir::TempDir;
+
+use crate::io::{
+    write_file_atomically, CountingReader, CountingWriter, LimitingReader, LimitingWriter,
+};
+
+#[test]
+fn counting_reader_partial_reads() {
+    let data: Vec<u8> = (0..100).collect();
+    let mut reader = CountingReader::new(&data[..]);
+
+    let mut buf = [0; 7];
+    let mut total = 0;
+    loop {
+        let n = reader.read(&mut buf).unwrap();
+        if n == 0 {
+            break;
+        }
+        total += n;
+        assert_eq!(reader.count(), total as u64);
+    }
+    assert_eq!(reader.count(), 100);
+}
+
+#[test]
+fn counting_reader_bufread() {
+    let data: Vec<u8> = (0..100).collect();
+    let mut reader = CountingReader::new(BufReader::with_capacity(16, &data[..]));
+
+    assert_eq!(reader.fill_buf().unwrap().len(), 16);
+    assert_eq!(reader.count(), 0);
+    reader.consume(10);
+    assert_eq!(reader.count(), 10);
+
+    let mut rest = Vec::new();
+    let _: usize = reader.read_to_end(&mut rest).unwrap();
+    assert_eq!(rest, &data[10..]);
+    assert_eq!(reader.count(), 100);
+}
+
+#[test]
+fn limiting_reader_within_limit() {
+    let data = [1, 2, 3, 4];
+    let mut buf = Vec::new();
+    let _: usize = LimitingReader::new(&data[..], 4)
+        .read_to_end(&mut buf)
+        .unwrap();
+    assert_eq!(buf, data);
+}
+
+#[test]
+fn limiting_reader_exceeded() {
+    let data = [1, 2, 3, 4, 5];
+    let mut reader = LimitingReader::new(&data[..], 4);
+    let mut buf = Vec::new();
+    let err = reader.read_to_end(&mut buf).unwrap_err();
+    assert_eq!(err.to_string(), "exceeded the limit of 4 bytes");
+    assert_eq!(buf, &data[..4]);
+}
+
+#[test]
+fn counting_writer() {
+    let mut writer = CountingWriter::new(Vec::new());
+    writer.write_all(b"hello").unwrap();
+    writer.write_all(b" world").unwrap();
+    assert_eq!(writer.count(), 11);
+    assert_eq!(writer.into_inner(), b"hello world");
+}
+
+#[test]
+fn limiting_writer() {
+    let mut writer = LimitingWriter::new(Vec::new(), 8);
+    writer.write_all(b"hello").unwrap();
+    let err = writer.write_all(b" world").unwrap_err();
+    assert_eq!(err.to_string(), "exceeded the limit of 8 bytes");
+    assert_eq!(writer.into_inner(), b"hello wo");
+}
+
+/// The names of the files in the directory
+fn dir_entries(dir: &TempDir) -> Vec<String> {
+    let mut entries: Vec<String> = fs::read_dir(dir.path())
+        .unwrap()
+        .map(|x| x.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    entries.sort();
+    entries
+}
+
+/// A write that fails halfway leaves the old file, and one that succeeds
+/// leaves the whole new one, without any temporary files left over either way
+#[test]
+fn atomic_write() {
+    let dir = TempDir::new("nbted").unwrap();
+    let path = dir.path().join("level.dat");
+
+    write_file_atomically(&path, |f| Ok(f.write_all(b"old")?)).unwrap();
+    assert_eq!(fs::read(&path).unwrap(), b"old");
+
+    let err = write_file_atomically(&path, |f| {
+        f.write_all(b"partial")?;
+        f.flush()?;
+        bail!("interrupted")
+    })
+    .unwrap_err();
+    assert_eq!(err.to_string(), "interrupted");
+    assert_eq!(fs::read(&path).unwrap(), b"old");
+    assert_eq!(dir_entries(&dir), &["level.dat"]);
+
+    write_file_atomically(&path, |f| Ok(f.write_all(b"new")?)).unwrap();
+    assert_eq!(fs::read(&path).unwrap(), b"new");
+    assert_eq!(dir_entries(&dir), &["level.dat"]);
+}
+
+#[cfg(unix)]
+#[test]
+fn atomic_write_keeps_permissions_and_symlinks() {
+    use std::os::unix::fs::{symlink, PermissionsExt};
+
+    let dir = TempDir::new("nbted").unwrap();
+    let path = dir.path().join("level.dat");
+    fs::write(&path, b"old").unwrap();
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o640)).unwrap();
+    let link = dir.path().join("link.dat");
+    symlink(&path, &link).unwrap();
+
+    write_file_atomically(&link, |f| Ok(f.write_all(b"new")?)).unwrap();
+    assert!(fs::symlink_metadata(&link)
+        .unwrap()
+        .file_type()
+        .is_symlink());
+    assert_eq!(fs::read(&path).unwrap(), b"new");
+    let mode = fs::metadata(&path).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o640);
+
+    write_file_atomically(std::path::Path::new("/dev/null"), |f| {
+        Ok(f.write_all(b"new")?)
+    })
+    .unwrap();
+}
+
+/// A modification time set by write isn't changed by the rest of the write
+#[test]
+fn atomic_write_keeps_mtime() {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    let dir = TempDir::new("nbted").unwrap();
+    let path = dir.path().join("level.dat");
+    let mtime = UNIX_EPOCH + Duration::from_secs(1_500_000_000);
+    write_file_atomically(&path, |f| {
+        f.write_all(b"new")?;
+        f.flush()?;
+        Ok(f.get_ref().set_modified(mtime)?)
+    })
+    .unwrap();
+    assert_eq!(fs::metadata(&path).unwrap().modified().unwrap(), mtime);
+}
@@ -85,5 +85,6 @@ fn fuse() {
 #[should_panic]
 fn empty_replace_string() {
     let a: Vec<u8> = vec![0, 1];
-    let _ = a.iter().replacer(&[], &[1]);
+    let empty: &[u8] = &[];
+    let _ = a.iter().replacer(empty, &[1]);
 }
@@ -0,0 +1,298 @@
+/// Tests of converting NBT to JSON and back, either plain with a TypeMap or
+/// tagged with the type of every tag
+use crate::data::NBT;
+
+use serde_json::{json, Value};
+
+use super::read_fixture;
+use super::tests_data;
+
+#[test]
+fn custom_round_trip() {
+    let original = read_fixture(&tests_data::CUSTOM).root;
+    let (value, types) = original.to_json_with_types().unwrap();
+    let looped = NBT::from_json_with_types(&value, &types).unwrap();
+    assert!(original.bitwise_eq(&looped));
+}
+
+#[test]
+fn bigtest_round_trip() {
+    let original = read_fixture(&tests_data::BIGTEST_UNCOMPRESSED).root;
+    let (value, types) = original.to_json_with_types().unwrap();
+    let looped = NBT::from_json_with_types(&value, &types).unwrap();
+    assert!(original.bitwise_eq(&looped));
+}
+
+#[test]
+fn plain_values() {
+    let original = NBT::Compound(vec![
+        (b"byte".to_vec(), NBT::Byte(1)),
+        (b"long".to_vec(), NBT::Long(-5)),
+        (b"double".to_vec(), NBT::Double(0.5)),
+        (b"name".to_vec(), NBT::String(b"Steve".to_vec())),
+        (b"ints".to_vec(), NBT::IntArray(vec![1, 2])),
+        (b"list".to_vec(), NBT::list(vec![NBT::Short(3)])),
+    ]);
+    let (value, types) = original.to_json_with_types().unwrap();
+
+    assert_eq!(
+        value,
+        json!({
+            "byte": 1,
+            "long": -5,
+            "double": 0.5,
+            "name": "Steve",
+            "ints": [1, 2],
+            "list": [3],
+        })
+    );
+    /* Compounds keep their order */
+    let keys: Vec<&String> = value.as_object().unwrap().keys().collect();
+    assert_eq!(keys, ["byte", "long", "double", "name", "ints", "list"]);
+    /* The root, its six tags and the element of the list */
+    assert_eq!(types.len(), 8);
+}
+
+/// Edited values keep their original types, and new values get their
+/// natural type
+#[test]
+fn edited_json() {
+    let original = NBT::Compound(vec![
+        (b"count".to_vec(), NBT::Byte(1)),
+        (b"longs".to_vec(), NBT::LongArray(vec![1])),
+    ]);
+    let (_, types) = original.to_json_with_types().unwrap();
+
+    let edited = json!({
+        "count": 64,
+        "longs": [1, i64::MAX],
+        "new": 7,
+        "big": 1_i64 << 40,
+        "flag": true,
+    });
+    assert_eq!(
+        NBT::from_json_with_types(&edited, &types).unwrap(),
+        NBT::Compound(vec![
+            (b"count".to_vec(), NBT::Byte(64)),
+            (b"longs".to_vec(), NBT::LongArray(vec![1, i64::MAX])),
+            (b"new".to_vec(), NBT::Int(7)),
+            (b"big".to_vec(), NBT::Long(1 << 40)),
+            (b"flag".to_vec(), NBT::Byte(1)),
+        ])
+    );
+}
+
+#[test]
+fn edited_json_errors() {
+    let original = NBT::Compound(vec![
+        (b"count".to_vec(), NBT::Byte(1)),
+        (b"name".to_vec(), NBT::String(b"Steve".to_vec())),
+    ]);
+    let (_, types) = original.to_json_with_types().unwrap();
+
+    let too_big = json!({"count": 200});
+    assert_eq!(
+        NBT::from_json_with_types(&too_big, &types)
+            .unwrap_err()
+            .to_string(),
+        "200 at count doesn't fit in a Byte"
+    );
+
+    let wrong_type = json!({"name": 1});
+    assert_eq!(
+        NBT::from_json_with_types(&wrong_type, &types)
+            .unwrap_err()
+            .to_string(),
+        "Expected a number at name to be a string, since it was String"
+    );
+
+    let mixed = json!([1, "a"]);
+    assert!(NBT::from_json_with_types(&mixed, &types).is_err());
+}
+
+#[test]
+fn invalid_utf8() {
+    let nbt = NBT::Compound(vec![(b"name".to_vec(), NBT::String(vec![0xff]))]);
+    assert_eq!(
+        nbt.to_json_with_types().unwrap_err().to_string(),
+        "String at name isn't valid modified UTF-8, so it can't be converted to JSON"
+    );
+
+    /* Plain UTF-8 would be written back as modified UTF-8, which is
+     * different bytes */
+    for bytes in &[&b"\xf0\x9f\x98\x80"[..], &b"a\x00b"[..]] {
+        let nbt = NBT::Compound(vec![(b"name".to_vec(), NBT::String(bytes.to_vec()))]);
+        assert!(nbt.to_json_with_types().is_err());
+        assert!(nbt.to_tagged_json().is_err());
+    }
+
+    /* While modified UTF-8 comes back as the same bytes */
+    let emoji = b"\xed\xa0\xbd\xed\xb8\x80".to_vec();
+    let nbt = NBT::Compound(vec![(b"name".to_vec(), NBT::String(emoji))]);
+    let (value, types) = nbt.to_json_with_types().unwrap();
+    assert_eq!(value, json!({"name": "\u{1f600}"}));
+    assert!(nbt.bitwise_eq(&NBT::from_json_with_types(&value, &types).unwrap()));
+}
+
+/// JSON can't hold everything a tag can, which fails rather than changing it
+#[test]
+fn unrepresentable() {
+    let nan = NBT::Compound(vec![(b"a".to_vec(), NBT::Double(f64::NAN))]);
+    assert_eq!(
+        nan.to_json_with_types().unwrap_err().to_string(),
+        "NaN at a can't be converted to JSON"
+    );
+
+    let duplicate = NBT::Compound(vec![
+        (b"a".to_vec(), NBT::Int(1)),
+        (b"a".to_vec(), NBT::Int(2)),
+    ]);
+    assert_eq!(
+        duplicate.to_tagged_json().unwrap_err().to_string(),
+        "Duplicate key at a, so its compound can't be converted to JSON"
+    );
+}
+
+#[test]
+fn tagged_custom_round_trip() {
+    let original = read_fixture(&tests_data::CUSTOM).root;
+    let value = original.to_tagged_json().unwrap();
+    let looped = NBT::from_tagged_json(&value).unwrap();
+    assert!(original.bitwise_eq(&looped));
+}
+
+#[test]
+fn tagged_bigtest_round_trip() {
+    let original = read_fixture(&tests_data::BIGTEST_UNCOMPRESSED).root;
+    let value = original.to_tagged_json().unwrap();
+    let looped = NBT::from_tagged_json(&value).unwrap();
+    assert!(original.bitwise_eq(&looped));
+}
+
+#[test]
+fn tagged_values() {
+    let original = NBT::Compound(vec![
+        (b"short".to_vec(), NBT::Short(1)),
+        (b"int".to_vec(), NBT::Int(1)),
+        (b"bytes".to_vec(), NBT::ByteArray(vec![-1, 2])),
+        (b"longs".to_vec(), NBT::LongArray(vec![i64::MIN])),
+        (
+            b"lists".to_vec(),
+            NBT::list(vec![NBT::list(vec![NBT::Float(0.5)]), NBT::list(vec![])]),
+        ),
+    ]);
+    let value = original.to_tagged_json().unwrap();
+
+    assert_eq!(
+        value,
+        json!({"Compound": {
+            "short": {"Short": 1},
+            "int": {"Int": 1},
+            "bytes": {"ByteArray": [-1, 2]},
+            "longs": {"LongArray": [i64::MIN]},
+            "lists": {"List": {"List": [
+                {"Float": [0.5]},
+                {"End": []},
+            ]}},
+        }})
+    );
+    assert!(original.bitwise_eq(&NBT::from_tagged_json(&value).unwrap()));
+}
+
+/// NaN and infinity can't be JSON numbers, so the tagged JSON gives their bits
+#[test]
+fn tagged_special_floats() {
+    let original = NBT::Compound(vec![
+        (b"nan".to_vec(), NBT::Float(f32::NAN)),
+        (b"inf".to_vec(), NBT::Float(f32::INFINITY)),
+        (b"-inf".to_vec(), NBT::Double(f64::NEG_INFINITY)),
+        (
+            b"payload".to_vec(),
+            NBT::Double(f64::from_bits(0x7ff8_0000_0000_0001)),
+        ),
+        (b"zero".to_vec(), NBT::Double(-0.0)),
+    ]);
+    let value = original.to_tagged_json().unwrap();
+    assert_eq!(
+        value,
+        json!({"Compound": {
+            "nan": {"Float": "0x7fc00000"},
+            "inf": {"Float": "0x7f800000"},
+            "-inf": {"Double": "0xfff0000000000000"},
+            "payload": {"Double": "0x7ff8000000000001"},
+            "zero": {"Double": -0.0},
+        }})
+    );
+    let looped = NBT::from_tagged_json(&value).unwrap();
+    assert!(original.bitwise_eq(&looped), "{:?}", looped);
+
+    assert_eq!(
+        NBT::from_tagged_json(&json!({"Float": "0x100000000"}))
+            .unwrap_err()
+            .to_string(),
+        "0x100000000 at the root doesn't fit in a Float"
+    );
+    assert_eq!(
+        NBT::from_tagged_json(&json!({"Double": "NaN"}))
+            .unwrap_err()
+            .to_string(),
+        "Expected NaN at the root to be a number or its bits in hex, since it was Double"
+    );
+}
+
+/// An empty list keeps its declared type through either kind of JSON
+#[test]
+fn empty_list_type() {
+    let original = NBT::Compound(vec![(
+        b"l".to_vec(),
+        NBT::List {
+            element_type: 0x03,
+            items: Vec::new(),
+        },
+    )]);
+
+    let value = original.to_tagged_json().unwrap();
+    assert_eq!(value, json!({"Compound": {"l": {"List": {"Int": []}}}}));
+    assert!(original.bitwise_eq(&NBT::from_tagged_json(&value).unwrap()));
+
+    let (value, types) = original.to_json_with_types().unwrap();
+    assert_eq!(value, json!({"l": []}));
+    assert!(original.bitwise_eq(&NBT::from_json_with_types(&value, &types).unwrap()));
+}
+
+#[test]
+fn tagged_errors() {
+    let err = |value: &Value| NBT::from_tagged_json(value).unwrap_err().to_string();
+
+    assert_eq!(
+        err(&json!(1)),
+        "Expected a number at the root to be an object with a single key naming its type"
+    );
+    assert_eq!(
+        err(&json!({"Integer": 1})),
+        "Unknown type Integer at the root"
+    );
+    assert_eq!(
+        err(&json!({"Compound": {"a": {"Byte": 300}}})),
+        "300 at a doesn't fit in a Byte"
+    );
+    assert_eq!(
+        err(&json!({"List": 1})),
+        "Expected a number at the root to be an object, since it was List"
+    );
+    assert_eq!(
+        err(&json!({"List": {"Int": 1}})),
+        "Expected a number at the root to be an array of the elements of the list"
+    );
+    assert_eq!(
+        err(&json!({"List": {"Int": [1, "a"]}})),
+        "Expected a string at 1 to be a number, since it was Int"
+    );
+
+    /* Which is why a list of mixed types can't be converted */
+    let mixed = NBT::list(vec![NBT::Int(1), NBT::Short(1)]);
+    assert_eq!(
+        mixed.to_tagged_json().unwrap_err().to_string(),
+        "Short at 1 in a list of Int, so the list can't be converted to JSON"
+    );
+}
@@ -0,0 +1,143 @@
+use crate::data::{Endianness, NBT};
+use crate::lint::{lint_file, lint_tree, IssueKind};
+use crate::read::ReadOptions;
+
+use super::tests_data;
+
+/// An uncompressed file written by a buggy tool, with a root compound named
+/// "" containing an empty List of Int "a", a list "l" of two compounds where
+/// the second has an empty List of Byte "b", a duplicate Int "k", a String
+/// "s" that isn't UTF-8, a Double "x" of 1e9 and a list "Pos" with a NaN y
+fn buggy_file() -> Vec<u8> {
+    let mut data = vec![0x0a, 0, 0];
+    data.extend_from_slice(&[0x09, 0, 1, b'a', 0x03, 0, 0, 0, 0]);
+    data.extend_from_slice(&[0x09, 0, 1, b'l', 0x0a, 0, 0, 0, 2, 0]);
+    data.extend_from_slice(&[0x09, 0, 1, b'b', 0x01, 0, 0, 0, 0, 0]);
+    data.extend_from_slice(&[0x03, 0, 1, b'k', 0, 0, 0, 1]);
+    data.extend_from_slice(&[0x03, 0, 1, b'k', 0, 0, 0, 2]);
+    data.extend_from_slice(&[0x08, 0, 1, b's', 0, 2, b'a', 0xff]);
+    data.push(0x06);
+    data.extend_from_slice(&[0, 1, b'x']);
+    data.extend_from_slice(&1e9f64.to_be_bytes());
+    data.extend_from_slice(&[0x09, 0, 3, b'P', b'o', b's', 0x06, 0, 0, 0, 3]);
+    data.extend_from_slice(&0f64.to_be_bytes());
+    data.extend_from_slice(&f64::NAN.to_be_bytes());
+    data.extend_from_slice(&0f64.to_be_bytes());
+    data.push(0);
+    data
+}
+
+#[test]
+fn lint_buggy_file() {
+    let issues = lint_file(&mut &buggy_file()[..], &ReadOptions::default()).unwrap();
+    let found: Vec<(String, IssueKind)> = issues
+        .into_iter()
+        .map(|x| (x.path.to_string(), x.kind))
+        .collect();
+    assert_eq!(
+        found,
+        vec![
+            ("/k".to_string(), IssueKind::DuplicateKey),
+            (
+                "/a".to_string(),
+                IssueKind::TypedEmptyList { type_id: 0x03 }
+            ),
+            (
+                "/l/1/b".to_string(),
+                IssueKind::TypedEmptyList { type_id: 0x01 }
+            ),
+            ("/s".to_string(), IssueKind::InvalidUtf8),
+            ("/x".to_string(), IssueKind::Coordinate),
+            ("/Pos/1".to_string(), IssueKind::Coordinate),
+        ]
+    );
+}
+
+/// A little endian file is read as one, rather than as garbage
+#[test]
+fn lint_little_endian() {
+    /* An empty List of Int "a" and an Int "x" of 100, which would be outside
+     * of the world if it was read big endian */
+    let data = [
+        0x0a, 0, 0, 0x09, 1, 0, b'a', 0x03, 0, 0, 0, 0, 0x03, 1, 0, b'x', 100, 0, 0, 0, 0,
+    ];
+    let options = ReadOptions {
+        endianness: Endianness::Little,
+        ..Default::default()
+    };
+    let issues = lint_file(&mut &data[..], &options).unwrap();
+    assert_eq!(issues.len(), 1);
+    assert_eq!(issues[0].path.to_string(), "/a");
+    assert_eq!(issues[0].kind, IssueKind::TypedEmptyList { type_id: 0x03 });
+}
+
+/// Files written by Minecraft have no problems, whatever their compression
+#[test]
+fn lint_clean_files() {
+    for data in [
+        &tests_data::BIGTEST_COMPRESSED[..],
+        &tests_data::BIGTEST_ZLIB[..],
+        #[cfg(feature = "zstd")]
+        &tests_data::BIGTEST_ZSTD[..],
+        #[cfg(feature = "lz4")]
+        &tests_data::BIGTEST_LZ4[..],
+        &tests_data::PLAYER_FILE[..],
+    ] {
+        assert_eq!(
+            lint_file(&mut &data[..], &ReadOptions::default()).unwrap(),
+            Vec::new()
+        );
+    }
+}
+
+/// Lists of mixed types can't be read from a file, but can be built in
+/// memory
+#[test]
+fn lint_mixed_list() {
+    let tag = NBT::Compound(vec![(
+        b"l".to_vec(),
+        NBT::list(vec![NBT::Int(1), NBT::Int(2), NBT::Byte(3)]),
+    )]);
+    let issues = lint_tree(&tag);
+    assert_eq!(issues.len(), 1);
+    assert_eq!(issues[0].path.to_string(), "l/2");
+    assert_eq!(
+        issues[0].kind,
+        IssueKind::MixedList {
+            expected: 0x03,
+            found: 0x01
+        }
+    );
+    assert_eq!(
+        issues[0].kind.to_string(),
+        "List of Int also contains a Byte"
+    );
+}
+
+/// The file is read under the limits given, however it's compressed
+#[test]
+fn lint_limits() {
+    let options = ReadOptions {
+        max_tags: Some(5),
+        ..Default::default()
+    };
+    let err = lint_file(&mut &tests_data::BIGTEST_COMPRESSED[..], &options).unwrap_err();
+    assert_eq!(err.to_string(), "exceeded maximum tag count 5");
+}
+
+/// Modified UTF-8 isn't UTF-8, but is what strings and keys are supposed to be
+#[test]
+fn lint_modified_utf8() {
+    /* A NUL and an emoji as a surrogate pair, and the emoji as UTF-8 */
+    let tag = NBT::Compound(vec![
+        (
+            vec![b'k', 0xc0, 0x80],
+            NBT::String(vec![0xed, 0xa0, 0xbd, 0xed, 0xb8, 0x80]),
+        ),
+        (b"u".to_vec(), NBT::String(vec![0xf0, 0x9f, 0x98, 0x80])),
+    ]);
+    let issues = lint_tree(&tag);
+    assert_eq!(issues.len(), 1);
+    assert_eq!(issues[0].path.to_string(), "u");
+    assert_eq!(issues[0].kind, IssueKind::InvalidUtf8);
+}
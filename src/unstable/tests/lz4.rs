@@ -0,0 +1,100 @@
+/// Tests of the LZ4 frame format
+use std::io::{Cursor, Read};
+
+use crate::data::Compression;
+use crate::lz4::{compress_with, decoder};
+use crate::read;
+
+use super::tests_data;
+
+fn decompress(data: &[u8]) -> crate::Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    let _: usize = decoder(data)?.read_to_end(&mut buf)?;
+    Ok(buf)
+}
+
+fn compress(data: &[u8]) -> Vec<u8> {
+    let mut buf = Vec::new();
+    compress_with(&mut buf, |w| Ok(w.write_all(data)?)).unwrap();
+    buf
+}
+
+/// Frames written by the lz4 tool can be read
+#[test]
+fn decompress_lz4_tool() {
+    let data = decompress(&tests_data::BIGTEST_LZ4).unwrap();
+    assert_eq!(&data[..], &tests_data::BIGTEST_UNCOMPRESSED[..]);
+}
+
+#[test]
+fn compress_loop() {
+    let long: Vec<u8> = (0..200_000u32).map(|x| (x % 251 / 7) as u8).collect();
+    let cases: [&[u8]; 5] = [
+        b"",
+        b"a",
+        b"aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa",
+        &tests_data::PLAYER_FILE[..],
+        &long[..],
+    ];
+    for data in cases.iter() {
+        assert_eq!(&decompress(&compress(data)).unwrap()[..], *data);
+    }
+
+    let buf = compress(&tests_data::PLAYER_FILE);
+    assert_eq!(&buf[..4], &[0x04, 0x22, 0x4d, 0x18]);
+    assert!(buf.len() < tests_data::PLAYER_FILE.len() / 2);
+}
+
+#[test]
+fn decompress_invalid() {
+    let mut data = tests_data::BIGTEST_LZ4.to_vec();
+    /* The content checksum at the end */
+    let len = data.len();
+    data[len - 1] ^= 1;
+    assert!(decompress(&data).is_err());
+
+    /* Cut short */
+    assert!(decompress(&tests_data::BIGTEST_LZ4[..100]).is_err());
+}
+
+/// LZ4 files are read and written like any other compression
+#[test]
+fn lz4_file() {
+    assert_eq!(Compression::from_first_byte(0x04), Some(Compression::Lz4));
+    let nbt = read::read_file(&mut Cursor::new(&tests_data::BIGTEST_LZ4[..])).unwrap();
+    assert_eq!(nbt.compression, Compression::Lz4);
+    let uncompressed =
+        read::read_file(&mut Cursor::new(&tests_data::BIGTEST_UNCOMPRESSED[..])).unwrap();
+    assert_eq!(nbt.root, uncompressed.root);
+
+    let mut buf = Vec::new();
+    crate::write::write_file(&mut buf, &nbt).unwrap();
+    assert_eq!(&buf[..4], &[0x04, 0x22, 0x4d, 0x18]);
+    assert_eq!(read::read_file(&mut Cursor::new(buf)).unwrap(), nbt);
+}
+
+/// An uncompressed file starts with a Compound or End tag, never with 04,
+/// the type id of Long, so LZ4 frames can't be mistaken for one
+#[test]
+fn lz4_not_bare_nbt() {
+    for data in [
+        &tests_data::EMPTY_ROOT[..],
+        &tests_data::HELLO_WORLD[..],
+        &tests_data::BIGTEST_UNCOMPRESSED[..],
+        &tests_data::CUSTOM[..],
+    ] {
+        assert_eq!(
+            Compression::from_first_byte(data[0]),
+            Some(Compression::None)
+        );
+    }
+
+    let mut buf = Vec::new();
+    let nbt = read::read_file(&mut Cursor::new(&tests_data::HELLO_WORLD[..])).unwrap();
+    crate::write::write_file(&mut buf, &nbt).unwrap();
+    assert_ne!(buf[0], 0x04);
+
+    /* A bare Long tag isn't a valid file, and is read as a broken frame */
+    let long = [0x04, 0x00, 0x00, 0, 0, 0, 0, 0, 0, 0, 1];
+    assert!(read::read_file(&mut Cursor::new(&long[..])).is_err());
+}
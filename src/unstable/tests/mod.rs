@@ -1,10 +1,52 @@
 use std::io::Cursor;
 
-use crate::data::{Compression, NBTFile};
-
+use crate::data::{Compression, NBTFile, NBT};
+
+mod base64;
+mod checksum;
+mod data;
+mod deflate;
+mod diff;
+mod dot;
+mod humanize;
+mod io;
 mod iter_replacer;
+mod json;
+mod lint;
+#[cfg(feature = "lz4")]
+mod lz4;
+mod mutf8;
+mod network;
+mod patch;
+mod random;
+mod read;
+mod region;
+mod repro;
+mod snbt_read;
+mod snbt_write;
+mod stream;
 mod string_read;
+mod string_write;
 mod tests_data;
+#[cfg(feature = "trace")]
+mod trace;
+
+/// Read one of the files in tests_data
+fn read_fixture(data: &[u8]) -> NBTFile {
+    crate::read::read_file(&mut Cursor::new(data)).unwrap()
+}
+
+/// bigtest.nbt, the example file with every type of tag that came with the
+/// original NBT specification
+fn bigtest() -> NBTFile {
+    read_fixture(&tests_data::BIGTEST_UNCOMPRESSED)
+}
+
+/// Like assert_eq, but comparing with NBTFile::bitwise_eq so that trees
+/// containing NaN can be compared
+fn assert_bitwise_eq(a: &NBTFile, b: &NBTFile) {
+    assert!(a.bitwise_eq(b), "{:?} != {:?}", a, b);
+}
 
 /// Given some NBT data in original format, loops it around by converting it
 /// in the following way: Read NBT -> Write String -> Read String -> Write NBT
@@ -23,7 +65,7 @@ fn complete_loop_from_nbt(nbt: &[u8]) {
     let mut cursor = Cursor::new(string.into_bytes());
     let nbtfile2 = crate::string_read::read_file(&mut cursor).unwrap();
 
-    assert_eq!(&nbtfile1, &nbtfile2);
+    assert_bitwise_eq(&nbtfile1, &nbtfile2);
 
     let mut tmp = Vec::new();
     crate::write::write_file(&mut tmp, &nbtfile2).unwrap();
@@ -42,7 +84,7 @@ fn complete_loop_from_enum(original: &NBTFile) {
     let mut cursor = Cursor::new(string.into_bytes());
     let nbtfile = crate::string_read::read_file(&mut cursor).unwrap();
 
-    assert_eq!(original, &nbtfile);
+    assert_bitwise_eq(original, &nbtfile);
 
     let mut tmp = Vec::new();
     crate::write::write_file(&mut tmp, &nbtfile).unwrap();
@@ -50,7 +92,61 @@ fn complete_loop_from_enum(original: &NBTFile) {
     let mut cursor = Cursor::new(tmp);
     let nbtfile = crate::read::read_file(&mut cursor).unwrap();
 
-    assert_eq!(original, &nbtfile);
+    assert_bitwise_eq(original, &nbtfile);
+}
+
+#[test]
+fn special_floats_loop() {
+    let file = NBTFile {
+        root: NBT::Compound(vec![(
+            b"".to_vec(),
+            NBT::Compound(vec![
+                (b"a".to_vec(), NBT::Float(f32::NAN)),
+                (b"b".to_vec(), NBT::Float(f32::INFINITY)),
+                (b"c".to_vec(), NBT::Float(f32::NEG_INFINITY)),
+                (b"d".to_vec(), NBT::Double(f64::NAN)),
+                (b"e".to_vec(), NBT::Double(f64::INFINITY)),
+                (b"f".to_vec(), NBT::Double(f64::NEG_INFINITY)),
+                (b"g".to_vec(), NBT::Float(-f32::NAN)),
+                (b"h".to_vec(), NBT::Double(-f64::NAN)),
+            ]),
+        )]),
+        compression: Compression::None,
+        compression_level: None,
+    };
+    complete_loop_from_enum(&file);
+
+    let mut text = Vec::new();
+    crate::string_write::write_file(&mut text, &file).unwrap();
+    assert_eq!(
+        String::from_utf8(text).unwrap(),
+        "None
+Compound \"\"
+\tFloat \"a\" NaN
+\tFloat \"b\" Infinity
+\tFloat \"c\" -Infinity
+\tDouble \"d\" NaN
+\tDouble \"e\" Infinity
+\tDouble \"f\" -Infinity
+\tFloat \"g\" NaN(0xffc00000)
+\tDouble \"h\" NaN(0xfff8000000000000)
+\tEnd
+End
+"
+    );
+}
+
+#[test]
+fn empty_root_loop() {
+    complete_loop_from_nbt(&tests_data::EMPTY_ROOT);
+
+    let nbtfile = crate::read::read_file(&mut Cursor::new(&tests_data::EMPTY_ROOT[..])).unwrap();
+    assert_eq!(nbtfile.root, NBT::Compound(Vec::new()));
+    assert_eq!(nbtfile.compression, Compression::None);
+
+    let mut text = Vec::new();
+    crate::string_write::write_file(&mut text, &nbtfile).unwrap();
+    assert_eq!(text, b"None\nEnd\n");
 }
 
 #[test]
@@ -58,6 +154,88 @@ fn hello_world_loop() {
     complete_loop_from_nbt(&tests_data::HELLO_WORLD);
 }
 
+/// The name of the root compound is written on its line like any other name,
+/// so that it can be seen and edited
+#[test]
+fn named_root_loop() {
+    complete_loop_from_nbt(&tests_data::NAMED_ROOT);
+
+    let nbtfile = crate::read::read_file(&mut Cursor::new(&tests_data::NAMED_ROOT[..])).unwrap();
+    let mut text = Vec::new();
+    crate::string_write::write_file(&mut text, &nbtfile).unwrap();
+    assert_eq!(
+        String::from_utf8(text).unwrap(),
+        "None\nCompound \"worldname\"\n\tInt \"SpawnX\" 16\n\tEnd\nEnd\n"
+    );
+
+    /* Renaming it in the text renames the root */
+    let edited = b"None\nCompound \"renamed\"\n\tInt \"SpawnX\" 16\n\tEnd\nEnd\n";
+    let nbtfile = crate::string_read::read_file(&mut Cursor::new(&edited[..])).unwrap();
+    let mut binary = Vec::new();
+    crate::write::write_file(&mut binary, &nbtfile).unwrap();
+    assert_eq!(&binary[..10], b"\x0a\x00\x07renamed");
+}
+
+/// Names and strings that aren't UTF-8 are written as escapes, so that the
+/// text is UTF-8, and read back as the same bytes
+#[test]
+fn non_utf8_loop() {
+    complete_loop_from_nbt(&tests_data::NON_UTF8);
+
+    let nbtfile = crate::read::read_file(&mut Cursor::new(&tests_data::NON_UTF8[..])).unwrap();
+    let mut text = Vec::new();
+    crate::string_write::write_file(&mut text, &nbtfile).unwrap();
+    assert_eq!(
+        String::from_utf8(text).unwrap(),
+        "None\nCompound \"\"\n\tString \"\\xffk\" \"\\xc0\\x80\\\"v\"\n\tEnd\nEnd\n"
+    );
+}
+
+/// Modified UTF-8 is printed as the characters it encodes, so an emoji is
+/// printed as itself rather than as the escaped halves of a surrogate pair
+#[test]
+fn emoji_loop() {
+    complete_loop_from_nbt(&tests_data::EMOJI);
+
+    let nbtfile = crate::read::read_file(&mut Cursor::new(&tests_data::EMOJI[..])).unwrap();
+    let mut text = Vec::new();
+    crate::string_write::write_file(&mut text, &nbtfile).unwrap();
+    let text = String::from_utf8(text).unwrap();
+    assert_eq!(
+        text,
+        "None\nCompound \"\"\n\tString \"\u{1f600}\" \"a\u{1f600}\"\n\tEnd\nEnd\n"
+    );
+
+    /* And typing it into the text writes it as Java would */
+    let nbtfile = crate::string_read::read_file(&mut Cursor::new(text.as_bytes())).unwrap();
+    let mut binary = Vec::new();
+    crate::write::write_file(&mut binary, &nbtfile).unwrap();
+    assert_eq!(&binary[..], &tests_data::EMOJI[..]);
+}
+
+/// An empty list keeps the type it was declared with, rather than becoming a
+/// list of End
+#[test]
+fn empty_list_type_loop() {
+    complete_loop_from_nbt(&tests_data::EMPTY_INT_LIST);
+
+    let nbtfile =
+        crate::read::read_file(&mut Cursor::new(&tests_data::EMPTY_INT_LIST[..])).unwrap();
+    assert_eq!(
+        nbtfile.root.get("").unwrap().get("l"),
+        Some(&NBT::List {
+            element_type: 3,
+            items: Vec::new()
+        })
+    );
+    let mut text = Vec::new();
+    crate::string_write::write_file(&mut text, &nbtfile).unwrap();
+    assert_eq!(
+        String::from_utf8(text).unwrap(),
+        "None\nCompound \"\"\n\tList \"l\" Int 0\n\tEnd\nEnd\n"
+    );
+}
+
 #[test]
 fn bigtest_uncompressed_loop() {
     complete_loop_from_nbt(&tests_data::BIGTEST_UNCOMPRESSED);
@@ -68,6 +246,24 @@ fn player_file_loop() {
     complete_loop_from_nbt(&tests_data::PLAYER_FILE);
 }
 
+#[test]
+fn long_array_loop() {
+    /* Heightmaps and block states in modern chunks are LongArrays */
+    complete_loop_from_nbt(&tests_data::LONG_ARRAY);
+
+    let nbtfile = crate::read::read_file(&mut Cursor::new(&tests_data::LONG_ARRAY[..])).unwrap();
+    assert_eq!(
+        nbtfile.root,
+        NBT::Compound(vec![(
+            b"Level".to_vec(),
+            NBT::Compound(vec![(
+                b"Heightmap".to_vec(),
+                NBT::LongArray(vec![1, -1, i64::MAX, i64::MIN]),
+            )]),
+        )])
+    );
+}
+
 #[test]
 fn custom_loop() {
     /* The custom file is a custom NBT file made to contain various tricky
@@ -75,6 +271,23 @@ fn custom_loop() {
     complete_loop_from_nbt(&tests_data::CUSTOM);
 }
 
+/// Tests that NaN survives the loop, which a plain == would fail on
+#[test]
+fn nan_loop() {
+    let nbtfile = NBTFile {
+        root: NBT::Compound(vec![(
+            b"".to_vec(),
+            NBT::Compound(vec![
+                (b"float".to_vec(), NBT::Float(f32::NAN)),
+                (b"double".to_vec(), NBT::Double(f64::NAN)),
+            ]),
+        )]),
+        compression: Compression::None,
+        compression_level: None,
+    };
+    complete_loop_from_enum(&nbtfile);
+}
+
 /// Tests that we can read the original (gzip compressed) bigtest and that we
 /// can loop it around correctly
 #[test]
@@ -138,11 +351,13 @@ fn compression_write() {
     let hello_world_gzip = NBTFile {
         root: hello_world.root.clone(),
         compression: Compression::Gzip,
+        compression_level: None,
     };
 
     let hello_world_zlib = NBTFile {
         root: hello_world.root.clone(),
         compression: Compression::Zlib,
+        compression_level: None,
     };
 
     assert_eq!(
@@ -157,13 +372,335 @@ fn compression_write() {
     let bigtest_gzip = NBTFile {
         root: bigtest.root.clone(),
         compression: Compression::Gzip,
+        compression_level: None,
     };
 
     let bigtest_zlib = NBTFile {
         root: bigtest.root.clone(),
         compression: Compression::Zlib,
+        compression_level: None,
     };
 
     assert_eq!(&bigtest.root, &write_read_binary(&bigtest_gzip).root);
     assert_eq!(&bigtest.root, &write_read_binary(&bigtest_zlib).root);
 }
+
+/// Writing the same tree twice gives the same bytes with every compression,
+/// and gzip files don't record when they were written
+#[test]
+fn deterministic_write() {
+    let bigtest =
+        crate::read::read_file(&mut Cursor::new(&tests_data::BIGTEST_UNCOMPRESSED[..])).unwrap();
+    for compression in [
+        Compression::None,
+        Compression::Gzip,
+        Compression::Zlib,
+        #[cfg(feature = "zstd")]
+        Compression::Zstd,
+        #[cfg(feature = "lz4")]
+        Compression::Lz4,
+    ] {
+        let gzip = compression == Compression::Gzip;
+        let file = NBTFile {
+            root: bigtest.root.clone(),
+            compression,
+            compression_level: None,
+        };
+        let mut a = Vec::new();
+        crate::write::write_file(&mut a, &file).unwrap();
+        let mut b = Vec::new();
+        crate::write::write_file(&mut b, &file).unwrap();
+        assert_eq!(a, b);
+        if gzip {
+            assert_eq!(&a[4..8], &[0, 0, 0, 0]);
+        }
+    }
+}
+
+/// Leaving the compression level unset writes the same bytes as asking for
+/// the default level, under a gzip header naming the unknown operating system
+#[test]
+fn gzip_header() {
+    let root = crate::read::read_file(&mut Cursor::new(&tests_data::HELLO_WORLD[..]))
+        .unwrap()
+        .root;
+    let write = |compression_level| {
+        let mut out = Vec::new();
+        crate::write::write_file(
+            &mut out,
+            &NBTFile {
+                root: root.clone(),
+                compression: Compression::Gzip,
+                compression_level,
+            },
+        )
+        .unwrap();
+        out
+    };
+    let unset = write(None);
+    assert_eq!(unset, write(Some(crate::write::DEFAULT_COMPRESSION_LEVEL)));
+    assert_ne!(unset, write(Some(9)));
+    assert_eq!(unset[8], 0);
+    assert_eq!(unset[9], 255);
+}
+
+#[test]
+fn mixed_list_write() {
+    let nbt = NBTFile {
+        root: NBT::Compound(vec![(
+            b"list".to_vec(),
+            NBT::list(vec![NBT::Int(1), NBT::Long(2)]),
+        )]),
+        compression: Compression::None,
+        compression_level: None,
+    };
+
+    let expected = "List of Int also contains a Long at index 1, all elements of a list must have the same type";
+
+    let mut tmp = Vec::new();
+    let err = crate::write::write_file(&mut tmp, &nbt).unwrap_err();
+    assert_eq!(err.to_string(), expected);
+
+    /* Nor in the text format, where it would fail to be read back */
+    for format in &[
+        crate::string_write::TextFormat::default(),
+        crate::string_write::TextFormat::canonical(),
+    ] {
+        let mut tmp = Vec::new();
+        let err = crate::string_write::write_file_with(&mut tmp, &nbt, format).unwrap_err();
+        assert_eq!(err.to_string(), expected);
+    }
+}
+
+/// The root compound can be ended with an End tag, and files are read the
+/// same with or without it
+#[test]
+fn root_end_write() {
+    let nbtfile = crate::read::read_file(&mut Cursor::new(&tests_data::HELLO_WORLD[..])).unwrap();
+
+    let mut tmp = Vec::new();
+    let options = crate::write::WriteOptions {
+        root_end: true,
+        ..Default::default()
+    };
+    crate::write::write_file_with_options(&mut tmp, &nbtfile, &options).unwrap();
+    assert_eq!(tmp.len(), tests_data::HELLO_WORLD.len() + 1);
+    assert_eq!(&tmp[..tmp.len() - 1], &tests_data::HELLO_WORLD[..]);
+    assert_eq!(tmp.last(), Some(&0));
+
+    let read = crate::read::read_file(&mut Cursor::new(tmp)).unwrap();
+    assert_eq!(read, nbtfile);
+}
+
+/// A higher level gives a file no larger than a lower one, and the level in
+/// the options is used over that of the file
+#[test]
+fn compression_level_write() {
+    let mut bigtest =
+        crate::read::read_file(&mut Cursor::new(&tests_data::BIGTEST_UNCOMPRESSED[..])).unwrap();
+    let write = |file: &NBTFile, level| {
+        let options = crate::write::WriteOptions {
+            level,
+            ..Default::default()
+        };
+        let mut tmp = Vec::new();
+        crate::write::write_file_with_options(&mut tmp, file, &options).unwrap();
+        tmp
+    };
+
+    for compression in [Compression::Gzip, Compression::Zlib] {
+        bigtest.compression = compression;
+        bigtest.compression_level = Some(1);
+        let fast = write(&bigtest, None);
+        let best = write(&bigtest, Some(9));
+        assert!(best.len() <= fast.len(), "{} > {}", best.len(), fast.len());
+        assert_ne!(fast, best);
+        assert_eq!(write(&bigtest, Some(1)), fast);
+        let read = crate::read::read_file(&mut Cursor::new(best)).unwrap();
+        assert_eq!(read.root, bigtest.root);
+    }
+
+    let options = crate::write::WriteOptions {
+        level: Some(10),
+        ..Default::default()
+    };
+    assert!(crate::write::write_file_with_options(&mut Vec::new(), &bigtest, &options).is_err());
+}
+
+/// Padded files are a multiple of the sector size and read back the same,
+/// whatever their compression
+#[test]
+fn padded_write() {
+    for data in [
+        &tests_data::HELLO_WORLD[..],
+        &tests_data::BIGTEST_COMPRESSED[..],
+        &tests_data::BIGTEST_ZLIB[..],
+        #[cfg(feature = "zstd")]
+        &tests_data::BIGTEST_ZSTD[..],
+        #[cfg(feature = "lz4")]
+        &tests_data::BIGTEST_LZ4[..],
+    ] {
+        let nbtfile = crate::read::read_file(&mut Cursor::new(data)).unwrap();
+        let mut unpadded = Vec::new();
+        crate::write::write_file(&mut unpadded, &nbtfile).unwrap();
+
+        for &sector in &[1, 7, 4096] {
+            let mut tmp = Vec::new();
+            crate::write::write_file_padded(&mut tmp, &nbtfile, sector).unwrap();
+            assert!(tmp.len().is_multiple_of(sector));
+            assert!(tmp.len() - unpadded.len() < sector);
+            assert_eq!(&tmp[..unpadded.len()], &unpadded[..]);
+            assert!(tmp[unpadded.len()..].iter().all(|x| *x == 0));
+
+            let read = crate::read::read_file(&mut Cursor::new(tmp)).unwrap();
+            assert_bitwise_eq(&read, &nbtfile);
+        }
+    }
+
+    let nbtfile = crate::read::read_file(&mut Cursor::new(&tests_data::HELLO_WORLD[..])).unwrap();
+    assert!(crate::write::write_file_padded(&mut Vec::new(), &nbtfile, 0).is_err());
+}
+
+/// The length of a file is what would be written, with its compression and
+/// padding
+#[test]
+fn file_len() {
+    for data in [
+        &tests_data::HELLO_WORLD[..],
+        &tests_data::BIGTEST_COMPRESSED[..],
+        &tests_data::BIGTEST_ZLIB[..],
+    ] {
+        let nbtfile = crate::read::read_file(&mut Cursor::new(data)).unwrap();
+        for options in &[
+            crate::write::WriteOptions::default(),
+            crate::write::WriteOptions {
+                pad: Some(4096),
+                ..Default::default()
+            },
+        ] {
+            let mut tmp = Vec::new();
+            crate::write::write_file_with_options(&mut tmp, &nbtfile, options).unwrap();
+            assert_eq!(
+                crate::write::file_len(&nbtfile, options).unwrap(),
+                tmp.len() as u64
+            );
+        }
+    }
+}
+
+/// A writer that throws away what is written to it, keeping only how much
+/// was written and the largest single write
+#[derive(Default)]
+struct SinkWriter {
+    count: u64,
+    largest: usize,
+}
+impl std::io::Write for SinkWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.count += buf.len() as u64;
+        self.largest = self.largest.max(buf.len());
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Writing a gzip file compresses as it goes, so the compressed file is
+/// never held in memory, however large it is
+#[test]
+fn gzip_write_streams() {
+    /* Pseudorandom numbers, so that the compressed file is about as large as
+     * the 16 MiB of uncompressed data */
+    let mut x: i64 = 1;
+    let arrays = (0..16)
+        .map(|_| {
+            NBT::LongArray(
+                (0..128 * 1024)
+                    .map(|_| {
+                        x = x
+                            .wrapping_mul(6_364_136_223_846_793_005)
+                            .wrapping_add(1_442_695_040_888_963_407);
+                        x
+                    })
+                    .collect(),
+            )
+        })
+        .collect();
+    let nbtfile = NBTFile {
+        root: NBT::Compound(vec![(
+            Vec::new(),
+            NBT::Compound(vec![(b"arrays".to_vec(), NBT::list(arrays))]),
+        )]),
+        compression: Compression::Gzip,
+        compression_level: None,
+    };
+
+    let mut sink = SinkWriter::default();
+    crate::write::write_file(&mut sink, &nbtfile).unwrap();
+    assert!(sink.count > 16 * 1024 * 1024);
+    assert!(
+        sink.largest <= 64 * 1024,
+        "wrote {} bytes at once",
+        sink.largest
+    );
+}
+
+/// Files converted from Java to Bedrock Edition byte order and back are
+/// unchanged, and the lengths of strings, lists and arrays are converted too
+#[test]
+fn endian_round_trip() {
+    use crate::data::Endianness;
+    use crate::read::{read_file_with_options, ReadOptions};
+    use crate::write::{write_file_with_options, WriteOptions};
+
+    let little_read = ReadOptions {
+        endianness: Endianness::Little,
+        ..Default::default()
+    };
+    let little_write = WriteOptions {
+        endianness: Endianness::Little,
+        ..Default::default()
+    };
+
+    for data in [
+        &tests_data::BIGTEST_UNCOMPRESSED[..],
+        &tests_data::BIGTEST_COMPRESSED[..],
+        &tests_data::LONG_ARRAY[..],
+    ] {
+        let java = crate::read::read_file(&mut Cursor::new(data)).unwrap();
+        let mut bedrock = Vec::new();
+        write_file_with_options(&mut bedrock, &java, &little_write).unwrap();
+
+        let read = read_file_with_options(&mut Cursor::new(&bedrock), &little_read).unwrap();
+        assert_bitwise_eq(&read, &java);
+
+        let mut back = Vec::new();
+        write_file_with_options(&mut back, &read, &WriteOptions::default()).unwrap();
+        let mut original = Vec::new();
+        crate::write::write_file(&mut original, &java).unwrap();
+        assert_eq!(back, original);
+    }
+
+    let nbtfile = NBTFile {
+        root: NBT::Compound(vec![(
+            Vec::new(),
+            NBT::Compound(vec![
+                (b"a".to_vec(), NBT::Int(1)),
+                (b"b".to_vec(), NBT::IntArray(vec![2])),
+                (b"s".to_vec(), NBT::String(b"hi".to_vec())),
+            ]),
+        )]),
+        compression: Compression::None,
+        compression_level: None,
+    };
+    let mut tmp = Vec::new();
+    write_file_with_options(&mut tmp, &nbtfile, &little_write).unwrap();
+    let mut expected = vec![0x0a, 0, 0];
+    expected.extend_from_slice(&[0x03, 1, 0, b'a', 1, 0, 0, 0]);
+    expected.extend_from_slice(&[0x0b, 1, 0, b'b', 1, 0, 0, 0, 2, 0, 0, 0]);
+    expected.extend_from_slice(&[0x08, 1, 0, b's', 2, 0, b'h', b'i']);
+    expected.push(0);
+    assert_eq!(tmp, expected);
+}
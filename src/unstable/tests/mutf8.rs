@@ -0,0 +1,39 @@
+use crate::mutf8::{decode, decode_char, encode};
+
+#[test]
+fn nul() {
+    assert_eq!(encode("a\0b"), b"a\xc0\x80b");
+    assert_eq!(decode(b"a\xc0\x80b").unwrap(), "a\0b");
+    /* Java never writes a zero byte */
+    assert!(decode(b"a\x00b").is_err());
+}
+
+#[test]
+fn surrogate_pairs() {
+    let emoji = b"\xed\xa0\xbd\xed\xb8\x80";
+    assert_eq!(encode("\u{1f600}"), emoji);
+    assert_eq!(decode(emoji).unwrap(), "\u{1f600}");
+    assert_eq!(decode_char(emoji), Some(('\u{1f600}', 6)));
+
+    /* Characters in the Basic Multilingual Plane are the same as in UTF-8 */
+    assert_eq!(encode("hé€"), "hé€".as_bytes());
+    assert_eq!(decode("hé€".as_bytes()).unwrap(), "hé€");
+}
+
+#[test]
+fn invalid() {
+    /* The four byte UTF-8 encoding of the emoji */
+    assert_eq!(
+        decode(b"a\xf0\x9f\x98\x80").unwrap_err().to_string(),
+        "Invalid modified UTF-8 at byte 1 of the string, 0xf0"
+    );
+    /* A high surrogate on its own, and one followed by another */
+    assert!(decode(b"\xed\xa0\xbd").is_err());
+    assert!(decode(b"\xed\xa0\xbd\xed\xa0\xbd").is_err());
+    /* A low surrogate on its own */
+    assert_eq!(decode_char(b"\xed\xb8\x80"), None);
+    /* Overlong encodings other than NUL, and truncated characters */
+    assert_eq!(decode_char(b"\xc1\x81"), None);
+    assert_eq!(decode_char(b"\xe0\x81\x81"), None);
+    assert_eq!(decode_char(b"\xe2\x82"), None);
+}
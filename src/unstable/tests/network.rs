@@ -0,0 +1,83 @@
+/// Tests of the network framing of NBT, and of item components
+use crate::components::{read_components, write_components};
+use crate::data::NBT;
+use crate::network::{read_nameless, read_varint, write_nameless, write_varint};
+
+use super::tests_data;
+
+#[test]
+fn varint_loop() {
+    let cases: [(i32, &[u8]); 6] = [
+        (0, &[0x00]),
+        (1, &[0x01]),
+        (127, &[0x7f]),
+        (300, &[0xac, 0x02]),
+        (i32::MAX, &[0xff, 0xff, 0xff, 0xff, 0x07]),
+        (-1, &[0xff, 0xff, 0xff, 0xff, 0x0f]),
+    ];
+    for (val, bytes) in cases.iter() {
+        let mut buf = Vec::new();
+        write_varint(&mut buf, *val).unwrap();
+        assert_eq!(&buf[..], *bytes);
+        assert_eq!(read_varint(&mut &bytes[..]).unwrap(), *val);
+    }
+
+    assert!(read_varint(&mut &[0xff, 0xff, 0xff, 0xff, 0xff, 0x01][..]).is_err());
+    assert!(read_varint(&mut &[0x80][..]).is_err());
+}
+
+#[test]
+fn nameless_loop() {
+    /* The root of network NBT may be any type, not just a compound */
+    let tag = NBT::String(b"hi".to_vec());
+    let mut buf = Vec::new();
+    write_nameless(&mut buf, &tag).unwrap();
+    assert_eq!(buf, b"\x08\x00\x02hi");
+    assert_eq!(read_nameless(&mut &buf[..]).unwrap(), tag);
+
+    assert!(read_nameless(&mut &[0x00][..]).is_err());
+}
+
+#[test]
+fn components_loop() {
+    let nbt = read_components(&mut &tests_data::COMPONENTS[..]).unwrap();
+    assert_eq!(
+        nbt.root,
+        NBT::Compound(vec![
+            (
+                b"added".to_vec(),
+                NBT::Compound(vec![(
+                    b"0".to_vec(),
+                    NBT::Compound(vec![
+                        (b"owner".to_vec(), NBT::String(b"C4K3".to_vec())),
+                        (b"count".to_vec(), NBT::Int(300)),
+                    ]),
+                )]),
+            ),
+            (b"removed".to_vec(), NBT::list(vec![NBT::Int(300)])),
+        ])
+    );
+
+    let mut buf = Vec::new();
+    write_components(&mut buf, &nbt).unwrap();
+    assert_eq!(&buf[..], &tests_data::COMPONENTS[..]);
+}
+
+#[test]
+fn components_invalid() {
+    /* Trailing junk */
+    let mut data = tests_data::COMPONENTS.to_vec();
+    data.push(0);
+    assert!(read_components(&mut &data[..]).is_err());
+
+    /* Cut short */
+    let data = &tests_data::COMPONENTS[..tests_data::COMPONENTS.len() - 1];
+    assert!(read_components(&mut &data[..]).is_err());
+
+    /* A type id that isn't a number */
+    let mut nbt = read_components(&mut &tests_data::COMPONENTS[..]).unwrap();
+    if let NBT::Compound(ref mut x) = nbt.root {
+        x[0].1 = NBT::Compound(vec![(b"custom_data".to_vec(), NBT::Byte(1))]);
+    }
+    assert!(write_components(&mut Vec::new(), &nbt).is_err());
+}
@@ -0,0 +1,214 @@
+/// Tests of making, applying, writing and reading binary patches
+use std::io::Cursor;
+
+use crate::data::NBT;
+use crate::patch::{self, PatchOp};
+use crate::path::{NbtPath, Segment};
+
+use proptest::prelude::*;
+use proptest::test_runner::{RngAlgorithm, TestRng, TestRunner};
+
+use super::bigtest;
+use super::random::{self, generate};
+use super::tests_data;
+
+/// Make a patch from old to new, write and read it back, apply it to old and
+/// check that the result is new
+fn check_patch(old: &NBT, new: &NBT) -> Vec<PatchOp> {
+    let ops = patch::make_patch(old, new);
+
+    let mut buf = Vec::new();
+    patch::write_patch(&mut buf, &ops).unwrap();
+    let read = patch::read_patch(&mut Cursor::new(&buf)).unwrap();
+    assert_eq!(read.len(), ops.len());
+
+    let mut patched = old.clone();
+    patch::apply_patch(&mut patched, &read).unwrap();
+    assert!(patched.bitwise_eq(new), "{:?}\n!=\n{:?}", patched, new);
+
+    ops
+}
+
+fn key(path: &[&str]) -> NbtPath {
+    let mut ret = NbtPath::new();
+    for x in path {
+        ret.push(Segment::Key(x.as_bytes().to_vec()));
+    }
+    ret
+}
+
+#[test]
+fn identical_files() {
+    let nbt = bigtest().root;
+    assert!(check_patch(&nbt, &nbt).is_empty());
+}
+
+#[test]
+fn small_edit() {
+    let old = bigtest().root;
+    let mut new = old.clone();
+    *new.get_path_mut(&[b"Level", b"intTest"]).unwrap() = NBT::Int(5);
+    let _: NBT = new
+        .get_path_mut(&[b"Level"])
+        .unwrap()
+        .remove(b"byteTest")
+        .unwrap();
+
+    let ops = check_patch(&old, &new);
+    assert_eq!(
+        ops,
+        vec![
+            PatchOp::Remove {
+                path: key(&["Level", "byteTest"]),
+            },
+            PatchOp::Set {
+                path: key(&["Level", "intTest"]),
+                value: NBT::Int(5),
+            },
+        ]
+    );
+}
+
+#[test]
+fn inserts_keep_order() {
+    let old = NBT::Compound(vec![
+        (b"a".to_vec(), NBT::Byte(1)),
+        (b"c".to_vec(), NBT::Byte(3)),
+    ]);
+    let new = NBT::Compound(vec![
+        (b"z".to_vec(), NBT::Byte(0)),
+        (b"a".to_vec(), NBT::Byte(1)),
+        (b"b".to_vec(), NBT::Byte(2)),
+        (b"c".to_vec(), NBT::Byte(3)),
+    ]);
+    assert_eq!(check_patch(&old, &new).len(), 2);
+
+    /* Reordered keys replace the whole compound */
+    let swapped = NBT::Compound(vec![
+        (b"c".to_vec(), NBT::Byte(3)),
+        (b"a".to_vec(), NBT::Byte(1)),
+    ]);
+    assert_eq!(check_patch(&old, &swapped).len(), 1);
+}
+
+#[test]
+fn lists() {
+    let old = NBT::list(vec![NBT::Int(1), NBT::Int(2), NBT::Int(3)]);
+
+    let shorter = NBT::list(vec![NBT::Int(1)]);
+    assert_eq!(check_patch(&old, &shorter).len(), 2);
+
+    let longer = NBT::list(vec![NBT::Int(1), NBT::Int(2), NBT::Int(3), NBT::Int(4)]);
+    assert_eq!(check_patch(&old, &longer).len(), 1);
+
+    /* A list changing type is replaced as a whole, so it's never mixed */
+    let shorts = NBT::list(vec![NBT::Short(1), NBT::Short(2)]);
+    assert_eq!(check_patch(&old, &shorts).len(), 1);
+}
+
+#[test]
+fn apply_errors() {
+    let mut nbt = bigtest().root;
+    let missing = PatchOp::Remove {
+        path: key(&["Level", "nothing"]),
+    };
+    assert_eq!(
+        patch::apply_patch(&mut nbt, &[missing])
+            .unwrap_err()
+            .to_string(),
+        "Unable to remove Level/nothing, it doesn't exist"
+    );
+
+    let mut list = NBT::list(vec![NBT::Int(1)]);
+    let mixed = PatchOp::Insert {
+        path: NbtPath::new().join(Segment::Index(1)),
+        position: 1,
+        value: NBT::Byte(2),
+    };
+    assert_eq!(
+        patch::apply_patch(&mut list, &[mixed])
+            .unwrap_err()
+            .to_string(),
+        "Unable to put Byte at 1 in a list of Int"
+    );
+}
+
+#[test]
+fn not_a_patch() {
+    let err = patch::read_patch(&mut Cursor::new(&tests_data::HELLO_WORLD[..])).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Not an NBT patch, it doesn't start with NBTPATCH"
+    );
+}
+
+/// A random number in 0..n
+fn below(runner: &mut TestRunner, n: usize) -> usize {
+    generate(runner, 0..n)
+}
+
+/// Make a few random edits somewhere in the tree: removing, adding or
+/// replacing a tag inside of a compound or list
+fn mutate(runner: &mut TestRunner, tag: &mut NBT, depth: u32) {
+    match tag {
+        NBT::Compound(x) if !x.is_empty() && below(runner, 2) == 0 => {
+            let i = below(runner, x.len());
+            mutate(runner, &mut x[i].1, depth + 1);
+        }
+        NBT::List { items: x, .. } if !x.is_empty() && below(runner, 2) == 0 => {
+            let i = below(runner, x.len());
+            mutate(runner, &mut x[i], depth + 1);
+        }
+        NBT::Compound(x) => match below(runner, 3) {
+            0 if !x.is_empty() => {
+                let _: (Vec<u8>, NBT) = x.remove(below(runner, x.len()));
+            }
+            _ => {
+                let name = generate(runner, random::string());
+                if x.iter().all(|(k, _)| *k != name) {
+                    let tag = generate(runner, random::any_tag(depth));
+                    let position = below(runner, x.len() + 1);
+                    x.insert(position, (name, tag));
+                }
+            }
+        },
+        NBT::List {
+            element_type,
+            items: x,
+        } => match below(runner, 3) {
+            0 if !x.is_empty() => {
+                let _: NBT = x.remove(below(runner, x.len()));
+            }
+            _ => {
+                let type_id = match x.first() {
+                    Some(first) => first.type_byte(),
+                    None => generate(runner, random::type_id()),
+                };
+                let tag = generate(runner, random::tag(type_id, depth));
+                let position = below(runner, x.len() + 1);
+                x.insert(position, tag);
+                *element_type = type_id;
+            }
+        },
+        x => *x = generate(runner, random::tag(x.type_byte(), depth)),
+    }
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(random::TREES))]
+
+    /// The edits are picked with an rng seeded by proptest, so that a failing
+    /// case can be recreated
+    #[test]
+    fn random_patches(old in random::root(), seed in any::<[u8; 32]>()) {
+        let rng = TestRng::from_seed(RngAlgorithm::ChaCha, &seed);
+        let mut runner = TestRunner::new_with_rng(ProptestConfig::default(), rng);
+        let mut new = old.clone();
+        for _ in 0..1 + below(&mut runner, 4) {
+            mutate(&mut runner, &mut new, 0);
+        }
+        let _: Vec<PatchOp> = check_patch(&old, &new);
+        /* And the other way around, which turns inserts into removals */
+        let _: Vec<PatchOp> = check_patch(&new, &old);
+    }
+}
@@ -0,0 +1,282 @@
+/// Randomly generated NBT trees, to run the loops on far more shapes of data
+/// than the fixed test files contain: odd float values, empty containers,
+/// deeply nested lists and strings full of characters that need escaping.
+///
+/// The trees are generated by proptest, which shrinks a failing tree down to
+/// the smallest one that still fails.
+use std::io::Cursor;
+
+use proptest::collection::vec;
+use proptest::prelude::*;
+use proptest::sample::select;
+use proptest::strategy::ValueTree;
+use proptest::test_runner::TestRunner;
+
+use crate::data::{Compression, NBTFile, NBT};
+use crate::string_write::TextFormat;
+
+use super::{assert_bitwise_eq, complete_loop_from_enum};
+
+/// How many trees random_loops checks
+pub(super) const TREES: u32 = 200;
+/// How deeply containers may be nested inside of the root
+pub(super) const MAX_DEPTH: u32 = 4;
+/// The most tags in a compound or list
+const MAX_CONTAINER_LEN: usize = 6;
+/// The most elements in an array
+const MAX_ARRAY_LEN: usize = 20;
+
+/// A random string, favouring characters that are awkward in the text format
+pub(super) fn string() -> impl Strategy<Value = Vec<u8>> {
+    const CHARS: &[&str] = &[
+        "a", "Z", "0", " ", "\"", "\\", "#", "\n", "\t", "\r", "{", "}", "End", "ä", "€", "𝄞",
+        "\u{0}", "\u{7f}",
+    ];
+    vec(select(CHARS), 0..12).prop_map(|x| x.concat().into_bytes())
+}
+
+fn f32s() -> impl Strategy<Value = f32> {
+    prop_oneof![
+        1 => select(&[
+            0.0,
+            -0.0,
+            1.5,
+            f32::MIN_POSITIVE,
+            f32::MAX,
+            f32::INFINITY,
+            f32::NEG_INFINITY,
+            f32::NAN,
+            -f32::NAN,
+        ][..]),
+        3 => any::<u32>().prop_map(f32::from_bits),
+    ]
+}
+
+fn f64s() -> impl Strategy<Value = f64> {
+    prop_oneof![
+        1 => select(&[
+            0.0,
+            -0.0,
+            0.1,
+            f64::MIN_POSITIVE,
+            f64::MAX,
+            f64::INFINITY,
+            f64::NEG_INFINITY,
+            f64::NAN,
+            -f64::NAN,
+        ][..]),
+        3 => any::<u64>().prop_map(f64::from_bits),
+    ]
+}
+
+/// Every tag type except End
+pub(super) fn type_id() -> impl Strategy<Value = u8> {
+    1..=12u8
+}
+
+/// A random tag of the given type. Containers are only generated while
+/// depth is below MAX_DEPTH, deeper than that they are always empty.
+pub(super) fn tag(type_byte: u8, depth: u32) -> BoxedStrategy<NBT> {
+    let container_len = if depth < MAX_DEPTH {
+        MAX_CONTAINER_LEN
+    } else {
+        0
+    };
+
+    match type_byte {
+        1 => any::<i8>().prop_map(NBT::Byte).boxed(),
+        2 => any::<i16>().prop_map(NBT::Short).boxed(),
+        3 => any::<i32>().prop_map(NBT::Int).boxed(),
+        4 => any::<i64>().prop_map(NBT::Long).boxed(),
+        5 => f32s().prop_map(NBT::Float).boxed(),
+        6 => f64s().prop_map(NBT::Double).boxed(),
+        7 => vec(any::<i8>(), 0..=MAX_ARRAY_LEN)
+            .prop_map(NBT::ByteArray)
+            .boxed(),
+        8 => string().prop_map(NBT::String).boxed(),
+        /* Lists have a single element type */
+        9 => type_id()
+            .prop_flat_map(move |element_type| {
+                vec(tag(element_type, depth + 1), 0..=container_len).prop_map(move |items| {
+                    NBT::List {
+                        element_type,
+                        items,
+                    }
+                })
+            })
+            .boxed(),
+        10 => compound(container_len, depth)
+            .prop_map(NBT::Compound)
+            .boxed(),
+        11 => vec(any::<i32>(), 0..=MAX_ARRAY_LEN)
+            .prop_map(NBT::IntArray)
+            .boxed(),
+        12 => vec(any::<i64>(), 0..=MAX_ARRAY_LEN)
+            .prop_map(NBT::LongArray)
+            .boxed(),
+        _ => unreachable!(),
+    }
+}
+
+/// A random tag of any type but End
+pub(super) fn any_tag(depth: u32) -> BoxedStrategy<NBT> {
+    type_id()
+        .prop_flat_map(move |type_id| tag(type_id, depth))
+        .boxed()
+}
+
+/// The contents of a compound with up to max_len tags in it, with unique
+/// names
+fn compound(max_len: usize, depth: u32) -> impl Strategy<Value = Vec<(Vec<u8>, NBT)>> {
+    vec((string(), any_tag(depth + 1)), 0..=max_len).prop_map(unique_names)
+}
+
+/// Drop the tags whose names are already taken by an earlier one, since the
+/// generated names often collide once proptest shrinks them
+fn unique_names(tags: Vec<(Vec<u8>, NBT)>) -> Vec<(Vec<u8>, NBT)> {
+    let mut map: Vec<(Vec<u8>, NBT)> = Vec::new();
+    for (name, tag) in tags {
+        if map.iter().all(|(k, _)| *k != name) {
+            map.push((name, tag));
+        }
+    }
+    map
+}
+
+/// The root of a file, which is usually a single named compound, but may
+/// also be empty or have several in it. Only compounds are put directly in
+/// the root, since uncompressed files are recognised by starting with one,
+/// and they have unique names like the tags of any other compound.
+pub(super) fn root() -> impl Strategy<Value = NBT> {
+    let count = prop_oneof![1 => 0..4usize, 3 => Just(1)];
+    count
+        .prop_flat_map(|count| vec((string(), tag(10, 0)), count))
+        .prop_map(|x| NBT::Compound(unique_names(x)))
+}
+
+/// Generate a single value, such as for picking where to edit a tree
+pub(super) fn generate<S: Strategy>(runner: &mut TestRunner, strategy: S) -> S::Value {
+    strategy.new_tree(runner).unwrap().current()
+}
+
+/// Write the file to binary and read it back, checking that the tree is the
+/// same and that writing it again gives the same bytes
+fn binary_loop(original: &NBTFile) {
+    let mut first = Vec::new();
+    crate::write::write_file(&mut first, original).unwrap();
+    let looped = crate::read::read_file(&mut Cursor::new(&first)).unwrap();
+    assert_bitwise_eq(original, &looped);
+
+    let mut second = Vec::new();
+    crate::write::write_file(&mut second, &looped).unwrap();
+    /* Compressed output only has to decompress to the same thing */
+    if original.compression == Compression::None {
+        assert_eq!(first, second);
+    }
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(TREES))]
+
+    #[test]
+    fn random_loops(root in root()) {
+        for compression in &[
+            Compression::None,
+            Compression::Gzip,
+            Compression::Zlib,
+            #[cfg(feature = "zstd")]
+            Compression::Zstd,
+            #[cfg(feature = "lz4")]
+            Compression::Lz4,
+        ] {
+            let file = NBTFile {
+                root: root.clone(),
+                compression: compression.clone(),
+                compression_level: None,
+            };
+            binary_loop(&file);
+            complete_loop_from_enum(&file);
+        }
+    }
+}
+
+/// Every Float and Double must be read back from the text format with the
+/// same bits, including -0.0 and NaNs with a sign or a payload
+#[test]
+fn float_bits_loop() {
+    const SAMPLES: usize = 10_000;
+    let mut runner = TestRunner::deterministic();
+    let floats = generate(
+        &mut runner,
+        vec(
+            any::<u32>().prop_map(|x| NBT::Float(f32::from_bits(x))),
+            SAMPLES,
+        ),
+    );
+    let doubles = generate(
+        &mut runner,
+        vec(
+            any::<u64>().prop_map(|x| NBT::Double(f64::from_bits(x))),
+            SAMPLES,
+        ),
+    );
+    let special = [
+        NBT::Float(-0.0),
+        NBT::Float(-f32::NAN),
+        NBT::Float(f32::from_bits(0x7f80_0001)),
+        NBT::Double(-0.0),
+        NBT::Double(-f64::NAN),
+        NBT::Double(f64::from_bits(0x7ff0_0000_0000_0001)),
+    ];
+    let file = NBTFile {
+        root: NBT::Compound(
+            floats
+                .into_iter()
+                .chain(doubles)
+                .chain(special.iter().cloned())
+                .enumerate()
+                .map(|(i, x)| (i.to_string().into_bytes(), x))
+                .collect(),
+        ),
+        compression: Compression::None,
+        compression_level: None,
+    };
+
+    for format in &[TextFormat::default(), TextFormat::canonical()] {
+        let mut text = Vec::new();
+        crate::string_write::write_file_with(&mut text, &file, format).unwrap();
+        let looped = crate::string_read::read_file(&mut Cursor::new(text)).unwrap();
+        /* The canonical format sorts the keys */
+        let mut root = match looped.root {
+            NBT::Compound(x) => x,
+            _ => unreachable!(),
+        };
+        root.sort_by_key(|(k, _)| {
+            String::from_utf8(k.clone())
+                .unwrap()
+                .parse::<usize>()
+                .unwrap()
+        });
+        let looped = NBTFile {
+            root: NBT::Compound(root),
+            ..looped
+        };
+        assert_bitwise_eq(&file, &looped);
+    }
+}
+
+/// The generator should actually produce the awkward cases it is meant to
+#[test]
+fn random_trees_have_edge_cases() {
+    let mut runner = TestRunner::deterministic();
+    let trees: Vec<String> = (0..TREES)
+        .map(|_| format!("{:?}", generate(&mut runner, root())))
+        .collect();
+    let any = |x: &str| trees.iter().any(|tree| tree.contains(x));
+
+    assert!(any("items: []"));
+    assert!(any("Compound([])"));
+    assert!(any("NaN"));
+    assert!(any("inf"));
+    assert!(any("items: [List {"));
+}
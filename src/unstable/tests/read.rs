@@ -0,0 +1,605 @@
+use std::io::{self, Cursor, Read};
+
+use crate::data::{Compression, Endianness, NBTFile, NBT};
+use crate::read::{
+    read_file, read_file_from, read_file_preview, read_file_recursive_decompress, read_file_with,
+    read_file_with_options, read_leading, skip_tag, ReadOptions, DEFAULT_MAX_DEPTH,
+};
+
+use super::tests_data;
+
+/// Check that previewing the file gives the first limit tags of the full read
+/// of every root compound, and that the rest are counted as skipped
+fn check_preview(data: &[u8], limit: usize) {
+    let full = read_file(&mut Cursor::new(data)).unwrap();
+    let (preview, skipped) =
+        read_file_preview(&mut Cursor::new(data), limit, &ReadOptions::default()).unwrap();
+
+    assert_eq!(full.compression, preview.compression);
+
+    let (full_root, preview_root) = match (&full.root, &preview.root) {
+        (NBT::Compound(a), NBT::Compound(b)) => (a, b),
+        _ => panic!("root is not a compound"),
+    };
+    assert_eq!(full_root.len(), preview_root.len());
+
+    let mut expected_skipped = 0;
+    for ((full_name, full_tag), (preview_name, preview_tag)) in
+        full_root.iter().zip(preview_root.iter())
+    {
+        assert_eq!(full_name, preview_name);
+        match (full_tag, preview_tag) {
+            (NBT::Compound(a), NBT::Compound(b)) => {
+                let kept = a.len().min(limit);
+                assert_eq!(&a[..kept], &b[..]);
+                expected_skipped += a.len() - kept;
+            }
+            (a, b) => assert_eq!(a, b),
+        }
+    }
+    assert_eq!(skipped, expected_skipped);
+}
+
+#[test]
+fn preview_truncates() {
+    check_preview(&tests_data::BIGTEST_UNCOMPRESSED, 3);
+    check_preview(&tests_data::PLAYER_FILE, 5);
+}
+
+#[test]
+fn preview_compressed() {
+    check_preview(&tests_data::BIGTEST_COMPRESSED, 2);
+    check_preview(&tests_data::BIGTEST_ZLIB, 2);
+}
+
+#[test]
+fn preview_everything() {
+    let (_, skipped) = read_file_preview(
+        &mut Cursor::new(&tests_data::BIGTEST_UNCOMPRESSED[..]),
+        1000,
+        &ReadOptions::default(),
+    )
+    .unwrap();
+    assert_eq!(skipped, 0);
+    check_preview(&tests_data::BIGTEST_UNCOMPRESSED, 1000);
+}
+
+#[test]
+fn preview_nothing() {
+    let (preview, skipped) = read_file_preview(
+        &mut Cursor::new(&tests_data::HELLO_WORLD[..]),
+        0,
+        &ReadOptions::default(),
+    )
+    .unwrap();
+    assert_eq!(skipped, 1);
+    assert_eq!(
+        preview.root.get(b"hello world"),
+        Some(&NBT::Compound(Vec::new()))
+    );
+}
+
+#[test]
+fn skip_tag_truncated() {
+    /* A String claiming to be 5 bytes long with only 2 bytes of data */
+    let data = [0x00, 0x05, b'a', b'b'];
+    assert!(skip_tag(&mut &data[..], 0x08).is_err());
+
+    /* An IntArray with a negative length */
+    let data = [0xff, 0xff, 0xff, 0xff];
+    assert!(skip_tag(&mut &data[..], 0x0b).is_err());
+}
+
+#[test]
+fn huge_length() {
+    /* A root compound with an IntArray named "a" claiming i32::MAX elements,
+     * with none following */
+    let data = [
+        0x0a, 0x00, 0x00, 0x0b, 0x00, 0x01, b'a', 0x7f, 0xff, 0xff, 0xff,
+    ];
+    assert!(read_file(&mut &data[..]).is_err());
+
+    /* The same for a ByteArray, a LongArray and a List of Ints */
+    for &type_id in &[0x07, 0x0c] {
+        let mut data = data.to_vec();
+        data[3] = type_id;
+        assert!(read_file(&mut &data[..]).is_err());
+    }
+    let data = [
+        0x0a, 0x00, 0x00, 0x09, 0x00, 0x01, b'a', 0x03, 0x7f, 0xff, 0xff, 0xff,
+    ];
+    assert!(read_file(&mut &data[..]).is_err());
+
+    /* A List of End tags, which take up no bytes, so only the length can be
+     * rejected */
+    let data = [
+        0x0a, 0x00, 0x00, 0x09, 0x00, 0x01, b'a', 0x00, 0x7f, 0xff, 0xff, 0xff,
+    ];
+    let err = read_file(&mut &data[..]).unwrap_err();
+    assert_eq!(err.to_string(), "Got a list of 2147483647 End tags");
+}
+
+#[test]
+fn negative_length() {
+    let data = [
+        0x0a, 0x00, 0x00, 0x0b, 0x00, 0x01, b'a', 0xff, 0xff, 0xff, 0xfe,
+    ];
+    let err = read_file(&mut &data[..]).unwrap_err();
+    assert_eq!(err.to_string(), "Got negative length -2");
+
+    let data = [
+        0x0a, 0x00, 0x00, 0x09, 0x00, 0x01, b'a', 0x03, 0xff, 0xff, 0xff, 0xff,
+    ];
+    let err = read_file(&mut &data[..]).unwrap_err();
+    assert_eq!(err.to_string(), "Got negative length -1");
+}
+
+/// A file with a List named a in the root compound, with lists nested in it
+/// so that the innermost one is in depth compounds and lists, counting the
+/// implicit root compound
+fn nested_lists(depth: usize) -> Vec<u8> {
+    let mut data = vec![0x0a, 0x00, 0x00, 0x09, 0x00, 0x01, b'a'];
+    for _ in 2..depth {
+        data.extend_from_slice(&[0x09, 0x00, 0x00, 0x00, 0x01]);
+    }
+    data.extend_from_slice(&[0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+    data
+}
+
+#[test]
+fn max_depth() {
+    let data = nested_lists(DEFAULT_MAX_DEPTH);
+    assert!(read_file(&mut &data[..]).is_ok());
+    assert!(skip_tag(&mut &data[7..], 0x09).is_ok());
+
+    for &depth in &[DEFAULT_MAX_DEPTH + 1, 100_000] {
+        let data = nested_lists(depth);
+        let err = read_file(&mut &data[..]).unwrap_err();
+        assert_eq!(err.to_string(), "NBT nesting exceeds max depth 512");
+    }
+    let data = nested_lists(100_000);
+    assert!(skip_tag(&mut &data[7..], 0x09).is_err());
+
+    let options = ReadOptions {
+        max_depth: Some(2),
+        ..Default::default()
+    };
+    assert!(read_with_options(&nested_lists(2), &options).is_ok());
+    let err = read_with_options(&nested_lists(3), &options).unwrap_err();
+    assert_eq!(err.to_string(), "NBT nesting exceeds max depth 2");
+}
+
+#[test]
+fn skip_tag_stops_at_end() {
+    /* A Compound containing a Byte, followed by data that must not be read */
+    let data = [0x01, 0x00, 0x01, b'a', 0x05, 0x00, 0xaa];
+    let mut reader = &data[..];
+    skip_tag(&mut reader, 0x0a).unwrap();
+    assert_eq!(reader, &[0xaa]);
+}
+
+/// A reader that is only Read, to check that read_file_from doesn't need
+/// BufRead
+struct Unbuffered<'a>(&'a [u8]);
+impl<'a> Read for Unbuffered<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.read(buf)
+    }
+}
+
+#[test]
+fn read_from_unbuffered() {
+    for data in &[
+        &tests_data::HELLO_WORLD[..],
+        &tests_data::BIGTEST_UNCOMPRESSED[..],
+        &tests_data::BIGTEST_COMPRESSED[..],
+        &tests_data::BIGTEST_ZLIB[..],
+        #[cfg(feature = "zstd")]
+        &tests_data::BIGTEST_ZSTD[..],
+        #[cfg(feature = "lz4")]
+        &tests_data::BIGTEST_LZ4[..],
+    ] {
+        let expected = read_file(&mut Cursor::new(data)).unwrap();
+        let nbt = read_file_from(&mut Unbuffered(data)).unwrap();
+        assert_eq!(nbt, expected);
+    }
+
+    assert!(read_file_from(&mut Unbuffered(&[])).is_err());
+    assert!(read_file_from(&mut Unbuffered(&[0xff])).is_err());
+}
+
+fn read_with_options(data: &[u8], options: &ReadOptions) -> crate::Result<NBTFile> {
+    read_file_with_options(&mut Cursor::new(data), options)
+}
+
+#[test]
+fn max_tags() {
+    /* hello_world has the root compound and the String inside of it */
+    let options = ReadOptions {
+        max_tags: Some(2),
+        ..Default::default()
+    };
+    assert!(read_with_options(&tests_data::HELLO_WORLD, &options).is_ok());
+
+    let options = ReadOptions {
+        max_tags: Some(1),
+        ..Default::default()
+    };
+    let err = read_with_options(&tests_data::HELLO_WORLD, &options).unwrap_err();
+    assert_eq!(err.to_string(), "exceeded maximum tag count 1");
+}
+
+#[test]
+fn max_tags_counts_list_elements() {
+    /* bigtest has Level with 11 tags in it, the 2 compounds inside of nested
+     * compound test with 2 tags each, the 5 Longs in listTest (long), and the
+     * 2 compounds in listTest (compound) with 2 tags each */
+    let options = ReadOptions {
+        max_tags: Some(29),
+        ..Default::default()
+    };
+    assert!(read_with_options(&tests_data::BIGTEST_UNCOMPRESSED, &options).is_ok());
+
+    let options = ReadOptions {
+        max_tags: Some(28),
+        ..Default::default()
+    };
+    assert!(read_with_options(&tests_data::BIGTEST_UNCOMPRESSED, &options).is_err());
+}
+
+#[test]
+fn duplicate_keys() {
+    /* A root compound with a Byte a followed by an Int a */
+    let data = [
+        0x0a, 0x00, 0x00, 0x01, 0x00, 0x01, b'a', 0x01, 0x03, 0x00, 0x01, b'a', 0x00, 0x00, 0x00,
+        0x02, 0x00,
+    ];
+
+    let nbt = read_with_options(&data, &ReadOptions::default()).unwrap();
+    assert_eq!(
+        nbt.root.get(""),
+        Some(&NBT::Compound(vec![
+            (b"a".to_vec(), NBT::Byte(1)),
+            (b"a".to_vec(), NBT::Int(2)),
+        ]))
+    );
+
+    let options = ReadOptions {
+        forbid_duplicate_keys: true,
+        ..Default::default()
+    };
+    let err = read_with_options(&data, &options).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Duplicate key a in compound, the second one is a Int"
+    );
+    /* The same key in different compounds is fine */
+    assert!(read_with_options(&tests_data::BIGTEST_UNCOMPRESSED, &options).is_ok());
+}
+
+#[test]
+fn max_string_bytes() {
+    /* The names "hello world" and "name", plus the value "Bananrama" */
+    let options = ReadOptions {
+        max_string_bytes: Some(24),
+        ..Default::default()
+    };
+    assert!(read_with_options(&tests_data::HELLO_WORLD, &options).is_ok());
+
+    let options = ReadOptions {
+        max_string_bytes: Some(23),
+        ..Default::default()
+    };
+    let err = read_with_options(&tests_data::HELLO_WORLD, &options).unwrap_err();
+    assert_eq!(err.to_string(), "exceeded maximum total string length 23");
+}
+
+/// Compress the data with gzip, and then the result of that with zlib
+fn double_compress(data: &[u8]) -> Vec<u8> {
+    use flate2::write::{GzEncoder, ZlibEncoder};
+    use flate2::Compression as Level;
+    use std::io::Write;
+
+    let mut gz = GzEncoder::new(Vec::new(), Level::default());
+    gz.write_all(data).unwrap();
+    let mut zlib = ZlibEncoder::new(Vec::new(), Level::default());
+    zlib.write_all(&gz.finish().unwrap()).unwrap();
+    zlib.finish().unwrap()
+}
+
+#[test]
+fn recursive_decompress() {
+    let data = double_compress(&tests_data::BIGTEST_UNCOMPRESSED);
+    let original = read_file(&mut Cursor::new(&tests_data::BIGTEST_UNCOMPRESSED[..])).unwrap();
+
+    assert!(read_file(&mut Cursor::new(&data)).is_err());
+
+    let (nbt, layers) =
+        read_file_recursive_decompress(&mut Cursor::new(&data), &ReadOptions::default()).unwrap();
+    assert_eq!(layers, 2);
+    assert_eq!(nbt.root, original.root);
+    assert_eq!(nbt.compression, Compression::Zlib);
+
+    /* The option makes read_file_with_options do the same */
+    let options = ReadOptions {
+        recursive_decompress: true,
+        ..Default::default()
+    };
+    assert_eq!(
+        read_with_options(&data, &options).unwrap().root,
+        original.root
+    );
+
+    /* Files that aren't compressed at all are read as usual */
+    let (nbt, layers) = read_file_recursive_decompress(
+        &mut Cursor::new(&tests_data::BIGTEST_UNCOMPRESSED[..]),
+        &ReadOptions::default(),
+    )
+    .unwrap();
+    assert_eq!(layers, 0);
+    assert_eq!(nbt.compression, Compression::None);
+}
+
+#[test]
+fn recursive_decompress_limit() {
+    let mut data = double_compress(&tests_data::HELLO_WORLD);
+    data = double_compress(&data);
+    assert!(
+        read_file_recursive_decompress(&mut Cursor::new(&data), &ReadOptions::default()).is_ok()
+    );
+
+    data = double_compress(&data);
+    let err = read_file_recursive_decompress(&mut Cursor::new(&data), &ReadOptions::default())
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "File is still compressed after removing 4 layers of compression"
+    );
+}
+
+/// Every layer is decompressed as it's read, so the limits apply to the NBT
+/// data inside of all of them
+#[test]
+fn recursive_decompress_limits() {
+    let data = double_compress(&tests_data::BIGTEST_UNCOMPRESSED);
+    let options = ReadOptions {
+        max_tags: Some(28),
+        ..Default::default()
+    };
+    let err = read_file_recursive_decompress(&mut Cursor::new(&data), &options).unwrap_err();
+    assert_eq!(err.to_string(), "exceeded maximum tag count 28");
+}
+
+#[test]
+fn skip_leading() {
+    let data = &tests_data::LEADING_BYTE_HELLO_WORLD[..];
+    let hello_world = read_file(&mut Cursor::new(&tests_data::HELLO_WORLD[..])).unwrap();
+    assert!(read_file(&mut Cursor::new(data)).is_err());
+
+    let options = ReadOptions {
+        skip_leading: 1,
+        ..Default::default()
+    };
+    assert_eq!(read_with_options(data, &options).unwrap(), hello_world);
+    let (preview, _) = read_file_preview(&mut Cursor::new(data), 10, &options).unwrap();
+    assert_eq!(preview, hello_world);
+
+    /* Keeping the skipped bytes lets the file be written back as it was */
+    let mut cursor = Cursor::new(data);
+    let prefix = read_leading(&mut cursor, 1).unwrap();
+    let nbt = read_file(&mut cursor).unwrap();
+    let mut written = prefix;
+    crate::write::write_file(&mut written, &nbt).unwrap();
+    assert_eq!(written, data);
+}
+
+#[test]
+fn skip_leading_too_short() {
+    let options = ReadOptions {
+        skip_leading: 100,
+        ..Default::default()
+    };
+    let err = read_with_options(&tests_data::HELLO_WORLD, &options).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "File is shorter than the 100 leading bytes to skip"
+    );
+}
+
+/// The number of bytes to skip isn't allocated up front, so a huge one is
+/// just an error
+#[test]
+fn skip_leading_huge() {
+    let err = read_leading(&mut &tests_data::HELLO_WORLD[..], usize::MAX).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "File is shorter than the {} leading bytes to skip",
+            usize::MAX
+        )
+    );
+}
+
+/// Zlib files are written back with the level given by their header. The
+/// compressed data can still differ from what another zlib implementation
+/// wrote, but the header and size are kept.
+#[test]
+fn zlib_level_kept() {
+    let original = &tests_data::BIGTEST_ZLIB[..];
+    let nbt = read_file(&mut Cursor::new(original)).unwrap();
+    assert_eq!(nbt.compression_level, None);
+
+    let mut rewritten = Vec::new();
+    crate::write::write_file(&mut rewritten, &nbt).unwrap();
+    assert_eq!(rewritten[..2], original[..2]);
+    assert_eq!(rewritten.len(), original.len());
+
+    /* Every level is read back as one that writes the same header */
+    for level in 0..10 {
+        let file = NBTFile {
+            compression_level: Some(level),
+            ..read_file(&mut Cursor::new(original)).unwrap()
+        };
+        let mut first = Vec::new();
+        crate::write::write_file(&mut first, &file).unwrap();
+
+        let file = read_file(&mut Cursor::new(&first)).unwrap();
+        let mut second = Vec::new();
+        crate::write::write_file(&mut second, &file).unwrap();
+        assert_eq!(first[..2], second[..2], "level {}", level);
+    }
+}
+
+#[test]
+fn compression_level_only_for_zlib() {
+    let gzip = read_file(&mut Cursor::new(&tests_data::BIGTEST_COMPRESSED[..])).unwrap();
+    assert_eq!(gzip.compression_level, None);
+    let none = read_file(&mut Cursor::new(&tests_data::BIGTEST_UNCOMPRESSED[..])).unwrap();
+    assert_eq!(none.compression_level, None);
+}
+
+/// An uncompressed file with little endian numbers, except for the elements
+/// of the IntArray, which are big endian
+fn mixed_endian_file() -> Vec<u8> {
+    let mut data = vec![0x0a, 0, 0];
+    data.extend_from_slice(&[0x03, 1, 0, b'a', 1, 0, 0, 0]);
+    data.extend_from_slice(&[0x0b, 1, 0, b'b', 2, 0, 0, 0]);
+    data.extend_from_slice(&[0, 0, 0, 1, 0, 0, 0, 2]);
+    data.extend_from_slice(&[0x08, 1, 0, b's', 2, 0, b'h', b'i']);
+    data.push(0);
+    data
+}
+
+#[test]
+fn array_endianness() {
+    let options = ReadOptions {
+        endianness: Endianness::Little,
+        array_endianness: Some(Endianness::Big),
+        ..Default::default()
+    };
+    let nbt = read_file_with_options(&mut &mixed_endian_file()[..], &options).unwrap();
+    assert_eq!(
+        nbt.root,
+        NBT::Compound(vec![(
+            Vec::new(),
+            NBT::Compound(vec![
+                (b"a".to_vec(), NBT::Int(1)),
+                (b"b".to_vec(), NBT::IntArray(vec![1, 2])),
+                (b"s".to_vec(), NBT::String(b"hi".to_vec())),
+            ])
+        )])
+    );
+
+    /* By default the elements have the same byte order as everything else */
+    let options = ReadOptions {
+        endianness: Endianness::Little,
+        ..Default::default()
+    };
+    let nbt = read_file_with_options(&mut &mixed_endian_file()[..], &options).unwrap();
+    assert_eq!(
+        nbt.root.get("").and_then(|x| x.get("b")),
+        Some(&NBT::IntArray(vec![1 << 24, 2 << 24]))
+    );
+
+    /* Skipping over the tags in a preview uses the byte order of the file */
+    let (preview, skipped) = read_file_preview(&mut &mixed_endian_file()[..], 1, &options).unwrap();
+    assert_eq!(skipped, 2);
+    assert_eq!(
+        preview.root.get("").and_then(|x| x.get("a")),
+        Some(&NBT::Int(1))
+    );
+}
+
+/// Bedrock Edition's level.dat is read in little endian after its header,
+/// which gives the length of the rest of the file
+#[test]
+fn bedrock_level_dat() {
+    let data = &tests_data::BEDROCK_LEVEL_DAT[..];
+    let payload = &data[8..];
+    assert_eq!(&data[4..8], &(payload.len() as u32).to_le_bytes());
+
+    let nbt = read_file_with(&mut &payload[..], Endianness::Little).unwrap();
+    assert_eq!(nbt.compression, Compression::None);
+    assert_eq!(
+        nbt.root,
+        NBT::Compound(vec![(
+            Vec::new(),
+            NBT::Compound(vec![
+                (b"StorageVersion".to_vec(), NBT::Int(10)),
+                (b"LevelName".to_vec(), NBT::String(b"My World".to_vec())),
+                (b"LastPlayed".to_vec(), NBT::Long(1_700_000_000)),
+                (b"rainLevel".to_vec(), NBT::Float(0.5)),
+                (
+                    b"lastOpenedWithVersion".to_vec(),
+                    NBT::list(vec![NBT::Int(1), NBT::Int(20), NBT::Int(80)])
+                ),
+            ])
+        )])
+    );
+
+    /* Skipping the header with the options reads the same */
+    let options = ReadOptions {
+        skip_leading: 8,
+        endianness: Endianness::Little,
+        ..Default::default()
+    };
+    let skipped = read_file_with_options(&mut &data[..], &options).unwrap();
+    assert_eq!(skipped, nbt);
+
+    /* Big endian is the same as read_file, and can't read the file */
+    assert!(read_file(&mut &payload[..]).is_err());
+    assert!(read_file_with(&mut &payload[..], Endianness::Big).is_err());
+    assert_eq!(
+        read_file_with(&mut &tests_data::HELLO_WORLD[..], Endianness::Big).unwrap(),
+        read_file(&mut &tests_data::HELLO_WORLD[..]).unwrap()
+    );
+}
+
+/// Zstd files are recognized by their magic number, and read and written
+/// like any other compression
+#[cfg(feature = "zstd")]
+#[test]
+fn zstd_file() {
+    assert_eq!(Compression::from_first_byte(0x28), Some(Compression::Zstd));
+    assert_eq!(Compression::from_str("Zstd"), Some(Compression::Zstd));
+    assert_eq!(Compression::Zstd.to_str(), "Zstd");
+
+    let nbt = read_file(&mut Cursor::new(&tests_data::BIGTEST_ZSTD[..])).unwrap();
+    assert_eq!(nbt.compression, Compression::Zstd);
+    let uncompressed = read_file(&mut Cursor::new(&tests_data::BIGTEST_UNCOMPRESSED[..])).unwrap();
+    assert_eq!(nbt.root, uncompressed.root);
+
+    let mut buf = Vec::new();
+    crate::write::write_file(&mut buf, &nbt).unwrap();
+    assert_eq!(&buf[..4], &[0x28, 0xb5, 0x2f, 0xfd]);
+    assert!(buf.len() < tests_data::BIGTEST_UNCOMPRESSED.len() / 2);
+    assert_eq!(read_file(&mut Cursor::new(buf)).unwrap(), nbt);
+
+    /* The content checksum at the end */
+    let mut data = tests_data::BIGTEST_ZSTD.to_vec();
+    let len = data.len();
+    data[len - 1] ^= 1;
+    assert!(read_file(&mut Cursor::new(data)).is_err());
+}
+
+/// Without the zstd feature Zstd files are still recognized, and reading or
+/// writing them says why they can't be
+#[cfg(not(feature = "zstd"))]
+#[test]
+fn zstd_unsupported() {
+    let err = read_file(&mut Cursor::new(&tests_data::BIGTEST_ZSTD[..])).unwrap_err();
+    assert!(err.to_string().contains("without the zstd feature"));
+
+    let mut nbt = read_file(&mut &tests_data::HELLO_WORLD[..]).unwrap();
+    nbt.compression = Compression::Zstd;
+    assert!(crate::write::write_file(&mut Vec::new(), &nbt).is_err());
+}
+
+/// Without the lz4 feature LZ4 files are still recognized, and reading them
+/// says why they can't be read
+#[cfg(not(feature = "lz4"))]
+#[test]
+fn lz4_unsupported() {
+    let err = read_file(&mut Cursor::new(&tests_data::BIGTEST_LZ4[..])).unwrap_err();
+    assert!(err.to_string().contains("without the lz4 feature"));
+}
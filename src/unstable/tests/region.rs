@@ -0,0 +1,231 @@
+use std::io::Cursor;
+
+use crate::data::{NBTFile, NBT};
+use crate::read::ReadOptions;
+use crate::region::{for_each_chunk, read_region, write_region, Chunk, ChunkIter, ChunkPos};
+
+use super::tests_data;
+
+fn chunks() -> Vec<Chunk> {
+    vec![
+        Chunk {
+            x: 0,
+            z: 0,
+            timestamp: 1000,
+            data: tests_data::HELLO_WORLD.to_vec(),
+        },
+        Chunk {
+            x: 31,
+            z: 2,
+            timestamp: 2000,
+            data: tests_data::BIGTEST_ZLIB.to_vec(),
+        },
+        Chunk {
+            x: 5,
+            z: 31,
+            timestamp: 3000,
+            data: tests_data::PLAYER_FILE.to_vec(),
+        },
+    ]
+}
+
+#[test]
+fn region_loop() {
+    let mut buf = Vec::new();
+    write_region(&mut buf, &chunks()).unwrap();
+
+    /* The tables, then one sector for each of the first two chunks, and three
+     * for the uncompressed player file */
+    assert_eq!(buf.len(), 7 * 4096);
+
+    let read = read_region(&mut Cursor::new(buf)).unwrap();
+    assert_eq!(read, chunks());
+    assert_eq!(
+        read[1].read_nbt().unwrap().compression,
+        crate::data::Compression::Zlib
+    );
+}
+
+#[test]
+fn region_layout() {
+    let mut buf = Vec::new();
+    write_region(&mut buf, &chunks()[1..2]).unwrap();
+
+    /* Chunk 31, 2 is at index 95, at sector 2 with a length of 1 sector */
+    assert_eq!(&buf[95 * 4..96 * 4], &[0, 0, 2, 1]);
+    assert_eq!(&buf[4096 + 95 * 4..4096 + 96 * 4], &2000u32.to_be_bytes());
+    /* Followed by the length including the compression byte, and 2 for zlib */
+    let length = (tests_data::BIGTEST_ZLIB.len() + 1) as u32;
+    assert_eq!(&buf[8192..8196], &length.to_be_bytes());
+    assert_eq!(buf[8196], 2);
+}
+
+#[test]
+fn region_invalid_coordinates() {
+    let mut chunks = chunks();
+    chunks[0].x = 32;
+    assert!(write_region(&mut Vec::new(), &chunks).is_err());
+}
+
+#[test]
+fn region_duplicate_chunk() {
+    let mut chunks = chunks();
+    chunks[1].x = 0;
+    chunks[1].z = 0;
+    assert!(write_region(&mut Vec::new(), &chunks).is_err());
+}
+
+#[test]
+fn region_too_short() {
+    assert!(read_region(&mut Cursor::new(vec![0; 100])).is_err());
+}
+
+/// Iterating lazily gives the same chunks as reading the whole region
+#[test]
+fn chunk_iter() {
+    let mut buf = Vec::new();
+    write_region(&mut buf, &chunks()).unwrap();
+
+    let eager = read_region(&mut Cursor::new(&buf)).unwrap();
+    let iter = ChunkIter::new(Cursor::new(&buf)).unwrap();
+    assert_eq!(iter.timestamp(ChunkPos { x: 31, z: 2 }), Some(2000));
+    assert_eq!(iter.timestamp(ChunkPos { x: 1, z: 1 }), None);
+
+    let lazy: Vec<(ChunkPos, NBTFile)> = iter.map(|(pos, nbt)| (pos, nbt.unwrap())).collect();
+    assert_eq!(lazy.len(), eager.len());
+    for (chunk, (pos, nbt)) in eager.iter().zip(lazy.iter()) {
+        assert_eq!(chunk.pos(), *pos);
+        assert_eq!(chunk.read_nbt().unwrap(), *nbt);
+    }
+
+    let mut count = 0;
+    for_each_chunk(Cursor::new(&buf), |_, nbt| {
+        let _: NBTFile = nbt?;
+        count += 1;
+        Ok(())
+    })
+    .unwrap();
+    assert_eq!(count, eager.len());
+}
+
+/// A broken chunk only gives an error for itself
+#[test]
+fn chunk_iter_broken_chunk() {
+    let mut buf = Vec::new();
+    write_region(&mut buf, &chunks()).unwrap();
+    /* Give the first chunk an unsupported compression type */
+    buf[2 * 4096 + 4] = 9;
+
+    let read: Vec<(ChunkPos, bool)> = ChunkIter::new(Cursor::new(&buf))
+        .unwrap()
+        .map(|(pos, nbt)| (pos, nbt.is_ok()))
+        .collect();
+    assert_eq!(
+        read,
+        vec![
+            (ChunkPos { x: 0, z: 0 }, false),
+            (ChunkPos { x: 31, z: 2 }, true),
+            (ChunkPos { x: 5, z: 31 }, true),
+        ]
+    );
+}
+
+/// Chunks saved too early are left out, raw chunks are as stored, and the
+/// chunks are parsed under the limits given
+#[test]
+fn chunk_iter_since() {
+    let mut buf = Vec::new();
+    write_region(&mut buf, &chunks()).unwrap();
+
+    let mut iter = ChunkIter::new(Cursor::new(&buf)).unwrap().since(2000);
+    assert_eq!(iter.chunk_count(), 3);
+    let mut raw = Vec::new();
+    while let Some((_, chunk)) = iter.next_raw() {
+        raw.push(chunk.unwrap());
+    }
+    assert_eq!(raw, &chunks()[1..]);
+
+    let options = ReadOptions {
+        max_tags: Some(5),
+        ..Default::default()
+    };
+    let read: Vec<(ChunkPos, bool)> = ChunkIter::with_options(Cursor::new(&buf), &options)
+        .unwrap()
+        .map(|(pos, nbt)| (pos, nbt.is_ok()))
+        .collect();
+    assert_eq!(
+        read,
+        vec![
+            (ChunkPos { x: 0, z: 0 }, true),
+            (ChunkPos { x: 31, z: 2 }, false),
+            (ChunkPos { x: 5, z: 31 }, false),
+        ]
+    );
+}
+
+#[test]
+fn chunk_iter_too_short() {
+    assert!(ChunkIter::new(Cursor::new(vec![0; 100])).is_err());
+}
+
+/// The chunks of a region are found at their positions, with the contents
+/// they were written with
+#[test]
+fn region_known_key() {
+    let mut buf = Vec::new();
+    write_region(&mut buf, &chunks()).unwrap();
+
+    let read: Vec<(ChunkPos, NBTFile)> = ChunkIter::new(Cursor::new(&buf))
+        .unwrap()
+        .map(|(pos, nbt)| (pos, nbt.unwrap()))
+        .collect();
+    assert_eq!(read.len(), 3);
+    assert_eq!(read[0].0, ChunkPos { x: 0, z: 0 });
+    assert_eq!(
+        read[0]
+            .1
+            .root
+            .get("hello world")
+            .and_then(|x| x.get("name")),
+        Some(&NBT::String(b"Bananrama".to_vec()))
+    );
+    assert_eq!(read[1].0, ChunkPos { x: 31, z: 2 });
+    assert_eq!(
+        read[1].1.root.get("Level").and_then(|x| x.get("shortTest")),
+        Some(&NBT::Short(32767))
+    );
+}
+
+/// Chunks that are read, edited and made into chunks again are written back
+/// with their compression, and read back the same
+#[test]
+fn region_repack() {
+    let mut buf = Vec::new();
+    write_region(&mut buf, &chunks()).unwrap();
+
+    let iter = ChunkIter::new(Cursor::new(&buf)).unwrap();
+    let timestamps: Vec<u32> = chunks().iter().map(|x| x.timestamp).collect();
+    let mut edited = Vec::new();
+    for ((pos, nbt), timestamp) in iter.zip(timestamps) {
+        let mut nbt = nbt.unwrap();
+        if let NBT::Compound(ref mut x) = nbt.root {
+            x.push((b"edited".to_vec(), NBT::Byte(1)));
+        }
+        edited.push((Chunk::from_nbt(pos, timestamp, &nbt).unwrap(), nbt));
+    }
+
+    let mut repacked = Vec::new();
+    let chunks: Vec<Chunk> = edited.iter().map(|(chunk, _)| chunk.clone()).collect();
+    write_region(&mut repacked, &chunks).unwrap();
+
+    let read = read_region(&mut Cursor::new(&repacked)).unwrap();
+    assert_eq!(read, chunks);
+    for (chunk, (_, nbt)) in read.iter().zip(edited.iter()) {
+        let reread = chunk.read_nbt().unwrap();
+        assert!(reread.bitwise_eq(nbt));
+        assert_eq!(reread.root.get("edited"), Some(&NBT::Byte(1)));
+    }
+    /* The compression byte follows the compression of each file */
+    assert_eq!(repacked[2 * 4096 + 4], 3);
+    assert_eq!(repacked[3 * 4096 + 4], 2);
+}
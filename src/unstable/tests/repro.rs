@@ -0,0 +1,109 @@
+/// Tests of extracting the bytes around a parse failure
+use std::io::Write;
+
+use flate2::write::GzEncoder;
+
+use crate::read::ReadOptions;
+use crate::repro::{extract_error, write_report, ParseFailure, CONTEXT};
+
+use super::tests_data;
+
+/// BIGTEST_UNCOMPRESSED with the type of the String "name" in the compound
+/// "ham" replaced by the unknown type 0x2a
+fn corrupt_bigtest() -> Vec<u8> {
+    let mut data = tests_data::BIGTEST_UNCOMPRESSED.to_vec();
+    assert_eq!(&data[156..163], b"\x08\x00\x04name");
+    data[156] = 0x2a;
+    data
+}
+
+fn extract(data: &[u8]) -> Option<ParseFailure> {
+    extract_error(&mut &data[..], &ReadOptions::default()).unwrap()
+}
+
+#[test]
+fn unknown_type() {
+    let data = corrupt_bigtest();
+    let failure = extract(&data).unwrap();
+
+    /* The name is read before the type is checked */
+    assert_eq!(failure.offset, 163);
+    assert_eq!(failure.len, 1544);
+    assert_eq!(failure.start, 99);
+    assert_eq!(failure.data, &data[99..227]);
+    assert_eq!(
+        failure.error.to_string(),
+        "Got unknown type id 2a trying to read NBT tag"
+    );
+}
+
+#[test]
+fn compressed_offsets() {
+    /* Offsets are in the uncompressed data */
+    let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(&corrupt_bigtest()).unwrap();
+    let data = encoder.finish().unwrap();
+
+    let failure = extract(&data).unwrap();
+    assert_eq!(failure.offset, 163);
+    assert_eq!(failure.data, &corrupt_bigtest()[99..227]);
+}
+
+#[test]
+fn truncated() {
+    let data = &tests_data::HELLO_WORLD[..20];
+    let failure = extract(data).unwrap();
+    assert_eq!(failure.offset, 20);
+    assert_eq!(failure.start, 0);
+    assert_eq!(failure.data, data);
+}
+
+/// Going beyond the limits is a failure like any other
+#[test]
+fn limits() {
+    let options = ReadOptions {
+        max_tags: Some(5),
+        ..Default::default()
+    };
+    let failure = extract_error(&mut &tests_data::BIGTEST_COMPRESSED[..], &options)
+        .unwrap()
+        .unwrap();
+    assert_eq!(failure.error.to_string(), "exceeded maximum tag count 5");
+    assert_eq!(failure.len, 1544);
+    /* The bytes after the failure are still found */
+    assert_eq!(
+        failure.start + failure.data.len() as u64,
+        failure.offset + CONTEXT as u64
+    );
+}
+
+#[test]
+fn parses() {
+    assert!(extract(&tests_data::HELLO_WORLD).is_none());
+    assert!(extract(&tests_data::BIGTEST_COMPRESSED).is_none());
+}
+
+#[test]
+fn report() {
+    let failure = extract(&tests_data::HELLO_WORLD[..20]).unwrap();
+    let mut buf = Vec::new();
+    write_report(&mut buf, "hello.nbt", &failure).unwrap();
+    let report = String::from_utf8(buf).unwrap();
+
+    let lines: Vec<&str> = report.lines().collect();
+    assert_eq!(
+        lines[0],
+        format!(
+            "nbted {} failed to parse hello.nbt, which is uncompressed",
+            env!("CARGO_PKG_VERSION")
+        )
+    );
+    assert_eq!(
+        &lines[lines.len() - 3..],
+        &[
+            "Bytes 0x0 to 0x14:",
+            "00000000: 0a00 0b68 656c 6c6f 2077 6f72 6c64 0800  ...hello world..",
+            "00000010: 046e 616d                                .nam",
+        ]
+    );
+}
@@ -0,0 +1,223 @@
+/// Testing of reading SNBT, and of telling it apart from the pretty text
+/// format
+use crate::data::NBT;
+use crate::snbt_read::{looks_like_snbt, read_snbt};
+
+use super::tests_data;
+
+fn err_msg(snbt: &str) -> String {
+    match read_snbt(snbt) {
+        Ok(x) => panic!("expected {} to fail, but it was read as {:?}", snbt, x),
+        Err(e) => e.to_string(),
+    }
+}
+
+#[test]
+fn detect_format() {
+    let mut cursor = std::io::Cursor::new(&tests_data::HELLO_WORLD[..]);
+    let nbt = crate::read::read_file(&mut cursor).unwrap();
+    let mut native = Vec::new();
+    crate::string_write::write_file(&mut native, &nbt).unwrap();
+    assert!(!looks_like_snbt(&native));
+    assert!(!looks_like_snbt(b"# A comment {\nNone\nEnd"));
+
+    assert!(looks_like_snbt(b"{name:\"Bananrama\"}"));
+    assert!(looks_like_snbt(b"\n  {}"));
+}
+
+#[test]
+fn values() {
+    let nbt = read_snbt(
+        r#"{b: 1b, s: -2S, i: 3, l: 4L, f: 0.5f, d: 1.5, d2: 2d, t: true, f2: false,
+            str: hello, q: "a \"quoted\" \\ string", q2: 'it\'s "fine"'}"#,
+    )
+    .unwrap();
+    assert_eq!(
+        nbt,
+        NBT::Compound(vec![
+            (b"b".to_vec(), NBT::Byte(1)),
+            (b"s".to_vec(), NBT::Short(-2)),
+            (b"i".to_vec(), NBT::Int(3)),
+            (b"l".to_vec(), NBT::Long(4)),
+            (b"f".to_vec(), NBT::Float(0.5)),
+            (b"d".to_vec(), NBT::Double(1.5)),
+            (b"d2".to_vec(), NBT::Double(2.0)),
+            (b"t".to_vec(), NBT::Byte(1)),
+            (b"f2".to_vec(), NBT::Byte(0)),
+            (b"str".to_vec(), NBT::String(b"hello".to_vec())),
+            (
+                b"q".to_vec(),
+                NBT::String(br#"a "quoted" \ string"#.to_vec())
+            ),
+            (b"q2".to_vec(), NBT::String(br#"it's "fine""#.to_vec())),
+        ])
+    );
+}
+
+#[test]
+fn numbers_that_are_strings() {
+    /* Out of range, or not quite numbers */
+    assert_eq!(read_snbt("300b").unwrap(), NBT::String(b"300b".to_vec()));
+    assert_eq!(
+        read_snbt("3000000000").unwrap(),
+        NBT::String(b"3000000000".to_vec())
+    );
+    assert_eq!(read_snbt("1e5").unwrap(), NBT::String(b"1e5".to_vec()));
+    assert_eq!(read_snbt("1.2.3").unwrap(), NBT::String(b"1.2.3".to_vec()));
+    assert_eq!(read_snbt("1e5f").unwrap(), NBT::Float(1e5));
+}
+
+/// The type of every unquoted value, following the rules of Minecraft
+#[test]
+fn type_inference() {
+    let cases = [
+        /* Suffixes in either case */
+        ("1.0f", NBT::Float(1.0)),
+        ("1.0F", NBT::Float(1.0)),
+        ("1.0d", NBT::Double(1.0)),
+        ("1.0D", NBT::Double(1.0)),
+        ("1f", NBT::Float(1.0)),
+        ("1e5d", NBT::Double(1e5)),
+        ("-1.5E-2f", NBT::Float(-1.5e-2)),
+        ("-3b", NBT::Byte(-3)),
+        ("7B", NBT::Byte(7)),
+        ("7s", NBT::Short(7)),
+        ("7l", NBT::Long(7)),
+        /* Plain decimals need a point, and are Doubles */
+        ("1.0", NBT::Double(1.0)),
+        ("1.", NBT::Double(1.0)),
+        (".5", NBT::Double(0.5)),
+        ("-.5", NBT::Double(-0.5)),
+        ("1.5e3", NBT::Double(1500.0)),
+        ("007.5", NBT::Double(7.5)),
+        /* Plain integers are Ints whatever their size, not the smallest type
+         * they fit in */
+        ("0", NBT::Int(0)),
+        ("-0", NBT::Int(0)),
+        ("+1", NBT::Int(1)),
+        ("1", NBT::Int(1)),
+        ("2147483647", NBT::Int(i32::MAX)),
+        ("-2147483648", NBT::Int(i32::MIN)),
+        /* Infinity as a number too large for its type */
+        ("1e39f", NBT::Float(f32::INFINITY)),
+        ("-1e309d", NBT::Double(f64::NEG_INFINITY)),
+    ];
+    for (snbt, expected) in &cases {
+        assert_eq!(&read_snbt(snbt).unwrap(), expected, "{}", snbt);
+    }
+
+    /* Not numbers in Minecraft, so they are Strings */
+    for snbt in &[
+        "007",
+        "01b",
+        "-00s",
+        "2147483648",
+        "128b",
+        "1e5",
+        "1.0x",
+        "NaNf",
+        "Infinity",
+        "TRUE",
+        ".f",
+        "-",
+        "1.0fd",
+    ] {
+        assert_eq!(
+            read_snbt(snbt).unwrap(),
+            NBT::String(snbt.as_bytes().to_vec()),
+            "{}",
+            snbt
+        );
+    }
+}
+
+#[test]
+fn lists_and_arrays() {
+    let nbt = read_snbt(
+        "{empty: [], list: [{a: 1}, {}], bytes: [B; 1b, -2b], ints: [I;], longs: [L; 1L, 2]}",
+    )
+    .unwrap();
+    assert_eq!(nbt.get("empty"), Some(&NBT::list(Vec::new())));
+    assert_eq!(
+        nbt.get("list"),
+        Some(&NBT::list(vec![
+            NBT::Compound(vec![(b"a".to_vec(), NBT::Int(1))]),
+            NBT::Compound(Vec::new())
+        ]))
+    );
+    assert_eq!(nbt.get("bytes"), Some(&NBT::ByteArray(vec![1, -2])));
+    assert_eq!(nbt.get("ints"), Some(&NBT::IntArray(Vec::new())));
+    assert_eq!(nbt.get("longs"), Some(&NBT::LongArray(vec![1, 2])));
+}
+
+#[test]
+fn errors() {
+    assert_eq!(
+        err_msg("[1, 2b]"),
+        "Byte in a list of Int, all elements of a list must have the same type at character 4"
+    );
+    assert_eq!(
+        err_msg("[B; 1, 200]"),
+        "200 doesn't fit in a B; array at character 7"
+    );
+    assert_eq!(err_msg("{a: 1, a: 2}"), "Duplicate key a at character 8");
+    assert_eq!(err_msg("{a: 1"), "Expected , at character 5");
+    assert_eq!(
+        err_msg("{a: 1} x"),
+        "Unexpected data after the end of the tag at character 7"
+    );
+    assert_eq!(
+        err_msg("{a: \"x\\q\"}"),
+        "Invalid escape sequence in a string at character 7"
+    );
+    assert_eq!(
+        err_msg(r#"{a: "\x+f"}"#),
+        r"Invalid string, \x must be followed by two hex digits giving a byte at character 6"
+    );
+    /* Quotes inside of a string have to be escaped */
+    assert_eq!(
+        err_msg("'it''s'"),
+        "Unexpected data after the end of the tag at character 4"
+    );
+    /* Only quoted keys can be empty */
+    assert_eq!(err_msg("{: 1}"), "Expected a key at character 1");
+    assert_eq!(
+        read_snbt("{'': 1}").unwrap(),
+        NBT::Compound(vec![(Vec::new(), NBT::Int(1))])
+    );
+    /* Offsets count characters, not bytes */
+    assert_eq!(err_msg("{\"ä\": 1 x}"), "Expected , at character 8");
+}
+
+#[test]
+fn max_depth() {
+    use crate::read::DEFAULT_MAX_DEPTH;
+    use crate::snbt_read::read_snbt_with_max_depth;
+
+    /* Lists nested in a so that the innermost Int is in depth compounds and
+     * lists */
+    let nested =
+        |depth: usize| format!("{{a:{}1{}}}", "[".repeat(depth - 1), "]".repeat(depth - 1));
+    assert!(read_snbt(&nested(DEFAULT_MAX_DEPTH)).is_ok());
+    for &depth in &[DEFAULT_MAX_DEPTH + 1, 100_000] {
+        let err = read_snbt(&nested(depth)).unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("NBT nesting exceeds max depth 512 at character "));
+    }
+
+    /* The lists are in the compound, and the Int in both lists */
+    let snbt = "{a: [[1]]}";
+    assert_eq!(
+        read_snbt_with_max_depth(snbt, Some(3)).unwrap(),
+        NBT::Compound(vec![(
+            b"a".to_vec(),
+            NBT::list(vec![NBT::list(vec![NBT::Int(1)])])
+        )])
+    );
+    let err = read_snbt_with_max_depth(snbt, Some(2)).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "NBT nesting exceeds max depth 2 at character 6"
+    );
+}
@@ -0,0 +1,151 @@
+/// Testing of writing SNBT, and that snbt_read reads it back the same
+use crate::data::NBT;
+use crate::snbt_read::read_snbt;
+use crate::snbt_write::write_snbt;
+
+use super::tests_data;
+
+fn snbt(tag: &NBT) -> String {
+    let mut buf = Vec::new();
+    write_snbt(&mut buf, tag).unwrap();
+    String::from_utf8(buf).unwrap()
+}
+
+/// The root compound of bigtest
+fn bigtest() -> NBT {
+    match super::bigtest().root {
+        NBT::Compound(mut x) => x.remove(0).1,
+        _ => unreachable!(),
+    }
+}
+
+#[test]
+fn bigtest_snbt() {
+    let bytes: Vec<String> = (0..1000)
+        .map(|n| format!("{}b", (n * n * 255 + n * 7) % 100))
+        .collect();
+    let expected = format!(
+        concat!(
+            r#"{{longTest:9223372036854775807L,shortTest:32767s,"#,
+            r#"stringTest:"HELLO WORLD THIS IS A TEST STRING ÅÄÖ!",floatTest:0.49823147f,"#,
+            r#"intTest:2147483647,"#,
+            r#""nested compound test":{{ham:{{name:"Hampus",value:0.75f}},"#,
+            r#"egg:{{name:"Eggbert",value:0.5f}}}},"#,
+            r#""listTest (long)":[11L,12L,13L,14L,15L],"#,
+            r#""listTest (compound)":[{{name:"Compound tag #0",created-on:1264099775885L}},"#,
+            r#"{{name:"Compound tag #1",created-on:1264099775885L}}],byteTest:127b,"#,
+            r#""byteArrayTest (the first 1000 values of (n*n*255+n*7)%100, starting with n=0 (0, 62, 34, 16, 8, ...))":[B;{}],"#,
+            r#"doubleTest:0.4931287132182315d}}"#
+        ),
+        bytes.join(",")
+    );
+    assert_eq!(snbt(&bigtest()), expected);
+}
+
+#[test]
+fn snbt_round_trip() {
+    let tag = bigtest();
+    assert!(read_snbt(&snbt(&tag)).unwrap().bitwise_eq(&tag));
+
+    let tag = NBT::Compound(vec![
+        (b"a b".to_vec(), NBT::Short(-2)),
+        (b"q\"\\".to_vec(), NBT::String(b"say \"hi\" \\o/".to_vec())),
+        (b"f".to_vec(), NBT::Float(1.0)),
+        (b"d".to_vec(), NBT::Double(-1e300)),
+        (b"i".to_vec(), NBT::IntArray(vec![1, -2])),
+        (b"l".to_vec(), NBT::LongArray(vec![i64::MIN])),
+        (b"e".to_vec(), NBT::list(Vec::new())),
+        (b"n".to_vec(), NBT::list(vec![NBT::ByteArray(vec![])])),
+    ]);
+    let text = snbt(&tag);
+    assert_eq!(
+        text,
+        r#"{"a b":-2s,"q\"\\":"say \"hi\" \\o/",f:1.0f,d:-1e300d,i:[I;1,-2],l:[L;-9223372036854775808L],e:[],n:[[B;]]}"#
+    );
+    assert_eq!(read_snbt(&text).unwrap(), tag);
+
+    /* Empty keys have to be quoted */
+    let tag = NBT::Compound(vec![(Vec::new(), NBT::Int(1))]);
+    assert_eq!(snbt(&tag), r#"{"":1}"#);
+    assert_eq!(read_snbt(&snbt(&tag)).unwrap(), tag);
+}
+
+/// Decimals are written like Minecraft writes them, always with a decimal
+/// point or an exponent
+#[test]
+fn snbt_decimals() {
+    assert_eq!(snbt(&NBT::Float(1.0)), "1.0f");
+    assert_eq!(snbt(&NBT::Float(-0.0)), "-0.0f");
+    assert_eq!(snbt(&NBT::Double(100.0)), "100.0d");
+    assert_eq!(snbt(&NBT::Double(1e-7)), "1e-7d");
+    assert_eq!(snbt(&NBT::Float(3.4028235e38)), "3.4028235e38f");
+
+    /* Infinity is written as a number that is too large for its type */
+    assert_eq!(snbt(&NBT::Float(f32::INFINITY)), "1e39f");
+    assert_eq!(snbt(&NBT::Double(f64::NEG_INFINITY)), "-1e309d");
+    for tag in &[
+        NBT::Float(f32::INFINITY),
+        NBT::Float(f32::NEG_INFINITY),
+        NBT::Double(f64::INFINITY),
+        NBT::Double(f64::NEG_INFINITY),
+        NBT::Double(f64::MIN_POSITIVE),
+    ] {
+        assert_eq!(&read_snbt(&snbt(tag)).unwrap(), tag);
+    }
+
+    assert_eq!(
+        write_snbt(&mut Vec::new(), &NBT::Double(f64::NAN))
+            .unwrap_err()
+            .to_string(),
+        "Unable to write NaN as SNBT, it has no SNBT form"
+    );
+}
+
+/// Control characters and bytes that aren't modified UTF-8 are escaped, and
+/// read back as the same bytes
+#[test]
+fn snbt_escapes() {
+    let tag = NBT::String(b"a\nb\tc\r\x01\xc0\x80\xff\xf0\x9f\x98\x80\xc3\xa4".to_vec());
+    let text = snbt(&tag);
+    assert_eq!(text, r#""a\nb\tc\r\x01\xc0\x80\xff\xf0\x9f\x98\x80ä""#);
+    assert_eq!(read_snbt(&text).unwrap(), tag);
+
+    /* An emoji Java wrote as a surrogate pair is kept as it is */
+    let tag = NBT::String(crate::mutf8::encode("😀"));
+    assert_eq!(snbt(&tag), "\"😀\"");
+    assert_eq!(read_snbt(&snbt(&tag)).unwrap(), tag);
+}
+
+/// Trees nested deeper than SNBT can be read back aren't written
+#[test]
+fn snbt_max_depth() {
+    use crate::read::DEFAULT_MAX_DEPTH;
+
+    let nested = |depth| (0..depth).fold(NBT::Int(1), |tag, _| NBT::list(vec![tag]));
+    let text = snbt(&nested(DEFAULT_MAX_DEPTH));
+    assert_eq!(read_snbt(&text).unwrap(), nested(DEFAULT_MAX_DEPTH));
+    assert_eq!(
+        write_snbt(&mut Vec::new(), &nested(DEFAULT_MAX_DEPTH + 1))
+            .unwrap_err()
+            .to_string(),
+        format!("NBT nesting exceeds max depth {}", DEFAULT_MAX_DEPTH)
+    );
+}
+
+#[test]
+fn snbt_end_tag() {
+    assert!(write_snbt(&mut Vec::new(), &NBT::End).is_err());
+    assert!(write_snbt(&mut Vec::new(), &NBT::list(vec![NBT::End])).is_err());
+}
+
+/// Every tree written as SNBT reads back the same
+#[test]
+fn custom_snbt_round_trip() {
+    let nbt = crate::read::read_file(&mut &tests_data::CUSTOM[..]).unwrap();
+    let root = match nbt.root {
+        NBT::Compound(mut x) => x.remove(0).1,
+        _ => unreachable!(),
+    };
+    let read = read_snbt(&snbt(&root)).unwrap();
+    assert!(read.bitwise_eq(&root), "{:?} != {:?}", read, root);
+}
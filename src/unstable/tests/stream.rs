@@ -0,0 +1,141 @@
+use std::io::Cursor;
+
+use crate::data::NBT;
+use crate::stream::{transform_file, Action, EventReader, EventWriter, NbtEvent};
+
+use super::read_fixture;
+use super::tests_data;
+
+fn transform<F: FnMut(&mut NbtEvent) -> Action>(nbt: &[u8], f: F) -> crate::Result<Vec<u8>> {
+    let mut ret = Vec::new();
+    transform_file(&mut Cursor::new(nbt), &mut ret, f)?;
+    Ok(ret)
+}
+
+#[test]
+fn passthrough_is_identical() {
+    for nbt in &[
+        &tests_data::EMPTY_ROOT[..],
+        &tests_data::HELLO_WORLD[..],
+        &tests_data::BIGTEST_UNCOMPRESSED[..],
+        &tests_data::PLAYER_FILE[..],
+        &tests_data::CUSTOM[..],
+    ] {
+        assert_eq!(&transform(nbt, |_| Action::Keep).unwrap()[..], *nbt);
+    }
+}
+
+#[test]
+fn passthrough_compressed() {
+    let out = transform(&tests_data::BIGTEST_COMPRESSED, |_| Action::Keep).unwrap();
+    assert_eq!(
+        read_fixture(&out),
+        read_fixture(&tests_data::BIGTEST_COMPRESSED)
+    );
+}
+
+#[test]
+fn modify_and_drop() {
+    let out = transform(&tests_data::BIGTEST_UNCOMPRESSED, |event| match event {
+        NbtEvent::Value {
+            name: Some(name),
+            value: NBT::String(value),
+        } if name == b"name" => {
+            *value = b"redacted".to_vec();
+            Action::Keep
+        }
+        NbtEvent::CompoundStart { name: Some(name) } if name == b"nested compound test" => {
+            Action::Drop
+        }
+        NbtEvent::ListStart {
+            name: Some(name), ..
+        } if name == b"listTest (long)" => Action::Drop,
+        _ => Action::Keep,
+    })
+    .unwrap();
+
+    let mut expected = read_fixture(&tests_data::BIGTEST_UNCOMPRESSED);
+    if let NBT::Compound(ref mut root) = expected.root {
+        if let NBT::Compound(ref mut level) = root[0].1 {
+            level.retain(|(key, _)| key != b"nested compound test" && key != b"listTest (long)");
+            for (key, val) in level.iter_mut() {
+                if key == b"listTest (compound)" {
+                    if let NBT::List {
+                        items: ref mut list,
+                        ..
+                    } = val
+                    {
+                        for compound in list {
+                            if let NBT::Compound(ref mut x) = compound {
+                                x[0].1 = NBT::String(b"redacted".to_vec());
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    assert_eq!(read_fixture(&out), expected);
+}
+
+#[test]
+fn drop_list_element() {
+    let res = transform(&tests_data::BIGTEST_UNCOMPRESSED, |event| match event {
+        NbtEvent::Value {
+            name: None,
+            value: NBT::Long(_),
+        } => Action::Drop,
+        _ => Action::Keep,
+    });
+    assert!(res.is_err());
+}
+
+#[test]
+fn change_list_length() {
+    let res = transform(&tests_data::BIGTEST_UNCOMPRESSED, |event| {
+        if let NbtEvent::ListStart { length, .. } = event {
+            *length += 1;
+        }
+        Action::Keep
+    });
+    assert!(res.is_err());
+}
+
+#[test]
+fn writer_checks_list_type() {
+    let mut writer = EventWriter::new(Vec::new());
+    writer
+        .write_event(&NbtEvent::ListStart {
+            name: Some(b"x".to_vec()),
+            type_id: 0x01,
+            length: 1,
+        })
+        .unwrap();
+    assert!(writer
+        .write_event(&NbtEvent::Value {
+            name: None,
+            value: NBT::Int(1),
+        })
+        .is_err());
+}
+
+#[test]
+fn reader_events() {
+    let mut reader = EventReader::new(&tests_data::HELLO_WORLD[..]);
+    assert_eq!(
+        reader.next_event().unwrap(),
+        Some(NbtEvent::CompoundStart {
+            name: Some(b"hello world".to_vec())
+        })
+    );
+    assert_eq!(
+        reader.next_event().unwrap(),
+        Some(NbtEvent::Value {
+            name: Some(b"name".to_vec()),
+            value: NBT::String(b"Bananrama".to_vec()),
+        })
+    );
+    assert_eq!(reader.next_event().unwrap(), Some(NbtEvent::CompoundEnd));
+    assert_eq!(reader.next_event().unwrap(), None);
+}
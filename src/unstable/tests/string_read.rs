@@ -5,11 +5,16 @@
 ///
 /// Testing of valid and regularly formatted files are conducted in the main
 /// file with its "loops".
+use std::ffi::OsStr;
+use std::fs;
 use std::io::Cursor;
+use std::path::Path;
 
 use crate::data::NBTFile;
 use crate::Result;
 
+use super::tests_data;
+
 /// Convenience method
 fn try_parse_string(original: &str) -> Result<NBTFile> {
     let mut cursor = Cursor::new(original.as_bytes());
@@ -39,6 +44,12 @@ fn text_file_with_no_trailing_bytes() {
     let _: NBTFile = try_parse_string("None End").unwrap();
 }
 
+#[test]
+fn empty_root() {
+    let nbtfile = try_parse_string("None\nEnd\n").unwrap();
+    assert_eq!(nbtfile.root, crate::data::NBT::Compound(Vec::new()));
+}
+
 #[test]
 fn incomplete_string() {
     let err_msg = try_parse_string_get_err_msg(
@@ -50,7 +61,45 @@ fn incomplete_string() {
 #[test]
 fn escaped_unescapable_char() {
     let err_msg = try_parse_string_get_err_msg(r#"None Compound "\k" End End"#);
-    assert!(err_msg.contains("Invalid string, tried to escape the character"));
+    assert!(err_msg.contains("Invalid string, tried to escape the character 'k'"));
+}
+
+#[test]
+fn escaped_whitespace() {
+    let nbtfile = try_parse_string(r#"None String "a\tb" "1\r\n2" End"#).unwrap();
+    assert_eq!(
+        nbtfile.root,
+        crate::data::NBT::Compound(vec![(
+            b"a\tb".to_vec(),
+            crate::data::NBT::String(b"1\r\n2".to_vec())
+        )])
+    );
+}
+
+#[test]
+fn encoded_byte_array() {
+    let nbtfile = try_parse_string(r#"None ByteArray "a" 2 0xFF7f End"#).unwrap();
+    assert_eq!(
+        nbtfile.root,
+        crate::data::NBT::Compound(vec![(
+            b"a".to_vec(),
+            crate::data::NBT::ByteArray(vec![-1, 127])
+        )])
+    );
+
+    let err_msg = try_parse_string_get_err_msg(r#"None ByteArray "a" 3 0xff7f End"#);
+    assert!(err_msg.contains("ByteArray has length 3, but 2 bytes were given"));
+    let err_msg = try_parse_string_get_err_msg(r#"None ByteArray "a" 2 0xff7 End"#);
+    assert!(err_msg.contains("0x must be followed by pairs of hex digits"));
+    let err_msg = try_parse_string_get_err_msg(r#"None ByteArray "a" 2 base64:/38 End"#);
+    assert!(err_msg.contains("isn't a multiple of 4"));
+}
+
+#[test]
+fn escaped_unprintable_char() {
+    let err_msg = try_parse_string_get_err_msg("None Compound \"\\\x07\" End End");
+    /* The message is Debug formatted, so the \ of \x07 is itself escaped */
+    assert!(err_msg.contains(r"tried to escape the character \\x07 which"));
 }
 
 #[test]
@@ -63,6 +112,136 @@ fn eof_when_reading() {
 fn invalid_int() {
     let err_msg = try_parse_string_get_err_msg(r#"Zlib Compound "" Int "" NotAnInt End End"#);
     assert!(err_msg.contains("Invalid Int NotAnInt"));
+
+    let err_msg = try_parse_string_get_err_msg(r#"Zlib Compound "" Int "" 3000000000 End End"#);
+    assert!(err_msg.contains("Int value 3000000000 out of range (-2147483648..=2147483647)"));
+}
+
+#[test]
+fn nan_bits() {
+    let nbt = crate::string_read::read_file(&mut Cursor::new(
+        r#"None Float "a" NaN(0xffc00001) Double "b" NaN(0x7ff8000000000002) End"#,
+    ))
+    .unwrap();
+    match (nbt.root.get("a"), nbt.root.get("b")) {
+        (Some(crate::data::NBT::Float(a)), Some(crate::data::NBT::Double(b))) => {
+            assert_eq!(a.to_bits(), 0xffc0_0001);
+            assert_eq!(b.to_bits(), 0x7ff8_0000_0000_0002);
+        }
+        x => panic!("{:?}", x),
+    }
+
+    let err_msg = try_parse_string_get_err_msg(r#"None Float "" NaN(0xnope) End"#);
+    assert!(err_msg.contains("Invalid Float NaN(0xnope)"));
+}
+
+#[test]
+fn duplicate_keys() {
+    use crate::string_read::{read_file_with_options, TextReadOptions};
+
+    let text = r#"None Compound "" Byte "a" 1 Compound "b" Int "a" 2 End Int "a" 3 End End"#;
+    assert!(crate::string_read::read_file(&mut Cursor::new(text)).is_ok());
+
+    let options = TextReadOptions {
+        forbid_duplicate_keys: true,
+        ..Default::default()
+    };
+    let err = read_file_with_options(&mut Cursor::new(text), &options).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Duplicate key a in compound, the second one is a Int at line 1, column 60"
+    );
+
+    let options = TextReadOptions {
+        braces: true,
+        forbid_duplicate_keys: true,
+        ..Default::default()
+    };
+    let text = r#"None Compound "" { Byte "a" 1 Byte "a" 2 } }"#;
+    assert!(read_file_with_options(&mut Cursor::new(text), &options).is_err());
+}
+
+/// A file with a List named a in the root compound, with lists nested in it
+/// so that the innermost one is in depth compounds and lists, counting the
+/// implicit root compound
+fn nested_lists(depth: usize) -> String {
+    let mut text = String::from(r#"None Compound "" List "a" "#);
+    for _ in 2..depth {
+        text.push_str("List 1 ");
+    }
+    text.push_str("Byte 0 End End");
+    text
+}
+
+#[test]
+fn max_depth() {
+    use crate::string_read::{read_file_with_options, TextReadOptions};
+
+    let text = nested_lists(crate::read::DEFAULT_MAX_DEPTH);
+    assert!(crate::string_read::read_file(&mut Cursor::new(text)).is_ok());
+
+    for &depth in &[crate::read::DEFAULT_MAX_DEPTH + 1, 100_000] {
+        let text = nested_lists(depth);
+        let err = crate::string_read::read_file(&mut Cursor::new(text)).unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("NBT nesting exceeds max depth 512 at line 1, column "));
+    }
+
+    let options = TextReadOptions {
+        max_depth: Some(2),
+        ..Default::default()
+    };
+    let text = nested_lists(2);
+    assert!(read_file_with_options(&mut Cursor::new(text), &options).is_ok());
+    let text = nested_lists(3);
+    let err = read_file_with_options(&mut Cursor::new(text), &options).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "NBT nesting exceeds max depth 2 at line 1, column 32"
+    );
+}
+
+#[test]
+fn error_position() {
+    /* Columns count characters rather than bytes, and errors keep their
+     * causes */
+    let text = "None\nCompound \"ä\"\n\tString \"ö\" \"x\" Int \"a\" 1.5\n\tEnd\nEnd\n";
+    let err = crate::string_read::read_file(&mut Cursor::new(text)).unwrap_err();
+    let chain: Vec<String> = err.iter_chain().map(|x| x.to_string()).collect();
+    assert_eq!(
+        chain,
+        &[
+            "Invalid Int 1.5 at line 3, column 25",
+            "invalid digit found in string"
+        ]
+    );
+
+    /* Running out of file is reported at its end */
+    let err = try_parse_string("None\nCompound \"\"\n\tInt \"a\"").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "EOF when trying to read an int at line 3, column 9"
+    );
+}
+
+#[test]
+fn integers_out_of_range() {
+    let err_msg = try_parse_string_get_err_msg(r#"None Compound "" Byte "" 128 End End"#);
+    assert!(err_msg.contains("Byte value 128 out of range (-128..=127)"));
+
+    let err_msg = try_parse_string_get_err_msg(r#"None Compound "" Short "" -32769 End End"#);
+    assert!(err_msg.contains("Short value -32769 out of range (-32768..=32767)"));
+
+    let err_msg =
+        try_parse_string_get_err_msg(r#"None Compound "" Long "" 9223372036854775808 End End"#);
+    assert!(err_msg.contains(
+        "Long value 9223372036854775808 out of range (-9223372036854775808..=9223372036854775807)"
+    ));
+
+    /* Elements of arrays are read the same way */
+    let err_msg = try_parse_string_get_err_msg(r#"None Compound "" ByteArray "" 1 200 End End"#);
+    assert!(err_msg.contains("Byte value 200 out of range (-128..=127)"));
 }
 
 #[test]
@@ -88,3 +267,144 @@ fn unquoted_string() {
     let _: NBTFile =
         try_parse_string(r#"None Compound ForgotQuotationMarksAroundThisString End End"#).unwrap();
 }
+
+#[test]
+fn comments() {
+    let with_comments = try_parse_string(
+        "None # compression\nCompound \"\" # the root\n\tString \"a\" \"# not a comment\"\n\t#Int \"b\" 1\n\tEnd\nEnd # no newline",
+    )
+    .unwrap();
+    let without_comments =
+        try_parse_string(r##"None Compound "" String "a" "# not a comment" End End"##).unwrap();
+    assert_eq!(with_comments, without_comments);
+}
+
+/// Every .txt file in tests/string_read_err is a file in the text format that
+/// fails to parse, with the expected error message in the .err file of the
+/// same name.
+#[test]
+fn string_read_err() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/string_read_err");
+    let mut count = 0;
+
+    for entry in fs::read_dir(&dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension() != Some(OsStr::new("txt")) {
+            continue;
+        }
+
+        let text = fs::read(&path).unwrap();
+        let expected = fs::read_to_string(path.with_extension("err")).unwrap();
+        let err = match crate::string_read::read_file(&mut Cursor::new(text)) {
+            Ok(_) => panic!("{} was parsed without any errors", path.display()),
+            Err(e) => e,
+        };
+
+        assert_eq!(
+            normalize_newlines(&err.to_string()),
+            normalize_newlines(&expected),
+            "wrong error for {}",
+            path.display()
+        );
+        count += 1;
+    }
+
+    assert!(count > 0, "found no test files in {}", dir.display());
+}
+
+/// Git may check the .err files out with CRLF line endings on Windows, so
+/// compare them with \r\n and \n treated the same, and without the final
+/// newline
+fn normalize_newlines(x: &str) -> String {
+    x.lines().collect::<Vec<&str>>().join("\n")
+}
+
+#[test]
+fn normalize_newlines_crlf() {
+    assert_eq!(normalize_newlines("a\r\nb\r\n"), normalize_newlines("a\nb"));
+    assert_ne!(normalize_newlines("a\nb"), normalize_newlines("ab"));
+}
+
+#[test]
+fn braces_mismatched() {
+    let read = |x: &str| crate::string_read::read_file_braces(&mut Cursor::new(x.as_bytes()));
+
+    assert!(read("None { Int \"a\" 1 }").is_ok());
+    /* The End of the plain text format isn't accepted instead of } */
+    let err = format!("{:?}", read("None { Int \"a\" 1 End").unwrap_err());
+    assert!(err.contains("compounds end with }"), "{}", err);
+    let err = format!("{:?}", read("None Int \"a\" 1 }").unwrap_err());
+    assert!(err.contains("Expected { to start the contents of a container, found Int"));
+    let err = format!(
+        "{:?}",
+        read("None { List \"a\" Int 2 { 1 2 3 } }").unwrap_err()
+    );
+    assert!(err.contains("Expected } after the last element of a list or array, found 3"));
+    let err = format!("{:?}", read("None { IntArray \"a\" 2 { 1 }").unwrap_err());
+    assert!(err.contains("Invalid Int }"), "{}", err);
+}
+
+#[test]
+fn hex_escapes() {
+    let nbt = try_parse_string("None String \"a\" \"\\x41\\x0a\" End").unwrap();
+    assert_eq!(
+        nbt.root,
+        crate::data::NBT::Compound(vec![(
+            b"a".to_vec(),
+            crate::data::NBT::String(b"A\n".to_vec())
+        )])
+    );
+
+    /* Any byte can be escaped, including ones that make the string not
+     * UTF-8 */
+    let nbt = try_parse_string("None String \"a\" \"\\xff\\xC3\" End").unwrap();
+    assert_eq!(
+        nbt.root,
+        crate::data::NBT::Compound(vec![(
+            b"a".to_vec(),
+            crate::data::NBT::String(vec![0xff, 0xc3])
+        )])
+    );
+
+    for invalid in &["\\xzz", "\\x+1", "\\x4"] {
+        let err_msg =
+            try_parse_string_get_err_msg(&format!("None String \"a\" \"{}\" End", invalid));
+        assert!(
+            err_msg.contains(r"\\x must be followed by two hex digits"),
+            "{}: {}",
+            invalid,
+            err_msg
+        );
+    }
+}
+
+/// Editors may reformat the whitespace of a file without the user changing
+/// anything, in which case --edit relies on the file reading back as exactly
+/// the same NBT so that it reports no changes and leaves the file untouched.
+#[test]
+fn reformatted_whitespace_is_unchanged() {
+    /* Not the custom file, which has a String spanning several lines whose
+     * contents would be changed by reformatting its lines */
+    for data in &[
+        &tests_data::BIGTEST_UNCOMPRESSED[..],
+        &tests_data::PLAYER_FILE[..],
+    ] {
+        let original = crate::read::read_file(&mut Cursor::new(data)).unwrap();
+        let mut text = Vec::new();
+        crate::string_write::write_file(&mut text, &original).unwrap();
+        let text = String::from_utf8(text).unwrap();
+
+        /* Indent with spaces instead of tabs, add trailing spaces, blank
+         * lines and Windows line endings */
+        let mut reformatted = String::new();
+        for line in text.lines() {
+            let content = line.trim_start_matches('\t');
+            let depth = line.len() - content.len();
+            reformatted.push_str(&"    ".repeat(depth));
+            reformatted.push_str(content);
+            reformatted.push_str("  \r\n\r\n");
+        }
+
+        assert_eq!(try_parse_string(&reformatted).unwrap(), original);
+    }
+}
@@ -0,0 +1,555 @@
+use std::io::Cursor;
+
+use crate::data::{Compression, NBTFile, NBT};
+use crate::string_write::{
+    write_file, write_file_with, write_value, ArrayFormat, ByteArrayEncoding, IndentStyle,
+    TextFormat,
+};
+
+use super::{assert_bitwise_eq, tests_data};
+
+/// The format used by --pretty-compact
+const COMPACT: TextFormat = TextFormat {
+    arrays: ArrayFormat::SingleLine,
+    byte_array_encoding: ByteArrayEncoding::Decimal,
+    indent: IndentStyle::Tabs,
+    escape_control: false,
+    escape_whitespace: false,
+    sort_keys: false,
+    uuid_strings: false,
+    braces: false,
+    canonical_floats: false,
+};
+
+/// Check that writing the file with arrays on a single line and reading it
+/// back gives the same file
+fn compact_loop(nbt: &[u8]) {
+    let original = crate::read::read_file(&mut Cursor::new(nbt)).unwrap();
+
+    let mut tmp = Vec::new();
+    write_file_with(&mut tmp, &original, &COMPACT).unwrap();
+    let looped = crate::string_read::read_file(&mut Cursor::new(tmp)).unwrap();
+
+    assert_bitwise_eq(&original, &looped);
+}
+
+#[test]
+fn compact_loops() {
+    compact_loop(&tests_data::HELLO_WORLD);
+    compact_loop(&tests_data::BIGTEST_UNCOMPRESSED);
+    compact_loop(&tests_data::BIGTEST_COMPRESSED);
+    compact_loop(&tests_data::PLAYER_FILE);
+    compact_loop(&tests_data::CUSTOM);
+}
+
+#[test]
+fn compact_layout() {
+    let file = NBTFile {
+        root: NBT::Compound(vec![(
+            b"".to_vec(),
+            NBT::Compound(vec![
+                (b"a".to_vec(), NBT::IntArray(vec![1, 2, 3])),
+                (
+                    b"b".to_vec(),
+                    NBT::list(vec![NBT::String(b"x".to_vec()), NBT::String(b"y".to_vec())]),
+                ),
+                (b"c".to_vec(), NBT::list((0..17).map(NBT::Byte).collect())),
+                (b"d".to_vec(), NBT::list(Vec::new())),
+            ]),
+        )]),
+        compression: Compression::None,
+        compression_level: None,
+    };
+
+    let mut tmp = Vec::new();
+    write_file_with(&mut tmp, &file, &COMPACT).unwrap();
+    let text = String::from_utf8(tmp).unwrap();
+    let lines: Vec<&str> = text.lines().collect();
+
+    assert_eq!(lines[2], "\tIntArray \"a\" 3 1 2 3");
+    assert_eq!(lines[3], "\tList \"b\" String 2 \"x\" \"y\"");
+    /* Too long to be on a single line */
+    assert_eq!(lines[4], "\tList \"c\" Byte 17");
+    assert_eq!(lines[22], "\tList \"d\" End 0");
+}
+
+#[test]
+fn uuid_strings() {
+    #[allow(overflowing_literals)]
+    let uuid = NBT::IntArray(vec![0x0123_4567, 0x89ab_cdef, 0x0011_2233, 0x4455_6677]);
+    let file = NBTFile {
+        root: NBT::Compound(vec![(
+            b"".to_vec(),
+            NBT::Compound(vec![
+                (b"UUID".to_vec(), uuid),
+                (b"NotUUID".to_vec(), NBT::IntArray(vec![1, 2, 3])),
+            ]),
+        )]),
+        compression: Compression::None,
+        compression_level: None,
+    };
+
+    for compact in &[false, true] {
+        let format = TextFormat {
+            uuid_strings: true,
+            ..if *compact {
+                COMPACT
+            } else {
+                TextFormat::default()
+            }
+        };
+        let mut tmp = Vec::new();
+        write_file_with(&mut tmp, &file, &format).unwrap();
+        let text = String::from_utf8(tmp).unwrap();
+
+        let uuid_line = text.lines().find(|x| x.contains("\"UUID\"")).unwrap();
+        assert!(uuid_line.ends_with(" # 01234567-89ab-cdef-0011-223344556677"));
+        let other_line = text.lines().find(|x| x.contains("\"NotUUID\"")).unwrap();
+        assert!(!other_line.contains('#'));
+
+        let looped = crate::string_read::read_file(&mut Cursor::new(text)).unwrap();
+        assert_eq!(looped, file);
+    }
+}
+
+/// Check that writing the file with braces and reading it back with
+/// read_file_braces gives the same file, with and without compact
+fn braces_loop(nbt: &[u8]) {
+    let original = crate::read::read_file(&mut Cursor::new(nbt)).unwrap();
+
+    for compact in &[false, true] {
+        let format = TextFormat {
+            braces: true,
+            ..if *compact {
+                COMPACT
+            } else {
+                TextFormat::default()
+            }
+        };
+        let mut tmp = Vec::new();
+        write_file_with(&mut tmp, &original, &format).unwrap();
+        let looped = crate::string_read::read_file_braces(&mut Cursor::new(tmp)).unwrap();
+
+        assert_bitwise_eq(&original, &looped);
+    }
+}
+
+#[test]
+fn braces_loops() {
+    braces_loop(&tests_data::EMPTY_ROOT);
+    braces_loop(&tests_data::HELLO_WORLD);
+    braces_loop(&tests_data::BIGTEST_UNCOMPRESSED);
+    braces_loop(&tests_data::PLAYER_FILE);
+    braces_loop(&tests_data::CUSTOM);
+}
+
+#[test]
+fn braces_layout() {
+    let file = NBTFile {
+        root: NBT::Compound(vec![(
+            b"x".to_vec(),
+            NBT::Compound(vec![
+                (b"y".to_vec(), NBT::Int(5)),
+                (
+                    b"l".to_vec(),
+                    NBT::list(vec![NBT::Compound(vec![(b"z".to_vec(), NBT::Byte(1))])]),
+                ),
+                (b"a".to_vec(), NBT::IntArray(vec![1, 2])),
+                (b"e".to_vec(), NBT::list(Vec::new())),
+            ]),
+        )]),
+        compression: Compression::None,
+        compression_level: None,
+    };
+    let format = TextFormat {
+        braces: true,
+        ..Default::default()
+    };
+
+    let mut tmp = Vec::new();
+    write_file_with(&mut tmp, &file, &format).unwrap();
+    assert_eq!(
+        String::from_utf8(tmp).unwrap(),
+        r#"None {
+	Compound "x" {
+		Int "y" 5
+		List "l" Compound 1 {
+			{
+				Byte "z" 1
+			}
+		}
+		IntArray "a" 2 {
+			1
+			2
+		}
+		List "e" End 0 {
+		}
+	}
+}
+"#
+    );
+
+    let format = TextFormat {
+        braces: true,
+        ..COMPACT
+    };
+    let mut tmp = Vec::new();
+    write_file_with(&mut tmp, &file, &format).unwrap();
+    let text = String::from_utf8(tmp).unwrap();
+    assert!(text.contains("\tIntArray \"a\" 2 { 1 2 }\n"));
+    assert!(text.contains("\tList \"e\" End 0 { }\n"));
+}
+
+/// The default format has arrays on a line per element, tabs, keys in the
+/// order of the file, and only the escapes the text needs to be read back
+#[test]
+fn default_format() {
+    let file = NBTFile {
+        root: NBT::Compound(vec![(
+            b"".to_vec(),
+            NBT::Compound(vec![
+                (b"b".to_vec(), NBT::IntArray(vec![1, 2])),
+                (b"a".to_vec(), NBT::String(b"say \"hi\"\nbye".to_vec())),
+                (b"l".to_vec(), NBT::list(vec![NBT::Float(1.5)])),
+                (b"e".to_vec(), NBT::list(Vec::new())),
+            ]),
+        )]),
+        compression: Compression::Gzip,
+        compression_level: None,
+    };
+
+    let mut tmp = Vec::new();
+    write_file(&mut tmp, &file).unwrap();
+    let text = String::from_utf8(tmp).unwrap();
+    assert_eq!(
+        text,
+        r#"Gzip
+Compound ""
+	IntArray "b" 2
+		1
+		2
+	String "a" "say \"hi\"
+bye"
+	List "l" Float 1
+		1.5
+	List "e" End 0
+	End
+End
+"#
+    );
+}
+
+#[test]
+fn indent_and_sort_keys() {
+    let file = NBTFile {
+        root: NBT::Compound(vec![(
+            b"".to_vec(),
+            NBT::Compound(vec![
+                (b"b".to_vec(), NBT::Int(1)),
+                (b"a".to_vec(), NBT::list(vec![NBT::Short(2)])),
+            ]),
+        )]),
+        compression: Compression::None,
+        compression_level: None,
+    };
+    let format = TextFormat {
+        indent: IndentStyle::Spaces(2),
+        sort_keys: true,
+        ..Default::default()
+    };
+
+    let mut tmp = Vec::new();
+    write_file_with(&mut tmp, &file, &format).unwrap();
+    let text = String::from_utf8(tmp).unwrap();
+    assert_eq!(
+        text,
+        "None\nCompound \"\"\n  List \"a\" Short 1\n    2\n  Int \"b\" 1\n  End\nEnd\n"
+    );
+
+    /* Reading it back gives the sorted order */
+    let looped = crate::string_read::read_file(&mut Cursor::new(text)).unwrap();
+    let keys: Vec<&[u8]> = match looped.root.get("").unwrap() {
+        NBT::Compound(x) => x.iter().map(|(k, _)| k.as_slice()).collect(),
+        _ => unreachable!(),
+    };
+    assert_eq!(keys, &[b"a", b"b"]);
+}
+
+/// Files that only differ in the order of their keys, at any depth, print the
+/// same with sort_keys, and differently without it
+#[test]
+fn sort_keys_swapped_order() {
+    let file = |swapped: bool| {
+        let mut inner = vec![
+            (b"x".to_vec(), NBT::Int(1)),
+            (b"Data".to_vec(), NBT::String(b"y".to_vec())),
+        ];
+        let mut outer = vec![
+            (b"b".to_vec(), NBT::Compound(inner.clone())),
+            (b"a".to_vec(), NBT::list(vec![NBT::Compound(inner.clone())])),
+        ];
+        if swapped {
+            inner.reverse();
+            outer = vec![
+                (b"a".to_vec(), NBT::list(vec![NBT::Compound(inner.clone())])),
+                (b"b".to_vec(), NBT::Compound(inner)),
+            ];
+        }
+        NBTFile {
+            root: NBT::Compound(vec![(b"".to_vec(), NBT::Compound(outer))]),
+            compression: Compression::None,
+            compression_level: None,
+        }
+    };
+    let print = |file: &NBTFile, sort_keys: bool| {
+        let format = TextFormat {
+            sort_keys,
+            ..Default::default()
+        };
+        let mut tmp = Vec::new();
+        write_file_with(&mut tmp, file, &format).unwrap();
+        String::from_utf8(tmp).unwrap()
+    };
+
+    let (a, b) = (file(false), file(true));
+    assert_ne!(print(&a, false), print(&b, false));
+    assert_eq!(print(&a, true), print(&b, true));
+}
+
+#[test]
+fn escape_control() {
+    let file = NBTFile {
+        root: NBT::Compound(vec![(
+            b"a\nb".to_vec(),
+            NBT::String(b"tab\there \x7f \\x41 \"\x1b[0m".to_vec()),
+        )]),
+        compression: Compression::None,
+        compression_level: None,
+    };
+    let format = TextFormat {
+        escape_control: true,
+        ..Default::default()
+    };
+
+    let mut tmp = Vec::new();
+    write_file_with(&mut tmp, &file, &format).unwrap();
+    let text = String::from_utf8(tmp).unwrap();
+    assert_eq!(
+        text,
+        r#"None
+String "a\x0ab" "tab\x09here \x7f \\x41 \"\x1b[0m"
+End
+"#
+    );
+
+    let looped = crate::string_read::read_file(&mut Cursor::new(text)).unwrap();
+    assert_eq!(looped, file);
+}
+
+#[test]
+fn escape_whitespace() {
+    let file = NBTFile {
+        root: NBT::Compound(vec![(
+            b"a\nb".to_vec(),
+            NBT::String(b"line one\r\n\tline two\x07 \\n".to_vec()),
+        )]),
+        compression: Compression::None,
+        compression_level: None,
+    };
+    /* Newlines, tabs and carriage returns are escaped by name even with
+     * escape_control, other control characters as bytes */
+    let format = TextFormat {
+        escape_whitespace: true,
+        escape_control: true,
+        ..Default::default()
+    };
+
+    let mut tmp = Vec::new();
+    write_file_with(&mut tmp, &file, &format).unwrap();
+    let text = String::from_utf8(tmp).unwrap();
+    assert_eq!(
+        text,
+        r#"None
+String "a\nb" "line one\r\n\tline two\x07 \\n"
+End
+"#
+    );
+
+    let looped = crate::string_read::read_file(&mut Cursor::new(text)).unwrap();
+    assert_eq!(looped, file);
+}
+
+/// ByteArrays written as a single hex or base64 token read back as the same
+/// bytes, in both variants of the format and in lists
+#[test]
+fn byte_array_encodings() {
+    let bytes: Vec<i8> = (0..300).map(|x| (x * 7) as i8).collect();
+    let file = NBTFile {
+        root: NBT::Compound(vec![(
+            b"".to_vec(),
+            NBT::Compound(vec![
+                (b"a".to_vec(), NBT::ByteArray(bytes.clone())),
+                (b"b".to_vec(), NBT::ByteArray(Vec::new())),
+                (b"c".to_vec(), NBT::ByteArray(vec![-1, 0, 1])),
+                (
+                    b"d".to_vec(),
+                    NBT::list(vec![NBT::ByteArray(bytes), NBT::ByteArray(vec![5])]),
+                ),
+            ]),
+        )]),
+        compression: Compression::None,
+        compression_level: None,
+    };
+
+    for (encoding, c) in [
+        (ByteArrayEncoding::Hex, "ByteArray \"c\" 3 0xff0001\n"),
+        (ByteArrayEncoding::Base64, "ByteArray \"c\" 3 base64:/wAB\n"),
+    ] {
+        for braces in [false, true] {
+            let format = TextFormat {
+                byte_array_encoding: encoding,
+                braces,
+                ..Default::default()
+            };
+            let mut tmp = Vec::new();
+            write_file_with(&mut tmp, &file, &format).unwrap();
+            let text = String::from_utf8(tmp).unwrap();
+            assert!(text.contains(c), "{}", text);
+
+            let options = crate::string_read::TextReadOptions {
+                braces,
+                ..Default::default()
+            };
+            let looped =
+                crate::string_read::read_file_with_options(&mut Cursor::new(text), &options)
+                    .unwrap();
+            assert_eq!(looped, file);
+        }
+    }
+}
+
+#[test]
+fn canonical() {
+    let tags = vec![
+        (b"z".to_vec(), NBT::Double(1e300)),
+        (b"b".to_vec(), NBT::Float(2.0)),
+        (b"a".to_vec(), NBT::IntArray(vec![1, 2])),
+    ];
+    let file = |tags| NBTFile {
+        root: NBT::Compound(vec![(b"".to_vec(), NBT::Compound(tags))]),
+        compression: Compression::None,
+        compression_level: None,
+    };
+    let write = |file: &NBTFile| {
+        let mut tmp = Vec::new();
+        write_file_with(&mut tmp, file, &TextFormat::canonical()).unwrap();
+        String::from_utf8(tmp).unwrap()
+    };
+
+    let text = write(&file(tags.clone()));
+    assert_eq!(
+        text,
+        "None\nCompound \"\"\n\tIntArray \"a\" 2\n\t\t1\n\t\t2\n\tFloat \"b\" 2.0\n\tDouble \"z\" 1e300\n\tEnd\nEnd\n"
+    );
+
+    /* The order of the keys doesn't change the text */
+    let mut reversed = tags.clone();
+    reversed.reverse();
+    assert_eq!(write(&file(reversed)), text);
+
+    /* Reading it back gives the same tags in sorted order */
+    let mut sorted = tags;
+    sorted.sort_by(|a, b| a.0.cmp(&b.0));
+    let looped = crate::string_read::read_file(&mut Cursor::new(text)).unwrap();
+    assert_eq!(looped, file(sorted));
+}
+
+#[test]
+fn value() {
+    let value = |tag: &NBT| {
+        let mut tmp = Vec::new();
+        write_value(&mut tmp, tag, &TextFormat::default()).unwrap();
+        String::from_utf8(tmp).unwrap()
+    };
+
+    assert_eq!(value(&NBT::Int(5)), "5\n");
+    assert_eq!(value(&NBT::String(b"a b".to_vec())), "\"a b\"\n");
+    assert_eq!(value(&NBT::IntArray(vec![1, 2])), "2\n1\n2\n");
+    assert_eq!(
+        value(&NBT::list(vec![NBT::Short(1), NBT::Short(2)])),
+        "Short 2\n1\n2\n"
+    );
+    assert_eq!(
+        value(&NBT::Compound(vec![
+            (b"a".to_vec(), NBT::Byte(1)),
+            (b"b".to_vec(), NBT::Compound(Vec::new())),
+        ])),
+        "Byte \"a\" 1\nCompound \"b\"\n\tEnd\nEnd\n"
+    );
+}
+
+/// How deep deep_file nests its lists, which is far deeper than a recursive
+/// writer could go on the stack of a test thread
+const DEEP: usize = 50_000;
+
+/// A file with an Int at the bottom of DEEP nested lists, with every 1000th
+/// replaced by a compound
+fn deep_file() -> NBTFile {
+    let mut tag = NBT::Int(7);
+    for i in 0..DEEP {
+        tag = if i % 1000 == 500 {
+            NBT::Compound(vec![(b"c".to_vec(), tag)])
+        } else {
+            NBT::list(vec![tag])
+        };
+    }
+    NBTFile {
+        root: NBT::Compound(vec![(b"deep".to_vec(), tag)]),
+        compression: Compression::None,
+        compression_level: None,
+    }
+}
+
+/// Take the tree apart one level at a time, since dropping it would recurse
+/// as deep as it goes
+fn drop_deep(file: NBTFile) {
+    let mut stack = vec![file.root];
+    while let Some(tag) = stack.pop() {
+        match tag {
+            NBT::List { items: x, .. } => stack.extend(x),
+            NBT::Compound(x) => stack.extend(x.into_iter().map(|(_, v)| v)),
+            _ => (),
+        }
+    }
+}
+
+/// Writing doesn't recurse, so any tree can be written however deep it is
+#[test]
+fn deep_write() {
+    let file = deep_file();
+    for braces in [false, true] {
+        /* Without indentation, as the text would otherwise grow with the
+         * square of the depth */
+        let format = TextFormat {
+            indent: IndentStyle::Spaces(0),
+            braces,
+            ..Default::default()
+        };
+        let mut buf = Vec::new();
+        write_file_with(&mut buf, &file, &format).unwrap();
+        let text = String::from_utf8(buf).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+
+        assert_eq!(lines[1].trim_end_matches(" {"), "List \"deep\" List 1");
+        /* Every list has a line giving its length of 1 */
+        let lists = lines
+            .iter()
+            .filter(|x| x.trim_end_matches(" {").ends_with(" 1"))
+            .count();
+        assert_eq!(lists, DEEP - DEEP / 1000);
+        assert!(lines.contains(&"7"));
+        let closing = if braces { "}" } else { "End" };
+        assert_eq!(lines.last(), Some(&closing));
+    }
+    drop_deep(file);
+}
@@ -1,13 +1,74 @@
-/// Contains the binary NBT files
+//! Contains the binary NBT files
 
 /* Read binary files into rust format using
  * xxd -p file | sed 's/\(..\)/0x\1,/g' | tr -d '\n'
  * and then just let rustc tell you how long the array should be */
+/* A file with nothing in the root compound, which is written as just an End
+ * tag */
+pub static EMPTY_ROOT: [u8; 1] = [0x00];
+/* A root compound named "worldname" with an Int SpawnX = 16 in it */
+pub static NAMED_ROOT: [u8; 26] = [
+    0x0a, 0x00, 0x09, 0x77, 0x6f, 0x72, 0x6c, 0x64, 0x6e, 0x61, 0x6d, 0x65, 0x03, 0x00, 0x06, 0x53,
+    0x70, 0x61, 0x77, 0x6e, 0x58, 0x00, 0x00, 0x00, 0x10, 0x00,
+];
 pub static HELLO_WORLD: [u8; 33] = [
     0x0a, 0x00, 0x0b, 0x68, 0x65, 0x6c, 0x6c, 0x6f, 0x20, 0x77, 0x6f, 0x72, 0x6c, 0x64, 0x08, 0x00,
     0x04, 0x6e, 0x61, 0x6d, 0x65, 0x00, 0x09, 0x42, 0x61, 0x6e, 0x61, 0x6e, 0x72, 0x61, 0x6d, 0x61,
     0x00,
 ];
+/* A root compound with a String whose name, 0xff k, isn't UTF-8, and whose
+ * value is a modified UTF-8 NUL, 0xc0 0x80, followed by "v */
+pub static NON_UTF8: [u8; 15] = [
+    0x0a, 0x00, 0x00, 0x08, 0x00, 0x02, 0xff, 0x6b, 0x00, 0x04, 0xc0, 0x80, 0x22, 0x76, 0x00,
+];
+/* A root compound with a String named with an emoji, and whose value is "a"
+ * followed by the emoji, written as a surrogate pair like Java would */
+pub static EMOJI: [u8; 22] = [
+    0x0a, 0x00, 0x00, 0x08, 0x00, 0x06, 0xed, 0xa0, 0xbd, 0xed, 0xb8, 0x80, 0x00, 0x07, 0x61, 0xed,
+    0xa0, 0xbd, 0xed, 0xb8, 0x80, 0x00,
+];
+/* A root compound with an empty List "l" declared as a list of Int */
+pub static EMPTY_INT_LIST: [u8; 13] = [
+    0x0a, 0x00, 0x00, 0x09, 0x00, 0x01, 0x6c, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00,
+];
+/* HELLO_WORLD with a version byte of junk in front of it */
+pub static LEADING_BYTE_HELLO_WORLD: [u8; 34] = [
+    0x07, 0x0a, 0x00, 0x0b, 0x68, 0x65, 0x6c, 0x6c, 0x6f, 0x20, 0x77, 0x6f, 0x72, 0x6c, 0x64, 0x08,
+    0x00, 0x04, 0x6e, 0x61, 0x6d, 0x65, 0x00, 0x09, 0x42, 0x61, 0x6e, 0x61, 0x6e, 0x72, 0x61, 0x6d,
+    0x61, 0x00,
+];
+/* A root compound named "Level" with a LongArray Heightmap of 1, -1 and the
+ * largest and smallest longs in it */
+pub static LONG_ARRAY: [u8; 57] = [
+    0x0a, 0x00, 0x05, 0x4c, 0x65, 0x76, 0x65, 0x6c, 0x0c, 0x00, 0x09, 0x48, 0x65, 0x69, 0x67, 0x68,
+    0x74, 0x6d, 0x61, 0x70, 0x00, 0x00, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01,
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x7f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+    0x80, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+];
+/* The item components of an item stack in a 1.20.5+ packet, with an added
+ * custom_data component (type 0) of {owner: "C4K3", count: 300}, and a
+ * removed component of type 300, whose VarInt takes up two bytes */
+pub static COMPONENTS: [u8; 33] = [
+    0x01, 0x01, 0x00, 0x0a, 0x08, 0x00, 0x05, 0x6f, 0x77, 0x6e, 0x65, 0x72, 0x00, 0x04, 0x43, 0x34,
+    0x4b, 0x33, 0x03, 0x00, 0x05, 0x63, 0x6f, 0x75, 0x6e, 0x74, 0x00, 0x00, 0x01, 0x2c, 0x00, 0xac,
+    0x02,
+];
+/* A Bedrock Edition level.dat, which is little endian and starts with an 8
+ * byte header of the storage version 10 and the length of the rest of the
+ * file. The root compound is named "" and has StorageVersion = 10, LevelName
+ * = "My World", LastPlayed = 1700000000L, rainLevel = 0.5f and
+ * lastOpenedWithVersion = [1, 20, 80] as a list of Ints. */
+pub static BEDROCK_LEVEL_DAT: [u8; 133] = [
+    0x0a, 0x00, 0x00, 0x00, 0x7d, 0x00, 0x00, 0x00, 0x0a, 0x00, 0x00, 0x03, 0x0e, 0x00, 0x53, 0x74,
+    0x6f, 0x72, 0x61, 0x67, 0x65, 0x56, 0x65, 0x72, 0x73, 0x69, 0x6f, 0x6e, 0x0a, 0x00, 0x00, 0x00,
+    0x08, 0x09, 0x00, 0x4c, 0x65, 0x76, 0x65, 0x6c, 0x4e, 0x61, 0x6d, 0x65, 0x08, 0x00, 0x4d, 0x79,
+    0x20, 0x57, 0x6f, 0x72, 0x6c, 0x64, 0x04, 0x0a, 0x00, 0x4c, 0x61, 0x73, 0x74, 0x50, 0x6c, 0x61,
+    0x79, 0x65, 0x64, 0x00, 0xf1, 0x53, 0x65, 0x00, 0x00, 0x00, 0x00, 0x05, 0x09, 0x00, 0x72, 0x61,
+    0x69, 0x6e, 0x4c, 0x65, 0x76, 0x65, 0x6c, 0x00, 0x00, 0x00, 0x3f, 0x09, 0x15, 0x00, 0x6c, 0x61,
+    0x73, 0x74, 0x4f, 0x70, 0x65, 0x6e, 0x65, 0x64, 0x57, 0x69, 0x74, 0x68, 0x56, 0x65, 0x72, 0x73,
+    0x69, 0x6f, 0x6e, 0x03, 0x03, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x14, 0x00, 0x00, 0x00,
+    0x50, 0x00, 0x00, 0x00, 0x00,
+];
 pub static BIGTEST_UNCOMPRESSED: [u8; 1544] = [
     0x0a, 0x00, 0x05, 0x4c, 0x65, 0x76, 0x65, 0x6c, 0x04, 0x00, 0x08, 0x6c, 0x6f, 0x6e, 0x67, 0x54,
     0x65, 0x73, 0x74, 0x7f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x02, 0x00, 0x09, 0x73, 0x68,
@@ -174,6 +235,82 @@ pub static BIGTEST_ZLIB: [u8; 495] = [
     0x99, 0x6b, 0xcc, 0x35, 0xe6, 0x1a, 0xff, 0x57, 0x23, 0x08, 0x42, 0xcb, 0xe9, 0x1b, 0xd6, 0x78,
     0xc2, 0xec, 0xfe, 0xfc, 0x7a, 0xfb, 0x7d, 0x78, 0xd3, 0x84, 0xdf, 0xf6, 0x84, 0x58, 0x4f,
 ];
+/* BIGTEST_UNCOMPRESSED compressed by the zstd tool, with a content checksum */
+pub static BIGTEST_ZSTD: [u8; 520] = [
+    0x28, 0xb5, 0x2f, 0xfd, 0x64, 0x08, 0x05, 0xd5, 0x0f, 0x00, 0xd6, 0x9c, 0x6c, 0x46, 0x10, 0xa9,
+    0x6a, 0x3a, 0x00, 0x00, 0x00, 0x04, 0x20, 0x30, 0x02, 0x00, 0x2a, 0x58, 0x11, 0x00, 0x00, 0x00,
+    0x8c, 0x00, 0x28, 0xc2, 0x6e, 0x46, 0x8a, 0x0f, 0x5c, 0x15, 0xc6, 0x45, 0xdd, 0x7d, 0xf2, 0x5d,
+    0x5f, 0x4f, 0x24, 0xd1, 0x76, 0x5a, 0xb6, 0xe0, 0xe7, 0x85, 0x5d, 0x59, 0xbd, 0x2b, 0xcf, 0x33,
+    0x4c, 0x2f, 0x5e, 0x88, 0x5d, 0xbc, 0xaf, 0xb4, 0xaa, 0xac, 0xb6, 0xb6, 0xef, 0x5b, 0xac, 0xeb,
+    0x70, 0x1d, 0x6b, 0x0a, 0x59, 0x00, 0x4f, 0x00, 0x59, 0x00, 0xb9, 0x67, 0xf8, 0x96, 0x9e, 0xd8,
+    0x52, 0x93, 0x9e, 0xdd, 0xbc, 0x5e, 0xec, 0xb9, 0xcd, 0xf8, 0xcc, 0xdc, 0x42, 0xfa, 0xe4, 0x50,
+    0x65, 0x8a, 0xd2, 0x34, 0xbf, 0x0c, 0xa8, 0x80, 0x8b, 0x80, 0x93, 0x80, 0xeb, 0x05, 0xae, 0xd6,
+    0x8b, 0x84, 0x35, 0x09, 0x5b, 0x91, 0xbd, 0xd1, 0xb0, 0x56, 0xc3, 0x5a, 0x2c, 0xb2, 0x55, 0xe3,
+    0xeb, 0x83, 0x41, 0x7f, 0x8c, 0xe3, 0x8a, 0x05, 0xfe, 0x54, 0xf3, 0x6a, 0xef, 0x65, 0x35, 0xad,
+    0xad, 0x9a, 0x57, 0x72, 0x10, 0xf2, 0x7a, 0x49, 0x13, 0xa7, 0xa8, 0xf7, 0x0e, 0xbd, 0x28, 0x77,
+    0xbc, 0xdc, 0x06, 0xc7, 0xe9, 0x81, 0xd9, 0xcd, 0xe5, 0x55, 0x4d, 0xec, 0x3b, 0x91, 0x40, 0x9b,
+    0xa5, 0x82, 0x4e, 0xe5, 0xbf, 0x05, 0x05, 0x5c, 0xd3, 0xf9, 0xcf, 0xf9, 0x1f, 0xe0, 0xff, 0xe6,
+    0x3f, 0x80, 0xff, 0x97, 0xff, 0x1d, 0xf8, 0xf5, 0x7a, 0xa2, 0x9d, 0x6b, 0x9e, 0xd5, 0x2b, 0x33,
+    0xcd, 0x55, 0xc6, 0x34, 0xaf, 0xe5, 0xf6, 0x7f, 0x4a, 0xe3, 0xde, 0xd0, 0x12, 0xe5, 0xf2, 0xcb,
+    0x7a, 0x3b, 0xb1, 0x95, 0x2c, 0xcc, 0xaf, 0xad, 0xc4, 0xdd, 0x3d, 0x5b, 0x59, 0xb3, 0xdc, 0xe6,
+    0xf5, 0x06, 0x0e, 0x14, 0xa8, 0x67, 0xb0, 0xfc, 0x20, 0x28, 0x12, 0x89, 0x3a, 0x3c, 0x75, 0x68,
+    0x1a, 0x75, 0x58, 0x42, 0xd0, 0x21, 0xc9, 0xd4, 0xa1, 0xf8, 0xb5, 0x98, 0x00, 0x77, 0x2d, 0x9f,
+    0x0f, 0xda, 0xc4, 0x67, 0x56, 0xcb, 0xbc, 0x76, 0x28, 0x8a, 0xa3, 0x02, 0xe0, 0x45, 0x08, 0x1b,
+    0xb0, 0xb0, 0x20, 0x85, 0x70, 0x08, 0x7f, 0x4d, 0x4b, 0x7b, 0x6d, 0x51, 0x14, 0x47, 0x9d, 0x57,
+    0xf5, 0xa4, 0xf5, 0xad, 0xe5, 0xaf, 0x53, 0x4a, 0x55, 0xdd, 0xb8, 0xcf, 0x40, 0xb8, 0x39, 0x45,
+    0xe6, 0xbd, 0x61, 0xf4, 0x09, 0x17, 0x2e, 0x5e, 0xa0, 0x86, 0x07, 0x8f, 0x35, 0xc8, 0x59, 0x70,
+    0x40, 0x01, 0x83, 0x06, 0x2a, 0xbb, 0xa5, 0x33, 0x7b, 0x8c, 0x88, 0xf1, 0x28, 0x7d, 0x20, 0x51,
+    0x8a, 0x64, 0xc2, 0x55, 0xd1, 0xbd, 0x3a, 0x54, 0x63, 0x0c, 0x22, 0xc3, 0xe3, 0xa0, 0xd8, 0x5c,
+    0x86, 0xf9, 0x94, 0x46, 0x40, 0x25, 0xa9, 0xd0, 0x84, 0x44, 0x85, 0xe9, 0xe5, 0x51, 0x45, 0xc4,
+    0x58, 0x19, 0x18, 0x1c, 0xe4, 0x86, 0x24, 0x3b, 0x7d, 0x2a, 0x23, 0x22, 0x25, 0x9c, 0x10, 0x88,
+    0x44, 0x88, 0xa9, 0x3d, 0x0f, 0x11, 0x21, 0xcb, 0x62, 0x38, 0x90, 0x28, 0x92, 0x78, 0xf2, 0xa8,
+    0x7c, 0x22, 0x22, 0x0e, 0x09, 0x88, 0x2b, 0xa4, 0xeb, 0x8e, 0x87, 0x31, 0x84, 0xb0, 0x5e, 0x1b,
+    0x00, 0x65, 0xa4, 0xc0, 0x55, 0x81, 0x4f, 0x2c, 0x99, 0x82, 0xf6, 0xa2, 0xb9, 0xfa, 0xb5, 0x44,
+    0x6d, 0xad, 0x06, 0x94, 0x7b, 0xd4, 0x3a, 0x72, 0xa6, 0xdb, 0x1e, 0x04, 0x44, 0x46, 0x96, 0x10,
+    0x76, 0x03, 0x23, 0xc0, 0x08, 0x30, 0x82, 0x62, 0xc4, 0xc7, 0x4a, 0xf8, 0x0d, 0xbf, 0xa1, 0x0b,
+    0xb7, 0xae, 0x51, 0x9a, 0x7f, 0x51, 0x02, 0xd7, 0x20, 0x24, 0x49, 0xb8, 0xd0, 0xa8, 0x61, 0x6e,
+    0x0e, 0x7e, 0x00, 0x4c, 0x9f, 0x7a, 0xf5, 0x4e,
+];
+/* BIGTEST_UNCOMPRESSED compressed by the lz4 tool, as an LZ4 frame with a
+ * compressed block and a content checksum */
+pub static BIGTEST_LZ4: [u8; 573] = [
+    0x04, 0x22, 0x4d, 0x18, 0x64, 0x40, 0xa7, 0x2a, 0x02, 0x00, 0x00, 0xf2, 0x06, 0x0a, 0x00, 0x05,
+    0x4c, 0x65, 0x76, 0x65, 0x6c, 0x04, 0x00, 0x08, 0x6c, 0x6f, 0x6e, 0x67, 0x54, 0x65, 0x73, 0x74,
+    0x7f, 0xff, 0x01, 0x00, 0x82, 0x02, 0x00, 0x09, 0x73, 0x68, 0x6f, 0x72, 0x74, 0x14, 0x00, 0x72,
+    0x08, 0x00, 0x0a, 0x73, 0x74, 0x72, 0x69, 0x23, 0x00, 0xf1, 0x23, 0x00, 0x29, 0x48, 0x45, 0x4c,
+    0x4c, 0x4f, 0x20, 0x57, 0x4f, 0x52, 0x4c, 0x44, 0x20, 0x54, 0x48, 0x49, 0x53, 0x20, 0x49, 0x53,
+    0x20, 0x41, 0x20, 0x54, 0x45, 0x53, 0x54, 0x20, 0x53, 0x54, 0x52, 0x49, 0x4e, 0x47, 0x20, 0xc3,
+    0x85, 0xc3, 0x84, 0xc3, 0x96, 0x21, 0x05, 0x00, 0x09, 0x66, 0x6c, 0x6f, 0x61, 0x46, 0x00, 0x91,
+    0x3e, 0xff, 0x18, 0x32, 0x03, 0x00, 0x07, 0x69, 0x6e, 0x0e, 0x00, 0x00, 0x68, 0x00, 0xf4, 0x30,
+    0x0a, 0x00, 0x14, 0x6e, 0x65, 0x73, 0x74, 0x65, 0x64, 0x20, 0x63, 0x6f, 0x6d, 0x70, 0x6f, 0x75,
+    0x6e, 0x64, 0x20, 0x74, 0x65, 0x73, 0x74, 0x0a, 0x00, 0x03, 0x68, 0x61, 0x6d, 0x08, 0x00, 0x04,
+    0x6e, 0x61, 0x6d, 0x65, 0x00, 0x06, 0x48, 0x61, 0x6d, 0x70, 0x75, 0x73, 0x05, 0x00, 0x05, 0x76,
+    0x61, 0x6c, 0x75, 0x65, 0x3f, 0x40, 0x00, 0x00, 0x00, 0x0a, 0x00, 0x03, 0x65, 0x67, 0x67, 0x22,
+    0x00, 0x85, 0x07, 0x45, 0x67, 0x67, 0x62, 0x65, 0x72, 0x74, 0x23, 0x00, 0x10, 0x00, 0x01, 0x00,
+    0x61, 0x09, 0x00, 0x0f, 0x6c, 0x69, 0x73, 0x6c, 0x00, 0x20, 0x20, 0x28, 0xde, 0x00, 0x60, 0x29,
+    0x04, 0x00, 0x00, 0x00, 0x05, 0x1b, 0x00, 0x41, 0x00, 0x00, 0x00, 0x0b, 0x06, 0x00, 0x30, 0x00,
+    0x00, 0x0c, 0x05, 0x00, 0x41, 0x00, 0x00, 0x00, 0x0d, 0x06, 0x00, 0x30, 0x00, 0x00, 0x0e, 0x05,
+    0x00, 0x76, 0x00, 0x00, 0x00, 0x0f, 0x09, 0x00, 0x13, 0x3f, 0x00, 0x04, 0x9f, 0x00, 0x64, 0x29,
+    0x0a, 0x00, 0x00, 0x00, 0x02, 0x78, 0x00, 0x25, 0x0f, 0x43, 0xb6, 0x00, 0xff, 0x0c, 0x61, 0x67,
+    0x20, 0x23, 0x30, 0x04, 0x00, 0x0a, 0x63, 0x72, 0x65, 0x61, 0x74, 0x65, 0x64, 0x2d, 0x6f, 0x6e,
+    0x00, 0x00, 0x01, 0x26, 0x52, 0x37, 0xd5, 0x8d, 0x00, 0x2e, 0x00, 0x04, 0x1f, 0x31, 0x2e, 0x00,
+    0x03, 0x71, 0x01, 0x00, 0x08, 0x62, 0x79, 0x74, 0x65, 0x76, 0x01, 0x30, 0x07, 0x00, 0x65, 0x0c,
+    0x00, 0x50, 0x41, 0x72, 0x72, 0x61, 0x79, 0x11, 0x00, 0xf1, 0x02, 0x20, 0x28, 0x74, 0x68, 0x65,
+    0x20, 0x66, 0x69, 0x72, 0x73, 0x74, 0x20, 0x31, 0x30, 0x30, 0x30, 0x20, 0x11, 0x01, 0xff, 0x9b,
+    0x73, 0x20, 0x6f, 0x66, 0x20, 0x28, 0x6e, 0x2a, 0x6e, 0x2a, 0x32, 0x35, 0x35, 0x2b, 0x6e, 0x2a,
+    0x37, 0x29, 0x25, 0x31, 0x30, 0x30, 0x2c, 0x20, 0x73, 0x74, 0x61, 0x72, 0x74, 0x69, 0x6e, 0x67,
+    0x20, 0x77, 0x69, 0x74, 0x68, 0x20, 0x6e, 0x3d, 0x30, 0x20, 0x28, 0x30, 0x2c, 0x20, 0x36, 0x32,
+    0x2c, 0x20, 0x33, 0x34, 0x2c, 0x20, 0x31, 0x36, 0x2c, 0x20, 0x38, 0x2c, 0x20, 0x2e, 0x2e, 0x2e,
+    0x29, 0x29, 0x00, 0x00, 0x03, 0xe8, 0x00, 0x3e, 0x22, 0x10, 0x08, 0x0a, 0x16, 0x2c, 0x4c, 0x12,
+    0x46, 0x20, 0x04, 0x56, 0x4e, 0x50, 0x5c, 0x0e, 0x2e, 0x58, 0x28, 0x02, 0x4a, 0x38, 0x30, 0x32,
+    0x3e, 0x54, 0x10, 0x3a, 0x0a, 0x48, 0x2c, 0x1a, 0x12, 0x14, 0x20, 0x36, 0x56, 0x1c, 0x50, 0x2a,
+    0x0e, 0x60, 0x58, 0x5a, 0x02, 0x18, 0x38, 0x62, 0x32, 0x0c, 0x54, 0x42, 0x3a, 0x3c, 0x48, 0x5e,
+    0x1a, 0x44, 0x14, 0x52, 0x36, 0x24, 0x1c, 0x1e, 0x2a, 0x40, 0x60, 0x26, 0x5a, 0x34, 0x18, 0x06,
+    0x62, 0x00, 0x0c, 0x22, 0x42, 0x08, 0x3c, 0x16, 0x5e, 0x4c, 0x44, 0x46, 0x52, 0x04, 0x24, 0x4e,
+    0x1e, 0x5c, 0x40, 0x2e, 0x26, 0x28, 0x34, 0x4a, 0x06, 0x30, 0x64, 0x00, 0xff, 0xff, 0xff, 0x74,
+    0x81, 0x06, 0x00, 0x0a, 0x64, 0x6f, 0x75, 0x62, 0x6c, 0x62, 0x04, 0x90, 0x3f, 0xdf, 0x8f, 0x6b,
+    0xbb, 0xff, 0x6a, 0x5e, 0x00, 0x00, 0x00, 0x00, 0x00, 0xb6, 0xe3, 0x67, 0x17,
+];
 pub static PLAYER_FILE: [u8; 8589] = [
     0x0a, 0x00, 0x00, 0x03, 0x00, 0x0f, 0x48, 0x75, 0x72, 0x74, 0x42, 0x79, 0x54, 0x69, 0x6d, 0x65,
     0x73, 0x74, 0x61, 0x6d, 0x70, 0x0a, 0xff, 0xf2, 0xa7, 0x0a, 0x00, 0x06, 0x62, 0x75, 0x6b, 0x6b,
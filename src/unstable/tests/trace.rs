@@ -0,0 +1,89 @@
+/// Tests of the traces logged with the trace feature
+use std::fmt::{self, Write};
+use std::io::Cursor;
+use std::sync::{Arc, Mutex};
+
+use tracing::field::{Field, Visit};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+
+use crate::read::{self, ReadOptions};
+
+use super::tests_data;
+
+/// Records every event as its level and message
+struct Capture(Arc<Mutex<Vec<String>>>);
+impl<S: Subscriber> Layer<S> for Capture {
+    fn on_event(&self, event: &Event, _ctx: Context<S>) {
+        let mut message = format!("{} ", event.metadata().level());
+        event.record(&mut MessageVisitor(&mut message));
+        self.0.lock().unwrap().push(message);
+    }
+}
+
+struct MessageVisitor<'a>(&'a mut String);
+impl<'a> Visit for MessageVisitor<'a> {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            write!(self.0, "{:?}", value).unwrap();
+        }
+    }
+}
+
+/// Run the function, returning everything it logged. The subscriber is only
+/// set for the current thread, so tests running in parallel don't mix.
+fn capture<F: FnOnce()>(f: F) -> Vec<String> {
+    let messages = Arc::new(Mutex::new(Vec::new()));
+    let subscriber = tracing_subscriber::registry().with(Capture(messages.clone()));
+    tracing::subscriber::with_default(subscriber, f);
+
+    let messages = messages.lock().unwrap();
+    messages.clone()
+}
+
+#[test]
+fn read_traces() {
+    let messages = capture(|| {
+        let _: crate::data::NBTFile =
+            read::read_file(&mut Cursor::new(&tests_data::BIGTEST_COMPRESSED[..])).unwrap();
+    });
+
+    assert_eq!(messages[0], "DEBUG reading Gzip compressed file");
+    assert!(
+        messages.contains(&"TRACE reading compound at 'Level/nested compound test'".to_string())
+    );
+    assert!(messages.contains(
+        &"TRACE reading list of 2 tags of type 10 at 'Level/listTest (compound)'".to_string()
+    ));
+    assert!(
+        messages.contains(&"TRACE reading compound at 'Level/listTest (compound)/1'".to_string())
+    );
+    assert!(messages
+        .last()
+        .unwrap()
+        .starts_with("DEBUG read 507 bytes of Gzip compressed data, with"));
+}
+
+#[test]
+fn decompress_traces() {
+    let mut gzipped = Vec::new();
+    {
+        use std::io::Write;
+
+        let mut w = flate2::write::GzEncoder::new(&mut gzipped, flate2::Compression::default());
+        w.write_all(&tests_data::BIGTEST_ZLIB).unwrap();
+        let _: &mut Vec<u8> = w.finish().unwrap();
+    }
+
+    let options = ReadOptions {
+        recursive_decompress: true,
+        ..Default::default()
+    };
+    let messages = capture(|| {
+        let _: crate::data::NBTFile =
+            read::read_file_with_options(&mut &gzipped[..], &options).unwrap();
+    });
+
+    assert_eq!(messages[0], "DEBUG decompressing Gzip layer 1");
+    assert_eq!(messages[1], "DEBUG decompressing Zlib layer 2");
+}
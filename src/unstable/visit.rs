@@ -0,0 +1,111 @@
+use crate::data::NBT;
+use crate::path::{NbtPath, Segment};
+use crate::Result;
+
+/// A visitor that is shown every tag in an NBT tree, in the order the tags
+/// appear in the file.
+///
+/// The tags are borrowed for the lifetime of the tree, so a visitor may keep
+/// references to them.
+pub trait Visitor<'a> {
+    /// Called for every tag in the tree, including the root. Containers are
+    /// visited before their contents.
+    fn visit_tag(&mut self, _path: &NbtPath, _tag: &'a NBT) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Walk the whole tree depth-first, calling the visitor on every tag. Stops
+/// at the first error returned by the visitor.
+pub fn visit<'a, V: Visitor<'a>>(tag: &'a NBT, visitor: &mut V) -> Result<()> {
+    let mut path = NbtPath::new();
+    walk_tag_ref(tag, visitor, &mut path)
+}
+
+fn walk_tag_ref<'a, V: Visitor<'a>>(
+    tag: &'a NBT,
+    visitor: &mut V,
+    path: &mut NbtPath,
+) -> Result<()> {
+    visitor.visit_tag(path, tag)?;
+
+    match tag {
+        NBT::List { items: x, .. } => {
+            for (i, val) in x.iter().enumerate() {
+                path.push(Segment::Index(i));
+                walk_tag_ref(val, visitor, path)?;
+                let _: Option<Segment> = path.pop();
+            }
+        }
+        NBT::Compound(x) => {
+            for (key, val) in x {
+                path.push(Segment::Key(key.clone()));
+                walk_tag_ref(val, visitor, path)?;
+                let _: Option<Segment> = path.pop();
+            }
+        }
+        _ => (),
+    }
+
+    Ok(())
+}
+
+/// A visitor that is given mutable access to every part of an NBT tree, in
+/// the order the tags appear in the file.
+///
+/// All methods default to doing nothing, so implementors only need to
+/// override the ones they're interested in.
+pub trait VisitorMut {
+    /// Called for every tag in the tree, including the root. Containers are
+    /// visited before their contents, so any changes made to them here are
+    /// seen when walking their contents.
+    fn visit_tag(&mut self, _path: &NbtPath, _tag: &mut NBT) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called for every key in a compound, before the tag it names is
+    /// visited. The path is that of the compound, and the key is
+    /// map[index].0. The whole map is given so that the visitor can inspect
+    /// the other keys in the compound.
+    fn visit_compound_key(
+        &mut self,
+        _path: &NbtPath,
+        _map: &mut [(Vec<u8>, NBT)],
+        _index: usize,
+    ) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Walk the whole tree depth-first, calling the visitor on every part of it.
+/// Stops at the first error returned by the visitor.
+pub fn visit_mut<V: VisitorMut>(tag: &mut NBT, visitor: &mut V) -> Result<()> {
+    let mut path = NbtPath::new();
+    walk_tag(tag, visitor, &mut path)
+}
+
+fn walk_tag<V: VisitorMut>(tag: &mut NBT, visitor: &mut V, path: &mut NbtPath) -> Result<()> {
+    visitor.visit_tag(path, tag)?;
+
+    match tag {
+        NBT::List { items: x, .. } => {
+            for (i, val) in x.iter_mut().enumerate() {
+                path.push(Segment::Index(i));
+                walk_tag(val, visitor, path)?;
+                let _: Option<Segment> = path.pop();
+            }
+        }
+        NBT::Compound(x) => {
+            for i in 0..x.len() {
+                visitor.visit_compound_key(path, x, i)?;
+                let (key, val) = &mut x[i];
+                path.push(Segment::Key(key.clone()));
+                walk_tag(val, visitor, path)?;
+                let _: Option<Segment> = path.pop();
+            }
+        }
+        _ => (),
+    }
+
+    Ok(())
+}
@@ -1,86 +1,243 @@
-use crate::data::{Compression, NBTFile, NBT};
+use crate::checksum::Checksum;
+use crate::data::{Compression, Endianness, NBTFile, NBT};
+use crate::deflate::{self, Strategy};
+use crate::io::CountingWriter;
+use crate::lz4;
+use crate::zstd;
 use crate::Result;
 
-use std::io::Write;
+use std::io::{self, BufWriter, Read, Write};
 
-use byteorder::{BigEndian, WriteBytesExt};
+use byteorder::{BigEndian, LittleEndian, WriteBytesExt};
 
 use flate2::write::{GzEncoder, ZlibEncoder};
+use flate2::GzBuilder;
 
+/// Write a number in the given byte order with the byteorder method of the
+/// given name, such as write_i32
+macro_rules! write_number {
+    ($w:expr, $method:ident, $endianness:expr, $val:expr) => {
+        match $endianness {
+            Endianness::Big => $w.$method::<BigEndian>($val),
+            Endianness::Little => $w.$method::<LittleEndian>($val),
+        }
+        .map_err(failure::Error::from)
+    };
+}
+
+/// The level to compress the file with, as given by the options, or else by
+/// the file
 macro_rules! compression_level {
-    () => {
-        flate2::Compression::default()
+    ($file:expr, $options:expr) => {
+        flate2::Compression::new(
+            $options
+                .level
+                .or($file.compression_level)
+                .unwrap_or(DEFAULT_COMPRESSION_LEVEL),
+        )
     };
 }
 
-/// Given an NBT file, write it as a binary NBT file to the writer
+/// The level gzip and zlib files are compressed with when the file doesn't
+/// give one. It's zlib's default, and fixed here rather than left to flate2 so
+/// that the same file is always compressed the same way.
+pub const DEFAULT_COMPRESSION_LEVEL: u32 = 6;
+
+/// Options for how to write a binary NBT file. The default is to write files
+/// the same way as Minecraft.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct WriteOptions {
+    /// End the implicit root compound with an End tag like any other
+    /// compound, for readers that expect one. Minecraft doesn't write it, and
+    /// nbted reads files either with or without it.
+    pub root_end: bool,
+    /// Pad the file with zero bytes after the NBT data up to a multiple of
+    /// this many bytes, for containers that store files in fixed size
+    /// sectors. Readers stop at the end of the root compound, so the padding
+    /// is dropped when the file is read back.
+    pub pad: Option<usize>,
+    /// The byte order to write the numbers in the file in, including the
+    /// lengths of strings, lists and arrays
+    pub endianness: Endianness,
+    /// How gzip and zlib files are compressed, which has no effect on other
+    /// compression
+    pub strategy: Strategy,
+    /// The level to compress gzip and zlib files with, from 0 to 9, instead
+    /// of the file's compression_level. Higher levels give smaller files but
+    /// are slower to write.
+    pub level: Option<u32>,
+}
+
+/// Given an NBT file, write it as a binary NBT file to the writer. The same
+/// file is always written as the same bytes, so gzip files are given a
+/// modification time of 0 rather than the current time.
 pub fn write_file<W: Write>(w: &mut W, file: &NBTFile) -> Result<()> {
+    write_file_with_options(w, file, &WriteOptions::default())
+}
+
+/// Like write_file, but written as given by the options
+pub fn write_file_with_options<W: Write>(
+    w: &mut W,
+    file: &NBTFile,
+    options: &WriteOptions,
+) -> Result<()> {
+    if options.pad == Some(0) {
+        bail!("Unable to pad file to a multiple of 0 bytes");
+    }
+    if let Some(level) = options.level {
+        if level > 9 {
+            bail!("Invalid compression level {}, expected 0 to 9", level);
+        }
+    }
+
+    let mut w = CountingWriter::new(w);
+    write_nbt(&mut w, file, options)?;
+
+    if let Some(sector) = options.pad {
+        let len = w.count() % sector as u64;
+        if len != 0 {
+            let _: u64 = io::copy(&mut io::repeat(0).take(sector as u64 - len), &mut w)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Like write_file, but zero padded up to a multiple of sector bytes as with
+/// WriteOptions::pad
+pub fn write_file_padded<W: Write>(w: &mut W, file: &NBTFile, sector: usize) -> Result<()> {
+    let options = WriteOptions {
+        pad: Some(sector),
+        ..Default::default()
+    };
+    write_file_with_options(w, file, &options)
+}
+
+/// Returns how many bytes write_file_with_options would write for the file,
+/// compressed as given by the file, without keeping any of it
+pub fn file_len(file: &NBTFile, options: &WriteOptions) -> Result<u64> {
+    let mut w = CountingWriter::new(io::sink());
+    write_file_with_options(&mut w, file, options)?;
+    Ok(w.count())
+}
+
+/// Write the NBT data of the file, without any padding
+fn write_nbt<W: Write>(w: &mut W, file: &NBTFile, options: &WriteOptions) -> Result<()> {
     let map = match file.root {
         NBT::Compound(ref x) => x,
         _ => unreachable!(),
     };
 
+    /* The implicit compound is normally ended by the end of the file, but if
+     * it's empty then the file would be empty too, so it's given an End tag
+     * instead */
+    let end = map.is_empty() || options.root_end;
+    debug!("writing {} compressed file", file.compression.to_str());
+
     match file.compression {
-        Compression::None => write_compound(w, &map, false)?,
+        Compression::None => write_compound(w, map, end, options.endianness)?,
+        Compression::Gzip | Compression::Zlib if options.strategy != Strategy::Default => {
+            let level = compression_level!(file, options).level();
+            let encoder = match file.compression {
+                Compression::Gzip => deflate::Encoder::gzip(w, level, options.strategy)?,
+                _ => deflate::Encoder::zlib(w, level, options.strategy),
+            };
+            let mut w = BufWriter::new(encoder);
+            write_compound(&mut w, map, end, options.endianness)?;
+            let _: &mut W = finish_buffered(w)?.finish()?;
+        }
+        /* The encoders compress as they're written to, so the compressed
+         * file is never held in memory. Every number is its own small write,
+         * which the encoders are slow with, so they're given them in larger
+         * pieces through a BufWriter. */
         Compression::Gzip => {
-            let mut w = GzEncoder::new(w, compression_level!());
-            write_compound(&mut w, map, false)?;
-            let _: &mut W = w.finish()?;
+            let mut w = BufWriter::new(gzip_encoder(w, compression_level!(file, options)));
+            write_compound(&mut w, map, end, options.endianness)?;
+            let _: &mut W = finish_buffered(w)?.finish()?;
         }
         Compression::Zlib => {
-            let mut w = ZlibEncoder::new(w, compression_level!());
-            write_compound(&mut w, map, false)?;
-            let _: &mut W = w.finish()?;
+            let mut w = BufWriter::new(ZlibEncoder::new(w, compression_level!(file, options)));
+            write_compound(&mut w, map, end, options.endianness)?;
+            let _: &mut W = finish_buffered(w)?.finish()?;
         }
+        Compression::Zstd => zstd::compress_with(w, |mut w| {
+            write_compound(&mut w, map, end, options.endianness)
+        })?,
+        Compression::Lz4 => lz4::compress_with(w, |mut w| {
+            write_compound(&mut w, map, end, options.endianness)
+        })?,
     }
 
     Ok(())
 }
 
-fn write_tag<W: Write>(w: &mut W, tag: &NBT) -> Result<()> {
-    match *tag {
-        NBT::End => bail!("Unable to write End tag"),
-        NBT::Byte(x) => write_byte(w, x),
-        NBT::Short(x) => write_short(w, x),
-        NBT::Int(x) => write_int(w, x),
-        NBT::Long(x) => write_long(w, x),
-        NBT::Float(x) => write_float(w, x),
-        NBT::Double(x) => write_double(w, x),
-        NBT::ByteArray(ref x) => write_byte_array(w, x),
-        NBT::String(ref x) => write_string(w, x),
-        NBT::List(ref x) => write_list(w, x),
-        NBT::Compound(ref x) => write_compound(w, x, true),
-        NBT::IntArray(ref x) => write_int_array(w, x),
-        NBT::LongArray(ref x) => write_long_array(w, x),
-    }
+/// A gzip encoder without a modification time in the header, and with the
+/// operating system as unknown (255), so that writing the same file twice,
+/// anywhere, gives the same bytes
+pub(crate) fn gzip_encoder<W: Write>(w: W, level: flate2::Compression) -> GzEncoder<W> {
+    GzBuilder::new()
+        .mtime(0)
+        .operating_system(255)
+        .write(w, level)
 }
 
-fn write_byte<W: Write>(w: &mut W, val: i8) -> Result<()> {
-    w.write_i8(val).map_err(|e| e.into())
+/// Flush the BufWriter and return what it was writing to
+pub(crate) fn finish_buffered<W: Write>(w: BufWriter<W>) -> Result<W> {
+    w.into_inner().map_err(|e| e.into_error().into())
 }
 
-fn write_short<W: Write>(w: &mut W, val: i16) -> Result<()> {
-    w.write_i16::<BigEndian>(val).map_err(|e| e.into())
+/// Like write_file, but followed by a 4 byte big endian checksum of the file
+/// as written, as read by read::read_file_with_checksum
+pub fn write_file_with_checksum<W: Write>(
+    w: &mut W,
+    file: &NBTFile,
+    checksum: Checksum,
+) -> Result<()> {
+    let mut buf = Vec::new();
+    write_file(&mut buf, file)?;
+    w.write_all(&buf)?;
+    w.write_u32::<BigEndian>(checksum.compute(&buf))?;
+
+    Ok(())
 }
 
-fn write_int<W: Write>(w: &mut W, val: i32) -> Result<()> {
-    w.write_i32::<BigEndian>(val).map_err(|e| e.into())
+/// Write the value of a tag, with big endian numbers
+pub(crate) fn write_tag<W: Write>(w: &mut W, tag: &NBT) -> Result<()> {
+    write_tag_with(w, tag, Endianness::Big)
 }
 
-fn write_long<W: Write>(w: &mut W, val: i64) -> Result<()> {
-    w.write_i64::<BigEndian>(val).map_err(|e| e.into())
+fn write_tag_with<W: Write>(w: &mut W, tag: &NBT, endianness: Endianness) -> Result<()> {
+    match *tag {
+        NBT::End => bail!("Unable to write End tag"),
+        NBT::Byte(x) => write_byte(w, x),
+        NBT::Short(x) => write_number!(w, write_i16, endianness, x),
+        NBT::Int(x) => write_number!(w, write_i32, endianness, x),
+        NBT::Long(x) => write_number!(w, write_i64, endianness, x),
+        NBT::Float(x) => write_number!(w, write_f32, endianness, x),
+        NBT::Double(x) => write_number!(w, write_f64, endianness, x),
+        NBT::ByteArray(ref x) => write_byte_array(w, x, endianness),
+        NBT::String(ref x) => write_string_with(w, x, endianness),
+        NBT::List {
+            element_type,
+            ref items,
+        } => write_list(w, element_type, items, endianness),
+        NBT::Compound(ref x) => write_compound(w, x, true, endianness),
+        NBT::IntArray(ref x) => write_int_array(w, x, endianness),
+        NBT::LongArray(ref x) => write_long_array(w, x, endianness),
+    }
 }
 
-fn write_float<W: Write>(w: &mut W, val: f32) -> Result<()> {
-    w.write_f32::<BigEndian>(val).map_err(|e| e.into())
+fn write_byte<W: Write>(w: &mut W, val: i8) -> Result<()> {
+    w.write_i8(val).map_err(|e| e.into())
 }
 
-fn write_double<W: Write>(w: &mut W, val: f64) -> Result<()> {
-    w.write_f64::<BigEndian>(val).map_err(|e| e.into())
+/// Write a big endian Int, such as the length of a list
+pub(crate) fn write_int<W: Write>(w: &mut W, val: i32) -> Result<()> {
+    write_number!(w, write_i32, Endianness::Big, val)
 }
 
-fn write_byte_array<W: Write>(w: &mut W, val: &[i8]) -> Result<()> {
-    write_int(w, val.len() as i32)?;
+fn write_byte_array<W: Write>(w: &mut W, val: &[i8], endianness: Endianness) -> Result<()> {
+    write_number!(w, write_i32, endianness, val.len() as i32)?;
 
     for x in val {
         write_byte(w, *x)?;
@@ -89,35 +246,74 @@ fn write_byte_array<W: Write>(w: &mut W, val: &[i8]) -> Result<()> {
     Ok(())
 }
 
-fn write_string<W: Write>(w: &mut W, val: &[u8]) -> Result<()> {
+/// Write a string with a big endian length
+pub(crate) fn write_string<W: Write>(w: &mut W, val: &[u8]) -> Result<()> {
+    write_string_with(w, val, Endianness::Big)
+}
+
+fn write_string_with<W: Write>(w: &mut W, val: &[u8], endianness: Endianness) -> Result<()> {
     let bytes = &val;
-    w.write_u16::<BigEndian>(bytes.len() as u16)?;
+    write_number!(w, write_u16, endianness, bytes.len() as u16)?;
     w.write_all(bytes).map_err(|e| e.into())
 }
 
-fn write_list<W: Write>(w: &mut W, val: &[NBT]) -> Result<()> {
-    /* If the list has length 0, then it just defaults to type "End". */
-    #[rustfmt::skip]
+fn write_list<W: Write>(
+    w: &mut W,
+    element_type: u8,
+    val: &[NBT],
+    endianness: Endianness,
+) -> Result<()> {
+    /* An empty list keeps the type it was declared with */
     let tag_type = if val.is_empty() {
-        0
+        element_type
     } else {
         val[0].type_byte()
     };
+    check_list_types(val)?;
     w.write_all(&[tag_type])?;
-    write_int(w, val.len() as i32)?;
+    write_number!(w, write_i32, endianness, val.len() as i32)?;
 
     for tag in val {
-        write_tag(w, tag)?;
+        write_tag_with(w, tag, endianness)?;
     }
 
     Ok(())
 }
 
-fn write_compound<W: Write>(w: &mut W, map: &[(Vec<u8>, NBT)], end: bool) -> Result<()> {
-    for &(ref key, ref tag) in map {
+/// Fail if an element of the list has a different type than the first. A
+/// list can only store a single type, so anything else would be read back
+/// wrong.
+pub(crate) fn check_list_types(val: &[NBT]) -> Result<()> {
+    match mixed_elements(val).next() {
+        Some((i, x)) => bail!(
+            "List of {} also contains a {} at index {}, all elements of a list must have the same type",
+            val[0].type_string(),
+            x.type_string(),
+            i
+        ),
+        None => Ok(()),
+    }
+}
+
+/// The elements of the list that have a different type than the first, with
+/// their indexes
+pub(crate) fn mixed_elements(val: &[NBT]) -> impl Iterator<Item = (usize, &NBT)> {
+    let first = val.first().map(NBT::type_byte);
+    val.iter()
+        .enumerate()
+        .filter(move |(_, x)| Some(x.type_byte()) != first)
+}
+
+fn write_compound<W: Write>(
+    w: &mut W,
+    map: &[(Vec<u8>, NBT)],
+    end: bool,
+    endianness: Endianness,
+) -> Result<()> {
+    for (key, tag) in map {
         w.write_all(&[tag.type_byte()])?;
-        write_string(w, key)?;
-        write_tag(w, &tag)?;
+        write_string_with(w, key, endianness)?;
+        write_tag_with(w, tag, endianness)?;
     }
 
     /* Append the End tag, but not on the implicit Compound */
@@ -128,21 +324,21 @@ fn write_compound<W: Write>(w: &mut W, map: &[(Vec<u8>, NBT)], end: bool) -> Res
     Ok(())
 }
 
-fn write_int_array<W: Write>(w: &mut W, val: &[i32]) -> Result<()> {
-    write_int(w, val.len() as i32)?;
+fn write_int_array<W: Write>(w: &mut W, val: &[i32], endianness: Endianness) -> Result<()> {
+    write_number!(w, write_i32, endianness, val.len() as i32)?;
 
     for x in val {
-        write_int(w, *x)?;
+        write_number!(w, write_i32, endianness, *x)?;
     }
 
     Ok(())
 }
 
-fn write_long_array<W: Write>(w: &mut W, val: &[i64]) -> Result<()> {
-    write_int(w, val.len() as i32)?;
+fn write_long_array<W: Write>(w: &mut W, val: &[i64], endianness: Endianness) -> Result<()> {
+    write_number!(w, write_i32, endianness, val.len() as i32)?;
 
     for x in val {
-        write_long(w, *x)?;
+        write_number!(w, write_i64, endianness, *x)?;
     }
 
     Ok(())
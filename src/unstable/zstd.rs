@@ -0,0 +1,64 @@
+//! Zstandard, which some third party tools and modpacks compress NBT with.
+//!
+//! Files are read and written with the zstd crate, which is only built with
+//! the zstd feature. Without it, Zstd files are still recognized, but reading
+//! or writing them fails. Files are written as a single frame with a content
+//! checksum, at zstd's default level.
+
+use crate::Result;
+
+use std::io::{Read, Write};
+
+/// The magic number every frame starts with, stored little endian as
+/// 28 B5 2F FD
+pub const MAGIC: u32 = 0xfd2f_b528;
+
+/// A reader that decompresses the frame read from the given reader as it's
+/// read from. Anything after the frame, such as padding, is ignored.
+#[cfg(feature = "zstd")]
+pub fn decoder<'a, R: Read + 'a>(reader: R) -> Result<Box<dyn Read + 'a>> {
+    Ok(Box::new(
+        ::zstd::stream::read::Decoder::new(reader)?.single_frame(),
+    ))
+}
+
+#[cfg(not(feature = "zstd"))]
+pub fn decoder<'a, R: Read + 'a>(_reader: R) -> Result<Box<dyn Read + 'a>> {
+    Err(unsupported())
+}
+
+/// Write a frame to the writer, compressing everything write writes as it's
+/// written, so that the uncompressed data is never held in memory
+#[cfg(feature = "zstd")]
+pub fn compress_with<W, F>(w: W, write: F) -> Result<()>
+where
+    W: Write,
+    F: FnOnce(&mut dyn Write) -> Result<()>,
+{
+    /* Level 0 is zstd's default */
+    let mut encoder = ::zstd::stream::write::Encoder::new(w, 0)?;
+    encoder.include_checksum(true)?;
+    /* The encoder compresses every write it's given, so it's given larger
+     * pieces than single numbers */
+    let mut buffered = std::io::BufWriter::new(encoder);
+    write(&mut buffered)?;
+    let _: W = crate::write::finish_buffered(buffered)?.finish()?;
+
+    Ok(())
+}
+
+#[cfg(not(feature = "zstd"))]
+pub fn compress_with<W, F>(_w: W, _write: F) -> Result<()>
+where
+    W: Write,
+    F: FnOnce(&mut dyn Write) -> Result<()>,
+{
+    Err(unsupported())
+}
+
+/// The error given when reading or writing a Zstd file without the zstd
+/// feature
+#[cfg(not(feature = "zstd"))]
+fn unsupported() -> failure::Error {
+    format_err!("Zstd compressed files are recognized but not supported, nbted was built without the zstd feature")
+}
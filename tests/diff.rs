@@ -0,0 +1,36 @@
+//! Tests of the exit statuses of --diff, which follow diff(1)
+
+use std::process::Command;
+
+/// Bad arguments are trouble like a file that can't be read, exit status 2,
+/// since 1 means that the files differ
+#[test]
+fn bad_arguments() {
+    for args in &[
+        &["--diff", "a"][..],
+        &["--diff", "--estimate-size", "a", "b"],
+    ] {
+        let output = Command::new(env!("CARGO_BIN_EXE_nbted"))
+            .args(*args)
+            .output()
+            .unwrap();
+        assert_eq!(output.status.code(), Some(2), "{:?}", args);
+    }
+
+    let output = Command::new(env!("CARGO_BIN_EXE_nbted"))
+        .args(["--diff", "missing-a.nbt", "missing-b.nbt"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(2));
+}
+
+/// Only --diff itself picks the exit status, not an argument that happens to
+/// be spelled --diff, such as the value of --output
+#[test]
+fn diff_as_value() {
+    let output = Command::new(env!("CARGO_BIN_EXE_nbted"))
+        .args(["--print", "missing-a.nbt", "--output", "--diff"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+}
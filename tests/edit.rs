@@ -0,0 +1,105 @@
+//! Tests of --edit, which lives in the binary, run through the binary with
+//! --exec standing in for the user's editor
+
+use std::fs;
+use std::process::Command;
+
+use tempdir::TempDir;
+
+/// An uncompressed file with a root compound named "" containing an Int "a"
+/// of 1 and a List of Int "l" holding 5
+const FILE: &[u8] = &[
+    0x0a, 0, 0, 0x03, 0, 1, b'a', 0, 0, 0, 1, 0x09, 0, 1, b'l', 0x03, 0, 0, 0, 1, 0, 0, 0, 5, 0,
+];
+
+/// An uncompressed file with a root compound named "" containing a Float "f"
+/// of -0 and a Float "n" that is NaN
+const FLOATS: &[u8] = &[
+    0x0a, 0, 0, 0x05, 0, 1, b'f', 0x80, 0, 0, 0, 0x05, 0, 1, b'n', 0x7f, 0xc0, 0, 0, 0,
+];
+
+/// An editor that only changes the whitespace of the file, by indenting with
+/// spaces instead of tabs, leaves the file as it was
+#[test]
+fn reindented_is_unchanged() {
+    let dir = TempDir::new("nbted").unwrap();
+    let path = dir.path().join("file.nbt");
+    fs::write(&path, FILE).unwrap();
+    let modified = fs::metadata(&path).unwrap().modified().unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_nbted"))
+        .arg("--edit")
+        .arg(&path)
+        .arg("--exec")
+        .arg(r"sed -i 's/\t/    /g'")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{:?}", output);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("No changes, will do nothing."),
+        "{}",
+        stderr
+    );
+    assert_eq!(fs::read(&path).unwrap(), FILE);
+    assert_eq!(fs::metadata(&path).unwrap().modified().unwrap(), modified);
+}
+
+/// An editor that does change something makes --edit write the file, so that
+/// the test above would notice if the editor wasn't run at all
+#[test]
+fn edited_is_written() {
+    let dir = TempDir::new("nbted").unwrap();
+    let path = dir.path().join("file.nbt");
+    fs::write(&path, FILE).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_nbted"))
+        .arg("--edit")
+        .arg(&path)
+        .arg("--exec")
+        .arg(r#"sed -i 's/^\(\s*Int "a"\) 1$/\1 2/'"#)
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{:?}", output);
+    let mut expected = FILE.to_vec();
+    expected[10] = 2;
+    assert_eq!(fs::read(&path).unwrap(), expected);
+}
+
+/// Floats are compared by their bits, so a NaN is no change, but turning -0
+/// into 0 is
+#[test]
+fn floats_compared_bitwise() {
+    let dir = TempDir::new("nbted").unwrap();
+    let path = dir.path().join("file.nbt");
+    fs::write(&path, FLOATS).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_nbted"))
+        .arg("--edit")
+        .arg(&path)
+        .arg("--exec")
+        .arg("true")
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("No changes, will do nothing."),
+        "{}",
+        stderr
+    );
+
+    let output = Command::new(env!("CARGO_BIN_EXE_nbted"))
+        .arg("--edit")
+        .arg(&path)
+        .arg("--exec")
+        .arg(r#"sed -i 's/^\(\s*Float "f"\) -0$/\1 0/'"#)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let mut expected = FLOATS.to_vec();
+    expected[7] = 0;
+    assert_eq!(fs::read(&path).unwrap(), expected);
+}